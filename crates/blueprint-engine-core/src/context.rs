@@ -1,6 +1,8 @@
-use std::collections::HashSet;
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::io::{self, Write};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 use tokio::sync::RwLock;
 use tokio::task_local;
 
@@ -9,6 +11,52 @@ use crate::{BlueprintError, PermissionCheck, Permissions, Result};
 task_local! {
     static PERMISSIONS: Arc<Permissions>;
     static PROMPT_STATE: Arc<PromptState>;
+    static SESSION: Arc<Session>;
+}
+
+/// State that belongs to one script run rather than to the process, such as
+/// its `on_shutdown` hooks. Modules keep their own types here, one value per
+/// type, created on first use.
+#[derive(Default)]
+pub struct Session {
+    slots: Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+}
+
+impl Session {
+    pub fn get<T: Default + Send + Sync + 'static>(&self) -> Arc<T> {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Arc::new(T::default()))
+            .clone()
+            .downcast::<T>()
+            .expect("session slots are keyed by their type")
+    }
+}
+
+/// Runs `fut` in `session`. Each script run gets a fresh `Session`.
+pub async fn with_session<F: Future>(session: Arc<Session>, fut: F) -> F::Output {
+    SESSION.scope(session, fut).await
+}
+
+/// The session of the current script run. Code running outside
+/// `with_session` shares one process-wide session.
+pub fn session() -> Arc<Session> {
+    static PROCESS: OnceLock<Arc<Session>> = OnceLock::new();
+    SESSION
+        .try_with(|s| s.clone())
+        .unwrap_or_else(|_| PROCESS.get_or_init(Default::default).clone())
+}
+
+/// Wraps a future about to be spawned so it stays in the caller's session.
+pub fn inherit_session<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    let session = SESSION.try_with(|s| s.clone()).ok();
+    async move {
+        match session {
+            Some(session) => SESSION.scope(session, fut).await,
+            None => fut.await,
+        }
+    }
 }
 
 pub struct PromptState {
@@ -70,6 +118,25 @@ where
         .await
 }
 
+/// Wraps a future about to be spawned so it is checked against the caller's
+/// permissions; spawned tasks otherwise start with none set, which allows
+/// everything. The task also stays in the caller's session.
+pub fn inherit_permissions<F: std::future::Future>(
+    fut: F,
+) -> impl std::future::Future<Output = F::Output> {
+    let permissions = get_permissions();
+    let prompt_state = get_prompt_state();
+    inherit_session(async move {
+        match (permissions, prompt_state) {
+            (Some(permissions), Some(prompt_state)) => {
+                with_permissions_and_prompt(permissions, prompt_state, || fut).await
+            }
+            (Some(permissions), None) => with_permissions_async(permissions, || fut).await,
+            _ => fut.await,
+        }
+    })
+}
+
 pub fn get_permissions() -> Option<Arc<Permissions>> {
     PERMISSIONS.try_with(|p| p.clone()).ok()
 }
@@ -234,6 +301,16 @@ pub async fn check_ws(url: &str) -> Result<()> {
     }
 }
 
+pub async fn check_net_listen(addr: &str) -> Result<()> {
+    match get_permissions() {
+        None => Ok(()),
+        Some(p) => {
+            let check = p.check_net_listen(addr);
+            handle_permission_check(check, "net.listen", Some(addr)).await
+        }
+    }
+}
+
 pub async fn check_process_run(binary: &str) -> Result<()> {
     match get_permissions() {
         None => Ok(()),
@@ -273,3 +350,31 @@ pub async fn check_env_write() -> Result<()> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter(Mutex<u32>);
+
+    fn bump() -> u32 {
+        let counter = session().get::<Counter>();
+        let mut n = counter.0.lock().unwrap();
+        *n += 1;
+        *n
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_separate_and_inherited() {
+        with_session(Arc::default(), async {
+            assert_eq!(bump(), 1);
+            assert_eq!(bump(), 2);
+            let spawned = tokio::spawn(inherit_session(async { bump() }));
+            assert_eq!(spawned.await.unwrap(), 3);
+        })
+        .await;
+
+        with_session(Arc::default(), async { assert_eq!(bump(), 1) }).await;
+    }
+}
@@ -139,6 +139,22 @@ pub enum BlueprintError {
     #[error("Unsupported: {message}")]
     Unsupported { message: String },
 
+    #[error("Timed out after {seconds}s")]
+    Timeout { seconds: f64 },
+
+    #[error("Memory limit exceeded: {used} bytes resident, limit is {limit} bytes")]
+    MemoryLimitExceeded { used: u64, limit: u64 },
+
+    #[error("Resource limit exceeded: {resource} budget of {limit} used up")]
+    ResourceLimit { resource: String, limit: u64 },
+
+    #[error("Integrity check failed for {package}: expected sha256 {expected}, got {actual}")]
+    IntegrityError {
+        package: String,
+        expected: String,
+        actual: String,
+    },
+
     #[error("Permission denied: {operation} on '{resource}'")]
     PermissionDenied {
         operation: String,
@@ -155,6 +171,12 @@ pub enum BlueprintError {
     #[error("return")]
     Return { value: Arc<crate::Value> },
 
+    #[error("tail call")]
+    TailCall {
+        args: Vec<crate::Value>,
+        kwargs: std::collections::HashMap<String, crate::Value>,
+    },
+
     #[error("exit with code {code}")]
     Exit { code: i32 },
 
@@ -189,10 +211,20 @@ impl BlueprintError {
             BlueprintError::Break
                 | BlueprintError::Continue
                 | BlueprintError::Return { .. }
+                | BlueprintError::TailCall { .. }
                 | BlueprintError::Exit { .. }
         )
     }
 
+    /// Errors raised by evaluator limits. Script-level recovery such as
+    /// `task.with_retry` must let these through.
+    pub fn is_resource_exhausted(&self) -> bool {
+        matches!(
+            self.inner_error(),
+            BlueprintError::ResourceLimit { .. } | BlueprintError::MemoryLimitExceeded { .. }
+        )
+    }
+
     pub fn with_stack_frame(self, frame: StackFrame) -> Self {
         if self.is_control_flow() {
             return self;
@@ -261,6 +293,43 @@ impl BlueprintError {
         }
     }
 
+    pub fn kind(&self) -> &'static str {
+        match self.inner_error() {
+            BlueprintError::ParseError { .. } => "ParseError",
+            BlueprintError::TypeError { .. } => "TypeError",
+            BlueprintError::NameError { .. } => "NameError",
+            BlueprintError::ImportError { .. } => "ImportError",
+            BlueprintError::AttributeError { .. } => "AttributeError",
+            BlueprintError::IndexError { .. } => "IndexError",
+            BlueprintError::KeyError { .. } => "KeyError",
+            BlueprintError::ValueError { .. } => "ValueError",
+            BlueprintError::ArgumentError { .. } => "ArgumentError",
+            BlueprintError::DivisionByZero => "DivisionByZero",
+            BlueprintError::IoError { .. } => "IoError",
+            BlueprintError::HttpError { .. } => "HttpError",
+            BlueprintError::ProcessError { .. } => "ProcessError",
+            BlueprintError::JsonError { .. } => "JsonError",
+            BlueprintError::GlobError { .. } => "GlobError",
+            BlueprintError::AssertionError { .. } => "AssertionError",
+            BlueprintError::UserError { .. } => "UserError",
+            BlueprintError::NotCallable { .. } => "NotCallable",
+            BlueprintError::InternalError { .. } => "InternalError",
+            BlueprintError::Unsupported { .. } => "Unsupported",
+            BlueprintError::Timeout { .. } => "Timeout",
+            BlueprintError::MemoryLimitExceeded { .. } => "MemoryLimitExceeded",
+            BlueprintError::ResourceLimit { .. } => "ResourceLimit",
+            BlueprintError::IntegrityError { .. } => "IntegrityError",
+            BlueprintError::PermissionDenied { .. } => "PermissionDenied",
+            BlueprintError::Break => "Break",
+            BlueprintError::Continue => "Continue",
+            BlueprintError::Return { .. } => "Return",
+            BlueprintError::TailCall { .. } => "TailCall",
+            BlueprintError::Exit { .. } => "Exit",
+            BlueprintError::Silent => "Silent",
+            BlueprintError::WithStack { .. } => "WithStack",
+        }
+    }
+
     pub fn inner_error(&self) -> &BlueprintError {
         match self {
             BlueprintError::WithStack { error, .. } => error.inner_error(),
@@ -7,17 +7,19 @@ mod value;
 
 pub use context::{
     check_env_read, check_env_write, check_fs_delete, check_fs_read, check_fs_write, check_http,
-    check_process_run, check_process_shell, check_ws, get_permissions, with_permissions,
-    with_permissions_and_prompt, with_permissions_async, PromptState,
+    check_net_listen, check_process_run, check_process_shell, check_ws, get_permissions,
+    inherit_permissions, inherit_session, session, with_permissions, with_permissions_and_prompt,
+    with_permissions_async, with_session, PromptState, Session,
 };
 pub use error::{BlueprintError, Result, SourceLocation, Span, StackFrame, StackTrace};
 pub use package::{
     fetch_package, find_workspace_root, find_workspace_root_from, get_packages_dir,
-    get_packages_dir_from, PackageSpec,
+    get_packages_dir_from, get_vendor_dir_from, PackageSpec,
 };
 pub use permissions::{PermissionCheck, Permissions, Policy};
 pub use value::{
-    Generator, GeneratorMessage, HttpResponse, LambdaFunction, NativeFn, NativeFunction,
-    NativeFuture, Parameter, ParameterKind, ProcessResult, StreamIterator, StructField,
-    StructInstance, StructType, TypeAnnotation, UserFunction, Value,
+    format_float, intern, intern_owned, json_to_value, DictView, Generator, GeneratorMessage,
+    HttpResponse, LambdaFunction, NativeFn, NativeFunction, NativeFuture, Parameter, ParameterKind,
+    ProcessResult, StreamIterator, StructField, StructInstance, StructType, TypeAnnotation,
+    UserFunction, Value,
 };
@@ -78,35 +78,122 @@ pub fn get_packages_dir_from(start: Option<PathBuf>) -> PathBuf {
     }
 }
 
+/// Packages committed to the workspace by `bp vendor`, if that directory exists.
+pub fn get_vendor_dir_from(start: Option<PathBuf>) -> Option<PathBuf> {
+    let vendor = start.and_then(find_workspace_root_from)?.join("vendor");
+    vendor.is_dir().then_some(vendor)
+}
+
+/// Set by `--offline` or `BP_OFFLINE=1`; package fetches fail instead of
+/// reaching the registry.
+pub fn is_offline() -> bool {
+    std::env::var("BP_OFFLINE").is_ok_and(|v| !v.is_empty() && v != "0" && v != "false")
+}
+
 const DEFAULT_REGISTRY: &str = "https://blueprint.fleetnet.engineering";
 
 pub fn get_registry_url() -> String {
     std::env::var("BP_REGISTRY").unwrap_or_else(|_| DEFAULT_REGISTRY.to_string())
 }
 
-pub fn fetch_package(spec: &PackageSpec, dest: &PathBuf) -> Result<()> {
-    let registry = get_registry_url();
-    let download_url = format!(
-        "{}/api/v1/packages/{}/{}/{}/download",
-        registry, spec.user, spec.repo, spec.version
-    );
+/// Downloads `url`, returning the body and the raw response headers. curl
+/// writes the headers of every response it followed, so redirects appear
+/// before the final block.
+fn curl_download(url: &str, what: &str) -> Result<(Vec<u8>, String)> {
+    let header_path = std::env::temp_dir().join(format!(
+        "bp-download-{}-{}.headers",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default()
+    ));
 
     let output = std::process::Command::new("curl")
-        .args(["-fsSL", "-o", "-", &download_url])
-        .output()
-        .map_err(|e| BlueprintError::IoError {
-            path: download_url.clone(),
-            message: e.to_string(),
-        })?;
+        .args(["-fsSL", "-o", "-", "-D"])
+        .arg(&header_path)
+        .arg(url)
+        .output();
+    let headers = std::fs::read_to_string(&header_path).unwrap_or_default();
+    std::fs::remove_file(&header_path).ok();
+
+    let output = output.map_err(|e| BlueprintError::IoError {
+        path: url.to_string(),
+        message: e.to_string(),
+    })?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(BlueprintError::IoError {
-            path: download_url,
-            message: format!("Failed to download package: {}", stderr.trim()),
+            path: url.to_string(),
+            message: format!("Failed to download {}: {}", what, stderr.trim()),
         });
     }
 
+    Ok((output.stdout, headers))
+}
+
+/// The `X-Checksum-Sha256` the registry attached to the final response, if any.
+fn checksum_header(headers: &str) -> Option<String> {
+    let last_response = headers
+        .split("\r\n\r\n")
+        .flat_map(|block| block.split("\n\n"))
+        .filter(|block| !block.trim().is_empty())
+        .last()?;
+
+    last_response.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        let value = value.trim();
+        (name.trim().eq_ignore_ascii_case("x-checksum-sha256") && !value.is_empty())
+            .then(|| value.to_lowercase())
+    })
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Downloads a package archive, checks it against the checksum the registry
+/// sends with the download, and only then extracts it into `dest`. Registries
+/// that send no checksum are accepted with a warning.
+pub fn fetch_package(spec: &PackageSpec, dest: &PathBuf) -> Result<()> {
+    if is_offline() {
+        return Err(BlueprintError::IoError {
+            path: spec.display_name(),
+            message: "package not vendored (offline mode; run `bp vendor` while online)".into(),
+        });
+    }
+
+    let download_url = format!(
+        "{}/api/v1/packages/{}/{}/{}/download",
+        get_registry_url(),
+        spec.user,
+        spec.repo,
+        spec.version
+    );
+    let (archive, headers) = curl_download(&download_url, "package")?;
+
+    match checksum_header(&headers) {
+        Some(expected) => {
+            let actual = sha256_hex(&archive);
+            if actual != expected {
+                return Err(BlueprintError::IntegrityError {
+                    package: spec.display_name(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+        None => eprintln!(
+            "warning: registry sent no checksum for {}; skipping integrity check",
+            spec.display_name()
+        ),
+    }
+
     if let Some(parent) = dest.parent() {
         std::fs::create_dir_all(parent).map_err(|e| BlueprintError::IoError {
             path: parent.to_string_lossy().to_string(),
@@ -127,7 +214,7 @@ pub fn fetch_package(spec: &PackageSpec, dest: &PathBuf) -> Result<()> {
         .and_then(|mut child| {
             use std::io::Write;
             if let Some(stdin) = child.stdin.as_mut() {
-                stdin.write_all(&output.stdout)?;
+                stdin.write_all(&archive)?;
             }
             child.wait()
         })
@@ -146,3 +233,28 @@ pub fn fetch_package(spec: &PackageSpec, dest: &PathBuf) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_checksum_header() {
+        let headers = "HTTP/1.1 302 Found\r\nLocation: /b\r\nX-Checksum-Sha256: stale\r\n\r\n\
+                       HTTP/1.1 200 OK\r\nx-checksum-sha256: ABC123\r\n\r\n";
+        assert_eq!(checksum_header(headers).as_deref(), Some("abc123"));
+
+        let headers = "HTTP/1.1 302 Found\r\nX-Checksum-Sha256: stale\r\n\r\n\
+                       HTTP/1.1 200 OK\r\nContent-Type: application/gzip\r\n\r\n";
+        assert_eq!(checksum_header(headers), None);
+        assert_eq!(checksum_header(""), None);
+    }
+}
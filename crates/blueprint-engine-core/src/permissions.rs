@@ -165,6 +165,10 @@ impl Permissions {
         self.check("net.ws", Some(url))
     }
 
+    pub fn check_net_listen(&self, addr: &str) -> PermissionCheck {
+        self.check("net.listen", Some(addr))
+    }
+
     pub fn check_process_run(&self, binary: &str) -> PermissionCheck {
         let bin_name = std::path::Path::new(binary)
             .file_name()
@@ -424,4 +428,23 @@ mod tests {
             "stream.example.com"
         );
     }
+
+    #[test]
+    fn test_net_listen_patterns() {
+        let perms = Permissions {
+            policy: Policy::Deny,
+            allow: vec!["net.listen:127.0.0.1:*".to_string()],
+            ask: vec![],
+            deny: vec![],
+        };
+
+        assert_eq!(
+            perms.check_net_listen("127.0.0.1:8080"),
+            PermissionCheck::Allow
+        );
+        assert_eq!(
+            perms.check_net_listen("0.0.0.0:8080"),
+            PermissionCheck::Deny
+        );
+    }
 }
@@ -29,6 +29,7 @@ pub struct UserFunction {
     pub params: Vec<Parameter>,
     pub body: Box<dyn std::any::Any + Send + Sync>,
     pub closure: Option<Arc<dyn std::any::Any + Send + Sync>>,
+    pub source: Option<Arc<dyn std::any::Any + Send + Sync>>,
 }
 
 impl fmt::Debug for UserFunction {
@@ -44,6 +45,7 @@ pub struct LambdaFunction {
     pub params: Vec<Parameter>,
     pub body: Box<dyn std::any::Any + Send + Sync>,
     pub closure: Option<Arc<dyn std::any::Any + Send + Sync>>,
+    pub source: Option<Arc<dyn std::any::Any + Send + Sync>>,
 }
 
 impl fmt::Debug for LambdaFunction {
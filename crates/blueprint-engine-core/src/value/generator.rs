@@ -1,23 +1,328 @@
+use std::collections::VecDeque;
 use std::fmt;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use indexmap::IndexMap;
 use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
 
-use super::Value;
+use super::{intern, NativeFunction, Value};
+use crate::error::{BlueprintError, Result};
 
 pub struct StreamIterator {
-    rx: Mutex<mpsc::Receiver<Option<String>>>,
+    source: Source,
+    /// Range values and dict views are sequences rather than cursors: each
+    /// pass started with [`StreamIterator::iter`] walks them from the
+    /// beginning.
+    view: bool,
     content: Mutex<String>,
     done: Mutex<bool>,
     result: Mutex<Option<IndexMap<String, Value>>>,
 }
 
+enum Source {
+    Channel(Mutex<mpsc::Receiver<Option<String>>>),
+    Range(IntRange),
+    Items(std::sync::Mutex<VecDeque<Value>>),
+    Dict(DictIter),
+    Generator(Arc<Generator>),
+    Take {
+        inner: Arc<StreamIterator>,
+        remaining: AtomicU64,
+    },
+    Skip {
+        inner: Arc<StreamIterator>,
+        pending: AtomicU64,
+    },
+    Chain {
+        first: Arc<StreamIterator>,
+        second: Arc<StreamIterator>,
+    },
+}
+
+/// Integer range values computed on demand rather than materialized.
+struct IntRange {
+    start: i64,
+    stop: i64,
+    step: i64,
+    len: u64,
+    pos: AtomicU64,
+}
+
+impl IntRange {
+    fn new(start: i64, stop: i64, step: i64) -> Self {
+        let span = if step > 0 {
+            (stop as i128 - start as i128).max(0)
+        } else {
+            (start as i128 - stop as i128).max(0)
+        };
+        let step_abs = (step as i128).abs();
+        let len = ((span + step_abs - 1) / step_abs) as u64;
+        Self {
+            start,
+            stop,
+            step,
+            len,
+            pos: AtomicU64::new(0),
+        }
+    }
+
+    fn value_at(&self, idx: u64) -> i64 {
+        (self.start as i128 + idx as i128 * self.step as i128) as i64
+    }
+
+    fn next(&self) -> Option<i64> {
+        let pos = self.pos.fetch_add(1, Ordering::Relaxed);
+        if pos >= self.len {
+            self.pos.store(self.len, Ordering::Relaxed);
+            return None;
+        }
+        Some(self.value_at(pos))
+    }
+
+    fn remaining(&self) -> u64 {
+        self.len - self.pos.load(Ordering::Relaxed).min(self.len)
+    }
+
+    fn contains(&self, value: i64) -> bool {
+        let offset = value as i128 - self.start as i128;
+        if offset % self.step as i128 != 0 {
+            return false;
+        }
+        let idx = offset / self.step as i128;
+        idx >= self.pos.load(Ordering::Relaxed) as i128 && idx < self.len as i128
+    }
+}
+
+/// Which part of each entry `keys()`, `values()` or `items()` yields.
+#[derive(Clone, Copy)]
+pub enum DictView {
+    Keys,
+    Values,
+    Items,
+}
+
+impl DictView {
+    /// The key, value or `(key, value)` tuple for one entry.
+    pub fn project(self, key: &str, value: &Value) -> Value {
+        let key = || Value::String(intern(key));
+        match self {
+            DictView::Keys => key(),
+            DictView::Values => value.clone(),
+            DictView::Items => Value::Tuple(Arc::new(vec![key(), value.clone()])),
+        }
+    }
+}
+
+/// Walks a dict by position, reading one entry at a time so the caller never
+/// holds a copy of the whole map.
+struct DictIter {
+    map: Arc<RwLock<IndexMap<String, Value>>>,
+    view: DictView,
+    len: usize,
+    pos: AtomicUsize,
+}
+
+impl DictIter {
+    async fn next(&self) -> Result<Option<Value>> {
+        let map = self.map.read().await;
+        if map.len() != self.len {
+            return Err(BlueprintError::ValueError {
+                message: "dict changed size during iteration".into(),
+            });
+        }
+        let pos = self.pos.fetch_add(1, Ordering::Relaxed);
+        let Some((key, value)) = map.get_index(pos) else {
+            self.pos.store(self.len, Ordering::Relaxed);
+            return Ok(None);
+        };
+        Ok(Some(self.view.project(key, value)))
+    }
+
+    fn remaining(&self) -> usize {
+        self.len - self.pos.load(Ordering::Relaxed).min(self.len)
+    }
+}
+
 impl StreamIterator {
     pub fn new(rx: mpsc::Receiver<Option<String>>) -> Self {
+        Self::with_source(Source::Channel(Mutex::new(rx)))
+    }
+
+    /// A lazy `range(start, stop, step)`. `step` must be non-zero.
+    pub fn range(start: i64, stop: i64, step: i64) -> Self {
         Self {
-            rx: Mutex::new(rx),
+            view: true,
+            ..Self::with_source(Source::Range(IntRange::new(start, stop, step)))
+        }
+    }
+
+    /// Whether this is a re-iterable sequence such as a range value.
+    pub fn is_view(&self) -> bool {
+        self.view
+    }
+
+    /// Starts a pass over the values. A view gets a fresh cursor; any other
+    /// iterator is shared, so consuming the pass also advances it.
+    pub async fn iter(self: &Arc<Self>) -> Arc<Self> {
+        match &self.source {
+            Source::Range(range) if self.view => Arc::new(Self::with_source(Source::Range(
+                IntRange::new(range.start, range.stop, range.step),
+            ))),
+            Source::Dict(dict) if self.view => {
+                Arc::new(Self::dict_cursor(dict.map.clone(), dict.view).await)
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// The item at `idx` of a view, counting from the end when negative.
+    pub async fn index(&self, idx: i64) -> Result<Value> {
+        match &self.source {
+            Source::Range(range) if self.view => {
+                let pos = sequence_index("range", idx, range.len)?;
+                Ok(Value::Int(range.value_at(pos)))
+            }
+            Source::Dict(dict) if self.view => {
+                let map = dict.map.read().await;
+                let pos = sequence_index("dict view", idx, map.len() as u64)?;
+                let (key, value) = map.get_index(pos as usize).unwrap();
+                Ok(dict.view.project(key, value))
+            }
+            _ => Err(BlueprintError::TypeError {
+                expected: "subscriptable (use list() to materialize iterator first)".into(),
+                actual: "iterator".into(),
+            }),
+        }
+    }
+
+    /// The items `start..end` of a view; a range slices to a smaller range
+    /// and a dict view to a list.
+    pub async fn slice(&self, start: usize, end: usize) -> Option<Value> {
+        match &self.source {
+            Source::Dict(dict) if self.view => {
+                let map = dict.map.read().await;
+                let end = end.min(map.len());
+                let items = (start.min(end)..end)
+                    .filter_map(|i| map.get_index(i))
+                    .map(|(key, value)| dict.view.project(key, value))
+                    .collect();
+                Some(Value::List(Arc::new(RwLock::new(items))))
+            }
+            Source::Range(range) if self.view => {
+                let end = end.max(start);
+                let first = range.value_at(start as u64);
+                let stop = range.value_at(end as u64);
+                Some(Value::Iterator(Arc::new(Self::range(
+                    first, stop, range.step,
+                ))))
+            }
+            _ => None,
+        }
+    }
+
+    /// The `repr()` of the iterator: a range shows its bounds and a dict view
+    /// its items, as a list.
+    pub fn repr(&self) -> String {
+        match &self.source {
+            Source::Dict(dict) if self.view => match dict.map.try_read() {
+                Ok(map) => {
+                    let items: Vec<String> = map
+                        .iter()
+                        .map(|(key, value)| dict.view.project(key, value).repr())
+                        .collect();
+                    format!("[{}]", items.join(", "))
+                }
+                Err(_) => "[<locked>]".into(),
+            },
+            Source::Range(range) if self.view && range.step == 1 => {
+                format!("range({}, {})", range.start, range.stop)
+            }
+            Source::Range(range) if self.view => {
+                format!("range({}, {}, {})", range.start, range.stop, range.step)
+            }
+            _ => "<iterator>".into(),
+        }
+    }
+
+    /// Iterates over an already materialized sequence.
+    pub fn from_items(items: impl IntoIterator<Item = Value>) -> Self {
+        Self::with_source(Source::Items(std::sync::Mutex::new(
+            items.into_iter().collect(),
+        )))
+    }
+
+    /// A live view of `map`, as returned by `keys()`, `values()` and
+    /// `items()`. A pass over it fails if the dict grows or shrinks before
+    /// the pass is exhausted.
+    pub async fn dict_view(map: Arc<RwLock<IndexMap<String, Value>>>, view: DictView) -> Self {
+        Self {
+            view: true,
+            ..Self::dict_cursor(map, view).await
+        }
+    }
+
+    async fn dict_cursor(map: Arc<RwLock<IndexMap<String, Value>>>, view: DictView) -> Self {
+        let len = map.read().await.len();
+        Self::with_source(Source::Dict(DictIter {
+            map,
+            view,
+            len,
+            pos: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Wraps any iterable value. Iterators other than views are shared rather
+    /// than copied, so consuming the result also advances the original.
+    pub async fn from_value(value: &Value) -> Result<Arc<Self>> {
+        let items: Vec<Value> = match value {
+            Value::Iterator(it) => return Ok(it.iter().await),
+            Value::Generator(gen) => {
+                return Ok(Arc::new(Self::with_source(Source::Generator(gen.clone()))))
+            }
+            Value::List(l) => l.read().await.clone(),
+            Value::Tuple(t) => t.as_ref().clone(),
+            Value::Set(s) => s.read().await.iter().cloned().collect(),
+            Value::Dict(d) => d
+                .read()
+                .await
+                .keys()
+                .map(|k| Value::String(intern(k)))
+                .collect(),
+            Value::String(s) => s
+                .chars()
+                .map(|c| Value::String(Arc::new(c.to_string())))
+                .collect(),
+            other => {
+                return Err(BlueprintError::TypeError {
+                    expected: "iterable".into(),
+                    actual: other.type_name().into(),
+                })
+            }
+        };
+        Ok(Arc::new(Self::from_items(items)))
+    }
+
+    /// Yields at most the first `n` items of `inner`.
+    pub fn take(inner: Arc<StreamIterator>, n: i64) -> Result<Self> {
+        Ok(Self::with_source(Source::Take {
+            inner,
+            remaining: AtomicU64::new(non_negative("take", n)?),
+        }))
+    }
+
+    /// Yields the items of `inner` after dropping the first `n`.
+    pub fn skip(inner: Arc<StreamIterator>, n: i64) -> Result<Self> {
+        Ok(Self::with_source(Source::Skip {
+            inner,
+            pending: AtomicU64::new(non_negative("skip", n)?),
+        }))
+    }
+
+    fn with_source(source: Source) -> Self {
+        Self {
+            source,
+            view: false,
             content: Mutex::new(String::new()),
             done: Mutex::new(false),
             result: Mutex::new(None),
@@ -25,23 +330,120 @@ impl StreamIterator {
     }
 
     pub async fn next(&self) -> Option<Value> {
+        self.try_next().await.ok().flatten()
+    }
+
+    /// Like `next`, but reports a dict that changed size during the pass, or
+    /// a failing generator, instead of ending early.
+    pub async fn try_next(&self) -> Result<Option<Value>> {
+        if let Source::Range(range) = &self.source {
+            return Ok(range.next().map(Value::Int));
+        }
+
         let mut done = self.done.lock().await;
         if *done {
-            return None;
+            return Ok(None);
         }
 
-        let mut rx = self.rx.lock().await;
-        match rx.recv().await {
-            Some(Some(chunk)) => {
-                let mut content = self.content.lock().await;
-                content.push_str(&chunk);
-                Some(Value::String(Arc::new(chunk)))
+        let item = match &self.source {
+            Source::Channel(rx) => match rx.lock().await.recv().await {
+                Some(Some(chunk)) => {
+                    let mut content = self.content.lock().await;
+                    content.push_str(&chunk);
+                    Some(Value::String(Arc::new(chunk)))
+                }
+                Some(None) | None => None,
+            },
+            Source::Range(range) => range.next().map(Value::Int),
+            Source::Dict(dict) => dict.next().await?,
+            Source::Items(items) => items.lock().unwrap().pop_front(),
+            Source::Generator(gen) => gen.try_next().await?,
+            Source::Take { inner, remaining } => {
+                if remaining.load(Ordering::Relaxed) == 0 {
+                    None
+                } else {
+                    remaining.fetch_sub(1, Ordering::Relaxed);
+                    Box::pin(inner.try_next()).await?
+                }
+            }
+            Source::Skip { inner, pending } => {
+                let mut item = None;
+                for _ in 0..=pending.swap(0, Ordering::Relaxed) {
+                    item = Box::pin(inner.try_next()).await?;
+                    if item.is_none() {
+                        break;
+                    }
+                }
+                item
+            }
+            Source::Chain { first, second } => match Box::pin(first.try_next()).await? {
+                Some(value) => Some(value),
+                None => Box::pin(second.try_next()).await?,
+            },
+        };
+
+        if item.is_none() {
+            *done = true;
+        }
+        Ok(item)
+    }
+
+    /// Number of items left, when known without consuming the iterator.
+    pub fn len(&self) -> Option<usize> {
+        if matches!(self.done.try_lock().as_deref(), Ok(true)) {
+            return Some(0);
+        }
+        match &self.source {
+            Source::Range(range) => Some(range.remaining() as usize),
+            Source::Items(items) => Some(items.lock().unwrap().len()),
+            Source::Dict(dict) if self.view => dict.map.try_read().ok().map(|map| map.len()),
+            Source::Dict(dict) => Some(dict.remaining()),
+            Source::Take { inner, remaining } => {
+                let remaining = remaining.load(Ordering::Relaxed) as usize;
+                match inner.len() {
+                    Some(n) => Some(n.min(remaining)),
+                    None if remaining == 0 => Some(0),
+                    None => None,
+                }
             }
-            Some(None) | None => {
-                *done = true;
-                None
+            Source::Skip { inner, pending } => inner
+                .len()
+                .map(|n| n.saturating_sub(pending.load(Ordering::Relaxed) as usize)),
+            Source::Chain { first, second } => Some(first.len()? + second.len()?),
+            Source::Channel(_) | Source::Generator(_) => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|n| n == 0)
+    }
+
+    /// Membership test for sources that support it without consuming items.
+    pub async fn contains(&self, value: &Value) -> Option<bool> {
+        match (&self.source, value) {
+            (Source::Range(range), Value::Int(i)) => Some(range.contains(*i)),
+            (Source::Range(_), _) => Some(false),
+            (Source::Dict(dict), _) if self.view => {
+                let map = dict.map.read().await;
+                Some(match (dict.view, value) {
+                    (DictView::Keys, Value::String(key)) => map.contains_key(key.as_str()),
+                    (DictView::Keys, _) => false,
+                    (DictView::Values, _) => map.values().any(|v| v == value),
+                    (DictView::Items, _) => map
+                        .iter()
+                        .any(|(key, v)| DictView::Items.project(key, v) == *value),
+                })
             }
+            _ => None,
+        }
+    }
+
+    pub async fn collect(&self) -> Result<Vec<Value>> {
+        let mut items = Vec::with_capacity(self.len().unwrap_or(0));
+        while let Some(item) = self.try_next().await? {
+            items.push(item);
         }
+        Ok(items)
     }
 
     pub async fn set_result(&self, result: IndexMap<String, Value>) {
@@ -49,13 +451,16 @@ impl StreamIterator {
         *r = Some(result);
     }
 
-    pub fn get_attr(&self, name: &str) -> Option<Value> {
+    pub fn get_attr(self: &Arc<Self>, name: &str) -> Option<Value> {
         match name {
             "content" => {
                 let content = self.content.try_lock().ok()?;
                 Some(Value::String(Arc::new(content.clone())))
             }
             "done" => {
+                if let Source::Range(range) = &self.source {
+                    return Some(Value::Bool(range.remaining() == 0));
+                }
                 let done = self.done.try_lock().ok()?;
                 Some(Value::Bool(*done))
             }
@@ -66,14 +471,100 @@ impl StreamIterator {
                     None => Some(Value::None),
                 }
             }
+            "take" | "skip" => {
+                let it = self.clone();
+                let method = name.to_string();
+                Some(Value::NativeFunction(Arc::new(
+                    NativeFunction::new_with_state(name, move |args, _kwargs| {
+                        let it = it.clone();
+                        let method = method.clone();
+                        Box::pin(async move {
+                            let n = count_arg(&method, &args)?;
+                            Ok(Value::Iterator(Arc::new(if method == "take" {
+                                StreamIterator::take(it.iter().await, n)?
+                            } else {
+                                StreamIterator::skip(it.iter().await, n)?
+                            })))
+                        })
+                    }),
+                )))
+            }
+            "chain" => {
+                let it = self.clone();
+                Some(Value::NativeFunction(Arc::new(
+                    NativeFunction::new_with_state("chain", move |args, _kwargs| {
+                        let it = it.clone();
+                        Box::pin(async move {
+                            if args.len() != 1 {
+                                return Err(BlueprintError::ArgumentError {
+                                    message: format!(
+                                        "chain() takes exactly 1 argument ({} given)",
+                                        args.len()
+                                    ),
+                                });
+                            }
+                            let second = StreamIterator::from_value(&args[0]).await?;
+                            Ok(Value::Iterator(Arc::new(StreamIterator::with_source(
+                                Source::Chain {
+                                    first: it.iter().await,
+                                    second,
+                                },
+                            ))))
+                        })
+                    }),
+                )))
+            }
+            "collect" => {
+                let it = self.clone();
+                Some(Value::NativeFunction(Arc::new(
+                    NativeFunction::new_with_state("collect", move |_args, _kwargs| {
+                        let it = it.clone();
+                        Box::pin(async move {
+                            let items = it.iter().await.collect().await?;
+                            Ok(Value::List(Arc::new(RwLock::new(items))))
+                        })
+                    }),
+                )))
+            }
             _ => None,
         }
     }
 }
 
+fn count_arg(method: &str, args: &[Value]) -> Result<i64> {
+    if args.len() != 1 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!(
+                "{}() takes exactly 1 argument ({} given)",
+                method,
+                args.len()
+            ),
+        });
+    }
+    args[0].as_int()
+}
+
+fn sequence_index(kind: &str, idx: i64, len: u64) -> Result<u64> {
+    let actual = if idx < 0 { len as i64 + idx } else { idx };
+    if actual < 0 || actual as u64 >= len {
+        return Err(BlueprintError::IndexError {
+            message: format!("{} index {} out of range (len={})", kind, idx, len),
+        });
+    }
+    Ok(actual as u64)
+}
+
+fn non_negative(method: &str, n: i64) -> Result<u64> {
+    u64::try_from(n).map_err(|_| BlueprintError::ValueError {
+        message: format!("{}() count must be non-negative, got {}", method, n),
+    })
+}
+
 pub enum GeneratorMessage {
     Yielded(Value, oneshot::Sender<()>),
     Complete,
+    /// The generator body raised; the error surfaces at the consumer.
+    Failed(BlueprintError),
 }
 
 pub struct Generator {
@@ -92,19 +583,29 @@ impl Generator {
     }
 
     pub async fn next(&self) -> Option<Value> {
+        self.try_next().await.ok().flatten()
+    }
+
+    /// Like `next`, but raises the error the generator body failed with
+    /// instead of ending early.
+    pub async fn try_next(&self) -> Result<Option<Value>> {
         if self.done.load(Ordering::SeqCst) {
-            return None;
+            return Ok(None);
         }
 
         let mut rx = self.rx.lock().await;
         match rx.recv().await {
             Some(GeneratorMessage::Yielded(value, resume_tx)) => {
                 let _ = resume_tx.send(());
-                Some(value)
+                Ok(Some(value))
+            }
+            Some(GeneratorMessage::Failed(err)) => {
+                self.done.store(true, Ordering::SeqCst);
+                Err(err)
             }
             Some(GeneratorMessage::Complete) | None => {
                 self.done.store(true, Ordering::SeqCst);
-                None
+                Ok(None)
             }
         }
     }
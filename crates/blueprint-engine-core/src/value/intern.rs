@@ -0,0 +1,131 @@
+use std::borrow::{Borrow, Cow};
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Strings up to this many bytes are shared; longer ones rarely repeat.
+const MAX_LEN: usize = 32;
+/// The table is split into this many independently locked shards, chosen by
+/// hash, so threads interning different strings rarely wait on each other.
+const SHARDS: usize = 16;
+/// Entries per shard before it is swept.
+const SHARD_CAPACITY: usize = 1024;
+
+#[derive(PartialEq, Eq)]
+struct Entry(Arc<String>);
+
+impl Hash for Entry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_str().hash(state);
+    }
+}
+
+impl Borrow<str> for Entry {
+    fn borrow(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+struct Table {
+    hasher: RandomState,
+    shards: Vec<Mutex<HashSet<Entry>>>,
+}
+
+fn table() -> &'static Table {
+    static TABLE: OnceLock<Table> = OnceLock::new();
+    TABLE.get_or_init(|| Table {
+        hasher: RandomState::new(),
+        shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+    })
+}
+
+/// Returns `s` as an `Arc<String>`, sharing one allocation between every
+/// short string with the same contents, such as dict keys, JSON values and
+/// header values.
+pub fn intern(s: &str) -> Arc<String> {
+    share(Cow::Borrowed(s))
+}
+
+/// Like `intern`, but keeps `s`'s buffer when it is not already interned.
+pub fn intern_owned(s: String) -> Arc<String> {
+    share(Cow::Owned(s))
+}
+
+fn share(s: Cow<'_, str>) -> Arc<String> {
+    if s.len() > MAX_LEN {
+        return Arc::new(s.into_owned());
+    }
+    let table = table();
+    let shard = table.hasher.hash_one(s.as_ref()) as usize % SHARDS;
+    let mut shard = table.shards[shard]
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(entry) = shard.get(s.as_ref()) {
+        return entry.0.clone();
+    }
+    if shard.len() >= SHARD_CAPACITY {
+        evict(&mut shard);
+    }
+    let shared = Arc::new(s.into_owned());
+    shard.insert(Entry(shared.clone()));
+    shared
+}
+
+/// Drops strings nothing else holds any more. If most entries are still in
+/// use, drops arbitrary ones down to half capacity as well: they stay valid
+/// and only stop being shared. Either way a quarter of the shard is free
+/// afterwards, so sweeps cost O(1) per insert over time.
+fn evict(shard: &mut HashSet<Entry>) {
+    shard.retain(|entry| Arc::strong_count(&entry.0) > 1);
+    if shard.len() > SHARD_CAPACITY * 3 / 4 {
+        let mut keep = SHARD_CAPACITY / 2;
+        shard.retain(|_| {
+            keep = keep.saturating_sub(1);
+            keep > 0
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_strings_share_storage() {
+        let a = intern("status");
+        let b = intern_owned("status".to_string());
+        assert!(Arc::ptr_eq(&a, &b));
+
+        let long = "x".repeat(MAX_LEN + 1);
+        assert!(!Arc::ptr_eq(&intern(&long), &intern(&long)));
+
+        let mut edited = intern("status");
+        Arc::make_mut(&mut edited).push('!');
+        assert_eq!(intern("status").as_str(), "status");
+        assert_eq!(edited.as_str(), "status!");
+    }
+
+    #[test]
+    fn test_full_shard_evicts_unused_strings_first() {
+        let held: Vec<Arc<String>> = (0..SHARD_CAPACITY)
+            .map(|i| Arc::new(format!("held-{}", i)))
+            .collect();
+
+        let mut shard: HashSet<Entry> = held[..SHARD_CAPACITY / 2]
+            .iter()
+            .map(|s| Entry(s.clone()))
+            .collect();
+        shard.extend((0..SHARD_CAPACITY / 2).map(|i| Entry(Arc::new(format!("free-{}", i)))));
+        evict(&mut shard);
+        assert_eq!(shard.len(), SHARD_CAPACITY / 2);
+        assert!(held[..SHARD_CAPACITY / 2]
+            .iter()
+            .all(|s| shard.contains(s.as_str())));
+
+        // When every entry is still in use, some are dropped anyway.
+        let mut shard: HashSet<Entry> = held.iter().map(|s| Entry(s.clone())).collect();
+        evict(&mut shard);
+        assert!(shard.len() <= SHARD_CAPACITY / 2);
+    }
+}
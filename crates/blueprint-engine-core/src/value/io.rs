@@ -1,50 +1,292 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use indexmap::IndexMap;
 use tokio::sync::RwLock;
 
-use super::Value;
+use super::{intern, intern_owned, NativeFunction, Value};
+use crate::error::{BlueprintError, Result};
+
+const BODY_SNIPPET_LEN: usize = 200;
 
 #[derive(Debug, Clone)]
 pub struct HttpResponse {
     pub status: i64,
+    /// The body decoded as UTF-8, with invalid sequences replaced.
     pub body: String,
-    pub headers: HashMap<String, String>,
+    /// The body exactly as received.
+    pub body_bytes: Arc<Vec<u8>>,
+    /// Header values keyed by lowercased name, in the order they were received.
+    pub headers: IndexMap<String, Vec<String>>,
+    json_cache: Arc<OnceLock<serde_json::Value>>,
 }
 
 impl HttpResponse {
+    pub fn new(
+        status: i64,
+        body: Vec<u8>,
+        headers: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        let mut normalized: IndexMap<String, Vec<String>> = IndexMap::new();
+        for (name, value) in headers {
+            normalized
+                .entry(name.to_ascii_lowercase())
+                .or_default()
+                .push(value);
+        }
+        Self {
+            status,
+            body: String::from_utf8_lossy(&body).into_owned(),
+            body_bytes: Arc::new(body),
+            headers: normalized,
+            json_cache: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Looks up a header by name, ignoring case. Returns a string, or a list
+    /// when the server sent the header more than once.
+    pub fn header(&self, name: &str) -> Option<Value> {
+        let values = self.headers.get(&name.to_ascii_lowercase())?;
+        match values.as_slice() {
+            [] => None,
+            [single] => Some(Value::String(intern(single))),
+            many => {
+                let items: Vec<Value> = many.iter().map(|v| Value::String(intern(v))).collect();
+                Some(Value::List(Arc::new(RwLock::new(items))))
+            }
+        }
+    }
+
+    /// Parses the body as JSON. The parsed document is cached, but each call
+    /// returns fresh values so callers can mutate the result freely.
+    pub fn json(&self) -> Result<Value> {
+        if let Some(parsed) = self.json_cache.get() {
+            return json_to_value(parsed.clone());
+        }
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(&self.body).map_err(|e| BlueprintError::JsonError {
+                message: format!(
+                    "Response (status {}) body is not valid JSON: {}; body: {}",
+                    self.status,
+                    e,
+                    body_snippet(&self.body)
+                ),
+            })?;
+
+        let parsed = self.json_cache.get_or_init(|| parsed);
+        json_to_value(parsed.clone())
+    }
+
     pub fn get_attr(&self, name: &str) -> Option<Value> {
         match name {
             "status" => Some(Value::Int(self.status)),
             "body" => Some(Value::String(Arc::new(self.body.clone()))),
+            "json" => {
+                let resp = self.clone();
+                Some(Value::NativeFunction(Arc::new(
+                    NativeFunction::new_with_state("json", move |_args, _kwargs| {
+                        let result = resp.json();
+                        Box::pin(async move { result })
+                    }),
+                )))
+            }
+            "text" => {
+                let body = Arc::new(self.body.clone());
+                Some(Value::NativeFunction(Arc::new(
+                    NativeFunction::new_with_state("text", move |_args, _kwargs| {
+                        let body = body.clone();
+                        Box::pin(async move { Ok(Value::String(body)) })
+                    }),
+                )))
+            }
+            "bytes" => {
+                let body = self.body_bytes.clone();
+                Some(Value::NativeFunction(Arc::new(
+                    NativeFunction::new_with_state("bytes", move |_args, _kwargs| {
+                        let bytes: Vec<Value> =
+                            body.iter().map(|&b| Value::Int(b as i64)).collect();
+                        Box::pin(async move { Ok(Value::List(Arc::new(RwLock::new(bytes)))) })
+                    }),
+                )))
+            }
+            // A plain dict keyed by lowercased names, so indexing it is case
+            // sensitive; `header()` is the case-insensitive accessor.
             "headers" => {
                 let map: IndexMap<String, Value> = self
                     .headers
                     .iter()
-                    .map(|(k, v)| (k.clone(), Value::String(Arc::new(v.clone()))))
+                    .map(|(k, v)| (k.clone(), Value::String(intern_owned(v.join(", ")))))
                     .collect();
                 Some(Value::Dict(Arc::new(RwLock::new(map))))
             }
+            "header" => {
+                let resp = self.clone();
+                Some(Value::NativeFunction(Arc::new(
+                    NativeFunction::new_with_state("header", move |args, kwargs| {
+                        let result = match args.first() {
+                            Some(name) => {
+                                let default = args
+                                    .get(1)
+                                    .or_else(|| kwargs.get("default"))
+                                    .cloned()
+                                    .unwrap_or(Value::None);
+                                Ok(resp.header(&name.to_display_string()).unwrap_or(default))
+                            }
+                            None => Err(BlueprintError::ArgumentError {
+                                message: "header() requires a header name".into(),
+                            }),
+                        };
+                        Box::pin(async move { result })
+                    }),
+                )))
+            }
             _ => None,
         }
     }
 }
 
+fn body_snippet(body: &str) -> String {
+    if body.len() <= BODY_SNIPPET_LEN {
+        return format!("{:?}", body);
+    }
+    let mut end = BODY_SNIPPET_LEN;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{:?}...", &body[..end])
+}
+
+/// Converts a parsed JSON document to script values. Integers that fit in
+/// an `i64` stay integers; every other number becomes a float.
+pub fn json_to_value(json: serde_json::Value) -> Result<Value> {
+    match json {
+        serde_json::Value::Null => Ok(Value::None),
+        serde_json::Value::Bool(b) => Ok(Value::Bool(b)),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Ok(Value::Int(i))
+            } else if let Some(f) = n.as_f64() {
+                Ok(Value::Float(f))
+            } else {
+                Err(BlueprintError::JsonError {
+                    message: "Invalid JSON number".into(),
+                })
+            }
+        }
+        serde_json::Value::String(s) => Ok(Value::String(intern_owned(s))),
+        serde_json::Value::Array(arr) => {
+            let mut items = Vec::with_capacity(arr.len());
+            for item in arr {
+                items.push(json_to_value(item)?);
+            }
+            Ok(Value::List(Arc::new(RwLock::new(items))))
+        }
+        serde_json::Value::Object(obj) => {
+            let mut map = IndexMap::with_capacity(obj.len());
+            for (k, v) in obj {
+                map.insert(k, json_to_value(v)?);
+            }
+            Ok(Value::Dict(Arc::new(RwLock::new(map))))
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ProcessResult {
+    pub command: String,
     pub code: i64,
     pub stdout: String,
     pub stderr: String,
+    /// Exit code of every stage; a single command has one entry.
+    pub stage_codes: Vec<i64>,
 }
 
 impl ProcessResult {
+    /// Returns the result unchanged on success, or a `ProcessError` carrying
+    /// stderr when the exit code is nonzero.
+    pub fn check(&self) -> Result<Value> {
+        if self.code == 0 {
+            return Ok(Value::ProcessResult(Arc::new(self.clone())));
+        }
+
+        let mut message = format!("exited with code {}", self.code);
+        if self.stage_codes.len() > 1 {
+            if let Some(stage) = self.stage_codes.iter().position(|c| *c != 0) {
+                message.push_str(&format!(" (pipeline stage {})", stage));
+            }
+        }
+        let stderr = self.stderr.trim();
+        if !stderr.is_empty() {
+            message.push_str(": ");
+            message.push_str(stderr);
+        }
+
+        Err(BlueprintError::ProcessError {
+            command: self.command.clone(),
+            message,
+        })
+    }
+
     pub fn get_attr(&self, name: &str) -> Option<Value> {
         match name {
+            "command" => Some(Value::String(Arc::new(self.command.clone()))),
             "code" => Some(Value::Int(self.code)),
             "stdout" => Some(Value::String(Arc::new(self.stdout.clone()))),
             "stderr" => Some(Value::String(Arc::new(self.stderr.clone()))),
+            "stage_codes" => {
+                let codes: Vec<Value> = self.stage_codes.iter().map(|c| Value::Int(*c)).collect();
+                Some(Value::List(Arc::new(RwLock::new(codes))))
+            }
+            "ok" => {
+                let ok = self.code == 0;
+                Some(Value::NativeFunction(Arc::new(
+                    NativeFunction::new_with_state("ok", move |_args, _kwargs| {
+                        Box::pin(async move { Ok(Value::Bool(ok)) })
+                    }),
+                )))
+            }
+            "check" => {
+                let result = self.clone();
+                Some(Value::NativeFunction(Arc::new(
+                    NativeFunction::new_with_state("check", move |_args, _kwargs| {
+                        let checked = result.check();
+                        Box::pin(async move { checked })
+                    }),
+                )))
+            }
+            "failed_stage" => Some(
+                self.stage_codes
+                    .iter()
+                    .position(|c| *c != 0)
+                    .map(|i| Value::Int(i as i64))
+                    .unwrap_or(Value::None),
+            ),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_keeps_raw_body_bytes() {
+        let resp = HttpResponse::new(200, vec![0xff, b'{', b'}'], Vec::new());
+        assert_eq!(resp.body_bytes.as_slice(), &[0xff, b'{', b'}']);
+        assert_eq!(resp.body, "\u{fffd}{}");
+    }
+
+    #[test]
+    fn test_response_json() {
+        let resp = HttpResponse::new(200, br#"{"n": 1}"#.to_vec(), Vec::new());
+        let Value::Dict(map) = resp.json().unwrap() else {
+            panic!("expected a dict");
+        };
+        assert_eq!(map.blocking_read().get("n"), Some(&Value::Int(1)));
+
+        let bad = HttpResponse::new(502, b"oops".to_vec(), Vec::new());
+        let message = bad.json().unwrap_err().to_string();
+        assert!(message.contains("status 502"), "{}", message);
+    }
+}
@@ -4,7 +4,7 @@ use indexmap::IndexMap;
 use tokio::sync::RwLock;
 
 use crate::error::BlueprintError;
-use crate::value::{NativeFunction, Value};
+use crate::value::{DictView, NativeFunction, StreamIterator, Value};
 
 pub fn get_dict_method(d: Arc<RwLock<IndexMap<String, Value>>>, name: &str) -> Option<Value> {
     match name {
@@ -42,53 +42,29 @@ pub fn get_dict_method(d: Arc<RwLock<IndexMap<String, Value>>>, name: &str) -> O
                 }),
             )))
         }
-        "keys" => {
+        "keys" | "values" | "items" => {
+            let view = match name {
+                "keys" => DictView::Keys,
+                "values" => DictView::Values,
+                _ => DictView::Items,
+            };
             let d_clone = d.clone();
             Some(Value::NativeFunction(Arc::new(
-                NativeFunction::new_with_state("keys", move |_args, _kwargs| {
+                NativeFunction::new_with_state(name, move |_args, _kwargs| {
                     let d = d_clone.clone();
                     Box::pin(async move {
-                        let map = d.read().await;
-                        let keys: Vec<Value> = map
-                            .keys()
-                            .map(|k| Value::String(Arc::new(k.clone())))
-                            .collect();
-                        Ok(Value::List(Arc::new(RwLock::new(keys))))
-                    })
-                }),
-            )))
-        }
-        "values" => {
-            let d_clone = d.clone();
-            Some(Value::NativeFunction(Arc::new(
-                NativeFunction::new_with_state("values", move |_args, _kwargs| {
-                    let d = d_clone.clone();
-                    Box::pin(async move {
-                        let map = d.read().await;
-                        let values: Vec<Value> = map.values().cloned().collect();
-                        Ok(Value::List(Arc::new(RwLock::new(values))))
+                        let it = StreamIterator::dict_view(d, view).await;
+                        Ok(Value::Iterator(Arc::new(it)))
                     })
                 }),
             )))
         }
-        "items" => {
+        "get_path" => {
             let d_clone = d.clone();
             Some(Value::NativeFunction(Arc::new(
-                NativeFunction::new_with_state("items", move |_args, _kwargs| {
-                    let d = d_clone.clone();
-                    Box::pin(async move {
-                        let map = d.read().await;
-                        let items: Vec<Value> = map
-                            .iter()
-                            .map(|(k, v)| {
-                                Value::Tuple(Arc::new(vec![
-                                    Value::String(Arc::new(k.clone())),
-                                    v.clone(),
-                                ]))
-                            })
-                            .collect();
-                        Ok(Value::List(Arc::new(RwLock::new(items))))
-                    })
+                NativeFunction::new_with_state("get_path", move |args, kwargs| {
+                    let root = Value::Dict(d_clone.clone());
+                    Box::pin(super::get_path(root, args, kwargs))
                 }),
             )))
         }
@@ -248,6 +248,15 @@ pub fn get_list_method(l: Arc<RwLock<Vec<Value>>>, name: &str) -> Option<Value>
                 }),
             )))
         }
+        "get_path" => {
+            let l_clone = l.clone();
+            Some(Value::NativeFunction(Arc::new(
+                NativeFunction::new_with_state("get_path", move |args, kwargs| {
+                    let root = Value::List(l_clone.clone());
+                    Box::pin(super::get_path(root, args, kwargs))
+                }),
+            )))
+        }
         _ => None,
     }
 }
@@ -7,3 +7,64 @@ pub use dict::get_dict_method;
 pub use list::get_list_method;
 pub use set::get_set_method;
 pub use string::get_string_method;
+
+use std::collections::HashMap;
+
+use crate::error::{BlueprintError, Result};
+use crate::value::Value;
+
+/// `get_path([key, ...], default=None)` on dicts and lists: follows string
+/// keys into dicts and struct fields and int indexes into lists and tuples,
+/// returning `default` on any missing key, bad index or type mismatch.
+pub(crate) async fn get_path(
+    root: Value,
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("get_path() takes 1 or 2 arguments ({} given)", args.len()),
+        });
+    }
+    let default = args
+        .get(1)
+        .or_else(|| kwargs.get("default"))
+        .cloned()
+        .unwrap_or(Value::None);
+    let path = match &args[0] {
+        Value::List(l) => l.read().await.clone(),
+        Value::Tuple(t) => t.as_ref().clone(),
+        other => {
+            return Err(BlueprintError::TypeError {
+                expected: "list or tuple of keys for get_path()".into(),
+                actual: other.type_name().into(),
+            })
+        }
+    };
+
+    let mut current = root;
+    for key in path {
+        let next = match (&current, &key) {
+            (Value::Dict(d), Value::String(k)) => d.read().await.get(k.as_str()).cloned(),
+            (Value::StructInstance(s), Value::String(k)) => s.get_field(k),
+            (Value::List(l), Value::Int(i)) => item_at(&l.read().await, *i),
+            (Value::Tuple(t), Value::Int(i)) => item_at(t, *i),
+            _ => None,
+        };
+        match next {
+            Some(value) => current = value,
+            None => return Ok(default),
+        }
+    }
+    Ok(current)
+}
+
+fn item_at(items: &[Value], index: i64) -> Option<Value> {
+    let len = items.len() as i64;
+    let index = if index < 0 { len + index } else { index };
+    if (0..len).contains(&index) {
+        Some(items[index as usize].clone())
+    } else {
+        None
+    }
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
-use crate::error::BlueprintError;
+use indexmap::IndexMap;
+
+use crate::error::{BlueprintError, Result};
 use crate::value::{NativeFunction, Value};
 
 pub fn get_string_method(s: Arc<String>, name: &str) -> Option<Value> {
@@ -176,6 +178,80 @@ pub fn get_string_method(s: Arc<String>, name: &str) -> Option<Value> {
                 }),
             )))
         }
+        "format_map" => {
+            let s = s.clone();
+            Some(Value::NativeFunction(Arc::new(
+                NativeFunction::new_with_state("format_map", move |args, _kwargs| {
+                    let s = s.clone();
+                    Box::pin(async move {
+                        if args.len() != 1 {
+                            return Err(BlueprintError::ArgumentError {
+                                message: format!(
+                                    "format_map() takes exactly 1 argument ({} given)",
+                                    args.len()
+                                ),
+                            });
+                        }
+                        let mapping = match &args[0] {
+                            Value::Dict(d) => d.read().await.clone(),
+                            Value::StructInstance(inst) => inst.to_dict(),
+                            other => {
+                                return Err(BlueprintError::TypeError {
+                                    expected: "dict".into(),
+                                    actual: other.type_name().into(),
+                                })
+                            }
+                        };
+                        let result = format_map(&s, &mapping)?;
+                        Ok(Value::String(Arc::new(result)))
+                    })
+                }),
+            )))
+        }
         _ => None,
     }
 }
+
+/// Replaces `{name}` with the display form of `mapping[name]`; `{{` and `}}`
+/// produce literal braces.
+fn format_map(template: &str, mapping: &IndexMap<String, Value>) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => {
+                            return Err(BlueprintError::ValueError {
+                                message: "format_map(): unmatched '{' in format string".into(),
+                            })
+                        }
+                    }
+                }
+                let value = mapping
+                    .get(&name)
+                    .ok_or_else(|| BlueprintError::KeyError { key: name.clone() })?;
+                out.push_str(&value.to_display_string());
+            }
+            '}' => {
+                return Err(BlueprintError::ValueError {
+                    message: "format_map(): single '}' encountered in format string".into(),
+                })
+            }
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
@@ -1,5 +1,6 @@
 mod functions;
 mod generator;
+mod intern;
 mod io;
 mod methods;
 mod structs;
@@ -7,10 +8,12 @@ mod structs;
 pub use functions::{
     LambdaFunction, NativeFn, NativeFunction, NativeFuture, Parameter, ParameterKind, UserFunction,
 };
-pub use generator::{Generator, GeneratorMessage, StreamIterator};
-pub use io::{HttpResponse, ProcessResult};
+pub use generator::{DictView, Generator, GeneratorMessage, StreamIterator};
+pub use intern::{intern, intern_owned};
+pub use io::{json_to_value, HttpResponse, ProcessResult};
 pub use structs::{StructField, StructInstance, StructType, TypeAnnotation};
 
+use std::cell::RefCell;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
@@ -91,6 +94,17 @@ impl Value {
         }
     }
 
+    /// Whether the value has a stable hash and can key a dict-like cache.
+    pub fn is_hashable(&self) -> bool {
+        match self {
+            Value::None | Value::Bool(_) | Value::Int(_) | Value::Float(_) | Value::String(_) => {
+                true
+            }
+            Value::Tuple(t) => t.iter().all(Value::is_hashable),
+            _ => false,
+        }
+    }
+
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::None => false,
@@ -237,36 +251,30 @@ impl Value {
             Value::None => "None".into(),
             Value::Bool(b) => if *b { "True" } else { "False" }.into(),
             Value::Int(i) => i.to_string(),
-            Value::Float(f) => {
-                if f.fract() == 0.0 {
-                    format!("{f:.1}")
-                } else {
-                    f.to_string()
-                }
-            }
+            Value::Float(f) => format_float(*f),
             Value::String(s) => s.as_ref().clone(),
             Value::List(l) => match l.try_read() {
-                Ok(guard) => {
+                Ok(guard) => display_once(Arc::as_ptr(l) as usize, "[...]", || {
                     let items: Vec<String> = guard.iter().map(|v| v.repr()).collect();
                     format!("[{}]", items.join(", "))
-                }
+                }),
                 Err(_) => "[<locked>]".into(),
             },
             Value::Dict(d) => match d.try_read() {
-                Ok(guard) => {
+                Ok(guard) => display_once(Arc::as_ptr(d) as usize, "{...}", || {
                     let items: Vec<String> = guard
                         .iter()
                         .map(|(k, v)| format!("{:?}: {}", k, v.repr()))
                         .collect();
                     format!("{{{}}}", items.join(", "))
-                }
+                }),
                 Err(_) => "{<locked>}".into(),
             },
             Value::Set(s) => match s.try_read() {
-                Ok(guard) => {
+                Ok(guard) => display_once(Arc::as_ptr(s) as usize, "{...}", || {
                     let items: Vec<String> = guard.iter().map(|v| v.repr()).collect();
                     format!("{{{}}}", items.join(", "))
-                }
+                }),
                 Err(_) => "{<locked>}".into(),
             },
             Value::Tuple(t) => {
@@ -282,7 +290,7 @@ impl Value {
             Value::NativeFunction(f) => format!("<builtin_function {}>", f.name),
             Value::Response(r) => format!("<Response status={}>", r.status),
             Value::ProcessResult(r) => format!("<Result code={}>", r.code),
-            Value::Iterator(_) => "<iterator>".into(),
+            Value::Iterator(it) => it.repr(),
             Value::Generator(_) => "<generator>".into(),
             Value::StructType(s) => format!("<type {}>", s.name),
             Value::StructInstance(s) => s.to_display_string(),
@@ -305,7 +313,11 @@ impl Value {
             Value::Dict(d) => methods::get_dict_method(d.clone(), name),
             Value::Set(s) => methods::get_set_method(s.clone(), name),
             Value::Iterator(it) => it.get_attr(name),
-            Value::StructInstance(s) => s.get_field(name),
+            Value::StructInstance(s) => s.get_attr(name),
+            Value::StructType(t) => t
+                .methods
+                .get(name)
+                .map(|m| Value::NativeFunction(m.clone())),
             _ => None,
         }
     }
@@ -315,6 +327,61 @@ impl Value {
     }
 }
 
+thread_local! {
+    static DISPLAYING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Renders a mutable container, or `placeholder` if the same container is
+/// already being rendered further up, so self-referential values terminate.
+fn display_once(ptr: usize, placeholder: &str, render: impl FnOnce() -> String) -> String {
+    let entered = DISPLAYING.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        if stack.contains(&ptr) {
+            false
+        } else {
+            stack.push(ptr);
+            true
+        }
+    });
+    if !entered {
+        return placeholder.into();
+    }
+    let out = render();
+    DISPLAYING.with(|stack| {
+        stack.borrow_mut().pop();
+    });
+    out
+}
+
+/// Formats a float the way Python's `repr` does: the shortest digits that
+/// round-trip, in positional form for exponents from -4 to 15 and in
+/// scientific form (`1e+16`, `1.5e-05`) outside that range.
+pub fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        return "nan".into();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "inf" } else { "-inf" }.into();
+    }
+
+    let scientific = format!("{:e}", f);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("`{:e}` output always has an exponent");
+    let exponent: i32 = exponent.parse().expect("`{:e}` exponent is an integer");
+
+    if (-4..16).contains(&exponent) {
+        let mut out = f.to_string();
+        if !out.contains('.') {
+            out.push_str(".0");
+        }
+        out
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    }
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -347,3 +414,96 @@ impl Hash for Value {
         }
     }
 }
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::None
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Int(i)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(f: f64) -> Self {
+        Value::Float(f)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(Arc::new(s))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(Arc::new(s.to_string()))
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Value::List(Arc::new(RwLock::new(
+            items.into_iter().map(Into::into).collect(),
+        )))
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Value::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repr_of_self_referential_containers() {
+        let list = Arc::new(RwLock::new(vec![Value::Int(1)]));
+        let value = Value::List(list.clone());
+        list.try_write().unwrap().push(value.clone());
+        assert_eq!(value.repr(), "[1, [...]]");
+
+        let dict = Arc::new(RwLock::new(IndexMap::new()));
+        let outer = Value::Dict(dict.clone());
+        let inner = Value::from(vec![outer.clone()]);
+        dict.try_write().unwrap().insert("self".to_string(), inner);
+        assert_eq!(outer.to_display_string(), r#"{"self": [{...}]}"#);
+
+        let shared = Value::from(vec![1i64]);
+        let twice = Value::from(vec![shared.clone(), shared]);
+        assert_eq!(twice.repr(), "[[1], [1]]");
+    }
+
+    #[test]
+    fn test_format_float_matches_python_repr() {
+        for (value, expected) in [
+            (2.0, "2.0"),
+            (0.1, "0.1"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (-0.0, "-0.0"),
+            (1e15, "1000000000000000.0"),
+            (1e16, "1e+16"),
+            (1.5e-5, "1.5e-05"),
+            (0.0001, "0.0001"),
+            (123456789012345678.0, "1.2345678901234568e+17"),
+            (1e300, "1e+300"),
+            (f64::INFINITY, "inf"),
+            (f64::NAN, "nan"),
+        ] {
+            assert_eq!(format_float(value), expected);
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::sync::Arc;
 
 use indexmap::IndexMap;
 
-use super::Value;
+use super::{NativeFunction, Value};
 use crate::error::{BlueprintError, Result};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,10 +15,13 @@ pub enum TypeAnnotation {
 }
 
 impl TypeAnnotation {
+    /// Whether `value` fits the annotation. Element types of `list[T]`,
+    /// `set[T]`, `dict[str, V]` and `tuple[A, B, ...]` are checked too.
     pub fn matches(&self, value: &Value) -> bool {
         match self {
             TypeAnnotation::Any => true,
             TypeAnnotation::Simple(name) => match name.as_str() {
+                "any" | "Any" => true,
                 "int" => matches!(value, Value::Int(_)),
                 "float" => matches!(value, Value::Float(_) | Value::Int(_)),
                 "str" => matches!(value, Value::String(_)),
@@ -26,6 +29,7 @@ impl TypeAnnotation {
                 "list" => matches!(value, Value::List(_)),
                 "dict" => matches!(value, Value::Dict(_)),
                 "tuple" => matches!(value, Value::Tuple(_)),
+                "set" => matches!(value, Value::Set(_)),
                 "None" | "NoneType" => matches!(value, Value::None),
                 struct_name => {
                     if let Value::StructInstance(inst) = value {
@@ -35,11 +39,32 @@ impl TypeAnnotation {
                     }
                 }
             },
-            TypeAnnotation::Parameterized(name, _params) => match name.as_str() {
-                "list" => matches!(value, Value::List(_)),
-                "dict" => matches!(value, Value::Dict(_)),
-                _ => false,
-            },
+            TypeAnnotation::Parameterized(name, params) => {
+                // A container another task is writing to is only checked
+                // for its own type.
+                match (name.as_str(), value, params.as_slice()) {
+                    ("list", Value::List(items), [item]) => items
+                        .try_read()
+                        .map_or(true, |items| items.iter().all(|v| item.matches(v))),
+                    ("list", Value::List(_), _) => true,
+                    ("set", Value::Set(items), [item]) => items
+                        .try_read()
+                        .map_or(true, |items| items.iter().all(|v| item.matches(v))),
+                    ("set", Value::Set(_), _) => true,
+                    ("dict", Value::Dict(map), [key, val]) => {
+                        key.matches(&Value::String(Arc::new(String::new())))
+                            && map
+                                .try_read()
+                                .map_or(true, |map| map.values().all(|v| val.matches(v)))
+                    }
+                    ("dict", Value::Dict(_), _) => true,
+                    ("tuple", Value::Tuple(items), params) => {
+                        items.len() == params.len()
+                            && items.iter().zip(params).all(|(v, t)| t.matches(v))
+                    }
+                    _ => false,
+                }
+            }
             TypeAnnotation::Optional(inner) => matches!(value, Value::None) || inner.matches(value),
         }
     }
@@ -64,13 +89,55 @@ pub struct StructField {
     pub default: Option<Value>,
 }
 
+impl StructField {
+    /// Fails with a `TypeError` naming the field if `value` does not fit
+    /// its annotation.
+    pub fn check(&self, struct_name: &str, value: &Value) -> Result<()> {
+        if self.typ.matches(value) {
+            return Ok(());
+        }
+        Err(BlueprintError::TypeError {
+            expected: format!(
+                "{} for field '{}' in {}()",
+                self.typ.type_name(),
+                self.name,
+                struct_name
+            ),
+            actual: value.type_name().to_string(),
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StructType {
     pub name: String,
     pub fields: Vec<StructField>,
+    /// Called with the instance as the first argument when looked up on one.
+    pub methods: IndexMap<String, Arc<NativeFunction>>,
 }
 
 impl StructType {
+    /// A copy of this type with `methods` added, replacing any method of the
+    /// same name. Instances created from the original type are unaffected.
+    pub fn with_methods(
+        &self,
+        methods: impl IntoIterator<Item = (String, Arc<NativeFunction>)>,
+    ) -> Result<StructType> {
+        let mut struct_type = self.clone();
+        for (name, method) in methods {
+            if self.fields.iter().any(|f| f.name == name) {
+                return Err(BlueprintError::ValueError {
+                    message: format!(
+                        "method '{}' of {} clashes with a field of the same name",
+                        name, self.name
+                    ),
+                });
+            }
+            struct_type.methods.insert(name, method);
+        }
+        Ok(struct_type)
+    }
+
     pub fn instantiate(
         &self,
         args: Vec<Value>,
@@ -78,41 +145,35 @@ impl StructType {
     ) -> Result<StructInstance> {
         let mut field_values: IndexMap<String, Value> = IndexMap::new();
 
-        let mut positional_idx = 0;
-        for field in &self.fields {
-            let value = if let Some(v) = kwargs.get(&field.name) {
-                v.clone()
-            } else if positional_idx < args.len() {
-                let v = args[positional_idx].clone();
-                positional_idx += 1;
-                v
-            } else if let Some(default) = &field.default {
-                default.clone()
-            } else {
-                return Err(BlueprintError::ArgumentError {
-                    message: format!(
-                        "{}() missing required argument: '{}'",
-                        self.name, field.name
-                    ),
-                });
+        for (i, field) in self.fields.iter().enumerate() {
+            let value = match (args.get(i), kwargs.get(&field.name)) {
+                (Some(_), Some(_)) => {
+                    return Err(BlueprintError::ArgumentError {
+                        message: format!(
+                            "{}() got multiple values for argument '{}'",
+                            self.name, field.name
+                        ),
+                    });
+                }
+                (Some(v), None) | (None, Some(v)) => v.clone(),
+                (None, None) => match &field.default {
+                    Some(default) => default.clone(),
+                    None => {
+                        return Err(BlueprintError::ArgumentError {
+                            message: format!(
+                                "{}() missing required argument: '{}'",
+                                self.name, field.name
+                            ),
+                        });
+                    }
+                },
             };
 
-            if !field.typ.matches(&value) {
-                return Err(BlueprintError::TypeError {
-                    expected: format!(
-                        "{} for field '{}' in {}()",
-                        field.typ.type_name(),
-                        field.name,
-                        self.name
-                    ),
-                    actual: value.type_name().to_string(),
-                });
-            }
-
+            field.check(&self.name, &value)?;
             field_values.insert(field.name.clone(), value);
         }
 
-        if positional_idx < args.len() {
+        if args.len() > self.fields.len() {
             return Err(BlueprintError::ArgumentError {
                 message: format!(
                     "{}() takes {} positional arguments but {} were given",
@@ -149,6 +210,40 @@ impl StructInstance {
         self.fields.get(name).cloned()
     }
 
+    /// The field `name`, or else the method `name` bound to this instance.
+    /// Fields are looked up first, so a method can never hide one.
+    pub fn get_attr(self: &Arc<Self>, name: &str) -> Option<Value> {
+        if let Some(value) = self.get_field(name) {
+            return Some(value);
+        }
+        let method = self.struct_type.methods.get(name)?.clone();
+        let instance = self.clone();
+        Some(Value::NativeFunction(Arc::new(
+            NativeFunction::new_with_state(
+                format!("{}.{}", self.struct_type.name, name),
+                move |args, kwargs| {
+                    let method = method.clone();
+                    let mut call_args = vec![Value::StructInstance(instance.clone())];
+                    call_args.extend(args);
+                    Box::pin(async move { method.call(call_args, kwargs).await })
+                },
+            ),
+        )))
+    }
+
+    /// Field values keyed by name, in declaration order.
+    pub fn to_dict(&self) -> IndexMap<String, Value> {
+        self.struct_type
+            .fields
+            .iter()
+            .filter_map(|f| {
+                self.fields
+                    .get(&f.name)
+                    .map(|v| (f.name.clone(), v.clone()))
+            })
+            .collect()
+    }
+
     pub fn to_display_string(&self) -> String {
         let field_strs: Vec<String> = self
             .struct_type
@@ -166,3 +261,116 @@ impl StructInstance {
         format!("{}({})", self.struct_type.name, field_strs.join(", "))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::RwLock;
+
+    fn field(name: &str, typ: TypeAnnotation, default: Option<Value>) -> StructField {
+        StructField {
+            name: name.into(),
+            typ,
+            default,
+        }
+    }
+
+    fn simple(name: &str) -> TypeAnnotation {
+        TypeAnnotation::Simple(name.into())
+    }
+
+    #[test]
+    fn test_instantiate_validates_fields() {
+        let config = StructType {
+            name: "Config".into(),
+            fields: vec![
+                field("host", simple("str"), None),
+                field(
+                    "ports",
+                    TypeAnnotation::Parameterized("list".into(), vec![simple("int")]),
+                    None,
+                ),
+                field(
+                    "debug",
+                    TypeAnnotation::Optional(Box::new(simple("bool"))),
+                    Some(Value::None),
+                ),
+            ],
+            methods: IndexMap::new(),
+        };
+        let host = Value::String(Arc::new("localhost".into()));
+        let ports = |items: Vec<Value>| Value::List(Arc::new(RwLock::new(items)));
+
+        let ok = config
+            .instantiate(
+                vec![host.clone(), ports(vec![Value::Int(80)])],
+                HashMap::new(),
+            )
+            .unwrap();
+        assert_eq!(
+            ok.to_display_string(),
+            r#"Config(host="localhost", ports=[80], debug=None)"#
+        );
+
+        let err = config
+            .instantiate(
+                vec![host.clone(), ports(vec![Value::Int(80), host.clone()])],
+                HashMap::new(),
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("list[int] for field 'ports'"),
+            "{}",
+            err
+        );
+
+        let err = config
+            .instantiate(vec![host.clone()], HashMap::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("'ports'"), "{}", err);
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("host".to_string(), host.clone());
+        let err = config.instantiate(vec![host], kwargs).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("multiple values for argument 'host'"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_methods_bind_instance_after_fields() {
+        let point = StructType {
+            name: "Point".into(),
+            fields: vec![field("x", simple("int"), None)],
+            methods: IndexMap::new(),
+        };
+        let getter = Arc::new(NativeFunction::new("get_x", |args, _kwargs| async move {
+            match args.first() {
+                Some(Value::StructInstance(p)) => Ok(p.fields["x"].clone()),
+                _ => Ok(Value::None),
+            }
+        }));
+
+        let err = point
+            .with_methods([("x".to_string(), getter.clone())])
+            .unwrap_err();
+        assert!(err.to_string().contains("clashes with a field"), "{}", err);
+
+        let point = point.with_methods([("get_x".to_string(), getter)]).unwrap();
+        let p = Value::StructInstance(Arc::new(
+            point
+                .instantiate(vec![Value::Int(7)], HashMap::new())
+                .unwrap(),
+        ));
+        let Some(Value::NativeFunction(method)) = p.get_attr("get_x") else {
+            panic!("expected a bound method");
+        };
+        assert_eq!(method.name, "Point.get_x");
+        let x = method.call(vec![], HashMap::new()).await.unwrap();
+        assert!(matches!(x, Value::Int(7)));
+        assert!(p.get_attr("missing").is_none());
+    }
+}
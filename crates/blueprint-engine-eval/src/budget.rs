@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use blueprint_engine_core::{BlueprintError, Result};
+
+use crate::memory::current_rss;
+
+/// How many steps pass between RSS samples when a memory cap is set.
+const MEMORY_CHECK_INTERVAL: u64 = 1024;
+
+/// Limits shared by an evaluator and every evaluator derived from it, so
+/// loaded modules, callbacks and generators draw from the same pool.
+#[derive(Debug)]
+pub(crate) struct Budget {
+    max_steps: Option<u64>,
+    max_memory: Option<u64>,
+    steps: AtomicU64,
+}
+
+impl Budget {
+    pub(crate) fn new(max_steps: Option<u64>, max_memory: Option<u64>) -> Self {
+        Self {
+            max_steps,
+            max_memory,
+            steps: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn max_steps(&self) -> Option<u64> {
+        self.max_steps
+    }
+
+    pub(crate) fn max_memory(&self) -> Option<u64> {
+        self.max_memory
+    }
+
+    pub(crate) fn steps_used(&self) -> u64 {
+        self.steps.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub(crate) fn charge(&self) -> Result<()> {
+        let used = self.steps.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(limit) = self.max_steps {
+            if used > limit {
+                return Err(BlueprintError::ResourceLimit {
+                    resource: "step".into(),
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.max_memory {
+            if used.is_multiple_of(MEMORY_CHECK_INTERVAL) {
+                if let Some(rss) = current_rss().filter(|rss| *rss > limit) {
+                    return Err(BlueprintError::MemoryLimitExceeded { used: rss, limit });
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,17 @@
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
-use blueprint_engine_core::SourceLocation;
+use blueprint_engine_core::{find_workspace_root_from, SourceLocation};
 use blueprint_engine_parser::{
     AssignTargetP, AstExpr, AstParameter, AstStmt, Clause, ExprP, ForClause, ParameterP, StmtP,
 };
 use blueprint_starlark_syntax::codemap::CodeMap;
 use blueprint_starlark_syntax::syntax::ast::ArgumentP;
 
+use crate::eval::workspace_label_to_path;
+
 pub struct CheckerError {
+    pub code: &'static str,
     pub message: String,
     pub location: SourceLocation,
 }
@@ -17,7 +20,11 @@ pub struct Checker {
     codemap: Option<CodeMap>,
     current_file: Option<PathBuf>,
     builtins: HashSet<String>,
+    /// Names bound anywhere at module level. Function bodies run after the
+    /// module has loaded, so they may refer to globals defined further down.
+    module_names: HashSet<String>,
     errors: Vec<CheckerError>,
+    check_imports: bool,
 }
 
 impl Checker {
@@ -27,22 +34,27 @@ impl Checker {
             "True",
             "False",
             "None",
+            "__file__",
             "print",
             "len",
             "range",
+            "frange",
             "str",
             "int",
             "float",
             "bool",
             "list",
             "dict",
+            "dict_from_pairs",
             "tuple",
             "set",
+            "iter",
             "type",
             "isinstance",
             "hasattr",
             "getattr",
             "setattr",
+            "repr",
             "min",
             "max",
             "sum",
@@ -50,13 +62,25 @@ impl Checker {
             "round",
             "sorted",
             "reversed",
+            "partial",
+            "compose",
+            "pipe",
+            "memoize",
+            "with_methods",
             "enumerate",
             "zip",
+            "batched",
+            "windowed",
+            "flatten",
+            "unique",
+            "take",
+            "skip",
             "map",
             "filter",
             "any",
             "all",
             "input",
+            "pprint",
             "open",
             "exit",
             "http",
@@ -73,8 +97,19 @@ impl Checker {
             "run",
             "glob",
             "assert",
+            "assert_true",
+            "assert_eq",
+            "assert_ne",
+            "assert_contains",
+            "breakpoint",
+            "fail",
+            "eval",
             "redact",
             "hash",
+            "hashable",
+            "globals",
+            "locals",
+            "try_int",
         ] {
             builtins.insert(name.to_string());
         }
@@ -83,7 +118,9 @@ impl Checker {
             codemap: None,
             current_file: None,
             builtins,
+            module_names: HashSet::new(),
             errors: Vec::new(),
+            check_imports: true,
         }
     }
 
@@ -92,9 +129,16 @@ impl Checker {
         self
     }
 
+    pub fn without_import_checks(mut self) -> Self {
+        self.check_imports = false;
+        self
+    }
+
     pub fn check(&mut self, module: &blueprint_engine_parser::ParsedModule) -> Vec<CheckerError> {
         self.codemap = Some(module.codemap.clone());
         self.errors.clear();
+        self.module_names.clear();
+        collect_module_names(module.statements(), &mut self.module_names);
 
         let mut scope = CheckScope::new();
         self.check_stmt(module.statements(), &mut scope);
@@ -146,11 +190,20 @@ impl Checker {
                 self.check_stmt(&for_stmt.body, &mut loop_scope);
             }
 
+            StmtP::With(with_stmt) => {
+                self.check_expr(&with_stmt.resource, scope);
+                let mut with_scope = scope.child();
+                if let Some(target) = &with_stmt.target {
+                    self.define_target(target, &mut with_scope);
+                }
+                self.check_stmt(&with_stmt.body, &mut with_scope);
+            }
+
             StmtP::Def(def) => {
                 let func_name = def.name.node.ident.as_str();
                 scope.define(func_name.to_string());
 
-                let mut func_scope = scope.child();
+                let mut func_scope = scope.function();
                 for param in &def.params {
                     if let Some(name) = self.get_param_name(param) {
                         func_scope.define(name);
@@ -165,15 +218,19 @@ impl Checker {
             StmtP::Load(load) => {
                 let module_path = &load.module.node;
 
-                if let Err(msg) = self.check_module_exists(module_path) {
-                    self.errors.push(CheckerError {
-                        message: msg,
-                        location: self.get_location(&stmt.span),
-                    });
+                if self.check_imports {
+                    if let Err(msg) = self.check_module_exists(module_path) {
+                        self.errors.push(CheckerError {
+                            code: "module-not-found",
+                            message: msg,
+                            location: self.get_location(&stmt.span),
+                        });
+                    }
                 }
 
                 if load.args.is_empty() {
-                    if let Some(module_name) = module_path.strip_prefix("@bp/") {
+                    if let Some(module_spec) = module_path.strip_prefix("@bp/") {
+                        let module_name = module_spec.split('@').next().unwrap_or(module_spec);
                         scope.define_frozen(module_name.to_string());
                     }
                 } else if load.args.len() == 1 && load.args[0].their.node == "*" {
@@ -187,6 +244,7 @@ impl Checker {
 
                         if their_name.starts_with('_') && their_name != "__module__" {
                             self.errors.push(CheckerError {
+                                code: "private-import",
                                 message: format!(
                                     "'{}' is private and cannot be imported from '{}'",
                                     their_name, module_path
@@ -250,8 +308,9 @@ impl Checker {
         match &expr.node {
             ExprP::Identifier(ident) => {
                 let name = ident.node.ident.as_str();
-                if !scope.is_defined(name) && !self.builtins.contains(name) {
+                if !self.is_known(name, scope) {
                     self.errors.push(CheckerError {
+                        code: "undefined-variable",
                         message: format!("undefined variable '{}'", name),
                         location: self.get_location(&expr.span),
                     });
@@ -280,6 +339,7 @@ impl Checker {
                         if let Some(var_name) = self.get_identifier_name(target) {
                             if scope.is_frozen(&var_name) {
                                 self.errors.push(CheckerError {
+                                    code: "frozen-mutation",
                                     message: format!(
                                         "cannot call mutating method '.{}()' on frozen import '{}'",
                                         method_name, var_name
@@ -350,7 +410,7 @@ impl Checker {
             }
 
             ExprP::Lambda(lambda) => {
-                let mut lambda_scope = scope.child();
+                let mut lambda_scope = scope.function();
                 for param in &lambda.params {
                     if let Some(name) = self.get_param_name(param) {
                         lambda_scope.define(name);
@@ -392,6 +452,12 @@ impl Checker {
         }
     }
 
+    fn is_known(&self, name: &str, scope: &CheckScope) -> bool {
+        scope.is_defined(name)
+            || self.builtins.contains(name)
+            || (scope.in_function && self.module_names.contains(name))
+    }
+
     fn check_for_clause(
         &mut self,
         clause: &ForClause,
@@ -424,6 +490,7 @@ impl Checker {
                 let name = &ident.node.ident;
                 if scope.is_frozen(name) {
                     self.errors.push(CheckerError {
+                        code: "frozen-reassign",
                         message: format!("cannot reassign imported variable '{}'", name),
                         location: self.get_location(&target.span),
                     });
@@ -435,6 +502,7 @@ impl Checker {
                     self.define_target(t, scope);
                 }
             }
+            AssignTargetP::Starred(inner) => self.define_target(inner, scope),
             AssignTargetP::Index(pair) => {
                 let (target_expr, index_expr) = pair.as_ref();
                 self.check_expr(target_expr, scope);
@@ -443,6 +511,7 @@ impl Checker {
             AssignTargetP::Dot(target_expr, attr) => {
                 self.check_expr(target_expr, scope);
                 self.errors.push(CheckerError {
+                    code: "immutable-struct",
                     message: format!(
                         "cannot assign to field '.{}': structs are immutable",
                         attr.node
@@ -461,13 +530,15 @@ impl Checker {
         match &target.node {
             AssignTargetP::Identifier(ident) => {
                 let name = ident.node.ident.as_str();
-                if !scope.is_defined(name) && !self.builtins.contains(name) {
+                if !self.is_known(name, scope) {
                     self.errors.push(CheckerError {
+                        code: "undefined-variable",
                         message: format!("undefined variable '{}'", name),
                         location: self.get_location(&target.span),
                     });
                 } else if scope.is_frozen(name) {
                     self.errors.push(CheckerError {
+                        code: "frozen-reassign",
                         message: format!("cannot reassign imported variable '{}'", name),
                         location: self.get_location(&target.span),
                     });
@@ -481,6 +552,7 @@ impl Checker {
             AssignTargetP::Dot(target_expr, attr) => {
                 self.check_expr(target_expr, scope);
                 self.errors.push(CheckerError {
+                    code: "immutable-struct",
                     message: format!(
                         "cannot assign to field '.{}': structs are immutable",
                         attr.node
@@ -493,6 +565,7 @@ impl Checker {
                     self.check_assign_target(t, scope);
                 }
             }
+            AssignTargetP::Starred(inner) => self.check_assign_target(inner, scope),
         }
     }
 
@@ -510,6 +583,7 @@ impl Checker {
             ExprP::Minus(inner) => {
                 if !matches!(&inner.node, ExprP::Literal(_)) {
                     self.errors.push(CheckerError {
+                        code: "invalid-pattern",
                         message: "invalid pattern: negation only allowed on literals".to_string(),
                         location: self.get_location(&pattern.span),
                     });
@@ -528,6 +602,8 @@ impl Checker {
                 }
             }
 
+            ExprP::Starred(inner) => self.check_pattern(inner, scope),
+
             ExprP::Dict(pairs) => {
                 for (key, val) in pairs {
                     self.check_expr(key, scope);
@@ -550,6 +626,7 @@ impl Checker {
                         );
                         if !is_type_constraint && !scope.is_defined(name) {
                             self.errors.push(CheckerError {
+                                code: "undefined-variable",
                                 message: format!("undefined name in pattern: {}", name),
                                 location: self.get_location(&callee.span),
                             });
@@ -557,6 +634,7 @@ impl Checker {
                     }
                     _ => {
                         self.errors.push(CheckerError {
+                            code: "invalid-pattern",
                             message: "struct pattern must use a simple name".to_string(),
                             location: self.get_location(&callee.span),
                         });
@@ -570,6 +648,7 @@ impl Checker {
                         }
                         _ => {
                             self.errors.push(CheckerError {
+                                code: "invalid-pattern",
                                 message: "only positional and keyword arguments allowed in struct patterns".to_string(),
                                 location: self.get_location(&arg.span),
                             });
@@ -580,6 +659,7 @@ impl Checker {
 
             _ => {
                 self.errors.push(CheckerError {
+                    code: "invalid-pattern",
                     message: "unsupported pattern type".to_string(),
                     location: self.get_location(&pattern.span),
                 });
@@ -626,6 +706,25 @@ impl Checker {
             return Ok(());
         }
 
+        if let Some(label) = module_path.strip_prefix("//") {
+            let start_dir = self
+                .current_file
+                .as_ref()
+                .and_then(|f| f.parent().map(|p| p.to_path_buf()))
+                .or_else(|| std::env::current_dir().ok())
+                .unwrap_or_else(|| PathBuf::from("."));
+            let workspace_root = find_workspace_root_from(start_dir).ok_or_else(|| {
+                format!(
+                    "cannot resolve '{}': no BP.toml workspace root found",
+                    module_path
+                )
+            })?;
+            if !workspace_root.join(workspace_label_to_path(label)).exists() {
+                return Err(format!("module '{}' not found", module_path));
+            }
+            return Ok(());
+        }
+
         if module_path.starts_with("./") || module_path.starts_with("../") {
             let current_dir = self
                 .current_file
@@ -675,9 +774,74 @@ impl Default for Checker {
     }
 }
 
+/// Records every name a module binds at top level, including inside `if`
+/// and `for` blocks, but not inside function bodies.
+fn collect_module_names(stmt: &AstStmt, names: &mut HashSet<String>) {
+    match &stmt.node {
+        StmtP::Statements(stmts) => {
+            for s in stmts {
+                collect_module_names(s, names);
+            }
+        }
+        StmtP::Assign(assign) => collect_target_names(&assign.lhs, names),
+        StmtP::If(_, then_block) => collect_module_names(then_block, names),
+        StmtP::IfElse(_, branches) => {
+            let (then_block, else_block) = branches.as_ref();
+            collect_module_names(then_block, names);
+            collect_module_names(else_block, names);
+        }
+        StmtP::For(for_stmt) => {
+            collect_target_names(&for_stmt.var, names);
+            collect_module_names(&for_stmt.body, names);
+        }
+        StmtP::With(with_stmt) => {
+            if let Some(target) = &with_stmt.target {
+                collect_target_names(target, names);
+            }
+            collect_module_names(&with_stmt.body, names);
+        }
+        StmtP::Def(def) => {
+            names.insert(def.name.node.ident.clone());
+        }
+        StmtP::Struct(struct_def) => {
+            names.insert(struct_def.name.node.ident.clone());
+        }
+        StmtP::Load(load) => {
+            for arg in &load.args {
+                names.insert(arg.local.node.ident.clone());
+            }
+        }
+        StmtP::Match(match_stmt) => {
+            for case in &match_stmt.cases {
+                collect_module_names(&case.node.body, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_target_names(
+    target: &blueprint_starlark_syntax::syntax::ast::AstAssignTarget,
+    names: &mut HashSet<String>,
+) {
+    match &target.node {
+        AssignTargetP::Identifier(ident) => {
+            names.insert(ident.node.ident.clone());
+        }
+        AssignTargetP::Tuple(targets) => {
+            for t in targets {
+                collect_target_names(t, names);
+            }
+        }
+        AssignTargetP::Starred(inner) => collect_target_names(inner, names),
+        AssignTargetP::Index(_) | AssignTargetP::Dot(..) => {}
+    }
+}
+
 struct CheckScope {
     defined: HashSet<String>,
     frozen: HashSet<String>,
+    in_function: bool,
     parent: Option<Box<CheckScope>>,
 }
 
@@ -686,6 +850,7 @@ impl CheckScope {
         Self {
             defined: HashSet::new(),
             frozen: HashSet::new(),
+            in_function: false,
             parent: None,
         }
     }
@@ -694,11 +859,16 @@ impl CheckScope {
         Self {
             defined: HashSet::new(),
             frozen: HashSet::new(),
-            parent: Some(Box::new(Self {
-                defined: self.defined.clone(),
-                frozen: self.frozen.clone(),
-                parent: self.parent.clone(),
-            })),
+            in_function: self.in_function,
+            parent: Some(Box::new(self.clone())),
+        }
+    }
+
+    /// A scope for a function or lambda body.
+    fn function(&self) -> Self {
+        Self {
+            in_function: true,
+            ..self.child()
         }
     }
 
@@ -737,6 +907,7 @@ impl Clone for CheckScope {
         Self {
             defined: self.defined.clone(),
             frozen: self.frozen.clone(),
+            in_function: self.in_function,
             parent: self.parent.clone(),
         }
     }
@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use blueprint_engine_parser::{AstStmt, StmtP};
+use blueprint_starlark_syntax::codemap::{CodeMap, Pos, Span};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    /// Executable lines by file, so lines that never ran show up in reports
+    /// with a count of zero.
+    static ref LINES: Mutex<BTreeMap<PathBuf, BTreeSet<usize>>> = Mutex::new(BTreeMap::new());
+    /// Hit counts of every module evaluated so far, merged into `LINES` when
+    /// a report is taken.
+    static ref MODULES: Mutex<Vec<Arc<ModuleHits>>> = Mutex::new(Vec::new());
+}
+
+/// Hit counts for one evaluation of a module, held by its evaluator. Counts
+/// are keyed by where each statement starts, and only resolved to lines when
+/// a report is taken, once per statement rather than once per hit.
+pub(crate) struct ModuleHits {
+    file: PathBuf,
+    codemap: CodeMap,
+    counts: Mutex<HashMap<u32, u64>>,
+}
+
+impl ModuleHits {
+    pub(crate) fn record(&self, span: Span) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        *counts.entry(span.begin().get()).or_default() += 1;
+    }
+
+    /// Hit counts by 1-based line.
+    fn lines(&self) -> Vec<(usize, u64)> {
+        let end = self.codemap.full_span().end();
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        counts
+            .iter()
+            .map(|(&begin, &n)| (Pos::new(begin), n))
+            .filter(|(pos, _)| *pos <= end)
+            .map(|(pos, n)| (self.codemap.find_line(pos) + 1, n))
+            .collect()
+    }
+}
+
+/// Turns on line coverage for every evaluator in the process. Disabled runs
+/// only pay for a relaxed atomic load per statement.
+pub fn enable_coverage() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn coverage_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records the executable lines of a module about to be evaluated and
+/// returns the counts its evaluator should record hits into.
+pub(crate) fn register_module(file: &Path, codemap: &CodeMap, stmt: &AstStmt) -> Arc<ModuleHits> {
+    let mut lines = Vec::new();
+    collect_lines(codemap, stmt, &mut lines);
+    LINES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(file.to_path_buf())
+        .or_default()
+        .extend(lines);
+
+    let hits = Arc::new(ModuleHits {
+        file: file.to_path_buf(),
+        codemap: codemap.clone(),
+        counts: Mutex::new(HashMap::new()),
+    });
+    MODULES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(hits.clone());
+    hits
+}
+
+/// The first line of every statement, including those inside function
+/// bodies and branches that may never run.
+fn collect_lines(codemap: &CodeMap, stmt: &AstStmt, lines: &mut Vec<usize>) {
+    if let StmtP::Statements(stmts) = &stmt.node {
+        for s in stmts {
+            collect_lines(codemap, s, lines);
+        }
+        return;
+    }
+
+    lines.push(codemap.resolve_span(stmt.span).begin.line + 1);
+    match &stmt.node {
+        StmtP::If(_, then_block) => collect_lines(codemap, then_block, lines),
+        StmtP::IfElse(_, branches) => {
+            collect_lines(codemap, &branches.0, lines);
+            collect_lines(codemap, &branches.1, lines);
+        }
+        StmtP::For(for_stmt) => collect_lines(codemap, &for_stmt.body, lines),
+        StmtP::Def(def) => collect_lines(codemap, &def.body, lines),
+        StmtP::With(with_stmt) => collect_lines(codemap, &with_stmt.body, lines),
+        StmtP::Match(match_stmt) => {
+            for case in &match_stmt.cases {
+                collect_lines(codemap, &case.node.body, lines);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FileCoverage {
+    pub path: PathBuf,
+    /// Executable lines and how many times each ran, in line order.
+    pub lines: Vec<(usize, u64)>,
+}
+
+impl FileCoverage {
+    pub fn covered(&self) -> usize {
+        self.lines.iter().filter(|(_, hits)| *hits > 0).count()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CoverageReport {
+    pub files: Vec<FileCoverage>,
+}
+
+pub fn coverage_snapshot() -> CoverageReport {
+    let mut hits: BTreeMap<PathBuf, BTreeMap<usize, u64>> = BTreeMap::new();
+    for (file, lines) in LINES.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        let counts = hits.entry(file.clone()).or_default();
+        for &line in lines {
+            counts.entry(line).or_default();
+        }
+    }
+    let modules = MODULES.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    for module in &modules {
+        let counts = hits.entry(module.file.clone()).or_default();
+        for (line, n) in module.lines() {
+            *counts.entry(line).or_default() += n;
+        }
+    }
+
+    CoverageReport {
+        files: hits
+            .iter()
+            .map(|(path, lines)| FileCoverage {
+                path: path.clone(),
+                lines: lines.iter().map(|(line, n)| (*line, *n)).collect(),
+            })
+            .collect(),
+    }
+}
+
+impl CoverageReport {
+    pub fn covered(&self) -> usize {
+        self.files.iter().map(FileCoverage::covered).sum()
+    }
+
+    pub fn total(&self) -> usize {
+        self.files.iter().map(|f| f.lines.len()).sum()
+    }
+
+    /// The `lcov` tracefile format read by `genhtml` and most CI services.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            let _ = writeln!(out, "TN:");
+            let _ = writeln!(out, "SF:{}", file.path.display());
+            for (line, hits) in &file.lines {
+                let _ = writeln!(out, "DA:{},{}", line, hits);
+            }
+            let _ = writeln!(out, "LF:{}", file.lines.len());
+            let _ = writeln!(out, "LH:{}", file.covered());
+            let _ = writeln!(out, "end_of_record");
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let files: Vec<serde_json::Value> = self
+            .files
+            .iter()
+            .map(|file| {
+                let lines: serde_json::Map<String, serde_json::Value> = file
+                    .lines
+                    .iter()
+                    .map(|(line, hits)| (line.to_string(), serde_json::json!(hits)))
+                    .collect();
+                serde_json::json!({
+                    "path": file.path.to_string_lossy(),
+                    "lines": lines,
+                    "covered": file.covered(),
+                    "total": file.lines.len(),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "files": files,
+            "covered": self.covered(),
+            "total": self.total(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lcov_lists_every_executable_line() {
+        let source = "def f(x):\n    if x:\n        return 1\n    return 2\n\nf(False)\n";
+        let module = blueprint_engine_parser::parse("cov.bp", source).unwrap();
+        let mut lines = Vec::new();
+        collect_lines(&module.codemap, module.statements(), &mut lines);
+        assert_eq!(lines, vec![1, 2, 3, 4, 6]);
+
+        let report = CoverageReport {
+            files: vec![FileCoverage {
+                path: "cov.bp".into(),
+                lines: vec![(1, 1), (2, 1), (3, 0), (4, 1), (6, 1)],
+            }],
+        };
+        assert_eq!(
+            report.to_lcov(),
+            "TN:\nSF:cov.bp\nDA:1,1\nDA:2,1\nDA:3,0\nDA:4,1\nDA:6,1\nLF:5\nLH:4\nend_of_record\n"
+        );
+        assert_eq!(report.to_json()["covered"], 4);
+    }
+
+    #[test]
+    fn test_hits_resolve_to_lines_when_reported() {
+        let source = "x = 1\nif x:\n    y = 2\nz = 3\n";
+        let module = blueprint_engine_parser::parse("hits.bp", source).unwrap();
+        let file = Path::new("/coverage-test/hits.bp");
+        let hits = register_module(file, &module.codemap, module.statements());
+        let StmtP::Statements(stmts) = &module.statements().node else {
+            panic!("expected statements");
+        };
+        hits.record(stmts[0].span);
+        hits.record(stmts[2].span);
+        hits.record(stmts[2].span);
+
+        let report = coverage_snapshot();
+        let file = report.files.iter().find(|f| f.path == file).unwrap();
+        assert_eq!(file.lines, vec![(1, 1), (2, 0), (3, 0), (4, 2)]);
+    }
+}
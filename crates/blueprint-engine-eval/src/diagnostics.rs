@@ -0,0 +1,172 @@
+use blueprint_engine_core::{BlueprintError, SourceLocation};
+use blueprint_engine_parser::parse_all;
+
+use crate::checker::Checker;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Zero-based line and character position, as used by the language server protocol:
+/// `character` counts UTF-16 code units from the start of the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub range: Range,
+    pub message: String,
+    pub code: String,
+}
+
+/// Parses and statically checks `source` without reading other files or running anything.
+pub fn diagnostics(filename: &str, source: &str) -> Vec<Diagnostic> {
+    let (module, parse_errors) = parse_all(filename, source);
+
+    let mut result: Vec<Diagnostic> = parse_errors
+        .iter()
+        .filter_map(|error| match error {
+            BlueprintError::ParseError { location, message } => Some(Diagnostic {
+                severity: Severity::Error,
+                range: location_range(source, location),
+                message: message.lines().next().unwrap_or_default().to_string(),
+                code: "syntax-error".to_string(),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    if parse_errors.is_empty() {
+        if let Some(module) = module {
+            let mut checker = Checker::new().without_import_checks();
+            for error in checker.check(&module) {
+                result.push(Diagnostic {
+                    severity: Severity::Error,
+                    range: location_range(source, &error.location),
+                    message: error.message,
+                    code: error.code.to_string(),
+                });
+            }
+        }
+    }
+
+    result
+}
+
+fn location_range(source: &str, location: &SourceLocation) -> Range {
+    match &location.span {
+        Some(span) => Range {
+            start: offset_to_position(source, span.start),
+            end: offset_to_position(source, span.end),
+        },
+        None => {
+            // Without a span, the location's column counts characters.
+            let line = location.line.saturating_sub(1);
+            let text = source.lines().nth(line).unwrap_or_default();
+            let position = Position {
+                line,
+                character: utf16_len(text.chars().take(location.column.saturating_sub(1))),
+            };
+            Range {
+                start: position,
+                end: position,
+            }
+        }
+    }
+}
+
+/// Converts a byte offset into `source` to a line and UTF-16 column.
+fn offset_to_position(source: &str, offset: usize) -> Position {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Position {
+        line,
+        character: utf16_len(before[line_start..].chars()),
+    }
+}
+
+fn utf16_len(chars: impl Iterator<Item = char>) -> usize {
+    chars.map(char::len_utf16).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics_clean_source() {
+        assert!(diagnostics("test.bp", "x = 1\nprint(x)\n").is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics_undefined_variable() {
+        let result = diagnostics("test.bp", "x = 1\nprint(y)\n");
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].code, "undefined-variable");
+        assert_eq!(
+            result[0].range.start,
+            Position {
+                line: 1,
+                character: 6
+            }
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_count_utf16_units() {
+        let result = diagnostics("test.bp", "s = \"é🎉\" + y\n");
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result[0].range,
+            Range {
+                start: Position {
+                    line: 0,
+                    character: 12
+                },
+                end: Position {
+                    line: 0,
+                    character: 13
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_syntax_errors() {
+        let result = diagnostics("test.bp", "x = )\ny = 1\nz = ]\n");
+        assert_eq!(result.len(), 2);
+        assert!(result.iter().all(|d| d.code == "syntax-error"));
+        assert_eq!(result[1].range.start.line, 2);
+    }
+
+    #[test]
+    fn test_diagnostics_allow_later_globals_in_function_bodies() {
+        let source = "def f():\n    return g()\nx = g\ndef g():\n    return 1\n";
+        let result = diagnostics("test.bp", source);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].range.start.line, 2);
+    }
+
+    #[test]
+    fn test_diagnostics_do_not_resolve_imports() {
+        let result = diagnostics("test.bp", "load(\"./missing.bp\", \"f\")\nf()\n");
+        assert!(result.is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use blueprint_engine_core::{BlueprintError, NativeFunction, Result, Value};
+use blueprint_engine_parser::parse;
+
+use crate::eval::Evaluator;
+use crate::resolver::ModuleResolver;
+use crate::scope::Scope;
+
+/// An embeddable interpreter: an [`Evaluator`] plus a global scope that
+/// persists across calls to [`Engine::eval`].
+///
+/// ```ignore
+/// let greeting = Arc::new(String::from("Hello"));
+/// let mut engine = Engine::builder()
+///     .register_fn("greet", move |args| {
+///         Ok(format!("{}, {}!", greeting, args[0].as_string()?))
+///     })
+///     .build();
+/// let value = engine.eval("greet('world')").await?;
+/// ```
+pub struct Engine {
+    evaluator: Evaluator,
+    scope: Arc<Scope>,
+}
+
+#[derive(Default)]
+pub struct EngineBuilder {
+    functions: Vec<NativeFunction>,
+    globals: Vec<(String, Value)>,
+    resolver: Option<Arc<dyn ModuleResolver>>,
+    max_steps: Option<u64>,
+    file: Option<PathBuf>,
+}
+
+impl EngineBuilder {
+    /// Registers a synchronous host function taking positional arguments.
+    /// Anything convertible into a [`Value`] can be returned.
+    pub fn register_fn<F, R>(mut self, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(Vec<Value>) -> Result<R> + Send + Sync + 'static,
+        R: Into<Value>,
+    {
+        let name = name.into();
+        let fn_name = name.clone();
+        self.functions
+            .push(NativeFunction::new(name, move |args, kwargs| {
+                let result = if kwargs.is_empty() {
+                    f(args).map(Into::into)
+                } else {
+                    Err(BlueprintError::ArgumentError {
+                        message: format!("{}() does not accept keyword arguments", fn_name),
+                    })
+                };
+                async move { result }
+            }));
+        self
+    }
+
+    /// Registers an async host function that also receives keyword arguments.
+    pub fn register_async_fn<F, Fut, R>(mut self, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(Vec<Value>, HashMap<String, Value>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R>> + Send + 'static,
+        R: Into<Value>,
+    {
+        self.functions
+            .push(NativeFunction::new(name, move |args, kwargs| {
+                let fut = f(args, kwargs);
+                async move { fut.await.map(Into::into) }
+            }));
+        self
+    }
+
+    /// Predefines a global visible to the script and every module it loads.
+    pub fn global(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.globals.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn module_resolver(mut self, resolver: impl ModuleResolver + 'static) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// See [`Evaluator::with_step_budget`]. The budget covers the engine's
+    /// whole lifetime, not each `eval` call.
+    pub fn step_budget(mut self, steps: u64) -> Self {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    /// Resolves relative `load()` paths as if scripts lived at `path`.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    pub fn build(self) -> Engine {
+        let mut evaluator = Evaluator::new_isolated();
+        if let Some(steps) = self.max_steps {
+            evaluator = evaluator.with_step_budget(steps);
+        }
+        if let Some(file) = self.file {
+            evaluator.set_file(file);
+        }
+        for func in self.functions {
+            evaluator.register_native(func);
+        }
+        for (name, value) in self.globals {
+            evaluator.define_global(name, value);
+        }
+        if let Some(resolver) = self.resolver {
+            evaluator.set_module_resolver(resolver);
+        }
+        Engine {
+            evaluator,
+            scope: Scope::new_global(),
+        }
+    }
+}
+
+impl Engine {
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+
+    /// Evaluates `source` in the engine's global scope and returns the value
+    /// of its final expression, or `None` if it ends with a statement.
+    pub async fn eval(&mut self, source: &str) -> Result<Value> {
+        let module = parse("<engine>", source)?;
+        self.evaluator.eval_value(&module, self.scope.clone()).await
+    }
+
+    /// Looks up a global defined by previously evaluated code.
+    pub async fn get(&self, name: &str) -> Option<Value> {
+        self.scope.get(name).await
+    }
+
+    /// Calls a function defined by previously evaluated code.
+    pub async fn call(&self, name: &str, args: Vec<Value>) -> Result<Value> {
+        let func = self
+            .get(name)
+            .await
+            .ok_or_else(|| BlueprintError::NameError {
+                name: name.to_string(),
+            })?;
+        self.evaluator
+            .call_function(func, args, HashMap::new(), self.scope.clone())
+            .await
+    }
+
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.evaluator
+    }
+
+    pub fn evaluator_mut(&mut self) -> &mut Evaluator {
+        &mut self.evaluator
+    }
+
+    pub fn scope(&self) -> &Arc<Scope> {
+        &self.scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::MemoryResolver;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    #[tokio::test]
+    async fn test_engine_calls_closure_with_host_state() {
+        let counter = Arc::new(AtomicI64::new(0));
+        let host_counter = counter.clone();
+        let mut engine = Engine::builder()
+            .register_fn("bump", move |args| {
+                let by = args.first().map(|v| v.as_int()).transpose()?.unwrap_or(1);
+                Ok(host_counter.fetch_add(by, Ordering::SeqCst) + by)
+            })
+            .register_fn("greet", |args| {
+                Ok(format!("hello {}", args[0].as_string()?))
+            })
+            .build();
+
+        let value = engine
+            .eval("def twice():\n    bump()\n    return bump(3)\ntwice()\n")
+            .await
+            .unwrap();
+        assert!(matches!(value, Value::Int(4)));
+        assert_eq!(counter.load(Ordering::SeqCst), 4);
+
+        let greeting = engine.eval("[greet(n) for n in ['a', 'b']]").await.unwrap();
+        assert_eq!(greeting.repr(), r#"["hello a", "hello b"]"#);
+
+        assert!(engine.eval("greet(name='x')").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_engine_scope_persists_between_evals() {
+        let mut engine = Engine::builder().build();
+        engine
+            .eval("def add(a, b):\n    return a + b\n")
+            .await
+            .unwrap();
+        let sum = engine
+            .call("add", vec![Value::Int(2), Value::Int(3)])
+            .await
+            .unwrap();
+        assert!(matches!(sum, Value::Int(5)));
+    }
+
+    #[tokio::test]
+    async fn test_engine_globals_and_memory_resolver() {
+        let resolver = MemoryResolver::new()
+            .with_module(
+                "lib.bp",
+                "load(\"util.bp\", \"twice\")\ndef scaled(x):\n    return twice(x) * FACTOR\n",
+            )
+            .with_module("util.bp", "def twice(x):\n    return x * 2\n");
+        let mut engine = Engine::builder()
+            .global("FACTOR", 10i64)
+            .module_resolver(resolver)
+            .build();
+
+        let value = engine
+            .eval("load(\"lib.bp\", \"scaled\")\nscaled(FACTOR)")
+            .await
+            .unwrap();
+        assert!(matches!(value, Value::Int(200)));
+
+        let err = engine
+            .eval("load(\"/etc/passwd\", \"x\")")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not available"));
+    }
+}
@@ -1,9 +1,9 @@
 use std::sync::Arc;
 
-use blueprint_engine_core::{BlueprintError, Result, Value};
+use blueprint_engine_core::{intern, BlueprintError, Result, Value};
 use blueprint_starlark_syntax::syntax::ast::{AssignTargetP, AstAssignTarget};
 
-use super::Evaluator;
+use super::{gather_starred, Evaluator};
 use crate::scope::Scope;
 
 impl Evaluator {
@@ -20,8 +20,22 @@ impl Evaluator {
                 Ok(())
             }
             AssignTargetP::Tuple(targets) => {
-                let values = self.get_iterable(&value).await?;
-                if values.len() != targets.len() {
+                let mut values = self.get_iterable(&value).await?;
+                let star = targets
+                    .iter()
+                    .position(|t| matches!(t.node, AssignTargetP::Starred(_)));
+                if let Some(star) = star {
+                    if values.len() < targets.len() - 1 {
+                        return Err(BlueprintError::ValueError {
+                            message: format!(
+                                "cannot unpack {} values into at least {} targets",
+                                values.len(),
+                                targets.len() - 1
+                            ),
+                        });
+                    }
+                    values = gather_starred(values, targets.len(), star);
+                } else if values.len() != targets.len() {
                     return Err(BlueprintError::ValueError {
                         message: format!(
                             "cannot unpack {} values into {} targets",
@@ -35,6 +49,7 @@ impl Evaluator {
                 }
                 Ok(())
             }
+            AssignTargetP::Starred(inner) => self.assign_target(inner, value, scope).await,
             AssignTargetP::Index(pair) => {
                 let (target_expr, index_expr) = pair.as_ref();
                 let target_val = self.eval_expr(target_expr, scope.clone()).await?;
@@ -102,9 +117,11 @@ impl Evaluator {
                     }
                 })
             }
-            AssignTargetP::Tuple(_) => Err(BlueprintError::Unsupported {
-                message: "augmented assignment to tuple".into(),
-            }),
+            AssignTargetP::Tuple(_) | AssignTargetP::Starred(_) => {
+                Err(BlueprintError::Unsupported {
+                    message: "augmented assignment to tuple".into(),
+                })
+            }
         }
     }
 
@@ -118,15 +135,27 @@ impl Evaluator {
                 .collect()),
             Value::Dict(d) => {
                 let map = d.read().await;
-                Ok(map
-                    .keys()
-                    .map(|k| Value::String(Arc::new(k.clone())))
-                    .collect())
+                Ok(map.keys().map(|k| Value::String(intern(k))).collect())
             }
             Value::Set(s) => {
                 let set = s.read().await;
                 Ok(set.iter().cloned().collect())
             }
+            Value::Generator(gen) => {
+                let mut items = Vec::new();
+                while let Some(item) = gen.try_next().await? {
+                    items.push(item);
+                }
+                Ok(items)
+            }
+            Value::Iterator(iter) => {
+                let iter = iter.iter().await;
+                let mut items = Vec::with_capacity(iter.len().unwrap_or(0));
+                while let Some(item) = iter.try_next().await? {
+                    items.push(item);
+                }
+                Ok(items)
+            }
             _ => Err(BlueprintError::TypeError {
                 expected: "iterable".into(),
                 actual: value.type_name().into(),
@@ -48,6 +48,10 @@ impl Evaluator {
         let ForClause { var, over, .. } = for_clause;
         let iterable = self.eval_expr(over, scope.clone()).await?;
         let items = self.get_iterable(&iterable).await?;
+        if remaining.is_empty() {
+            // Every item yields exactly one result, so size for all of them.
+            results.reserve(items.len());
+        }
 
         for item in items {
             let iter_scope = Scope::new_child(scope.clone(), ScopeKind::Block);
@@ -136,6 +140,9 @@ impl Evaluator {
         let ForClause { var, over, .. } = for_clause;
         let iterable = self.eval_expr(over, scope.clone()).await?;
         let items = self.get_iterable(&iterable).await?;
+        if remaining.is_empty() {
+            results.reserve(items.len());
+        }
 
         for item in items {
             let iter_scope = Scope::new_child(scope.clone(), ScopeKind::Block);
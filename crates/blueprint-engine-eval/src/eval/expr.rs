@@ -10,10 +10,15 @@ use blueprint_starlark_syntax::syntax::ast::{AstLiteral, BinOp, ExprP};
 use super::ops;
 use super::Evaluator;
 use crate::scope::Scope;
+use crate::stats;
+use crate::trace;
 
 impl Evaluator {
     #[async_recursion::async_recursion]
     pub async fn eval_expr(&self, expr: &AstExpr, scope: Arc<Scope>) -> Result<Value> {
+        if let Some(budget) = &self.budget {
+            budget.charge()?;
+        }
         match &expr.node {
             ExprP::Literal(lit) => self.eval_literal(lit),
 
@@ -31,6 +36,10 @@ impl Evaluator {
                     return Ok(value);
                 }
 
+                if let Some(value) = self.globals.get(name) {
+                    return Ok(value.clone());
+                }
+
                 if let Some(native) = self.builtins.get(name) {
                     return Ok(Value::NativeFunction(native.clone()));
                 }
@@ -76,18 +85,14 @@ impl Evaluator {
                 Ok(Value::Set(Arc::new(tokio::sync::RwLock::new(set))))
             }
 
-            ExprP::Call(callee, args) => {
-                let func = self.eval_expr(callee, scope.clone()).await?;
-                let (positional, kwargs) = self.eval_call_args(&args.args, scope.clone()).await?;
-                self.call_function(func, positional, kwargs, scope).await
-            }
+            ExprP::Call(..) => self.eval_call(expr, false, scope).await,
 
             ExprP::Index(pair) => {
                 let (target, index) = pair.as_ref();
                 let target_val = self.eval_expr(target, scope.clone()).await?;
                 let index_val = self.eval_expr(index, scope).await?;
                 self.eval_index(target_val, index_val).await.map_err(|e| {
-                    let (line, column) = self.get_span_location(&expr.span);
+                    let (line, column) = self.display_location(&expr.span);
                     let file = self
                         .current_file
                         .as_ref()
@@ -184,7 +189,7 @@ impl Evaluator {
 
                 let right = self.eval_expr(rhs, scope).await?;
                 ops::eval_binary_op(left, *op, right).await.map_err(|e| {
-                    let (line, column) = self.get_span_location(&expr.span);
+                    let (line, column) = self.display_location(&expr.span);
                     let file = self
                         .current_file
                         .as_ref()
@@ -347,6 +352,7 @@ impl Evaluator {
                     None => Err(BlueprintError::KeyError { key }),
                 }
             }
+            Value::Iterator(it) if it.is_view() => it.index(index.as_int()?).await,
             Value::Generator(_) | Value::Iterator(_) => Err(BlueprintError::TypeError {
                 expected: "subscriptable (use list() to materialize generator first)".into(),
                 actual: target.type_name().into(),
@@ -385,6 +391,16 @@ impl Evaluator {
                 let slice: Vec<Value> = t[start_idx..end_idx].to_vec();
                 Ok(Value::Tuple(Arc::new(slice)))
             }
+            Value::Iterator(it) if it.is_view() => {
+                let len = it.len().unwrap_or(0) as i64;
+                let (start_idx, end_idx) = self.normalize_slice_indices(start, end, len)?;
+                it.slice(start_idx, end_idx)
+                    .await
+                    .ok_or_else(|| BlueprintError::TypeError {
+                        expected: "sliceable".into(),
+                        actual: target.type_name().into(),
+                    })
+            }
             _ => Err(BlueprintError::TypeError {
                 expected: "sliceable".into(),
                 actual: target.type_name().into(),
@@ -581,6 +597,38 @@ impl Evaluator {
         ops::eval_in(left, right, |v| self.value_to_dict_key(v)).await
     }
 
+    /// Evaluates the call expression `expr`. With `tail` set, a direct call
+    /// to the running function is handed back to `call_user_function` as a
+    /// `TailCall`, so self-recursion in tail position runs in constant stack.
+    #[async_recursion::async_recursion]
+    pub(super) async fn eval_call(
+        &self,
+        expr: &AstExpr,
+        tail: bool,
+        scope: Arc<Scope>,
+    ) -> Result<Value> {
+        let ExprP::Call(callee, args) = &expr.node else {
+            return self.eval_expr(expr, scope).await;
+        };
+        let func = self.eval_expr(callee, scope.clone()).await?;
+        let (positional, kwargs) = self.eval_call_args(&args.args, scope.clone()).await?;
+        let span = match &func {
+            Value::Function(f) if tail && scope.function_id() == Some(Arc::as_ptr(f) as usize) => {
+                if stats::stats_enabled() {
+                    stats::record_function_call(&f.name);
+                }
+                return Err(BlueprintError::TailCall {
+                    args: positional,
+                    kwargs,
+                });
+            }
+            Value::NativeFunction(native) => trace::native_span(self, &native.name, &expr.span),
+            _ => trace::no_span(),
+        };
+        // A single call site keeps one `call_function` future in this frame.
+        trace::instrument(span, self.call_function(func, positional, kwargs, scope)).await
+    }
+
     pub async fn eval_call_args(
         &self,
         args: &[blueprint_engine_parser::AstArgument],
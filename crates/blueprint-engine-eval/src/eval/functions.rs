@@ -5,12 +5,15 @@ use indexmap::IndexMap;
 use tokio::sync::mpsc;
 
 use blueprint_engine_core::{
-    BlueprintError, Generator, GeneratorMessage, Result, StackFrame, Value,
+    inherit_permissions, BlueprintError, Generator, GeneratorMessage, Result, StackFrame, Value,
 };
-use blueprint_engine_parser::{AstExpr, AstStmt};
+use blueprint_engine_parser::{AstExpr, AstStmt, StmtP};
 
 use super::Evaluator;
+use crate::profiler;
 use crate::scope::{Scope, ScopeKind};
+use crate::stats;
+use crate::trace;
 
 impl Evaluator {
     pub async fn handle_yield(&self, expr: Option<&AstExpr>, scope: Arc<Scope>) -> Result<Value> {
@@ -48,10 +51,33 @@ impl Evaluator {
         kwargs: HashMap<String, Value>,
         scope: Arc<Scope>,
     ) -> Result<Value> {
+        // The builtins that need the evaluator are rare; boxing them keeps
+        // their futures out of every call's stack frame. User functions and
+        // lambdas are boxed too, so recursion pays for one large frame per
+        // level rather than two.
         match func {
-            Value::NativeFunction(f) => f.call(args, kwargs).await,
-            Value::Function(f) => self.call_user_function(&f, args, kwargs, scope).await,
-            Value::Lambda(f) => self.call_lambda(&f, args, kwargs, scope).await,
+            Value::NativeFunction(f) if self.is_builtin(&f, "breakpoint") => {
+                Box::pin(crate::modules::run_breakpoint(scope)).await
+            }
+            Value::NativeFunction(f)
+                if self.is_builtin(&f, "globals") || self.is_builtin(&f, "locals") =>
+            {
+                Box::pin(crate::modules::scope_bindings(
+                    self, &f.name, scope, args, kwargs,
+                ))
+                .await
+            }
+            Value::NativeFunction(f) if self.is_builtin(&f, "eval") => {
+                Box::pin(self.eval_source(args, kwargs)).await
+            }
+            Value::NativeFunction(f) => {
+                if stats::stats_enabled() && self.is_builtin(&f, &f.name) {
+                    stats::record_native_call("builtins");
+                }
+                f.call(args, kwargs).await
+            }
+            Value::Function(f) => Box::pin(self.call_user_function(&f, args, kwargs, scope)).await,
+            Value::Lambda(f) => Box::pin(self.call_lambda(&f, args, kwargs, scope)).await,
             Value::StructType(s) => {
                 let instance = s.instantiate(args, kwargs)?;
                 Ok(Value::StructInstance(Arc::new(instance)))
@@ -62,6 +88,47 @@ impl Evaluator {
         }
     }
 
+    /// `eval(source, globals=None)`: evaluates one expression in a fresh
+    /// scope holding only `globals`. It shares this evaluator's builtins and
+    /// step budget, and runs under the caller's permissions.
+    async fn eval_source(&self, args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(BlueprintError::ArgumentError {
+                message: format!("eval() takes 1 or 2 arguments ({} given)", args.len()),
+            });
+        }
+        let source = args[0].as_string()?;
+        let scope = Scope::new_global();
+        match args.get(1).or_else(|| kwargs.get("globals")) {
+            None | Some(Value::None) => {}
+            Some(Value::Dict(d)) => {
+                for (name, value) in d.read().await.iter() {
+                    scope.define(name, value.clone()).await;
+                }
+            }
+            Some(other) => {
+                return Err(BlueprintError::TypeError {
+                    expected: "dict of globals for eval()".into(),
+                    actual: other.type_name().into(),
+                })
+            }
+        }
+
+        let module = blueprint_engine_parser::parse("<eval>", &source)?;
+        let stmt = match &module.statements().node {
+            StmtP::Statements(stmts) if stmts.len() == 1 => &stmts[0],
+            _ => module.statements(),
+        };
+        let StmtP::Expression(expr) = &stmt.node else {
+            return Err(BlueprintError::ValueError {
+                message: "eval() accepts a single expression, not statements".into(),
+            });
+        };
+        self.for_codemap(module.codemap.clone(), None)
+            .eval_expr(expr, scope)
+            .await
+    }
+
     pub async fn call_user_function(
         &self,
         func: &blueprint_engine_core::UserFunction,
@@ -69,6 +136,14 @@ impl Evaluator {
         kwargs: HashMap<String, Value>,
         _parent_scope: Arc<Scope>,
     ) -> Result<Value> {
+        // Functions defined in another module report against that module's source.
+        let relocated = self.relocated(func.source.as_ref());
+        let this = relocated.as_ref().unwrap_or(self);
+
+        if stats::stats_enabled() {
+            stats::record_function_call(&func.name);
+        }
+
         let body =
             func.body
                 .downcast_ref::<AstStmt>()
@@ -77,7 +152,7 @@ impl Evaluator {
                 })?;
 
         if Self::contains_yield(body) {
-            return self.create_generator(func, args, kwargs).await;
+            return Box::pin(this.create_generator(func, args, kwargs)).await;
         }
 
         let closure_scope = func
@@ -85,24 +160,47 @@ impl Evaluator {
             .as_ref()
             .and_then(|c| c.downcast_ref::<Arc<Scope>>().cloned());
         let base_scope = closure_scope.unwrap_or_else(Scope::new_global);
-        let call_scope = Scope::new_child(base_scope, ScopeKind::Function);
-
-        self.bind_parameters(&func.params, args, kwargs, &call_scope)
-            .await?;
+        let function_id = func as *const blueprint_engine_core::UserFunction as usize;
 
         let func_name = func.name.clone();
-        let file = self.current_file.as_ref().map(|p| p.display().to_string());
-        let (line, column) = self.get_span_location(&body.span);
-
-        match self.eval_stmt(body, call_scope).await {
-            Ok(_) => Ok(Value::None),
-            Err(BlueprintError::Return { value }) => Ok((*value).clone()),
-            Err(e) => Err(e.with_stack_frame(StackFrame {
-                function_name: func_name,
-                file,
-                line,
-                column,
-            })),
+        let file = this.current_file.as_ref().map(|p| p.display().to_string());
+        let (line, column) = this.display_location(&body.span);
+        let _frame = profiler::enter(|| StackFrame {
+            function_name: func_name.clone(),
+            file: file.clone(),
+            line,
+            column,
+        });
+
+        let span = trace::call_span(&func_name, file.as_deref(), line, column);
+
+        // Self tail calls come back as `TailCall` and rerun the body in a
+        // fresh scope here instead of nesting another call.
+        let (mut args, mut kwargs) = (args, kwargs);
+        loop {
+            let call_scope = Scope::new_function_call(base_scope.clone(), function_id);
+            this.bind_parameters(&func.params, args, kwargs, &call_scope)
+                .await?;
+
+            match trace::instrument(span.clone(), this.eval_stmt(body, call_scope)).await {
+                Ok(_) => return Ok(Value::None),
+                Err(BlueprintError::Return { value }) => return Ok((*value).clone()),
+                Err(BlueprintError::TailCall {
+                    args: next_args,
+                    kwargs: next_kwargs,
+                }) => {
+                    args = next_args;
+                    kwargs = next_kwargs;
+                }
+                Err(e) => {
+                    return Err(e.with_stack_frame(StackFrame {
+                        function_name: func_name,
+                        file,
+                        line,
+                        column,
+                    }))
+                }
+            }
         }
     }
 
@@ -134,20 +232,20 @@ impl Evaluator {
 
         let func_name = func.name.clone();
 
-        let evaluator = Evaluator::new();
+        let evaluator = Evaluator::for_source(func.source.as_ref());
 
-        tokio::spawn(async move {
+        tokio::spawn(inherit_permissions(async move {
             let result = evaluator.eval_stmt(&body, gen_scope).await;
 
             match result {
                 Ok(_) | Err(BlueprintError::Return { .. }) => {
                     let _ = tx.send(GeneratorMessage::Complete).await;
                 }
-                Err(_) => {
-                    let _ = tx.send(GeneratorMessage::Complete).await;
+                Err(err) => {
+                    let _ = tx.send(GeneratorMessage::Failed(err)).await;
                 }
             }
-        });
+        }));
 
         Ok(Value::Generator(Arc::new(Generator::new(rx, func_name))))
     }
@@ -159,6 +257,13 @@ impl Evaluator {
         kwargs: HashMap<String, Value>,
         _parent_scope: Arc<Scope>,
     ) -> Result<Value> {
+        let relocated = self.relocated(func.source.as_ref());
+        let this = relocated.as_ref().unwrap_or(self);
+
+        if stats::stats_enabled() {
+            stats::record_function_call("<lambda>");
+        }
+
         let closure_scope = func
             .closure
             .as_ref()
@@ -166,7 +271,7 @@ impl Evaluator {
         let base_scope = closure_scope.unwrap_or_else(Scope::new_global);
         let call_scope = Scope::new_child(base_scope, ScopeKind::Function);
 
-        self.bind_parameters(&func.params, args, kwargs, &call_scope)
+        this.bind_parameters(&func.params, args, kwargs, &call_scope)
             .await?;
 
         let body =
@@ -176,10 +281,18 @@ impl Evaluator {
                     message: "Invalid lambda body".into(),
                 })?;
 
-        let file = self.current_file.as_ref().map(|p| p.display().to_string());
-        let (line, column) = self.get_span_location(&body.span);
+        let file = this.current_file.as_ref().map(|p| p.display().to_string());
+        let (line, column) = this.display_location(&body.span);
+        let _frame = profiler::enter(|| StackFrame {
+            function_name: "<lambda>".to_string(),
+            file: file.clone(),
+            line,
+            column,
+        });
 
-        self.eval_expr(body, call_scope.clone()).await.map_err(|e| {
+        let span = trace::call_span("<lambda>", file.as_deref(), line, column);
+        let result = trace::instrument(span, this.eval_expr(body, call_scope.clone())).await;
+        result.map_err(|e| {
             e.with_stack_frame(StackFrame {
                 function_name: "<lambda>".to_string(),
                 file,
@@ -9,31 +9,132 @@ mod types;
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
 
+use futures_util::future::join_all;
 use indexmap::IndexMap;
 
 use blueprint_engine_core::{
-    fetch_package, find_workspace_root_from, get_packages_dir_from, BlueprintError, NativeFunction,
-    PackageSpec, Result, Value,
+    fetch_package, find_workspace_root_from, get_packages_dir_from, get_vendor_dir_from,
+    BlueprintError, NativeFunction, PackageSpec, Permissions, Result, Value,
 };
-use blueprint_engine_parser::{AstExpr, AstParameter, AstStmt, ParameterP, StmtP};
+use blueprint_engine_parser::{AstExpr, AstParameter, AstStmt, ParameterP, ParsedModule, StmtP};
 use blueprint_starlark_syntax::codemap::CodeMap;
 use blueprint_starlark_syntax::syntax::ast::{ArgumentP, ExprP};
-use tokio::sync::RwLock;
+use tokio::sync::{OnceCell, RwLock};
 
+use crate::budget::Budget;
+use crate::coverage::ModuleHits;
+use crate::modules::registry::split_module_spec;
 use crate::modules::ModuleRegistry;
+use crate::resolver::{MemoryResolver, ModuleResolver, ResolvedModule};
 use crate::scope::Scope;
+use crate::trace;
 
 pub struct FrozenModule {
     exports: HashMap<String, Value>,
 }
 
-static MODULE_CACHE: OnceLock<RwLock<HashMap<String, Arc<FrozenModule>>>> = OnceLock::new();
+type ModuleCell = Arc<OnceCell<Result<Arc<FrozenModule>>>>;
+
+/// Modules by canonical path. A module's cell exists from the moment its
+/// first importer starts loading it, so concurrent importers wait for that
+/// one evaluation instead of running the module again.
+#[derive(Default)]
+pub(crate) struct ModuleCache {
+    modules: HashMap<String, ModuleCell>,
+    /// Sources parsed ahead of their `load` by `preload_imports`, taken by
+    /// the load that evaluates them.
+    parsed: HashMap<String, ParsedModule>,
+    /// The modules each importer is blocked on, so an import cycle between
+    /// concurrent loads is reported instead of waiting forever.
+    waiting: HashMap<String, Vec<String>>,
+}
+
+impl ModuleCache {
+    fn loaded(&self, path: &str) -> Option<Result<Arc<FrozenModule>>> {
+        self.modules.get(path)?.get().cloned()
+    }
+
+    /// Records that `importer` is blocked on `module` and returns the cell to
+    /// wait on, or fails if `module` is itself blocked on `importer`.
+    fn wait_for(&mut self, importer: &str, module: &str) -> Result<ModuleCell> {
+        if let Some(mut cycle) = self.blocked_chain(module, importer) {
+            cycle.insert(0, importer.to_string());
+            return Err(BlueprintError::ImportError {
+                message: format!("Circular import detected: {}", cycle.join(" -> ")),
+            });
+        }
+        self.waiting
+            .entry(importer.to_string())
+            .or_default()
+            .push(module.to_string());
+        Ok(self.modules.entry(module.to_string()).or_default().clone())
+    }
+
+    /// Drops a failed load so that a later import tries the module again.
+    fn forget_failed(&mut self, path: &str, cell: &ModuleCell) {
+        if self.modules.get(path).is_some_and(|c| Arc::ptr_eq(c, cell)) {
+            self.modules.remove(path);
+        }
+    }
+
+    fn done_waiting(&mut self, importer: &str, module: &str) {
+        if let Some(blocked_on) = self.waiting.get_mut(importer) {
+            if let Some(i) = blocked_on.iter().position(|m| m == module) {
+                blocked_on.swap_remove(i);
+            }
+            if blocked_on.is_empty() {
+                self.waiting.remove(importer);
+            }
+        }
+    }
+
+    /// The chain of waits leading from `from` to `to`, if there is one.
+    fn blocked_chain(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut stack = vec![vec![from.to_string()]];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(chain) = stack.pop() {
+            let last = chain.last()?;
+            if last == to {
+                return Some(chain);
+            }
+            if !seen.insert(last.clone()) {
+                continue;
+            }
+            for next in self.waiting.get(last).into_iter().flatten() {
+                let mut longer = chain.clone();
+                longer.push(next.clone());
+                stack.push(longer);
+            }
+        }
+        None
+    }
+}
+
+/// Drops an importer's wait on a module however the load ends, including
+/// when the loading future is cancelled.
+struct Waiting<'a> {
+    cache: &'a Mutex<ModuleCache>,
+    importer: String,
+    module: String,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        lock_cache(self.cache).done_waiting(&self.importer, &self.module);
+    }
+}
+
+fn lock_cache(cache: &Mutex<ModuleCache>) -> MutexGuard<'_, ModuleCache> {
+    cache.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+static MODULE_CACHE: OnceLock<Mutex<ModuleCache>> = OnceLock::new();
 static STDLIB_REGISTRY: OnceLock<Arc<ModuleRegistry>> = OnceLock::new();
 
-fn get_module_cache() -> &'static RwLock<HashMap<String, Arc<FrozenModule>>> {
-    MODULE_CACHE.get_or_init(|| RwLock::new(HashMap::new()))
+fn get_module_cache() -> &'static Mutex<ModuleCache> {
+    MODULE_CACHE.get_or_init(Default::default)
 }
 
 fn get_stdlib_registry() -> Arc<ModuleRegistry> {
@@ -42,22 +143,88 @@ fn get_stdlib_registry() -> Arc<ModuleRegistry> {
         .clone()
 }
 
+async fn read_module_source(
+    hosted: Option<ResolvedModule>,
+    resolved_path: &Path,
+) -> Result<String> {
+    match hosted {
+        Some(module) => Ok(module.source),
+        None => {
+            tokio::fs::read_to_string(resolved_path)
+                .await
+                .map_err(|e| BlueprintError::IoError {
+                    path: resolved_path.to_string_lossy().to_string(),
+                    message: e.to_string(),
+                })
+        }
+    }
+}
+
+/// Regroups `values` for `targets` targets of which the one at `star` is
+/// starred: that target takes whatever the others leave, as a list. `values`
+/// must hold at least `targets - 1` items.
+pub(crate) fn gather_starred(mut values: Vec<Value>, targets: usize, star: usize) -> Vec<Value> {
+    let tail = values.split_off(values.len() - (targets - star - 1));
+    let middle = values.split_off(star);
+    values.push(Value::from(middle));
+    values.extend(tail);
+    values
+}
+
+/// Converts a `//`-stripped label such as `lib/net:http.bp` into `lib/net/http.bp`.
+pub(crate) fn workspace_label_to_path(label: &str) -> PathBuf {
+    match label.rsplit_once(':') {
+        Some((package, target)) => PathBuf::from(package).join(target),
+        None => PathBuf::from(label),
+    }
+}
+
+struct SourceInfo {
+    codemap: CodeMap,
+    file: Option<PathBuf>,
+    builtins: Arc<HashMap<String, Arc<NativeFunction>>>,
+    globals: Arc<HashMap<String, Value>>,
+    resolver: Option<Arc<dyn ModuleResolver>>,
+    budget: Option<Arc<Budget>>,
+    coverage: Option<Arc<ModuleHits>>,
+}
+
+const SANDBOX_MAX_STEPS: u64 = 10_000_000;
+
+type ResolutionCache = Arc<std::sync::RwLock<HashMap<(PathBuf, String), Result<PathBuf>>>>;
+
 pub struct Evaluator {
-    pub(crate) builtins: HashMap<String, Arc<NativeFunction>>,
+    pub(crate) builtins: Arc<HashMap<String, Arc<NativeFunction>>>,
+    pub(crate) globals: Arc<HashMap<String, Value>>,
+    pub(crate) resolver: Option<Arc<dyn ModuleResolver>>,
+    pub(crate) budget: Option<Arc<Budget>>,
+    pub(crate) permissions: Option<Arc<Permissions>>,
     pub(crate) stdlib: Arc<ModuleRegistry>,
     pub(crate) codemap: Option<CodeMap>,
     pub(crate) current_file: Option<PathBuf>,
-    pub(crate) local_cache: Option<Arc<RwLock<HashMap<String, Arc<FrozenModule>>>>>,
+    pub(crate) local_cache: Option<Arc<Mutex<ModuleCache>>>,
+    pub(crate) resolution_cache: ResolutionCache,
+    pub(crate) load_stack: Vec<String>,
+    /// Where statements of the current module count their hits while line
+    /// coverage is on.
+    pub(crate) coverage: Option<Arc<ModuleHits>>,
 }
 
 impl Evaluator {
     pub fn new() -> Self {
         let mut evaluator = Self {
-            builtins: HashMap::new(),
+            builtins: Arc::new(HashMap::new()),
+            globals: Arc::new(HashMap::new()),
+            resolver: None,
+            budget: None,
+            permissions: None,
             stdlib: get_stdlib_registry(),
             codemap: None,
             current_file: None,
             local_cache: None,
+            resolution_cache: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            load_stack: Vec::new(),
+            coverage: None,
         };
         evaluator.register_builtins();
         evaluator
@@ -65,16 +232,66 @@ impl Evaluator {
 
     pub fn new_isolated() -> Self {
         let mut evaluator = Self {
-            builtins: HashMap::new(),
+            builtins: Arc::new(HashMap::new()),
+            globals: Arc::new(HashMap::new()),
+            resolver: None,
+            budget: None,
+            permissions: None,
             stdlib: get_stdlib_registry(),
             codemap: None,
             current_file: None,
-            local_cache: Some(Arc::new(RwLock::new(HashMap::new()))),
+            local_cache: Some(Arc::default()),
+            resolution_cache: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            load_stack: Vec::new(),
+            coverage: None,
         };
         evaluator.register_builtins();
         evaluator
     }
 
+    /// An evaluator for untrusted scripts: every permission is denied,
+    /// `load()` can only reach `@bp/` modules, and a step cap applies.
+    /// Adjust it with `with_step_budget`. No memory cap is set, since the only
+    /// one available measures the whole process; see `with_memory_limit`.
+    pub fn sandboxed() -> Self {
+        let mut evaluator = Self::new_isolated();
+        evaluator.permissions = Some(Arc::new(Permissions::none()));
+        evaluator.resolver = Some(Arc::new(MemoryResolver::new()));
+        evaluator.budget = Some(Arc::new(Budget::new(Some(SANDBOX_MAX_STEPS), None)));
+        evaluator
+    }
+
+    /// Fails evaluation with `ResourceLimit` once `steps` statements and
+    /// expressions have been evaluated, counting loop iterations, recursive
+    /// calls and comprehension elements alike. Modules loaded by the script
+    /// and callbacks run by builtins share the same budget.
+    pub fn with_step_budget(mut self, steps: u64) -> Self {
+        let max_memory = self.budget.as_ref().and_then(|b| b.max_memory());
+        self.budget = Some(Arc::new(Budget::new(Some(steps), max_memory)));
+        self
+    }
+
+    /// Fails evaluation once process RSS exceeds `bytes`, sampled
+    /// periodically as statements run. RSS covers the whole host process,
+    /// not just this evaluator, so other work in the embedding counts toward
+    /// the cap. It is only read on Linux; elsewhere the cap is never hit.
+    pub fn with_memory_limit(mut self, bytes: u64) -> Self {
+        let max_steps = self.budget.as_ref().and_then(|b| b.max_steps());
+        self.budget = Some(Arc::new(Budget::new(max_steps, Some(bytes))));
+        self
+    }
+
+    /// Runs every `eval` under `permissions` instead of the caller's.
+    pub fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = Some(Arc::new(permissions));
+        self
+    }
+
+    /// Steps charged against the budget so far, or 0 without one.
+    pub fn steps_used(&self) -> u64 {
+        self.budget.as_ref().map_or(0, |b| b.steps_used())
+    }
+
     pub fn with_file(mut self, path: impl AsRef<Path>) -> Self {
         self.current_file = Some(path.as_ref().to_path_buf());
         self
@@ -84,7 +301,7 @@ impl Evaluator {
         self.current_file = Some(path.as_ref().to_path_buf());
     }
 
-    fn get_cache(&self) -> &RwLock<HashMap<String, Arc<FrozenModule>>> {
+    fn get_cache(&self) -> &Mutex<ModuleCache> {
         match &self.local_cache {
             Some(cache) => cache.as_ref(),
             None => get_module_cache(),
@@ -92,13 +309,95 @@ impl Evaluator {
     }
 
     pub fn register_native(&mut self, func: NativeFunction) {
-        self.builtins.insert(func.name.clone(), Arc::new(func));
+        Arc::make_mut(&mut self.builtins).insert(func.name.clone(), Arc::new(func));
+    }
+
+    /// Makes `value` visible to every module this evaluator runs. Script
+    /// definitions with the same name shadow it.
+    pub fn define_global(&mut self, name: impl Into<String>, value: Value) {
+        Arc::make_mut(&mut self.globals).insert(name.into(), value);
+    }
+
+    /// Routes `load()` through `resolver` before the filesystem.
+    pub fn set_module_resolver(&mut self, resolver: Arc<dyn ModuleResolver>) {
+        self.resolver = Some(resolver);
     }
 
     fn register_builtins(&mut self) {
         crate::modules::register_builtins(self);
     }
 
+    /// True when `func` is the builtin registered under `name`, as opposed to
+    /// a native function that merely shares the name.
+    pub(crate) fn is_builtin(&self, func: &Arc<NativeFunction>, name: &str) -> bool {
+        self.builtins
+            .get(name)
+            .is_some_and(|builtin| Arc::ptr_eq(builtin, func))
+    }
+
+    fn source_info(&self) -> Option<Arc<dyn std::any::Any + Send + Sync>> {
+        self.codemap.as_ref().map(|codemap| {
+            Arc::new(SourceInfo {
+                codemap: codemap.clone(),
+                file: self.current_file.clone(),
+                builtins: self.builtins.clone(),
+                globals: self.globals.clone(),
+                resolver: self.resolver.clone(),
+                budget: self.budget.clone(),
+                coverage: self.coverage.clone(),
+            }) as Arc<dyn std::any::Any + Send + Sync>
+        })
+    }
+
+    /// Builds a fresh evaluator positioned in the module a function was defined in.
+    pub(crate) fn for_source(source: Option<&Arc<dyn std::any::Any + Send + Sync>>) -> Self {
+        let mut evaluator = Self::new();
+        if let Some(info) = source.and_then(|s| s.downcast_ref::<SourceInfo>()) {
+            evaluator.codemap = Some(info.codemap.clone());
+            evaluator.current_file = info.file.clone();
+            evaluator.builtins = info.builtins.clone();
+            evaluator.globals = info.globals.clone();
+            evaluator.resolver = info.resolver.clone();
+            evaluator.budget = info.budget.clone();
+            evaluator.coverage = info.coverage.clone();
+        }
+        evaluator
+    }
+
+    /// Returns an evaluator for the module a function was defined in, or `None`
+    /// when that module is the one `self` is already evaluating.
+    pub(crate) fn relocated(
+        &self,
+        source: Option<&Arc<dyn std::any::Any + Send + Sync>>,
+    ) -> Option<Self> {
+        let info = source?.downcast_ref::<SourceInfo>()?;
+        if self.codemap.as_ref() == Some(&info.codemap) {
+            return None;
+        }
+        let mut evaluator = self.for_codemap(info.codemap.clone(), info.file.clone());
+        evaluator.coverage = info.coverage.clone();
+        Some(evaluator)
+    }
+
+    /// An evaluator sharing this one's builtins, globals, resolver and budget,
+    /// positioned in other source text.
+    pub(crate) fn for_codemap(&self, codemap: CodeMap, file: Option<PathBuf>) -> Self {
+        Self {
+            builtins: self.builtins.clone(),
+            globals: self.globals.clone(),
+            resolver: self.resolver.clone(),
+            budget: self.budget.clone(),
+            permissions: None,
+            stdlib: self.stdlib.clone(),
+            codemap: Some(codemap),
+            current_file: file,
+            local_cache: self.local_cache.clone(),
+            resolution_cache: self.resolution_cache.clone(),
+            load_stack: self.load_stack.clone(),
+            coverage: None,
+        }
+    }
+
     pub fn value_to_dict_key(&self, value: &Value) -> Result<String> {
         match value {
             Value::String(s) => Ok(s.as_ref().clone()),
@@ -126,6 +425,7 @@ impl Evaluator {
             params,
             body: Box::new((*def.body).clone()),
             closure: Some(Arc::new(scope) as Arc<dyn std::any::Any + Send + Sync>),
+            source: self.source_info(),
         };
 
         Ok(Value::Function(Arc::new(func)))
@@ -144,6 +444,7 @@ impl Evaluator {
             params,
             body: Box::new((*lambda.body).clone()),
             closure: Some(Arc::new(scope) as Arc<dyn std::any::Any + Send + Sync>),
+            source: self.source_info(),
         };
 
         Ok(Value::Lambda(Arc::new(func)))
@@ -249,40 +550,111 @@ impl Evaluator {
 
         // @bp/ prefix is reserved for Rust stdlib only
         if let Some(stdlib_module) = module_path.strip_prefix("@bp/") {
-            if self.stdlib.has_module(stdlib_module) {
-                return self.bind_stdlib_module(load, stdlib_module, scope).await;
-            }
-            return Err(BlueprintError::ImportError {
-                message: format!("Module '@bp/{}' not found in stdlib", stdlib_module),
-            });
+            return self.bind_stdlib_module(load, stdlib_module, scope).await;
         }
 
-        let resolved_path = self.resolve_module_path(module_path)?;
-        let canonical_path = std::fs::canonicalize(&resolved_path)
-            .unwrap_or_else(|_| resolved_path.clone())
-            .to_string_lossy()
-            .to_string();
+        let frozen = self.load_module(module_path).await?;
+        self.bind_load_args(load, &frozen.exports, scope, module_path)
+            .await
+    }
+
+    #[async_recursion::async_recursion]
+    async fn load_module(&self, module_path: &str) -> Result<Arc<FrozenModule>> {
+        let (hosted, resolved_path, canonical_path) = self.locate_module(module_path)?;
 
         let cache = self.get_cache();
+        if let Some(loaded) = lock_cache(cache).loaded(&canonical_path) {
+            return loaded;
+        }
 
-        {
-            let cache_read = cache.read().await;
-            if let Some(frozen) = cache_read.get(&canonical_path) {
-                return self
-                    .bind_load_args(load, &frozen.exports, scope, module_path)
-                    .await;
+        let mut load_stack = self.load_stack.clone();
+        if load_stack.is_empty() {
+            if let Some(ref current_file) = self.current_file {
+                let current = std::fs::canonicalize(current_file)
+                    .unwrap_or_else(|_| current_file.clone())
+                    .to_string_lossy()
+                    .to_string();
+                load_stack.push(current);
             }
         }
 
-        let source = tokio::fs::read_to_string(&resolved_path)
+        if let Some(start) = load_stack.iter().position(|p| p == &canonical_path) {
+            let mut cycle: Vec<&str> = load_stack[start..].iter().map(|p| p.as_str()).collect();
+            cycle.push(&canonical_path);
+            return Err(BlueprintError::ImportError {
+                message: format!("Circular import detected: {}", cycle.join(" -> ")),
+            });
+        }
+        let importer = load_stack.last().cloned().unwrap_or_default();
+        load_stack.push(canonical_path.clone());
+
+        let cell = lock_cache(cache).wait_for(&importer, &canonical_path)?;
+        let _waiting = Waiting {
+            cache,
+            importer,
+            module: canonical_path.clone(),
+        };
+        let loaded = cell
+            .get_or_init(|| {
+                self.eval_module_file(
+                    module_path,
+                    hosted,
+                    resolved_path,
+                    canonical_path.clone(),
+                    load_stack,
+                )
+            })
             .await
-            .map_err(|e| BlueprintError::IoError {
-                path: resolved_path.to_string_lossy().to_string(),
-                message: e.to_string(),
-            })?;
+            .clone();
+        if loaded.is_err() {
+            lock_cache(cache).forget_failed(&canonical_path, &cell);
+        }
+        loaded
+    }
 
-        let filename = resolved_path.to_string_lossy().to_string();
-        let module = blueprint_engine_parser::parse(&filename, &source)?;
+    /// Where `module_path` lives: the hosted module if the resolver serves
+    /// it, the path it is read from, and the canonical path it is cached by.
+    fn locate_module(
+        &self,
+        module_path: &str,
+    ) -> Result<(Option<ResolvedModule>, PathBuf, String)> {
+        let hosted = match &self.resolver {
+            Some(resolver) => resolver.resolve(module_path, self.current_file.as_deref())?,
+            None => None,
+        };
+        match hosted {
+            Some(module) => {
+                let name = module.name.clone();
+                Ok((Some(module), PathBuf::from(&name), name))
+            }
+            None => {
+                let resolved_path = self.resolve_module_path(module_path)?;
+                let canonical_path = std::fs::canonicalize(&resolved_path)
+                    .unwrap_or_else(|_| resolved_path.clone())
+                    .to_string_lossy()
+                    .to_string();
+                Ok((None, resolved_path, canonical_path))
+            }
+        }
+    }
+
+    async fn eval_module_file(
+        &self,
+        module_path: &str,
+        hosted: Option<ResolvedModule>,
+        resolved_path: PathBuf,
+        canonical_path: String,
+        load_stack: Vec<String>,
+    ) -> Result<Arc<FrozenModule>> {
+        let preloaded = lock_cache(self.get_cache()).parsed.remove(&canonical_path);
+        let module = match preloaded {
+            Some(module) => module,
+            None => {
+                let source = read_module_source(hosted, &resolved_path).await?;
+                let filename = resolved_path.to_string_lossy().to_string();
+                blueprint_engine_parser::parse(&filename, &source)?
+            }
+        };
 
         let module_scope = Scope::new_global();
         module_scope
@@ -291,23 +663,88 @@ impl Evaluator {
 
         let mut module_evaluator = Evaluator {
             builtins: self.builtins.clone(),
+            globals: self.globals.clone(),
+            resolver: self.resolver.clone(),
+            budget: self.budget.clone(),
+            permissions: None,
             stdlib: self.stdlib.clone(),
             codemap: None,
             current_file: Some(resolved_path.clone()),
             local_cache: self.local_cache.clone(),
+            resolution_cache: self.resolution_cache.clone(),
+            load_stack,
+            coverage: None,
         };
-        module_evaluator.eval(&module, module_scope.clone()).await?;
+        let span = trace::load_span(module_path, &canonical_path);
+        trace::instrument(span, module_evaluator.eval(&module, module_scope.clone())).await?;
 
         let exports = module_scope.exports().await;
-        let frozen = Arc::new(FrozenModule { exports });
+        Ok(Arc::new(FrozenModule { exports }))
+    }
 
-        {
-            let mut cache_write = cache.write().await;
-            cache_write.insert(canonical_path, frozen.clone());
+    /// Reads and parses the modules named by the block of `load` statements
+    /// at the top of a module concurrently. They are still evaluated one at a
+    /// time when each statement runs, so their side effects keep the order the
+    /// loads are written in. Returns the modules parsed here, for
+    /// `discard_preloaded`; errors are ignored and resurface when the
+    /// statement is evaluated.
+    pub(crate) async fn preload_imports(&self, stmt: &AstStmt) -> Vec<String> {
+        let StmtP::Statements(stmts) = &stmt.node else {
+            return Vec::new();
+        };
+
+        let mut module_paths: Vec<&str> = Vec::new();
+        for s in stmts {
+            let StmtP::Load(load) = &s.node else {
+                break;
+            };
+            let module_path = load.module.node.as_str();
+            if !module_path.starts_with("@bp/") && !module_paths.contains(&module_path) {
+                module_paths.push(module_path);
+            }
         }
 
-        self.bind_load_args(load, &frozen.exports, scope, module_path)
-            .await
+        if module_paths.len() < 2 {
+            return Vec::new();
+        }
+
+        let parsed = join_all(module_paths.into_iter().map(|p| self.parse_ahead(p))).await;
+        let mut cache = lock_cache(self.get_cache());
+        let mut preloaded = Vec::new();
+        for (canonical_path, module) in parsed.into_iter().flatten() {
+            if !cache.modules.contains_key(&canonical_path) {
+                cache.parsed.insert(canonical_path.clone(), module);
+                preloaded.push(canonical_path);
+            }
+        }
+        preloaded
+    }
+
+    /// Drops parsed modules that `preload_imports` left behind because their
+    /// `load` never ran.
+    pub(crate) fn discard_preloaded(&self, preloaded: &[String]) {
+        let mut cache = lock_cache(self.get_cache());
+        for canonical_path in preloaded {
+            cache.parsed.remove(canonical_path);
+        }
+    }
+
+    async fn parse_ahead(&self, module_path: &str) -> Option<(String, ParsedModule)> {
+        let (hosted, resolved_path, canonical_path) = self.locate_module(module_path).ok()?;
+        if lock_cache(self.get_cache())
+            .modules
+            .contains_key(&canonical_path)
+        {
+            return None;
+        }
+        let source = read_module_source(hosted, &resolved_path).await.ok()?;
+        let filename = resolved_path.to_string_lossy().to_string();
+        let module =
+            tokio::task::spawn_blocking(move || blueprint_engine_parser::parse(&filename, &source))
+                .await
+                .ok()?
+                .ok()?;
+        Some((canonical_path, module))
     }
 
     async fn bind_stdlib_module(
@@ -315,18 +752,33 @@ impl Evaluator {
         load: &blueprint_starlark_syntax::syntax::ast::LoadP<
             blueprint_starlark_syntax::syntax::ast::AstNoPayload,
         >,
-        module_name: &str,
+        module_spec: &str,
         scope: Arc<Scope>,
     ) -> Result<Value> {
         let module_funcs = self
             .stdlib
-            .get_module(module_name)
-            .ok_or_else(|| BlueprintError::ImportError {
-                message: format!("Module '@bp/{}' not found", module_name),
-            })?;
+            .resolve(module_spec)
+            .map_err(|message| BlueprintError::ImportError { message })?;
 
-        self.bind_native_functions(load, &module_funcs, scope, module_name, &format!("@bp/{}", module_name))
-            .await
+        let (module_name, _) = split_module_spec(module_spec)
+            .map_err(|message| BlueprintError::ImportError { message })?;
+
+        let instrumented;
+        let module_funcs = if crate::stats::stats_enabled() {
+            instrumented = crate::stats::instrument_module(module_name, module_funcs);
+            &instrumented
+        } else {
+            module_funcs
+        };
+
+        self.bind_native_functions(
+            load,
+            module_funcs,
+            scope,
+            module_name,
+            &format!("@bp/{}", module_spec),
+        )
+        .await
     }
 
     async fn bind_native_functions(
@@ -405,6 +857,12 @@ impl Evaluator {
             let local_name = arg.local.node.ident.as_str();
             let their_name = &arg.their.node;
 
+            if their_name == "__module__" {
+                let namespace = self.module_namespace(exports).await;
+                scope.define(local_name, namespace).await;
+                continue;
+            }
+
             let value = exports.get(their_name).ok_or_else(|| {
                 if their_name.starts_with('_') {
                     BlueprintError::ImportError {
@@ -427,12 +885,55 @@ impl Evaluator {
         Ok(Value::None)
     }
 
+    async fn module_namespace(&self, exports: &HashMap<String, Value>) -> Value {
+        let mut names: Vec<&String> = exports.keys().collect();
+        names.sort();
+
+        let mut dict = IndexMap::new();
+        for name in names {
+            dict.insert(name.clone(), exports[name].deep_copy().await);
+        }
+        Value::Dict(Arc::new(RwLock::new(dict)))
+    }
+
     fn resolve_module_path(&self, module_path: &str) -> Result<PathBuf> {
+        let base_dir = self
+            .current_file
+            .as_ref()
+            .and_then(|f| f.parent().map(|p| p.to_path_buf()))
+            .unwrap_or_else(|| PathBuf::from("."));
+        let key = (base_dir, module_path.to_string());
+
+        if let Some(cached) = self.resolution_cache.read().unwrap().get(&key) {
+            return cached.clone();
+        }
+
+        let resolved = self.resolve_module_path_uncached(module_path);
+        self.resolution_cache
+            .write()
+            .unwrap()
+            .insert(key, resolved.clone());
+        resolved
+    }
+
+    fn resolve_module_path_uncached(&self, module_path: &str) -> Result<PathBuf> {
         // @bp/ is handled in eval_load, so any @ prefix here is a package
         if module_path.starts_with('@') {
             return self.resolve_package_path(module_path);
         }
 
+        if let Some(label) = module_path.strip_prefix("//") {
+            let workspace_root =
+                self.find_workspace_root()
+                    .ok_or_else(|| BlueprintError::ImportError {
+                        message: format!(
+                            "Cannot resolve '{}': no BP.toml workspace root found",
+                            module_path
+                        ),
+                    })?;
+            return Ok(workspace_root.join(workspace_label_to_path(label)));
+        }
+
         if module_path.starts_with("./") || module_path.starts_with("../") {
             let current_dir = if let Some(ref current_file) = self.current_file {
                 current_file
@@ -471,6 +972,17 @@ impl Evaluator {
             .current_file
             .as_ref()
             .and_then(|f| f.parent().map(|p| p.to_path_buf()));
+
+        if let Some(vendor_dir) = get_vendor_dir_from(start_dir.clone()) {
+            let vendored = vendor_dir
+                .join(&spec.user)
+                .join(spec.dir_name())
+                .join("lib.bp");
+            if vendored.exists() {
+                return Ok(vendored);
+            }
+        }
+
         let packages_dir = get_packages_dir_from(start_dir);
         let package_dir = packages_dir.join(&spec.user).join(spec.dir_name());
         let lib_path = package_dir.join("lib.bp");
@@ -523,6 +1035,9 @@ impl Evaluator {
             StmtP::Return(Some(expr)) => Self::expr_contains_yield(expr),
             StmtP::Def(def) => Self::contains_yield(&def.body),
             StmtP::Match(m) => m.cases.iter().any(|c| Self::contains_yield(&c.node.body)),
+            StmtP::With(w) => {
+                Self::expr_contains_yield(&w.resource) || Self::contains_yield(&w.body)
+            }
             _ => false,
         }
     }
@@ -605,6 +1120,21 @@ impl Evaluator {
             (0, 0)
         }
     }
+
+    /// The 1-based line and column of `span`, as shown to users in errors,
+    /// traces and reports. `(0, 0)` when the span has no known location.
+    pub(crate) fn display_location(
+        &self,
+        span: &blueprint_starlark_syntax::codemap::Span,
+    ) -> (usize, usize) {
+        match &self.codemap {
+            Some(codemap) if span.end() <= codemap.full_span().end() => {
+                let (line, column) = self.get_span_location(span);
+                (line + 1, column + 1)
+            }
+            _ => (0, 0),
+        }
+    }
 }
 
 impl Default for Evaluator {
@@ -612,3 +1142,317 @@ impl Default for Evaluator {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_module(dir: &Path, name: &str, source: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    async fn run_file(path: &Path) -> Result<Value> {
+        let source = std::fs::read_to_string(path).unwrap();
+        let module = blueprint_engine_parser::parse(&path.to_string_lossy(), &source)?;
+        let mut evaluator = Evaluator::new_isolated().with_file(path);
+        evaluator.eval(&module, Scope::new_global()).await
+    }
+
+    #[tokio::test]
+    async fn test_error_in_loaded_module_reports_module_location() {
+        let dir = std::env::temp_dir().join(format!("bp_source_map_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_module(
+            &dir,
+            "lib.bp",
+            "def boom():\n    x = 1\n    return x + \"a\"\n",
+        );
+        let main = write_module(&dir, "main.bp", "load(\"./lib.bp\", \"boom\")\n\nboom()\n");
+
+        let err = run_file(&main).await.unwrap_err();
+        let location = err.error_location().expect("error should carry a location");
+        assert!(location.file.as_deref().unwrap_or("").ends_with("lib.bp"));
+        assert_eq!(location.line, 3);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_circular_import_detected() {
+        let dir = std::env::temp_dir().join(format!("bp_cycle_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_module(&dir, "a.bp", "load(\"./b.bp\", \"B\")\nA = 1\n");
+        write_module(&dir, "b.bp", "load(\"./a.bp\", \"A\")\nB = 2\n");
+        let main = write_module(&dir, "main.bp", "load(\"./a.bp\", \"A\")\n");
+
+        let err = run_file(&main).await.unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("Circular import detected"), "{}", message);
+        assert!(message.contains("a.bp -> "), "{}", message);
+        assert!(message.contains("b.bp -> "), "{}", message);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_self_import_detected() {
+        let dir = std::env::temp_dir().join(format!("bp_self_cycle_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let main = write_module(&dir, "main.bp", "load(\"./main.bp\", \"X\")\nX = 1\n");
+
+        let err = run_file(&main).await.unwrap_err();
+        assert!(err.to_string().contains("Circular import detected"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_diamond_import_is_not_a_cycle() {
+        let dir = std::env::temp_dir().join(format!("bp_diamond_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_module(&dir, "base.bp", "BASE = 1\n");
+        write_module(
+            &dir,
+            "left.bp",
+            "load(\"./base.bp\", \"BASE\")\nLEFT = BASE\n",
+        );
+        write_module(
+            &dir,
+            "right.bp",
+            "load(\"./base.bp\", \"BASE\")\nRIGHT = BASE\n",
+        );
+        let main = write_module(
+            &dir,
+            "main.bp",
+            "load(\"./left.bp\", \"LEFT\")\nload(\"./right.bp\", \"RIGHT\")\n",
+        );
+
+        assert!(run_file(&main).await.is_ok());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_cycle_between_preloaded_imports_detected() {
+        let dir = std::env::temp_dir().join(format!("bp_preload_cycle_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_module(&dir, "a.bp", "load(\"./b.bp\", \"B\")\nA = 1\n");
+        write_module(&dir, "b.bp", "load(\"./a.bp\", \"A\")\nB = 2\n");
+        let main = write_module(
+            &dir,
+            "main.bp",
+            "load(\"./a.bp\", \"A\")\nload(\"./b.bp\", \"B\")\n",
+        );
+
+        let result = tokio::time::timeout(std::time::Duration::from_secs(5), run_file(&main))
+            .await
+            .expect("concurrent imports of a cycle should not deadlock");
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Circular import detected"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_leading_imports_run_in_statement_order() {
+        let dir = std::env::temp_dir().join(format!("bp_import_order_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_module(&dir, "a.bp", "fail(\"first module ran\")\n");
+        write_module(&dir, "b.bp", "fail(\"second module ran\")\n");
+        let main = write_module(
+            &dir,
+            "main.bp",
+            "load(\"./a.bp\", \"A\")\nload(\"./b.bp\", \"B\")\n",
+        );
+
+        let err = run_file(&main).await.unwrap_err().to_string();
+        assert!(err.contains("first module ran"), "{}", err);
+        assert!(!err.contains("second module ran"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_failed_import_is_retried() {
+        let dir = std::env::temp_dir().join(format!("bp_import_retry_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_module(&dir, "lib.bp", "X = 1 +\n");
+        let main = write_module(&dir, "main.bp", "load(\"./lib.bp\", \"X\")\n");
+        let source = std::fs::read_to_string(&main).unwrap();
+        let module = blueprint_engine_parser::parse(&main.to_string_lossy(), &source).unwrap();
+        let mut evaluator = Evaluator::new_isolated().with_file(&main);
+
+        assert!(evaluator.eval(&module, Scope::new_global()).await.is_err());
+        write_module(&dir, "lib.bp", "X = 1\n");
+        assert!(evaluator.eval(&module, Scope::new_global()).await.is_ok());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_span_locations() {
+        let module = blueprint_engine_parser::parse("t.bp", "x = 1\ny = x\n").unwrap();
+        let mut evaluator = Evaluator::new_isolated();
+        evaluator.codemap = Some(module.codemap.clone());
+        let StmtP::Statements(stmts) = &module.statement.node else {
+            panic!("expected statements");
+        };
+        let span = stmts[1].span;
+        assert_eq!(evaluator.get_span_location(&span), (1, 0));
+        assert_eq!(evaluator.display_location(&span), (2, 1));
+
+        evaluator.codemap = None;
+        assert_eq!(evaluator.get_span_location(&span), (0, 0));
+        assert_eq!(evaluator.display_location(&span), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_eval_value_returns_final_expression() {
+        let mut evaluator = Evaluator::new_isolated();
+        let module = blueprint_engine_parser::parse(
+            "<test>",
+            "x = 20
+x * 2 + 2
+",
+        )
+        .unwrap();
+        let value = evaluator
+            .eval_value(&module, Scope::new_global())
+            .await
+            .unwrap();
+        assert!(matches!(value, Value::Int(42)));
+
+        let module = blueprint_engine_parser::parse(
+            "<test>",
+            "x = 20
+y = x
+",
+        )
+        .unwrap();
+        let value = evaluator
+            .eval_value(&module, Scope::new_global())
+            .await
+            .unwrap();
+        assert!(value.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sandboxed_evaluator_limits() {
+        async fn run(evaluator: &mut Evaluator, source: &str) -> Result<Value> {
+            let module = blueprint_engine_parser::parse("<sandbox>", source).unwrap();
+            evaluator.eval(&module, Scope::new_global()).await
+        }
+
+        let mut evaluator = Evaluator::sandboxed().with_step_budget(1000);
+        let err = run(
+            &mut evaluator,
+            "for i in range(100000):\n    for j in range(100000):\n        x = i * j\n",
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.inner_error(),
+            BlueprintError::ResourceLimit { limit: 1000, .. }
+        ));
+
+        let mut evaluator = Evaluator::sandboxed().with_step_budget(1000);
+        let err = run(
+            &mut evaluator,
+            "load(\"@bp/task\", \"with_retry\")\ndef spin():\n    for i in range(100000):\n        pass\nwith_retry(spin, 5)()\n",
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is_resource_exhausted());
+        assert!(evaluator.steps_used() <= 1001);
+
+        let mut evaluator = Evaluator::sandboxed().with_step_budget(1000);
+        let err = run(
+            &mut evaluator,
+            "load(\"@bp/test\", \"raises\")\ndef spin():\n    for i in range(100000):\n        pass\nraises(spin)\n",
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is_resource_exhausted());
+
+        let mut evaluator = Evaluator::sandboxed();
+        let err = run(
+            &mut evaluator,
+            "load(\"@bp/file\", \"read_file\")\nread_file(\"/etc/passwd\")\n",
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.inner_error(),
+            BlueprintError::PermissionDenied { .. }
+        ));
+
+        let err = run(&mut evaluator, "load(\"./secrets.bp\", \"KEY\")\n")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.inner_error(),
+            BlueprintError::ImportError { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_step_budget_covers_comprehensions_and_recursion() {
+        for source in [
+            "xs = [i * 2 for i in range(1000000)]\n",
+            "def down(n):\n    return 0 if n == 0 else down(n - 1)\ndown(100000)\n",
+            "total = sum(map(lambda x: x + 1, range(1000000)))\n",
+        ] {
+            let module = blueprint_engine_parser::parse("<budget>", source).unwrap();
+            let mut evaluator = Evaluator::new_isolated().with_step_budget(500);
+            let err = evaluator
+                .eval(&module, Scope::new_global())
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err.inner_error(), BlueprintError::ResourceLimit { .. }),
+                "{}: {}",
+                source,
+                err
+            );
+        }
+
+        let module = blueprint_engine_parser::parse("<budget>", "x = [1, 2, 3]\n").unwrap();
+        let mut evaluator = Evaluator::new_isolated().with_step_budget(500);
+        evaluator.eval(&module, Scope::new_global()).await.unwrap();
+        assert!(evaluator.steps_used() > 0 && evaluator.steps_used() < 10);
+    }
+
+    #[tokio::test]
+    async fn test_eval_keeps_sandbox_permissions_and_budget() {
+        let source = "\
+load(\"@bp/file\", \"read_file\")
+eval(\"read_file('/etc/passwd')\", {\"read_file\": read_file})
+";
+        let module = blueprint_engine_parser::parse("<eval-test>", source).unwrap();
+        let err = Evaluator::sandboxed()
+            .eval(&module, Scope::new_global())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.inner_error(),
+            BlueprintError::PermissionDenied { .. }
+        ));
+
+        let module =
+            blueprint_engine_parser::parse("<eval-test>", "eval(\"[i for i in range(100000)]\")\n")
+                .unwrap();
+        let err = Evaluator::sandboxed()
+            .with_step_budget(500)
+            .eval(&module, Scope::new_global())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.inner_error(),
+            BlueprintError::ResourceLimit { .. }
+        ));
+    }
+}
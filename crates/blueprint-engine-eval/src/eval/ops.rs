@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
-use blueprint_engine_core::{BlueprintError, Result, Value};
+use blueprint_engine_core::{format_float, BlueprintError, Result, Value};
 use blueprint_engine_parser::AssignOp;
 use blueprint_starlark_syntax::syntax::ast::BinOp;
+use indexmap::IndexMap;
 
 pub fn eval_unary_minus(value: Value) -> Result<Value> {
     match value {
@@ -31,7 +32,7 @@ pub async fn eval_binary_op(left: Value, op: BinOp, right: Value) -> Result<Valu
         BinOp::GreaterOrEqual => eval_compare(left, right, |o| o.is_ge()),
         BinOp::In | BinOp::NotIn => unreachable!("handled in eval_expr"),
         BinOp::BitAnd => eval_bit_and(left, right),
-        BinOp::BitOr => eval_bit_or(left, right),
+        BinOp::BitOr => eval_bit_or(left, right).await,
         BinOp::BitXor => eval_bit_xor(left, right),
         BinOp::LeftShift => eval_left_shift(left, right),
         BinOp::RightShift => eval_right_shift(left, right),
@@ -51,6 +52,11 @@ pub async fn eval_add(left: Value, right: Value) -> Result<Value> {
             result.extend(b.read().await.iter().cloned());
             Ok(Value::List(Arc::new(tokio::sync::RwLock::new(result))))
         }
+        _ if is_sequence(&left) && is_sequence(&right) => {
+            let mut result = sequence_items(&left).await?;
+            result.extend(sequence_items(&right).await?);
+            Ok(Value::List(Arc::new(tokio::sync::RwLock::new(result))))
+        }
         _ => Err(BlueprintError::TypeError {
             expected: format!("compatible types for +"),
             actual: format!("{} and {}", left.type_name(), right.type_name()),
@@ -58,6 +64,23 @@ pub async fn eval_add(left: Value, right: Value) -> Result<Value> {
     }
 }
 
+/// Lists, and views such as ranges and `keys()` that concatenate like lists.
+fn is_sequence(value: &Value) -> bool {
+    match value {
+        Value::List(_) => true,
+        Value::Iterator(it) => it.is_view(),
+        _ => false,
+    }
+}
+
+async fn sequence_items(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::List(l) => Ok(l.read().await.clone()),
+        Value::Iterator(it) => it.iter().await.collect().await,
+        _ => Ok(Vec::new()),
+    }
+}
+
 pub fn eval_sub(left: Value, right: Value) -> Result<Value> {
     match (&left, &right) {
         (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a - b)),
@@ -201,6 +224,9 @@ pub fn eval_mod(left: Value, right: Value) -> Result<Value> {
     }
 }
 
+/// Python-style `%` formatting. Each conversion takes optional `-+ 0#` flags,
+/// a minimum width and a `.precision`; `%f` without a precision prints the
+/// same shortest form as `str()`.
 pub fn format_string(fmt: &str, args: &Value) -> Result<Value> {
     let arg_list = match args {
         Value::Tuple(t) => t.as_ref().clone(),
@@ -212,56 +238,206 @@ pub fn format_string(fmt: &str, args: &Value) -> Result<Value> {
     let mut chars = fmt.chars().peekable();
 
     while let Some(c) = chars.next() {
-        if c == '%' {
-            if chars.peek() == Some(&'%') {
-                chars.next();
-                result.push('%');
-            } else {
-                while chars
-                    .peek()
-                    .map(|c| c.is_ascii_digit() || *c == '-' || *c == '+' || *c == ' ' || *c == '.')
-                    .unwrap_or(false)
-                {
-                    chars.next();
-                }
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            result.push('%');
+            continue;
+        }
 
-                let spec = chars.next().ok_or_else(|| BlueprintError::ValueError {
-                    message: "incomplete format".into(),
-                })?;
+        let mut spec = FormatSpec::default();
+        while let Some(&flag) = chars.peek().filter(|c| "-+ 0#".contains(**c)) {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '0' => spec.zero = true,
+                _ => spec.alternate = true,
+            }
+            chars.next();
+        }
+        spec.width = take_number(&mut chars).unwrap_or(0);
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.precision = Some(take_number(&mut chars).unwrap_or(0));
+        }
 
-                if arg_idx >= arg_list.len() {
-                    return Err(BlueprintError::ValueError {
-                        message: "not enough arguments for format string".into(),
-                    });
-                }
+        let conversion = chars.next().ok_or_else(|| BlueprintError::ValueError {
+            message: "incomplete format".into(),
+        })?;
 
-                let arg = &arg_list[arg_idx];
-                arg_idx += 1;
+        if arg_idx >= arg_list.len() {
+            return Err(BlueprintError::ValueError {
+                message: "not enough arguments for format string".into(),
+            });
+        }
+        let arg = &arg_list[arg_idx];
+        arg_idx += 1;
 
-                match spec {
-                    's' => result.push_str(&arg.to_display_string()),
-                    'd' | 'i' => {
-                        let i = arg.as_int()?;
-                        result.push_str(&i.to_string());
-                    }
-                    'f' => {
-                        let f = arg.as_float()?;
-                        result.push_str(&f.to_string());
-                    }
-                    'r' => result.push_str(&arg.repr()),
-                    _ => {
-                        return Err(BlueprintError::ValueError {
-                            message: format!("unsupported format character: {}", spec),
-                        })
-                    }
+        let formatted = match conversion {
+            's' | 'r' => {
+                let mut text = if conversion == 's' {
+                    arg.to_display_string()
+                } else {
+                    arg.repr()
+                };
+                if let Some(precision) = spec.precision {
+                    text = text.chars().take(precision).collect();
+                }
+                spec.pad(String::new(), text, false)
+            }
+            'd' | 'i' => {
+                let i = match arg {
+                    Value::Float(f) => f.trunc() as i64,
+                    Value::Bool(b) => *b as i64,
+                    other => other.as_int()?,
+                };
+                let mut digits = i.unsigned_abs().to_string();
+                if let Some(precision) = spec.precision {
+                    digits = format!("{:0>width$}", digits, width = precision);
                 }
+                spec.pad(spec.sign(i < 0), digits, true)
             }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                let f = match arg {
+                    Value::Int(i) => *i as f64,
+                    other => other.as_float()?,
+                };
+                let body = if !f.is_finite() {
+                    format_float(f.abs())
+                } else {
+                    match (conversion.to_ascii_lowercase(), spec.precision) {
+                        ('f', precision) => format!("{:.*}", precision.unwrap_or(6), f.abs()),
+                        ('e', precision) => format_exponent(f.abs(), precision.unwrap_or(6)),
+                        (_, precision) => format_general(f.abs(), precision, spec.alternate),
+                    }
+                };
+                let body = if spec.alternate && f.is_finite() {
+                    force_point(body)
+                } else {
+                    body
+                };
+                let body = if conversion.is_ascii_uppercase() {
+                    body.to_ascii_uppercase()
+                } else {
+                    body
+                };
+                spec.pad(spec.sign(f.is_sign_negative() && !f.is_nan()), body, true)
+            }
+            _ => {
+                return Err(BlueprintError::ValueError {
+                    message: format!("unsupported format character: {}", conversion),
+                })
+            }
+        };
+        result.push_str(&formatted);
+    }
+
+    Ok(Value::String(Arc::new(result)))
+}
+
+#[derive(Default)]
+struct FormatSpec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl FormatSpec {
+    fn sign(&self, negative: bool) -> String {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
         } else {
-            result.push(c);
+            ""
         }
+        .into()
     }
 
-    Ok(Value::String(Arc::new(result)))
+    /// Pads `sign + body` to the field width. Zero padding goes between the
+    /// sign and the digits and only applies to numbers.
+    fn pad(&self, sign: String, body: String, numeric: bool) -> String {
+        let len = sign.chars().count() + body.chars().count();
+        let fill = self.width.saturating_sub(len);
+        if self.left {
+            format!("{}{}{}", sign, body, " ".repeat(fill))
+        } else if self.zero && numeric {
+            format!("{}{}{}", sign, "0".repeat(fill), body)
+        } else {
+            format!("{}{}{}", " ".repeat(fill), sign, body)
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(&d) = chars.peek().filter(|c| c.is_ascii_digit()) {
+        digits.push(d);
+        chars.next();
+    }
+    digits.parse().ok()
+}
+
+/// The `#` flag keeps the decimal point even when no digits follow it.
+fn force_point(body: String) -> String {
+    let end = body.find('e').unwrap_or(body.len());
+    if body[..end].contains('.') {
+        body
+    } else {
+        format!("{}.{}", &body[..end], &body[end..])
+    }
+}
+
+/// `%e`: one digit before the point and at least two exponent digits,
+/// e.g. `1.500000e+03`.
+fn format_exponent(f: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, f);
+    let (mantissa, exponent) = formatted
+        .split_once('e')
+        .expect("`{:e}` output always has an exponent");
+    let exponent: i32 = exponent.parse().expect("`{:e}` exponent is an integer");
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+}
+
+/// `%g`: `%e` when the exponent is below -4 or at least the precision,
+/// `%f` otherwise, with trailing zeros removed unless `#` was given.
+fn format_general(f: f64, precision: Option<usize>, alternate: bool) -> String {
+    let precision = precision.unwrap_or(6).max(1);
+    let rounded = format!("{:.*e}", precision - 1, f);
+    let exponent: i32 = rounded
+        .split_once('e')
+        .and_then(|(_, e)| e.parse().ok())
+        .unwrap_or(0);
+
+    let mut body = if exponent < -4 || exponent >= precision as i32 {
+        format_exponent(f, precision - 1)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exponent) as usize, f)
+    };
+    if !alternate {
+        let (number, suffix) = match body.find('e') {
+            Some(pos) => body.split_at(pos),
+            None => (body.as_str(), ""),
+        };
+        let number = if number.contains('.') {
+            number.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            number
+        };
+        body = format!("{}{}", number, suffix);
+    }
+    body
 }
 
 pub fn eval_compare<F>(left: Value, right: Value, cmp: F) -> Result<Value>
@@ -312,6 +488,13 @@ pub async fn eval_in(
             let set = s.read().await;
             Ok(Value::Bool(set.contains(&left)))
         }
+        Value::Iterator(it) => match it.contains(&left).await {
+            Some(found) => Ok(Value::Bool(found)),
+            None => Err(BlueprintError::TypeError {
+                expected: "iterable supporting 'in' (use list() to materialize it first)".into(),
+                actual: right.type_name().into(),
+            }),
+        },
         _ => Err(BlueprintError::TypeError {
             expected: "iterable".into(),
             actual: right.type_name().into(),
@@ -329,11 +512,19 @@ pub fn eval_bit_and(left: Value, right: Value) -> Result<Value> {
     }
 }
 
-pub fn eval_bit_or(left: Value, right: Value) -> Result<Value> {
+/// On dicts this is a non-mutating merge where keys from `right` win.
+pub async fn eval_bit_or(left: Value, right: Value) -> Result<Value> {
     match (&left, &right) {
         (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a | b)),
+        (Value::Dict(a), Value::Dict(b)) => {
+            let mut result = a.read().await.clone();
+            for (k, v) in b.read().await.iter() {
+                result.insert(k.clone(), v.clone());
+            }
+            Ok(Value::Dict(Arc::new(tokio::sync::RwLock::new(result))))
+        }
         _ => Err(BlueprintError::TypeError {
-            expected: "integers".into(),
+            expected: "integers or dicts".into(),
             actual: format!("{} and {}", left.type_name(), right.type_name()),
         }),
     }
@@ -385,18 +576,59 @@ pub fn eval_right_shift(left: Value, right: Value) -> Result<Value> {
     }
 }
 
+/// `xs += ys` on lists extends `xs` in place, as in Python, so building a
+/// list in a loop is linear rather than copying it on every iteration.
+/// `d |= other` likewise updates `d` in place, so aliases see the merge.
 pub async fn apply_assign_op(op: AssignOp, left: Value, right: Value) -> Result<Value> {
     match op {
-        AssignOp::Add => eval_add(left, right).await,
+        AssignOp::Add => match (&left, &right) {
+            (Value::List(a), Value::List(b)) => {
+                extend_list(a, b).await;
+                Ok(left)
+            }
+            _ => eval_add(left, right).await,
+        },
         AssignOp::Subtract => eval_sub(left, right),
         AssignOp::Multiply => eval_mul(left, right),
         AssignOp::Divide => eval_div(left, right),
         AssignOp::FloorDivide => eval_floor_div(left, right),
         AssignOp::Percent => eval_mod(left, right),
         AssignOp::BitAnd => eval_bit_and(left, right),
-        AssignOp::BitOr => eval_bit_or(left, right),
+        AssignOp::BitOr => match (&left, &right) {
+            (Value::Dict(a), Value::Dict(b)) => {
+                update_dict(a, b).await;
+                Ok(left)
+            }
+            _ => eval_bit_or(left, right).await,
+        },
         AssignOp::BitXor => eval_bit_xor(left, right),
         AssignOp::LeftShift => eval_left_shift(left, right),
         AssignOp::RightShift => eval_right_shift(left, right),
     }
 }
+
+async fn extend_list(
+    target: &tokio::sync::RwLock<Vec<Value>>,
+    extra: &tokio::sync::RwLock<Vec<Value>>,
+) {
+    if std::ptr::eq(target, extra) {
+        let mut items = target.write().await;
+        let copy = items.clone();
+        items.extend(copy);
+        return;
+    }
+    // Copy out before locking the target so the two locks are never held together.
+    let extra = extra.read().await.clone();
+    target.write().await.extend(extra);
+}
+
+async fn update_dict(
+    target: &tokio::sync::RwLock<IndexMap<String, Value>>,
+    extra: &tokio::sync::RwLock<IndexMap<String, Value>>,
+) {
+    if std::ptr::eq(target, extra) {
+        return;
+    }
+    let extra = extra.read().await.clone();
+    target.write().await.extend(extra);
+}
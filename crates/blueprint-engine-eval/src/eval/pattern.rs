@@ -5,10 +5,13 @@ use blueprint_engine_parser::{AstExpr, ExprP};
 use blueprint_starlark_syntax::syntax::ast::{ArgumentP, BinOp};
 
 use super::ops;
-use super::Evaluator;
+use super::{gather_starred, Evaluator};
 use crate::scope::Scope;
 
 impl Evaluator {
+    /// Cases are tried in order. Bindings from the first case whose pattern
+    /// matches and whose guard passes are copied into the enclosing scope.
+    /// When no case matches the statement is a no-op, as in Python.
     pub async fn eval_match(
         &self,
         match_stmt: &blueprint_starlark_syntax::syntax::ast::MatchP<
@@ -76,36 +79,38 @@ impl Evaluator {
                 Ok(pattern_val == *subject)
             }
 
-            ExprP::List(patterns) => match subject {
-                Value::List(l) => {
-                    let items = l.read().await;
-                    if items.len() != patterns.len() {
-                        return Ok(false);
-                    }
-                    for (pat, item) in patterns.iter().zip(items.iter()) {
-                        if !self.match_pattern(pat, item, scope).await? {
-                            return Ok(false);
-                        }
+            // `[a, b]` and `(a, b)` are both sequence patterns and match
+            // lists and tuples alike, so nested data destructures uniformly.
+            ExprP::List(patterns) | ExprP::Tuple(patterns) => {
+                let items = match subject {
+                    Value::List(l) => l.read().await.clone(),
+                    Value::Tuple(t) => t.as_ref().clone(),
+                    _ => return Ok(false),
+                };
+                let star = patterns
+                    .iter()
+                    .position(|p| matches!(p.node, ExprP::Starred(_)));
+                let items = match star {
+                    Some(star) if items.len() + 1 >= patterns.len() => {
+                        gather_starred(items, patterns.len(), star)
                     }
-                    Ok(true)
+                    Some(_) => return Ok(false),
+                    None => items,
+                };
+                if items.len() != patterns.len() {
+                    return Ok(false);
                 }
-                _ => Ok(false),
-            },
-
-            ExprP::Tuple(patterns) => match subject {
-                Value::Tuple(t) => {
-                    if t.len() != patterns.len() {
+                for (pat, item) in patterns.iter().zip(items.iter()) {
+                    if !self.match_pattern(pat, item, scope).await? {
                         return Ok(false);
                     }
-                    for (pat, item) in patterns.iter().zip(t.iter()) {
-                        if !self.match_pattern(pat, item, scope).await? {
-                            return Ok(false);
-                        }
-                    }
-                    Ok(true)
                 }
-                _ => Ok(false),
-            },
+                Ok(true)
+            }
+
+            // `*rest` in a sequence pattern; the sequence has already grouped
+            // the items it takes into a list.
+            ExprP::Starred(inner) => self.match_pattern(inner, subject, scope).await,
 
             ExprP::Dict(pairs) => match subject {
                 Value::Dict(d) => {
@@ -128,7 +133,13 @@ impl Evaluator {
             },
 
             ExprP::Op(lhs, BinOp::BitOr, rhs) => {
-                if self.match_pattern(lhs, subject, scope).await? {
+                // Try the left alternative in its own scope so a partial
+                // match does not leave stray bindings behind.
+                let alt_scope = Scope::new_child(scope.clone(), crate::scope::ScopeKind::Block);
+                if self.match_pattern(lhs, subject, &alt_scope).await? {
+                    for (name, value) in alt_scope.variables_snapshot().await {
+                        scope.define(&name, value).await;
+                    }
                     return Ok(true);
                 }
                 self.match_pattern(rhs, subject, scope).await
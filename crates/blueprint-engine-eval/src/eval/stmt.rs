@@ -1,20 +1,241 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 
-use blueprint_engine_core::{BlueprintError, Result, Value};
-use blueprint_engine_parser::{AstStmt, ParsedModule, StmtP};
+use blueprint_engine_core::{with_permissions_async, BlueprintError, Result, Value};
+use blueprint_engine_parser::{
+    AssignOp, AssignTargetP, AstExpr, AstStmt, ExprP, ParsedModule, StmtP,
+};
 
 use super::ops;
 use super::Evaluator;
+use crate::coverage;
+use crate::memory;
 use crate::scope::{Scope, ScopeKind};
+use crate::stats;
 
 impl Evaluator {
     pub async fn eval(&mut self, module: &ParsedModule, scope: Arc<Scope>) -> Result<Value> {
         self.codemap = Some(module.codemap.clone());
-        self.eval_stmt(module.statements(), scope).await
+        self.coverage = match &self.current_file {
+            Some(file) if coverage::coverage_enabled() => Some(coverage::register_module(
+                file,
+                &module.codemap,
+                module.statements(),
+            )),
+            _ => None,
+        };
+        match self.permissions.clone() {
+            Some(permissions) => {
+                with_permissions_async(permissions, || self.eval_module(module, scope)).await
+            }
+            None => self.eval_module(module, scope).await,
+        }
+    }
+
+    async fn eval_module(&self, module: &ParsedModule, scope: Arc<Scope>) -> Result<Value> {
+        let preloaded = self.preload_imports(module.statements()).await;
+        let result = if stats::stats_enabled() {
+            self.eval_timed(module.statements(), scope).await
+        } else {
+            self.eval_stmt(module.statements(), scope).await
+        };
+        self.discard_preloaded(&preloaded);
+        result
+    }
+
+    /// Evaluates `module` and returns the value of its final top-level
+    /// statement if that statement is an expression, or `None` otherwise.
+    pub async fn eval_value(&mut self, module: &ParsedModule, scope: Arc<Scope>) -> Result<Value> {
+        let result = self.eval(module, scope).await?;
+        let last = match &module.statements().node {
+            StmtP::Statements(stmts) => stmts.last(),
+            _ => Some(module.statements()),
+        };
+        match last.map(|s| &s.node) {
+            Some(StmtP::Expression(_)) => Ok(result),
+            _ => Ok(Value::None),
+        }
+    }
+
+    async fn eval_timed(&self, stmt: &AstStmt, scope: Arc<Scope>) -> Result<Value> {
+        let stmts: Vec<&AstStmt> = match &stmt.node {
+            StmtP::Statements(stmts) => stmts.iter().collect(),
+            _ => vec![stmt],
+        };
+
+        let file = self
+            .current_file
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "<inline>".to_string());
+
+        let mut result = Value::None;
+        for s in stmts {
+            let start = Instant::now();
+            let outcome = self.eval_stmt(s, scope.clone()).await;
+            let (line, _) = self.display_location(&s.span);
+            stats::record_statement(format!("{}:{}", file, line), start.elapsed());
+            result = outcome?;
+        }
+        Ok(result)
+    }
+
+    async fn eval_for(
+        &self,
+        for_stmt: &blueprint_starlark_syntax::syntax::ast::ForP<
+            blueprint_starlark_syntax::syntax::ast::AstNoPayload,
+        >,
+        scope: Arc<Scope>,
+    ) -> Result<Value> {
+        let iterable = self.eval_expr(&for_stmt.over, scope.clone()).await?;
+
+        match &iterable {
+            Value::Iterator(iter) => {
+                let iter = iter.iter().await;
+                loop {
+                    let item = iter.try_next().await?;
+                    match item {
+                        Some(value) => {
+                            let loop_scope = Scope::new_child(scope.clone(), ScopeKind::Loop);
+                            self.assign_target(&for_stmt.var, value, loop_scope.clone())
+                                .await?;
+
+                            match self.eval_stmt(&for_stmt.body, loop_scope).await {
+                                Err(BlueprintError::Break) => break,
+                                Err(BlueprintError::Continue) => continue,
+                                Err(e) => return Err(e),
+                                Ok(_) => {}
+                            }
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Value::Generator(gen) => loop {
+                let item = gen.try_next().await?;
+                match item {
+                    Some(value) => {
+                        let loop_scope = Scope::new_child(scope.clone(), ScopeKind::Loop);
+                        self.assign_target(&for_stmt.var, value, loop_scope.clone())
+                            .await?;
+
+                        match self.eval_stmt(&for_stmt.body, loop_scope).await {
+                            Err(BlueprintError::Break) => break,
+                            Err(BlueprintError::Continue) => continue,
+                            Err(e) => return Err(e),
+                            Ok(_) => {}
+                        }
+                    }
+                    None => break,
+                }
+            },
+            _ => {
+                let items = self.get_iterable(&iterable).await?;
+
+                for item in items {
+                    let loop_scope = Scope::new_child(scope.clone(), ScopeKind::Loop);
+                    self.assign_target(&for_stmt.var, item, loop_scope.clone())
+                        .await?;
+
+                    match self.eval_stmt(&for_stmt.body, loop_scope).await {
+                        Err(BlueprintError::Break) => break,
+                        Err(BlueprintError::Continue) => continue,
+                        Err(e) => return Err(e),
+                        Ok(_) => {}
+                    }
+                }
+            }
+        }
+        Ok(Value::None)
+    }
+
+    /// Runs the body between the resource's `__enter__` and its `__exit__`
+    /// (or `close`). The exit hook runs however the body ends, including
+    /// failures, `break`, `continue` and `return`. Its future is boxed where
+    /// it is built, so it adds nothing to the frame of `eval_stmt`.
+    #[async_recursion::async_recursion]
+    async fn eval_with(
+        &self,
+        with_stmt: &blueprint_starlark_syntax::syntax::ast::WithP<
+            blueprint_starlark_syntax::syntax::ast::AstNoPayload,
+        >,
+        scope: Arc<Scope>,
+    ) -> Result<Value> {
+        let resource = self.eval_expr(&with_stmt.resource, scope.clone()).await?;
+        let exit = resource
+            .get_attr("__exit__")
+            .or_else(|| resource.get_attr("close"))
+            .ok_or_else(|| BlueprintError::TypeError {
+                expected: "resource with __exit__ or close in with statement".into(),
+                actual: resource.type_name().into(),
+            })?;
+
+        // Calls are boxed to keep their futures out of every statement's frame.
+        let entered = match resource.get_attr("__enter__") {
+            Some(enter) => {
+                Box::pin(self.call_function(enter, vec![], HashMap::new(), scope.clone())).await?
+            }
+            None => resource.clone(),
+        };
+
+        let block_scope = Scope::new_child(scope.clone(), ScopeKind::Block);
+        let result = match &with_stmt.target {
+            Some(target) => {
+                self.assign_target(target, entered, block_scope.clone())
+                    .await
+            }
+            None => Ok(()),
+        };
+        let result = match result {
+            Ok(()) => self.eval_stmt(&with_stmt.body, block_scope).await,
+            Err(e) => Err(e),
+        };
+        let exited = Box::pin(self.call_function(exit, vec![], HashMap::new(), scope)).await;
+
+        match (result, exited) {
+            (Err(e), _) => Err(e),
+            (Ok(_), Err(e)) => Err(e),
+            (Ok(_), Ok(_)) => Ok(Value::None),
+        }
+    }
+
+    /// Evaluates the operand of `return` inside a function, where a call is
+    /// in tail position, as is a call in either branch of `a if c else b`.
+    async fn eval_return_value(&self, expr: &AstExpr, scope: Arc<Scope>) -> Result<Value> {
+        let mut expr = expr;
+        while let ExprP::If(triple) = &expr.node {
+            if let Some(budget) = &self.budget {
+                budget.charge()?;
+            }
+            let (cond, then_expr, else_expr) = triple.as_ref();
+            expr = if self.eval_expr(cond, scope.clone()).await?.is_truthy() {
+                then_expr
+            } else {
+                else_expr
+            };
+        }
+        if !matches!(expr.node, ExprP::Call(..)) {
+            return self.eval_expr(expr, scope).await;
+        }
+        if let Some(budget) = &self.budget {
+            budget.charge()?;
+        }
+        self.eval_call(expr, true, scope).await
     }
 
     #[async_recursion::async_recursion]
     pub async fn eval_stmt(&self, stmt: &AstStmt, scope: Arc<Scope>) -> Result<Value> {
+        memory::check_memory()?;
+        if let Some(budget) = &self.budget {
+            budget.charge()?;
+        }
+        if let Some(hits) = &self.coverage {
+            if !matches!(stmt.node, StmtP::Statements(_)) {
+                hits.record(stmt.span);
+            }
+        }
         match &stmt.node {
             StmtP::Statements(stmts) => {
                 let mut result = Value::None;
@@ -33,8 +254,25 @@ impl Evaluator {
             }
 
             StmtP::AssignModify(lhs, op, rhs) => {
-                let current = self.eval_assign_target_value(lhs, scope.clone()).await?;
+                let name = match (&lhs.node, op) {
+                    (AssignTargetP::Identifier(ident), AssignOp::Add) => {
+                        Some(ident.node.ident.as_str())
+                    }
+                    _ => None,
+                };
+                let mut current = self.eval_assign_target_value(lhs, scope.clone()).await?;
                 let rhs_val = self.eval_expr(rhs, scope.clone()).await?;
+                if let (Some(name), Value::String(piece)) = (name, &rhs_val) {
+                    // `s += piece` appends to the bound string in place unless
+                    // the right-hand side rebound `s`.
+                    current = match current {
+                        Value::String(bound) => match scope.append_str(name, bound, piece).await {
+                            Ok(()) => return Ok(Value::None),
+                            Err(bound) => Value::String(bound),
+                        },
+                        other => other,
+                    };
+                }
                 let new_val = ops::apply_assign_op(*op, current, rhs_val).await?;
                 self.assign_target(lhs, new_val, scope).await?;
                 Ok(Value::None)
@@ -61,69 +299,22 @@ impl Evaluator {
                 Ok(Value::None)
             }
 
-            StmtP::For(for_stmt) => {
-                let iterable = self.eval_expr(&for_stmt.over, scope.clone()).await?;
-
-                match &iterable {
-                    Value::Iterator(iter) => loop {
-                        let item = iter.next().await;
-                        match item {
-                            Some(value) => {
-                                let loop_scope = Scope::new_child(scope.clone(), ScopeKind::Loop);
-                                self.assign_target(&for_stmt.var, value, loop_scope.clone())
-                                    .await?;
-
-                                match self.eval_stmt(&for_stmt.body, loop_scope).await {
-                                    Err(BlueprintError::Break) => break,
-                                    Err(BlueprintError::Continue) => continue,
-                                    Err(e) => return Err(e),
-                                    Ok(_) => {}
-                                }
-                            }
-                            None => break,
-                        }
-                    },
-                    Value::Generator(gen) => loop {
-                        let item = gen.next().await;
-                        match item {
-                            Some(value) => {
-                                let loop_scope = Scope::new_child(scope.clone(), ScopeKind::Loop);
-                                self.assign_target(&for_stmt.var, value, loop_scope.clone())
-                                    .await?;
-
-                                match self.eval_stmt(&for_stmt.body, loop_scope).await {
-                                    Err(BlueprintError::Break) => break,
-                                    Err(BlueprintError::Continue) => continue,
-                                    Err(e) => return Err(e),
-                                    Ok(_) => {}
-                                }
-                            }
-                            None => break,
-                        }
-                    },
-                    _ => {
-                        let items = self.get_iterable(&iterable).await?;
+            // Loops are boxed so their iteration state stays out of the frame
+            // every other statement, including each call's body, runs in.
+            StmtP::For(for_stmt) => Box::pin(self.eval_for(for_stmt, scope)).await,
 
-                        for item in items {
-                            let loop_scope = Scope::new_child(scope.clone(), ScopeKind::Loop);
-                            self.assign_target(&for_stmt.var, item, loop_scope.clone())
-                                .await?;
-
-                            match self.eval_stmt(&for_stmt.body, loop_scope).await {
-                                Err(BlueprintError::Break) => break,
-                                Err(BlueprintError::Continue) => continue,
-                                Err(e) => return Err(e),
-                                Ok(_) => {}
-                            }
-                        }
-                    }
-                }
-                Ok(Value::None)
-            }
+            StmtP::With(with_stmt) => self.eval_with(with_stmt, scope).await,
 
             StmtP::Break => Err(BlueprintError::Break),
             StmtP::Continue => Err(BlueprintError::Continue),
 
+            StmtP::Return(Some(e)) if scope.function_id().is_some() => {
+                let value = self.eval_return_value(e, scope).await?;
+                Err(BlueprintError::Return {
+                    value: Arc::new(value),
+                })
+            }
+
             StmtP::Return(expr) => {
                 let value = match expr {
                     Some(e) => self.eval_expr(e, scope).await?,
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use blueprint_engine_core::{BlueprintError, Result, StructField, StructType, TypeAnnotation, Value};
 use blueprint_engine_parser::{AstExpr, ExprP};
 use blueprint_starlark_syntax::syntax::ast::BinOp;
+use indexmap::IndexMap;
 
 use super::Evaluator;
 use crate::scope::Scope;
@@ -27,16 +28,21 @@ impl Evaluator {
                 None
             };
 
-            fields.push(StructField {
+            let field = StructField {
                 name: field_name,
                 typ: type_annotation,
                 default,
-            });
+            };
+            if let Some(default) = &field.default {
+                field.check(&struct_name, default)?;
+            }
+            fields.push(field);
         }
 
         let struct_type = StructType {
             name: struct_name.clone(),
             fields,
+            methods: IndexMap::new(),
         };
 
         let value = Value::StructType(Arc::new(struct_type));
@@ -80,6 +86,22 @@ impl Evaluator {
 
                 Ok(TypeAnnotation::Parameterized(base_name, params))
             }
+            // `dict[str, int]` parses as a two-element subscript.
+            ExprP::Index2(triple) => {
+                let (base, first, second) = triple.as_ref();
+                let ExprP::Identifier(ident) = &base.node else {
+                    return Err(BlueprintError::ValueError {
+                        message: "invalid type annotation".into(),
+                    });
+                };
+                Ok(TypeAnnotation::Parameterized(
+                    ident.node.ident.clone(),
+                    vec![
+                        self.convert_expr_to_type_annotation(first)?,
+                        self.convert_expr_to_type_annotation(second)?,
+                    ],
+                ))
+            }
             ExprP::Op(lhs, BinOp::BitOr, rhs) => {
                 if let ExprP::Identifier(ident) = &rhs.node {
                     if ident.node.ident == "None" {
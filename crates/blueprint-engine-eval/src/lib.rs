@@ -1,9 +1,32 @@
+mod budget;
 mod checker;
+mod coverage;
+mod diagnostics;
+mod engine;
 mod eval;
+mod memory;
 mod modules;
+mod profiler;
+mod resolver;
 mod scope;
+mod stats;
+mod symbols;
+mod trace;
 
 pub use checker::{Checker, CheckerError};
+pub use coverage::{coverage_snapshot, enable_coverage, CoverageReport, FileCoverage};
+pub use diagnostics::{diagnostics, Diagnostic, Position, Range, Severity};
+pub use engine::{Engine, EngineBuilder};
 pub use eval::Evaluator;
+pub use memory::{current_rss, start_memory_watchdog, stop_memory_watchdog};
 pub use modules::triggers;
+pub use modules::{
+    collect_test_results, freeze_clock, run_benchmark, set_breakpoint_handler, set_cache_backend,
+    set_seed, set_update_snapshots, take_bench_results, BenchResult, BreakpointHandler,
+    CacheBackend, CacheEntry, FileCache, MemoryCache, TestResult,
+};
+pub use profiler::{start_profiler, stop_profiler, with_profile_stack};
+pub use resolver::{MemoryResolver, ModuleResolver, ResolvedModule};
 pub use scope::{Scope, ScopeKind};
+pub use stats::{enable_stats, stats_snapshot, EvalStats};
+pub use symbols::{symbol_at, Definition, SymbolInfo};
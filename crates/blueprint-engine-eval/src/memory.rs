@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use blueprint_engine_core::{BlueprintError, Result};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+static LIMIT: AtomicU64 = AtomicU64::new(0);
+static EXCEEDED_AT: AtomicU64 = AtomicU64::new(0);
+
+lazy_static::lazy_static! {
+    static ref WATCHDOG: Mutex<Option<Watchdog>> = Mutex::new(None);
+}
+
+struct Watchdog {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+/// Resident set size of the whole process in bytes. Read from `/proc`, so
+/// this is `None` outside Linux.
+pub fn current_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Polls RSS on a background thread. Once it goes over `limit` bytes every
+/// evaluator fails at its next statement. Returns false when RSS cannot be
+/// read on this platform.
+pub fn start_memory_watchdog(limit: u64) -> bool {
+    if current_rss().is_none() {
+        return false;
+    }
+
+    let mut watchdog = WATCHDOG.lock().unwrap();
+    if watchdog.is_some() {
+        return true;
+    }
+
+    LIMIT.store(limit, Ordering::Relaxed);
+    EXCEEDED_AT.store(0, Ordering::Relaxed);
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_flag = stop.clone();
+    let handle = std::thread::spawn(move || {
+        while !stop_flag.load(Ordering::Relaxed) {
+            if let Some(rss) = current_rss() {
+                if rss > limit {
+                    EXCEEDED_AT.store(rss, Ordering::Relaxed);
+                    return;
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+
+    *watchdog = Some(Watchdog { stop, handle });
+    true
+}
+
+pub fn stop_memory_watchdog() {
+    if let Some(watchdog) = WATCHDOG.lock().unwrap().take() {
+        watchdog.stop.store(true, Ordering::Relaxed);
+        let _ = watchdog.handle.join();
+    }
+}
+
+#[inline]
+pub(crate) fn check_memory() -> Result<()> {
+    let used = EXCEEDED_AT.load(Ordering::Relaxed);
+    if used == 0 {
+        return Ok(());
+    }
+    Err(BlueprintError::MemoryLimitExceeded {
+        used,
+        limit: LIMIT.load(Ordering::Relaxed),
+    })
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use blueprint_engine_core::{
+    validation::{get_string_arg, require_args_range},
+    BlueprintError, NativeFunction, Result, Value,
+};
+use indexmap::IndexMap;
+use tokio::sync::RwLock;
+
+use super::builtins::call_callable;
+
+const DEFAULT_ITERATIONS: u64 = 100;
+const DEFAULT_WARMUP: u64 = 10;
+
+lazy_static::lazy_static! {
+    static ref RESULTS: Mutex<Vec<BenchResult>> = Mutex::new(Vec::new());
+}
+
+/// Per-call timings of one benchmark. Warmup calls are not included.
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: String,
+    pub iterations: u64,
+    pub mean: Duration,
+    pub median: Duration,
+    pub p99: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl BenchResult {
+    fn from_samples(name: &str, mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        let n = samples.len();
+        let median = if n.is_multiple_of(2) {
+            (samples[n / 2 - 1] + samples[n / 2]) / 2
+        } else {
+            samples[n / 2]
+        };
+        let p99 = samples[(n * 99).div_ceil(100).max(1) - 1];
+        BenchResult {
+            name: name.to_string(),
+            iterations: n as u64,
+            mean: samples.iter().sum::<Duration>() / n as u32,
+            median,
+            p99,
+            min: samples[0],
+            max: samples[n - 1],
+        }
+    }
+}
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![NativeFunction::new("run", run)]
+}
+
+fn lock() -> MutexGuard<'static, Vec<BenchResult>> {
+    RESULTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Calls `func` with no arguments `warmup` times, then times `iterations`
+/// further calls one by one. Each call runs the function body again; nothing
+/// is cached between calls unless the function itself caches.
+pub async fn run_benchmark(
+    name: &str,
+    func: &Value,
+    iterations: u64,
+    warmup: u64,
+) -> Result<BenchResult> {
+    if iterations == 0 {
+        return Err(BlueprintError::ValueError {
+            message: "bench.run(): iterations must be at least 1".into(),
+        });
+    }
+    for _ in 0..warmup {
+        call_callable(func, vec![], HashMap::new()).await?;
+    }
+
+    let mut samples = Vec::with_capacity(iterations as usize);
+    for _ in 0..iterations {
+        let started = Instant::now();
+        call_callable(func, vec![], HashMap::new()).await?;
+        samples.push(started.elapsed());
+    }
+
+    let result = BenchResult::from_samples(name, samples);
+    lock().push(result.clone());
+    Ok(result)
+}
+
+/// Results of every benchmark run since the last call. Used by `bp bench`
+/// to report benchmarks a script ran itself.
+pub fn take_bench_results() -> Vec<BenchResult> {
+    std::mem::take(&mut *lock())
+}
+
+/// `run(name, fn, iterations=100, warmup=10)` returns the timings in seconds
+/// as `{"name", "iterations", "mean", "median", "p99", "min", "max"}`.
+async fn run(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("bench.run", &args, 2, 4)?;
+    let name = get_string_arg("bench.run", &args, 0)?;
+    let func = &args[1];
+    if !matches!(
+        func,
+        Value::Function(_) | Value::Lambda(_) | Value::NativeFunction(_)
+    ) {
+        return Err(BlueprintError::TypeError {
+            expected: "callable argument to bench.run()".into(),
+            actual: func.type_name().into(),
+        });
+    }
+    let iterations = count_arg(&args, &kwargs, 2, "iterations", DEFAULT_ITERATIONS)?;
+    let warmup = count_arg(&args, &kwargs, 3, "warmup", DEFAULT_WARMUP)?;
+
+    let result = run_benchmark(&name, func, iterations, warmup).await?;
+
+    let seconds = |d: Duration| Value::Float(d.as_secs_f64());
+    let mut report = IndexMap::new();
+    report.insert("name".to_string(), Value::String(Arc::new(result.name)));
+    report.insert(
+        "iterations".to_string(),
+        Value::Int(result.iterations as i64),
+    );
+    report.insert("mean".to_string(), seconds(result.mean));
+    report.insert("median".to_string(), seconds(result.median));
+    report.insert("p99".to_string(), seconds(result.p99));
+    report.insert("min".to_string(), seconds(result.min));
+    report.insert("max".to_string(), seconds(result.max));
+    Ok(Value::Dict(Arc::new(RwLock::new(report))))
+}
+
+fn count_arg(
+    args: &[Value],
+    kwargs: &HashMap<String, Value>,
+    index: usize,
+    name: &str,
+    default: u64,
+) -> Result<u64> {
+    match args.get(index).or_else(|| kwargs.get(name)) {
+        None | Some(Value::None) => Ok(default),
+        Some(Value::Int(n)) if *n >= 0 => Ok(*n as u64),
+        Some(other) => Err(BlueprintError::TypeError {
+            expected: format!("non-negative int for bench.run() {}", name),
+            actual: other.type_name().into(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_statistics() {
+        let samples = (1..=200).map(Duration::from_millis).collect();
+        let result = BenchResult::from_samples("sum", samples);
+        assert_eq!(result.iterations, 200);
+        assert_eq!(result.min, Duration::from_millis(1));
+        assert_eq!(result.max, Duration::from_millis(200));
+        assert_eq!(result.median, Duration::from_micros(100_500));
+        assert_eq!(result.mean, Duration::from_micros(100_500));
+        assert_eq!(result.p99, Duration::from_millis(198));
+
+        let single = BenchResult::from_samples("one", vec![Duration::from_millis(5)]);
+        assert_eq!(single.p99, Duration::from_millis(5));
+        assert_eq!(single.median, Duration::from_millis(5));
+    }
+}
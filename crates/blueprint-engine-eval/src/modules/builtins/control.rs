@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 
+use blueprint_engine_core::validation::require_args_range;
 use blueprint_engine_core::{BlueprintError, Result, Value};
 
+use super::call_callable;
+
 pub async fn fail(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     let message = if args.is_empty() {
         "fail".to_string()
@@ -34,7 +37,7 @@ pub async fn exit(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<V
     Err(BlueprintError::Exit { code })
 }
 
-pub async fn assert_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+pub async fn assert_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.is_empty() || args.len() > 2 {
         return Err(BlueprintError::ArgumentError {
             message: format!("assert() takes 1 or 2 arguments ({} given)", args.len()),
@@ -42,13 +45,181 @@ pub async fn assert_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Res
     }
 
     if !args[0].is_truthy() {
-        let message = if args.len() == 2 {
-            args[1].to_display_string()
-        } else {
+        return assertion_failed(message_arg(&args, &kwargs, 1), || {
             "assertion failed".to_string()
-        };
-        return Err(BlueprintError::AssertionError { message });
+        })
+        .await;
     }
 
     Ok(Value::None)
 }
+
+pub async fn assert_true(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("assert_true", &args, 1, 2)?;
+    if !args[0].is_truthy() {
+        return assertion_failed(message_arg(&args, &kwargs, 1), || {
+            format!("expected a truthy value, got {}", args[0].repr())
+        })
+        .await;
+    }
+    Ok(Value::None)
+}
+
+pub async fn assert_eq(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("assert_eq", &args, 2, 3)?;
+    if !values_equal(&args[0], &args[1]).await {
+        return assertion_failed(message_arg(&args, &kwargs, 2), || {
+            format!("{} != {}", args[0].repr(), args[1].repr())
+        })
+        .await;
+    }
+    Ok(Value::None)
+}
+
+pub async fn assert_ne(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("assert_ne", &args, 2, 3)?;
+    if values_equal(&args[0], &args[1]).await {
+        return assertion_failed(message_arg(&args, &kwargs, 2), || {
+            format!("{} == {}", args[0].repr(), args[1].repr())
+        })
+        .await;
+    }
+    Ok(Value::None)
+}
+
+pub async fn assert_contains(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("assert_contains", &args, 2, 3)?;
+    if !contains(&args[0], &args[1]).await? {
+        return assertion_failed(message_arg(&args, &kwargs, 2), || {
+            format!("{} not found in {}", args[1].repr(), args[0].repr())
+        })
+        .await;
+    }
+    Ok(Value::None)
+}
+
+/// Fallback for indirect calls (e.g. `map(eval, sources)`), which would run
+/// outside the caller's evaluator and step budget.
+pub async fn eval(_args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    Err(BlueprintError::ArgumentError {
+        message: "eval() must be called directly".into(),
+    })
+}
+
+fn message_arg<'a>(
+    args: &'a [Value],
+    kwargs: &'a HashMap<String, Value>,
+    index: usize,
+) -> Option<&'a Value> {
+    args.get(index).or_else(|| kwargs.get("msg"))
+}
+
+/// Builds the failure message only once an assertion has failed. A callable
+/// message is called with no arguments, so expensive messages can be passed
+/// as `lambda: ...` and cost nothing while assertions pass.
+async fn assertion_failed(
+    message: Option<&Value>,
+    default: impl FnOnce() -> String,
+) -> Result<Value> {
+    let message = match message {
+        None | Some(Value::None) => default(),
+        Some(callable @ (Value::Function(_) | Value::Lambda(_) | Value::NativeFunction(_))) => {
+            call_callable(callable, vec![], HashMap::new())
+                .await?
+                .to_display_string()
+        }
+        Some(other) => other.to_display_string(),
+    };
+    Err(BlueprintError::AssertionError { message })
+}
+
+#[async_recursion::async_recursion]
+async fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::List(x), Value::List(y)) => {
+            let (x, y) = (x.read().await.clone(), y.read().await.clone());
+            sequences_equal(&x, &y).await
+        }
+        (Value::Tuple(x), Value::Tuple(y)) => sequences_equal(x, y).await,
+        (Value::Dict(x), Value::Dict(y)) => {
+            let (x, y) = (x.read().await.clone(), y.read().await.clone());
+            if x.len() != y.len() {
+                return false;
+            }
+            for (key, value) in &x {
+                match y.get(key) {
+                    Some(other) if values_equal(value, other).await => {}
+                    _ => return false,
+                }
+            }
+            true
+        }
+        (Value::Set(x), Value::Set(y)) => *x.read().await == *y.read().await,
+        _ => a == b,
+    }
+}
+
+async fn sequences_equal(x: &[Value], y: &[Value]) -> bool {
+    if x.len() != y.len() {
+        return false;
+    }
+    for (a, b) in x.iter().zip(y) {
+        if !values_equal(a, b).await {
+            return false;
+        }
+    }
+    true
+}
+
+async fn contains(container: &Value, item: &Value) -> Result<bool> {
+    match container {
+        Value::List(l) => {
+            let items = l.read().await.clone();
+            for candidate in &items {
+                if values_equal(candidate, item).await {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        Value::Tuple(t) => {
+            for candidate in t.iter() {
+                if values_equal(candidate, item).await {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        Value::Set(s) => Ok(s.read().await.contains(item)),
+        Value::Dict(d) => Ok(d.read().await.contains_key(&item.as_string()?)),
+        Value::String(s) => Ok(s.contains(item.as_str()?)),
+        other => Err(BlueprintError::TypeError {
+            expected: "list, tuple, set, dict or string".into(),
+            actual: other.type_name().into(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blueprint_engine_core::NativeFunction;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_callable_message_is_built_on_failure() {
+        let message = Value::NativeFunction(Arc::new(NativeFunction::new(
+            "msg",
+            |_args, _kwargs| async { Ok(Value::from("lazy detail")) },
+        )));
+        let err = assert_eq(vec![Value::Int(1), Value::Int(2), message], HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("lazy detail"));
+
+        let err = assert_eq(vec![Value::Int(1), Value::Int(2)], HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("1 != 2"));
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::IsTerminal;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
+use blueprint_engine_core::{Result, Value};
+
+use crate::scope::Scope;
+
+/// Called by `breakpoint()` with the caller's scope. Execution resumes when
+/// the returned future completes.
+pub type BreakpointHandler =
+    Arc<dyn Fn(Arc<Scope>) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync>;
+
+lazy_static::lazy_static! {
+    static ref BREAKPOINT_HANDLER: RwLock<Option<BreakpointHandler>> = RwLock::new(None);
+}
+
+pub fn set_breakpoint_handler(handler: BreakpointHandler) {
+    *BREAKPOINT_HANDLER.write().unwrap() = Some(handler);
+}
+
+/// Runs the installed handler when stdin is a terminal; otherwise a no-op so
+/// scripts with stray breakpoints still run unattended.
+pub async fn run_breakpoint(scope: Arc<Scope>) -> Result<Value> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(Value::None);
+    }
+
+    let handler = BREAKPOINT_HANDLER.read().unwrap().clone();
+    if let Some(handler) = handler {
+        handler(scope).await?;
+    }
+    Ok(Value::None)
+}
+
+/// Fallback for indirect calls (e.g. `f = breakpoint; f()`) where the caller's
+/// scope is not available.
+pub async fn breakpoint(_args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    run_breakpoint(Scope::new_global()).await
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use indexmap::IndexMap;
+
+use blueprint_engine_core::{intern, BlueprintError, NativeFunction, Result, Value};
+
+use super::call_callable;
+
+pub async fn partial(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    let Some((func, bound_args)) = args.split_first() else {
+        return Err(BlueprintError::ArgumentError {
+            message: "partial() requires a function argument".into(),
+        });
+    };
+    require_callable("partial", func)?;
+
+    let func = func.clone();
+    let bound_args = bound_args.to_vec();
+    let bound_kwargs = kwargs;
+    let name = format!("partial({})", callable_name(&func));
+
+    Ok(Value::NativeFunction(Arc::new(
+        NativeFunction::new_with_state(name, move |args, kwargs| {
+            let func = func.clone();
+            let mut call_args = bound_args.clone();
+            call_args.extend(args);
+            let mut call_kwargs = bound_kwargs.clone();
+            call_kwargs.extend(kwargs);
+            Box::pin(async move { call_callable(&func, call_args, call_kwargs).await })
+        }),
+    )))
+}
+
+/// `with_methods(Type, name=func, ...)` returns a copy of `Type` whose
+/// instances expose each `func` as a method, called with the instance first.
+pub async fn with_methods(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    let struct_type = match args.as_slice() {
+        [Value::StructType(t)] => t.clone(),
+        [other] => {
+            return Err(BlueprintError::TypeError {
+                expected: "struct type".into(),
+                actual: other.type_name().into(),
+            })
+        }
+        _ => {
+            return Err(BlueprintError::ArgumentError {
+                message: format!(
+                    "with_methods() takes exactly 1 positional argument ({} given)",
+                    args.len()
+                ),
+            })
+        }
+    };
+
+    let mut methods: Vec<(String, Value)> = kwargs.into_iter().collect();
+    methods.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut bound = Vec::with_capacity(methods.len());
+    for (name, func) in methods {
+        require_callable("with_methods", &func)?;
+        let method = match func {
+            Value::NativeFunction(f) => f,
+            func => Arc::new(NativeFunction::new_with_state(
+                callable_name(&func),
+                move |args, kwargs| {
+                    let func = func.clone();
+                    Box::pin(async move { call_callable(&func, args, kwargs).await })
+                },
+            )),
+        };
+        bound.push((name, method));
+    }
+
+    Ok(Value::StructType(Arc::new(
+        struct_type.with_methods(bound)?,
+    )))
+}
+
+pub async fn compose(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.is_empty() {
+        return Err(BlueprintError::ArgumentError {
+            message: "compose() requires at least one function".into(),
+        });
+    }
+    for func in &args {
+        require_callable("compose", func)?;
+    }
+
+    let name = format!(
+        "compose({})",
+        args.iter()
+            .map(callable_name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let funcs = Arc::new(args);
+
+    Ok(Value::NativeFunction(Arc::new(
+        NativeFunction::new_with_state(name, move |args, kwargs| {
+            let funcs = funcs.clone();
+            Box::pin(async move {
+                let (innermost, rest) = funcs.split_last().expect("compose() has functions");
+                let mut value = call_callable(innermost, args, kwargs).await?;
+                for func in rest.iter().rev() {
+                    value = call_callable(func, vec![value], HashMap::new()).await?;
+                }
+                Ok(value)
+            })
+        }),
+    )))
+}
+
+pub async fn pipe(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    let Some((value, funcs)) = args.split_first() else {
+        return Err(BlueprintError::ArgumentError {
+            message: "pipe() requires a value argument".into(),
+        });
+    };
+    for func in funcs {
+        require_callable("pipe", func)?;
+    }
+
+    let mut value = value.clone();
+    for func in funcs {
+        value = call_callable(func, vec![value], HashMap::new()).await?;
+    }
+    Ok(value)
+}
+
+pub async fn memoize(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.len() != 1 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("memoize() takes exactly 1 argument ({} given)", args.len()),
+        });
+    }
+    let func = args[0].clone();
+    require_callable("memoize", &func)?;
+
+    let max_size = match kwargs.get("max_size") {
+        None | Some(Value::None) => None,
+        Some(v) => {
+            let n = v.as_int()?;
+            if n <= 0 {
+                return Err(BlueprintError::ValueError {
+                    message: format!("memoize() max_size must be positive, got {}", n),
+                });
+            }
+            Some(n as usize)
+        }
+    };
+
+    let name = format!("memoize({})", callable_name(&func));
+    let cache: Arc<Mutex<IndexMap<Value, Value>>> = Arc::new(Mutex::new(IndexMap::new()));
+
+    Ok(Value::NativeFunction(Arc::new(
+        NativeFunction::new_with_state(name, move |args, kwargs| {
+            let func = func.clone();
+            let cache = cache.clone();
+            Box::pin(async move {
+                let key = cache_key(&args, &kwargs)?;
+
+                {
+                    let mut cache = cache.lock().unwrap();
+                    if let Some(index) = cache.get_index_of(&key) {
+                        let last = cache.len() - 1;
+                        cache.move_index(index, last);
+                        return Ok(cache[last].clone());
+                    }
+                }
+
+                let result = call_callable(&func, args, kwargs).await?;
+
+                let mut cache = cache.lock().unwrap();
+                cache.insert(key, result.clone());
+                if let Some(max) = max_size {
+                    while cache.len() > max {
+                        cache.shift_remove_index(0);
+                    }
+                }
+                Ok(result)
+            })
+        }),
+    )))
+}
+
+/// Builds a cache key from the call's arguments, rejecting anything that
+/// could change after being cached.
+fn cache_key(args: &[Value], kwargs: &HashMap<String, Value>) -> Result<Value> {
+    let mut named: Vec<(&String, &Value)> = kwargs.iter().collect();
+    named.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut key = Vec::with_capacity(args.len() + 1);
+    for arg in args.iter().chain(named.iter().map(|(_, v)| *v)) {
+        if !arg.is_hashable() {
+            return Err(BlueprintError::TypeError {
+                expected: "hashable arguments for memoized function".into(),
+                actual: format!("unhashable type: '{}'", arg.type_name()),
+            });
+        }
+    }
+    key.extend(args.iter().cloned());
+    key.push(Value::Tuple(Arc::new(
+        named
+            .into_iter()
+            .map(|(k, v)| Value::Tuple(Arc::new(vec![Value::String(intern(k)), v.clone()])))
+            .collect(),
+    )));
+    Ok(Value::Tuple(Arc::new(key)))
+}
+
+/// Fails with a `TypeError` naming `name` unless `value` can be called:
+/// a function, lambda, native function or struct type.
+pub(crate) fn require_callable(name: &str, value: &Value) -> Result<()> {
+    match value {
+        Value::Function(_) | Value::Lambda(_) | Value::NativeFunction(_) | Value::StructType(_) => {
+            Ok(())
+        }
+        other => Err(BlueprintError::TypeError {
+            expected: format!("callable argument to {}()", name),
+            actual: other.type_name().into(),
+        }),
+    }
+}
+
+pub(crate) fn callable_name(value: &Value) -> String {
+    match value {
+        Value::Function(f) => f.name.clone(),
+        Value::NativeFunction(f) => f.name.clone(),
+        Value::StructType(s) => s.name.clone(),
+        _ => "<lambda>".into(),
+    }
+}
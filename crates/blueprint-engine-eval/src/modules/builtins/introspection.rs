@@ -2,6 +2,13 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use blueprint_engine_core::{BlueprintError, Result, Value};
+use indexmap::IndexMap;
+use tokio::sync::RwLock;
+
+use crate::eval::Evaluator;
+use crate::scope::Scope;
+
+use super::repr_string;
 
 pub async fn len(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.len() != 1 {
@@ -16,6 +23,15 @@ pub async fn len(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
         Value::Dict(d) => d.read().await.len() as i64,
         Value::Tuple(t) => t.len() as i64,
         Value::Set(s) => s.read().await.len() as i64,
+        Value::Iterator(it) => match it.len() {
+            Some(n) => n as i64,
+            None => {
+                return Err(BlueprintError::TypeError {
+                    expected: "sized iterator (use list() to materialize it first)".into(),
+                    actual: "iterator".into(),
+                })
+            }
+        },
         other => {
             return Err(BlueprintError::TypeError {
                 expected: "string, list, dict, tuple, or set".into(),
@@ -73,5 +89,189 @@ pub async fn repr(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<V
         });
     }
 
-    Ok(Value::String(Arc::new(args[0].repr())))
+    Ok(Value::String(Arc::new(repr_string(&args[0]).await?)))
+}
+
+/// FNV-1a over a fixed byte encoding of the value, so the result is the same
+/// across runs, builds and platforms, which makes it safe for bucketing.
+/// Values that compare equal hash equally: `hash(1) == hash(1.0)`.
+pub async fn hash(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.len() != 1 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("hash() takes exactly 1 argument ({} given)", args.len()),
+        });
+    }
+
+    if !args[0].is_hashable() {
+        return Err(BlueprintError::TypeError {
+            expected: "hashable value (None, bool, int, float, string, or tuple of those)".into(),
+            actual: format!("unhashable type: '{}'", args[0].type_name()),
+        });
+    }
+
+    let mut hasher = Fnv1a::new();
+    hasher.value(&args[0]);
+    Ok(Value::Int(hasher.0 as i64))
+}
+
+const TWO_POW_63: f64 = 9_223_372_036_854_775_808.0;
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn int(&mut self, i: i64) {
+        self.write(&[2]);
+        self.write(&i.to_le_bytes());
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::None => self.write(&[0]),
+            Value::Bool(b) => self.write(&[1, *b as u8]),
+            Value::Int(i) => self.int(*i),
+            // Integral floats hash as the int they equal; -0.0 becomes 0.
+            Value::Float(f) if f.fract() == 0.0 && (-TWO_POW_63..TWO_POW_63).contains(f) => {
+                self.int(*f as i64)
+            }
+            Value::Float(f) => {
+                self.write(&[3]);
+                self.write(&f.to_bits().to_le_bytes());
+            }
+            Value::String(s) => {
+                self.write(&[4]);
+                self.write(&(s.len() as u64).to_le_bytes());
+                self.write(s.as_bytes());
+            }
+            Value::Tuple(items) => {
+                self.write(&[5]);
+                self.write(&(items.len() as u64).to_le_bytes());
+                for item in items.iter() {
+                    self.value(item);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+pub async fn hashable(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.len() != 1 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("hashable() takes exactly 1 argument ({} given)", args.len()),
+        });
+    }
+
+    Ok(Value::Bool(args[0].is_hashable()))
+}
+
+/// `globals()` or `locals()` as seen from `scope`, sorted by name. The dict is
+/// a copy, so changing it never rebinds anything. Builtin functions are only
+/// included with `builtins=True`.
+pub(crate) async fn scope_bindings(
+    evaluator: &Evaluator,
+    name: &str,
+    scope: Arc<Scope>,
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value> {
+    if !args.is_empty() {
+        return Err(BlueprintError::ArgumentError {
+            message: format!(
+                "{}() takes no positional arguments ({} given)",
+                name,
+                args.len()
+            ),
+        });
+    }
+    let with_builtins = kwargs.get("builtins").is_some_and(Value::is_truthy);
+
+    let mut bindings: IndexMap<String, Value> = IndexMap::new();
+    if with_builtins {
+        for (key, native) in evaluator.builtins.iter() {
+            bindings.insert(key.clone(), Value::NativeFunction(native.clone()));
+        }
+    }
+    if name == "globals" {
+        for (key, value) in evaluator.globals.iter() {
+            bindings.insert(key.clone(), value.clone());
+        }
+        bindings.extend(scope.root().variables_snapshot().await);
+    } else {
+        bindings.extend(scope.local_variables().await);
+    }
+    bindings.sort_keys();
+
+    Ok(Value::Dict(Arc::new(RwLock::new(bindings))))
+}
+
+/// Fallback for indirect calls (e.g. `g = globals; g()`) where the caller's
+/// scope is not available.
+pub async fn globals(_args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    Err(BlueprintError::ArgumentError {
+        message: "globals() must be called directly".into(),
+    })
+}
+
+pub async fn locals(_args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    Err(BlueprintError::ArgumentError {
+        message: "locals() must be called directly".into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn hash_of(items: &[Value]) -> i64 {
+        let value = match items {
+            [single] => single.clone(),
+            _ => Value::Tuple(Arc::new(items.to_vec())),
+        };
+        hash(vec![value], HashMap::new())
+            .await
+            .unwrap()
+            .as_int()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hash_is_stable_and_agrees_with_equality() {
+        assert_eq!(hash_of(&[Value::from("abc")]).await, 3937386813790840848);
+        assert_eq!(
+            hash_of(&[Value::Int(1)]).await,
+            hash_of(&[Value::Float(1.0)]).await
+        );
+        assert_eq!(
+            hash_of(&[Value::Int(0)]).await,
+            hash_of(&[Value::Float(-0.0)]).await
+        );
+        assert_eq!(
+            hash_of(&[Value::Int(2), Value::from("a")]).await,
+            hash_of(&[Value::Float(2.0), Value::from("a")]).await
+        );
+
+        assert_ne!(
+            hash_of(&[Value::Int(1)]).await,
+            hash_of(&[Value::Float(1.5)]).await
+        );
+        assert_ne!(
+            hash_of(&[Value::Int(1)]).await,
+            hash_of(&[Value::Bool(true)]).await
+        );
+        assert_ne!(
+            hash_of(&[Value::from("ab"), Value::from("c")]).await,
+            hash_of(&[Value::from("a"), Value::from("bc")]).await
+        );
+    }
 }
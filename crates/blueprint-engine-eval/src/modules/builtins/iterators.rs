@@ -1,13 +1,23 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
 use tokio::sync::{mpsc, RwLock};
 
-use blueprint_engine_core::{BlueprintError, Generator, GeneratorMessage, Result, Value};
+use blueprint_engine_core::{
+    inherit_permissions, validation::require_args, BlueprintError, Generator, GeneratorMessage,
+    Result, StreamIterator, Value,
+};
 
-use super::call_func;
+use super::{call_func, collect_iterable};
 
 pub async fn range(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    let (start, end, step) = range_bounds(&args)?;
+    Ok(Value::Iterator(Arc::new(StreamIterator::range(
+        start, end, step,
+    ))))
+}
+
+fn range_bounds(args: &[Value]) -> Result<(i64, i64, i64)> {
     let (start, end, step) = match args.len() {
         1 => (0, args[0].as_int()?, 1),
         2 => (args[0].as_int()?, args[1].as_int()?, 1),
@@ -25,22 +35,62 @@ pub async fn range(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<
         });
     }
 
-    let mut result = Vec::new();
-    let mut i = start;
+    Ok((start, end, step))
+}
 
-    if step > 0 {
-        while i < end {
-            result.push(Value::Int(i));
-            i += step;
-        }
-    } else {
-        while i > end {
-            result.push(Value::Int(i));
-            i += step;
+pub async fn frange(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    let (start, stop, step) = match args.len() {
+        1 => (0.0, args[0].as_float()?, 1.0),
+        2 => (args[0].as_float()?, args[1].as_float()?, 1.0),
+        3 => (
+            args[0].as_float()?,
+            args[1].as_float()?,
+            args[2].as_float()?,
+        ),
+        n => {
+            return Err(BlueprintError::ArgumentError {
+                message: format!("frange() takes 1 to 3 arguments ({} given)", n),
+            })
         }
+    };
+
+    if step == 0.0 {
+        return Err(BlueprintError::ValueError {
+            message: "frange() step argument must not be zero".into(),
+        });
     }
+    if !(start.is_finite() && stop.is_finite() && step.is_finite()) {
+        return Err(BlueprintError::ValueError {
+            message: "frange() arguments must be finite".into(),
+        });
+    }
+
+    // Each value is computed as start + i*step so rounding error does not
+    // accumulate across a long sequence.
+    let count = ((stop - start) / step).ceil().max(0.0) as u64;
 
-    Ok(Value::List(Arc::new(RwLock::new(result))))
+    let (tx, rx) = mpsc::channel::<GeneratorMessage>(1);
+
+    tokio::spawn(async move {
+        for i in 0..count {
+            let value = Value::Float(start + i as f64 * step);
+            let (resume_tx, resume_rx) = tokio::sync::oneshot::channel();
+            if tx
+                .send(GeneratorMessage::Yielded(value, resume_tx))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            let _ = resume_rx.await;
+        }
+        let _ = tx.send(GeneratorMessage::Complete).await;
+    });
+
+    Ok(Value::Generator(Arc::new(Generator::new(
+        rx,
+        "frange".to_string(),
+    ))))
 }
 
 pub async fn map_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
@@ -55,12 +105,11 @@ pub async fn map_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result
 
     let (tx, rx) = mpsc::channel::<GeneratorMessage>(1);
 
-    tokio::spawn(async move {
-        let result = map_generator_task(func, iterable, tx.clone()).await;
-        if result.is_err() {
-            let _ = tx.send(GeneratorMessage::Complete).await;
+    tokio::spawn(inherit_permissions(async move {
+        if let Err(err) = map_generator_task(func, iterable, tx.clone()).await {
+            let _ = tx.send(GeneratorMessage::Failed(err)).await;
         }
-    });
+    }));
 
     Ok(Value::Generator(Arc::new(Generator::new(
         rx,
@@ -75,7 +124,7 @@ async fn map_generator_task(
 ) -> Result<()> {
     match iterable {
         Value::Generator(gen) => {
-            while let Some(item) = gen.next().await {
+            while let Some(item) = gen.try_next().await? {
                 let result = call_func(&func, vec![item]).await?;
                 let (resume_tx, resume_rx) = tokio::sync::oneshot::channel();
                 if tx
@@ -89,7 +138,8 @@ async fn map_generator_task(
             }
         }
         Value::Iterator(iter) => {
-            while let Some(item) = iter.next().await {
+            let iter = iter.iter().await;
+            while let Some(item) = iter.try_next().await? {
                 let result = call_func(&func, vec![item]).await?;
                 let (resume_tx, resume_rx) = tokio::sync::oneshot::channel();
                 if tx
@@ -169,12 +219,11 @@ pub async fn filter_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Res
 
     let (tx, rx) = mpsc::channel::<GeneratorMessage>(1);
 
-    tokio::spawn(async move {
-        let result = filter_generator_task(func, iterable, tx.clone()).await;
-        if result.is_err() {
-            let _ = tx.send(GeneratorMessage::Complete).await;
+    tokio::spawn(inherit_permissions(async move {
+        if let Err(err) = filter_generator_task(func, iterable, tx.clone()).await {
+            let _ = tx.send(GeneratorMessage::Failed(err)).await;
         }
-    });
+    }));
 
     Ok(Value::Generator(Arc::new(Generator::new(
         rx,
@@ -189,7 +238,7 @@ async fn filter_generator_task(
 ) -> Result<()> {
     match iterable {
         Value::Generator(gen) => {
-            while let Some(item) = gen.next().await {
+            while let Some(item) = gen.try_next().await? {
                 let predicate = if func.is_none() {
                     item.is_truthy_async().await
                 } else {
@@ -212,7 +261,8 @@ async fn filter_generator_task(
             }
         }
         Value::Iterator(iter) => {
-            while let Some(item) = iter.next().await {
+            let iter = iter.iter().await;
+            while let Some(item) = iter.try_next().await? {
                 let predicate = if func.is_none() {
                     item.is_truthy_async().await
                 } else {
@@ -311,7 +361,9 @@ pub async fn enumerate(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Res
             let (tx, rx) = mpsc::channel::<GeneratorMessage>(1);
 
             tokio::spawn(async move {
-                let _ = enumerate_generator_task(iterable, start, tx.clone()).await;
+                if let Err(err) = enumerate_generator_task(iterable, start, tx.clone()).await {
+                    let _ = tx.send(GeneratorMessage::Failed(err)).await;
+                }
             });
 
             Ok(Value::Generator(Arc::new(Generator::new(
@@ -320,20 +372,7 @@ pub async fn enumerate(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Res
             ))))
         }
         _ => {
-            let items = match &iterable {
-                Value::List(l) => l.read().await.clone(),
-                Value::Tuple(t) => t.as_ref().clone(),
-                Value::String(s) => s
-                    .chars()
-                    .map(|c| Value::String(Arc::new(c.to_string())))
-                    .collect(),
-                other => {
-                    return Err(BlueprintError::TypeError {
-                        expected: "iterable".into(),
-                        actual: other.type_name().into(),
-                    })
-                }
-            };
+            let items = collect_iterable(&iterable).await?;
 
             let enumerated: Vec<Value> = items
                 .into_iter()
@@ -354,7 +393,7 @@ async fn enumerate_generator_task(
     let mut idx = start;
     match iterable {
         Value::Generator(gen) => {
-            while let Some(item) = gen.next().await {
+            while let Some(item) = gen.try_next().await? {
                 let tuple = Value::Tuple(Arc::new(vec![Value::Int(idx), item]));
                 let (resume_tx, resume_rx) = tokio::sync::oneshot::channel();
                 if tx
@@ -369,7 +408,8 @@ async fn enumerate_generator_task(
             }
         }
         Value::Iterator(iter) => {
-            while let Some(item) = iter.next().await {
+            let iter = iter.iter().await;
+            while let Some(item) = iter.try_next().await? {
                 let tuple = Value::Tuple(Arc::new(vec![Value::Int(idx), item]));
                 let (resume_tx, resume_rx) = tokio::sync::oneshot::channel();
                 if tx
@@ -389,6 +429,226 @@ async fn enumerate_generator_task(
     Ok(())
 }
 
+/// Lazily yields the first `n` items of any iterable, like the `.take(n)`
+/// method of iterators.
+pub async fn take(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("take", &args, 2)?;
+    let inner = StreamIterator::from_value(&args[0]).await?;
+    let taken = StreamIterator::take(inner, args[1].as_int()?)?;
+    Ok(Value::Iterator(Arc::new(taken)))
+}
+
+/// Lazily yields the items of any iterable after the first `n`, like the
+/// `.skip(n)` method of iterators.
+pub async fn skip(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("skip", &args, 2)?;
+    let inner = StreamIterator::from_value(&args[0]).await?;
+    let rest = StreamIterator::skip(inner, args[1].as_int()?)?;
+    Ok(Value::Iterator(Arc::new(rest)))
+}
+
+/// Lazily yields lists of up to `n` consecutive elements; only the last
+/// batch may be shorter.
+pub async fn batched(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    let (source, n) = chunk_args("batched", &args).await?;
+
+    let (tx, rx) = mpsc::channel::<GeneratorMessage>(1);
+
+    tokio::spawn(inherit_permissions(async move {
+        let result = batched_task(source, n, &tx).await;
+        finish_generator(&tx, result).await;
+    }));
+
+    Ok(Value::Generator(Arc::new(Generator::new(
+        rx,
+        "batched".to_string(),
+    ))))
+}
+
+/// Lazily yields each run of `n` consecutive elements as a list, sliding
+/// forward one element at a time. Yields nothing if there are fewer than `n`.
+pub async fn windowed(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    let (source, n) = chunk_args("windowed", &args).await?;
+
+    let (tx, rx) = mpsc::channel::<GeneratorMessage>(1);
+
+    tokio::spawn(inherit_permissions(async move {
+        let result = windowed_task(source, n, &tx).await;
+        finish_generator(&tx, result).await;
+    }));
+
+    Ok(Value::Generator(Arc::new(Generator::new(
+        rx,
+        "windowed".to_string(),
+    ))))
+}
+
+async fn batched_task(
+    source: Arc<StreamIterator>,
+    n: usize,
+    tx: &mpsc::Sender<GeneratorMessage>,
+) -> Result<()> {
+    loop {
+        let mut batch = Vec::with_capacity(n.min(1024));
+        while batch.len() < n {
+            match source.try_next().await? {
+                Some(item) => batch.push(item),
+                None => break,
+            }
+        }
+        let last = batch.len() < n;
+        if batch.is_empty() || !send_list(tx, batch).await || last {
+            return Ok(());
+        }
+    }
+}
+
+async fn windowed_task(
+    source: Arc<StreamIterator>,
+    n: usize,
+    tx: &mpsc::Sender<GeneratorMessage>,
+) -> Result<()> {
+    let mut window = VecDeque::with_capacity(n.min(1024));
+    while let Some(item) = source.try_next().await? {
+        if window.len() == n {
+            window.pop_front();
+        }
+        window.push_back(item);
+        if window.len() == n && !send_list(tx, window.iter().cloned().collect()).await {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Ends a generator task, handing any error on to the consumer.
+async fn finish_generator(tx: &mpsc::Sender<GeneratorMessage>, result: Result<()>) {
+    let message = match result {
+        Ok(()) => GeneratorMessage::Complete,
+        Err(err) => GeneratorMessage::Failed(err),
+    };
+    let _ = tx.send(message).await;
+}
+
+async fn chunk_args(name: &str, args: &[Value]) -> Result<(Arc<StreamIterator>, usize)> {
+    if args.len() != 2 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!(
+                "{}() takes exactly 2 arguments ({} given)",
+                name,
+                args.len()
+            ),
+        });
+    }
+    let n = args[1].as_int()?;
+    if n <= 0 {
+        return Err(BlueprintError::ValueError {
+            message: format!("{}() size must be greater than 0, got {}", name, n),
+        });
+    }
+    let source = StreamIterator::from_value(&args[0]).await?;
+    Ok((source, n as usize))
+}
+
+/// Yields `items` as a list; returns false once the consumer has gone away.
+async fn send_list(tx: &mpsc::Sender<GeneratorMessage>, items: Vec<Value>) -> bool {
+    let (resume_tx, resume_rx) = tokio::sync::oneshot::channel();
+    let list = Value::List(Arc::new(RwLock::new(items)));
+    if tx
+        .send(GeneratorMessage::Yielded(list, resume_tx))
+        .await
+        .is_err()
+    {
+        return false;
+    }
+    let _ = resume_rx.await;
+    true
+}
+
+/// Splices nested lists, tuples, sets and iterators into one list, `depth`
+/// levels deep (default 1; `None` for all levels). Strings, dicts and other
+/// values are kept as they are.
+pub async fn flatten(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("flatten() takes 1 or 2 arguments ({} given)", args.len()),
+        });
+    }
+
+    let depth = match args.get(1).or_else(|| kwargs.get("depth")) {
+        None => Some(1),
+        Some(Value::None) => None,
+        Some(v) => {
+            let depth = v.as_int()?;
+            if depth < 0 {
+                return Err(BlueprintError::ValueError {
+                    message: format!("flatten() depth must not be negative, got {}", depth),
+                });
+            }
+            Some(depth as usize)
+        }
+    };
+
+    let mut out = Vec::new();
+    for item in collect_iterable(&args[0]).await? {
+        flatten_into(item, depth, &mut out).await?;
+    }
+    Ok(Value::List(Arc::new(RwLock::new(out))))
+}
+
+#[async_recursion::async_recursion]
+async fn flatten_into(item: Value, depth: Option<usize>, out: &mut Vec<Value>) -> Result<()> {
+    let nested = matches!(
+        item,
+        Value::List(_) | Value::Tuple(_) | Value::Set(_) | Value::Generator(_) | Value::Iterator(_)
+    );
+    if !nested || depth == Some(0) {
+        out.push(item);
+        return Ok(());
+    }
+    for inner in collect_iterable(&item).await? {
+        flatten_into(inner, depth.map(|d| d - 1), out).await?;
+    }
+    Ok(())
+}
+
+/// The elements of `iterable` in order, keeping only the first occurrence of
+/// each value, or of each `key(value)` when a key is given.
+pub async fn unique(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("unique() takes 1 or 2 arguments ({} given)", args.len()),
+        });
+    }
+
+    let key = args
+        .get(1)
+        .or_else(|| kwargs.get("key"))
+        .filter(|v| !v.is_none());
+    // Only values that pass `is_hashable` are inserted, and those have no
+    // interior mutability, so their hashes cannot change while in the set.
+    #[allow(clippy::mutable_key_type)]
+    let mut seen: HashSet<Value> = HashSet::new();
+    let mut out = Vec::new();
+    for item in collect_iterable(&args[0]).await? {
+        let k = match key {
+            Some(f) => call_func(f, vec![item.clone()]).await?,
+            None => item.clone(),
+        };
+        if !k.is_hashable() {
+            return Err(BlueprintError::TypeError {
+                expected: "hashable value (None, bool, int, float, string, or tuple of those)"
+                    .into(),
+                actual: format!("unhashable type: '{}'", k.type_name()),
+            });
+        }
+        if seen.insert(k) {
+            out.push(item);
+        }
+    }
+    Ok(Value::List(Arc::new(RwLock::new(out))))
+}
+
 pub async fn zip(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.is_empty() {
         return Ok(Value::List(Arc::new(RwLock::new(vec![]))));
@@ -396,21 +656,7 @@ pub async fn zip(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
 
     let mut iterables: Vec<Vec<Value>> = Vec::new();
     for arg in &args {
-        let items = match arg {
-            Value::List(l) => l.read().await.clone(),
-            Value::Tuple(t) => t.as_ref().clone(),
-            Value::String(s) => s
-                .chars()
-                .map(|c| Value::String(Arc::new(c.to_string())))
-                .collect(),
-            other => {
-                return Err(BlueprintError::TypeError {
-                    expected: "iterable".into(),
-                    actual: other.type_name().into(),
-                })
-            }
-        };
-        iterables.push(items);
+        iterables.push(collect_iterable(arg).await?);
     }
 
     let min_len = iterables.iter().map(|i| i.len()).min().unwrap_or(0);
@@ -438,20 +684,7 @@ pub async fn sorted(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<
         .map(|v| v.is_truthy())
         .unwrap_or(false);
 
-    let mut items = match &args[0] {
-        Value::List(l) => l.read().await.clone(),
-        Value::Tuple(t) => t.as_ref().clone(),
-        Value::String(s) => s
-            .chars()
-            .map(|c| Value::String(Arc::new(c.to_string())))
-            .collect(),
-        other => {
-            return Err(BlueprintError::TypeError {
-                expected: "iterable".into(),
-                actual: other.type_name().into(),
-            })
-        }
-    };
+    let mut items = collect_iterable(&args[0]).await?;
 
     items.sort_by(|a, b| match (a, b) {
         (Value::Int(x), Value::Int(y)) => x.cmp(y),
@@ -474,20 +707,7 @@ pub async fn reversed(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resu
         });
     }
 
-    let mut items = match &args[0] {
-        Value::List(l) => l.read().await.clone(),
-        Value::Tuple(t) => t.as_ref().clone(),
-        Value::String(s) => s
-            .chars()
-            .map(|c| Value::String(Arc::new(c.to_string())))
-            .collect(),
-        other => {
-            return Err(BlueprintError::TypeError {
-                expected: "iterable".into(),
-                actual: other.type_name().into(),
-            })
-        }
-    };
+    let mut items = collect_iterable(&args[0]).await?;
 
     items.reverse();
     Ok(Value::List(Arc::new(RwLock::new(items))))
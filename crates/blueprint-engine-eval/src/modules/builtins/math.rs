@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use blueprint_engine_core::{BlueprintError, Result, Value};
 
+use super::{call_func, collect_iterable};
+
 pub async fn min(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.is_empty() {
         return Err(BlueprintError::ArgumentError {
@@ -9,12 +11,8 @@ pub async fn min(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
         });
     }
 
-    let items = if args.len() == 1 {
-        match &args[0] {
-            Value::List(l) => l.read().await.clone(),
-            Value::Tuple(t) => t.as_ref().clone(),
-            _ => args.clone(),
-        }
+    let items = if args.len() == 1 && is_iterable(&args[0]) {
+        collect_iterable(&args[0]).await?
     } else {
         args.clone()
     };
@@ -48,12 +46,8 @@ pub async fn max(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
         });
     }
 
-    let items = if args.len() == 1 {
-        match &args[0] {
-            Value::List(l) => l.read().await.clone(),
-            Value::Tuple(t) => t.as_ref().clone(),
-            _ => args.clone(),
-        }
+    let items = if args.len() == 1 && is_iterable(&args[0]) {
+        collect_iterable(&args[0]).await?
     } else {
         args.clone()
     };
@@ -93,16 +87,7 @@ pub async fn sum(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
         Value::Int(0)
     };
 
-    let items = match &args[0] {
-        Value::List(l) => l.read().await.clone(),
-        Value::Tuple(t) => t.as_ref().clone(),
-        other => {
-            return Err(BlueprintError::TypeError {
-                expected: "iterable".into(),
-                actual: other.type_name().into(),
-            })
-        }
-    };
+    let items = collect_iterable(&args[0]).await?;
 
     let mut total = start;
     for item in items {
@@ -140,44 +125,169 @@ pub async fn abs(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
     }
 }
 
-pub async fn all(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
-    if args.len() != 1 {
+/// `round(x, ndigits=None)` with Python's round-half-to-even: without
+/// `ndigits` the result is an int, with it the result keeps the input's type.
+pub async fn round(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.is_empty() || args.len() > 2 {
         return Err(BlueprintError::ArgumentError {
-            message: format!("all() takes exactly 1 argument ({} given)", args.len()),
+            message: format!("round() takes 1 or 2 arguments ({} given)", args.len()),
         });
     }
+    let ndigits = match args.get(1).or_else(|| kwargs.get("ndigits")) {
+        None | Some(Value::None) => None,
+        Some(v) => Some(v.as_int()?),
+    };
 
-    let items = match &args[0] {
-        Value::List(l) => l.read().await.clone(),
-        Value::Tuple(t) => t.as_ref().clone(),
-        other => {
-            return Err(BlueprintError::TypeError {
-                expected: "iterable".into(),
-                actual: other.type_name().into(),
-            })
+    match (&args[0], ndigits) {
+        (Value::Int(i), None) => Ok(Value::Int(*i)),
+        (Value::Int(i), Some(n)) => Ok(Value::Int(round_int(*i, n))),
+        (Value::Float(f), None) => {
+            let rounded = f.round_ties_even();
+            if !rounded.is_finite() || rounded < i64::MIN as f64 || rounded >= i64::MAX as f64 {
+                return Err(BlueprintError::ValueError {
+                    message: format!("cannot convert float {} to integer", f),
+                });
+            }
+            Ok(Value::Int(rounded as i64))
         }
+        (Value::Float(f), Some(n)) => Ok(Value::Float(round_float(*f, n))),
+        (other, _) => Err(BlueprintError::TypeError {
+            expected: "number".into(),
+            actual: other.type_name().into(),
+        }),
+    }
+}
+
+fn round_int(i: i64, ndigits: i64) -> i64 {
+    if ndigits >= 0 {
+        return i;
+    }
+    let Some(step) = u32::try_from(-ndigits)
+        .ok()
+        .and_then(|n| 10i64.checked_pow(n))
+    else {
+        return 0;
+    };
+    let (quotient, remainder) = (i.div_euclid(step), i.rem_euclid(step));
+    let up = match (remainder * 2).cmp(&step) {
+        std::cmp::Ordering::Less => false,
+        std::cmp::Ordering::Greater => true,
+        std::cmp::Ordering::Equal => quotient % 2 != 0,
     };
+    (quotient + up as i64).saturating_mul(step)
+}
 
-    Ok(Value::Bool(items.iter().all(|v| v.is_truthy())))
+/// Rounds on the exact decimal value of `f`, as Python does, so
+/// `round(2.675, 2)` is `2.67` because 2.675 is stored slightly below it.
+fn round_float(f: f64, ndigits: i64) -> f64 {
+    if !f.is_finite() {
+        return f;
+    }
+    if ndigits >= 0 {
+        let digits = ndigits.min(340) as usize;
+        return format!("{:.*}", digits, f).parse().unwrap_or(f);
+    }
+    let scale = 10f64.powi((-ndigits).min(400) as i32);
+    (f / scale).round_ties_even() * scale
 }
 
-pub async fn any(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
-    if args.len() != 1 {
+pub async fn all(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.is_empty() || args.len() > 2 {
         return Err(BlueprintError::ArgumentError {
-            message: format!("any() takes exactly 1 argument ({} given)", args.len()),
+            message: format!("all() takes 1 or 2 arguments ({} given)", args.len()),
         });
     }
 
-    let items = match &args[0] {
-        Value::List(l) => l.read().await.clone(),
-        Value::Tuple(t) => t.as_ref().clone(),
+    let key = predicate_arg(&args, &kwargs);
+    let found = find_truthiness(&args[0], key, false).await?;
+    Ok(Value::Bool(!found))
+}
+
+pub async fn any(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("any() takes 1 or 2 arguments ({} given)", args.len()),
+        });
+    }
+
+    let key = predicate_arg(&args, &kwargs);
+    let found = find_truthiness(&args[0], key, true).await?;
+    Ok(Value::Bool(found))
+}
+
+/// Reports whether any element's truthiness (after `key`) equals `wanted`,
+/// stopping at the first such element. Generators are consumed lazily.
+async fn find_truthiness(iterable: &Value, key: Option<&Value>, wanted: bool) -> Result<bool> {
+    async fn test(item: Value, key: Option<&Value>) -> Result<bool> {
+        match key {
+            Some(f) => Ok(call_func(f, vec![item]).await?.is_truthy()),
+            None => Ok(item.is_truthy()),
+        }
+    }
+
+    match iterable {
+        Value::Generator(gen) => {
+            while let Some(item) = gen.try_next().await? {
+                if test(item, key).await? == wanted {
+                    return Ok(true);
+                }
+            }
+        }
+        Value::Iterator(iter) => {
+            let iter = iter.iter().await;
+            while let Some(item) = iter.try_next().await? {
+                if test(item, key).await? == wanted {
+                    return Ok(true);
+                }
+            }
+        }
         other => {
-            return Err(BlueprintError::TypeError {
-                expected: "iterable".into(),
-                actual: other.type_name().into(),
-            })
+            for item in collect_iterable(other).await? {
+                if test(item, key).await? == wanted {
+                    return Ok(true);
+                }
+            }
         }
-    };
+    }
+
+    Ok(false)
+}
 
-    Ok(Value::Bool(items.iter().any(|v| v.is_truthy())))
+/// The predicate for `any`/`all`, given positionally or as `key=`.
+fn predicate_arg<'a>(args: &'a [Value], kwargs: &'a HashMap<String, Value>) -> Option<&'a Value> {
+    args.get(1)
+        .or_else(|| kwargs.get("key"))
+        .filter(|v| !v.is_none())
+}
+
+fn is_iterable(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::List(_)
+            | Value::Tuple(_)
+            | Value::String(_)
+            | Value::Dict(_)
+            | Value::Set(_)
+            | Value::Generator(_)
+            | Value::Iterator(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_half_even() {
+        assert_eq!(round_int(1250, -2), 1200);
+        assert_eq!(round_int(1350, -2), 1400);
+        assert_eq!(round_int(-1250, -2), -1200);
+        assert_eq!(round_int(1251, -2), 1300);
+        assert_eq!(round_int(7, 2), 7);
+        assert_eq!(round_float(2.675, 2), 2.67);
+        assert_eq!(round_float(0.125, 2), 0.12);
+        assert_eq!(round_float(0.375, 2), 0.38);
+        assert_eq!(round_float(15.0, -1), 20.0);
+        assert_eq!(round_float(25.0, -1), 20.0);
+    }
 }
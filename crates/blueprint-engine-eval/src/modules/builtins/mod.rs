@@ -1,4 +1,6 @@
 mod control;
+mod debug;
+mod functional;
 mod introspection;
 mod iterators;
 mod math;
@@ -7,46 +9,177 @@ mod types;
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use blueprint_engine_core::{BlueprintError, NativeFunction, Result, Value};
+use blueprint_engine_core::{intern, BlueprintError, NativeFunction, Result, Value};
 
 use crate::eval::Evaluator;
 use crate::scope::{Scope, ScopeKind};
 
+pub use debug::{run_breakpoint, set_breakpoint_handler, BreakpointHandler};
+pub(crate) use functional::{callable_name, require_callable};
+pub(crate) use introspection::scope_bindings;
+
 pub fn register(evaluator: &mut Evaluator) {
     evaluator.register_native(NativeFunction::new("len", introspection::len));
     evaluator.register_native(NativeFunction::new("str", types::to_str));
     evaluator.register_native(NativeFunction::new("int", types::to_int));
+    evaluator.register_native(NativeFunction::new("try_int", types::try_int));
     evaluator.register_native(NativeFunction::new("float", types::to_float));
     evaluator.register_native(NativeFunction::new("bool", types::to_bool));
     evaluator.register_native(NativeFunction::new("list", types::to_list));
     evaluator.register_native(NativeFunction::new("dict", types::to_dict));
+    evaluator.register_native(NativeFunction::new(
+        "dict_from_pairs",
+        types::dict_from_pairs,
+    ));
     evaluator.register_native(NativeFunction::new("tuple", types::to_tuple));
     evaluator.register_native(NativeFunction::new("set", types::to_set));
     evaluator.register_native(NativeFunction::new("iter", types::to_iter));
     evaluator.register_native(NativeFunction::new("range", iterators::range));
+    evaluator.register_native(NativeFunction::new("frange", iterators::frange));
     evaluator.register_native(NativeFunction::new("map", iterators::map_fn));
     evaluator.register_native(NativeFunction::new("filter", iterators::filter_fn));
     evaluator.register_native(NativeFunction::new("enumerate", iterators::enumerate));
     evaluator.register_native(NativeFunction::new("zip", iterators::zip));
+    evaluator.register_native(NativeFunction::new("batched", iterators::batched));
+    evaluator.register_native(NativeFunction::new("windowed", iterators::windowed));
+    evaluator.register_native(NativeFunction::new("flatten", iterators::flatten));
+    evaluator.register_native(NativeFunction::new("unique", iterators::unique));
+    evaluator.register_native(NativeFunction::new("take", iterators::take));
+    evaluator.register_native(NativeFunction::new("skip", iterators::skip));
     evaluator.register_native(NativeFunction::new("sorted", iterators::sorted));
     evaluator.register_native(NativeFunction::new("reversed", iterators::reversed));
+    evaluator.register_native(NativeFunction::new("partial", functional::partial));
+    evaluator.register_native(NativeFunction::new("compose", functional::compose));
+    evaluator.register_native(NativeFunction::new("pipe", functional::pipe));
+    evaluator.register_native(NativeFunction::new("memoize", functional::memoize));
+    evaluator.register_native(NativeFunction::new(
+        "with_methods",
+        functional::with_methods,
+    ));
     evaluator.register_native(NativeFunction::new("min", math::min));
     evaluator.register_native(NativeFunction::new("max", math::max));
     evaluator.register_native(NativeFunction::new("sum", math::sum));
     evaluator.register_native(NativeFunction::new("abs", math::abs));
+    evaluator.register_native(NativeFunction::new("round", math::round));
     evaluator.register_native(NativeFunction::new("all", math::all));
     evaluator.register_native(NativeFunction::new("any", math::any));
     evaluator.register_native(NativeFunction::new("type", introspection::type_of));
     evaluator.register_native(NativeFunction::new("hasattr", introspection::hasattr));
     evaluator.register_native(NativeFunction::new("getattr", introspection::getattr));
     evaluator.register_native(NativeFunction::new("repr", introspection::repr));
+    evaluator.register_native(NativeFunction::new("hash", introspection::hash));
+    evaluator.register_native(NativeFunction::new("hashable", introspection::hashable));
+    evaluator.register_native(NativeFunction::new("globals", introspection::globals));
+    evaluator.register_native(NativeFunction::new("locals", introspection::locals));
     evaluator.register_native(NativeFunction::new("fail", control::fail));
+    evaluator.register_native(NativeFunction::new("eval", control::eval));
     evaluator.register_native(NativeFunction::new("exit", control::exit));
+    evaluator.register_native(NativeFunction::new("breakpoint", debug::breakpoint));
     evaluator.register_native(NativeFunction::new("assert", control::assert_fn));
+    evaluator.register_native(NativeFunction::new("assert_true", control::assert_true));
+    evaluator.register_native(NativeFunction::new("assert_eq", control::assert_eq));
+    evaluator.register_native(NativeFunction::new("assert_ne", control::assert_ne));
+    evaluator.register_native(NativeFunction::new(
+        "assert_contains",
+        control::assert_contains,
+    ));
     evaluator.register_native(NativeFunction::new("ord", types::ord_fn));
     evaluator.register_native(NativeFunction::new("chr", types::chr_fn));
 }
 
+/// Materializes any iterable the way a `for` loop sees it: dicts yield their
+/// keys, sets their elements, strings their characters.
+pub(crate) async fn collect_iterable(value: &Value) -> Result<Vec<Value>> {
+    match value {
+        Value::List(l) => Ok(l.read().await.clone()),
+        Value::Tuple(t) => Ok(t.as_ref().clone()),
+        Value::String(s) => Ok(s
+            .chars()
+            .map(|c| Value::String(Arc::new(c.to_string())))
+            .collect()),
+        Value::Dict(d) => Ok(d
+            .read()
+            .await
+            .keys()
+            .map(|k| Value::String(intern(k)))
+            .collect()),
+        Value::Set(s) => Ok(s.read().await.iter().cloned().collect()),
+        Value::Generator(gen) => {
+            let mut items = Vec::new();
+            while let Some(item) = gen.try_next().await? {
+                items.push(item);
+            }
+            Ok(items)
+        }
+        Value::Iterator(iter) => {
+            let iter = iter.iter().await;
+            let mut items = Vec::with_capacity(iter.len().unwrap_or(0));
+            while let Some(item) = iter.try_next().await? {
+                items.push(item);
+            }
+            Ok(items)
+        }
+        other => Err(BlueprintError::TypeError {
+            expected: "iterable".into(),
+            actual: other.type_name().into(),
+        }),
+    }
+}
+
+/// `str()` of a value, using a struct's `__str__` method, or else its
+/// `__repr__`, when the struct defines one.
+pub(crate) async fn display_string(value: &Value) -> Result<String> {
+    match struct_format(value, &["__str__", "__repr__"]).await? {
+        Some(text) => Ok(text),
+        None => Ok(value.to_display_string()),
+    }
+}
+
+/// `repr()` of a value, using a struct's `__repr__` method if it has one.
+pub(crate) async fn repr_string(value: &Value) -> Result<String> {
+    match struct_format(value, &["__repr__"]).await? {
+        Some(text) => Ok(text),
+        None => Ok(value.repr()),
+    }
+}
+
+async fn struct_format(value: &Value, hooks: &[&str]) -> Result<Option<String>> {
+    let Value::StructInstance(instance) = value else {
+        return Ok(None);
+    };
+    let struct_type = &instance.struct_type;
+    let Some((hook, method)) = hooks
+        .iter()
+        .find_map(|hook| struct_type.methods.get(*hook).map(|m| (*hook, m)))
+    else {
+        return Ok(None);
+    };
+    match method.call(vec![value.clone()], HashMap::new()).await? {
+        Value::String(s) => Ok(Some(s.as_ref().clone())),
+        other => Err(BlueprintError::TypeError {
+            expected: format!("str returned from {}.{}()", struct_type.name, hook),
+            actual: other.type_name().into(),
+        }),
+    }
+}
+
+/// Calls any callable with keyword arguments, evaluating user code in the
+/// module it was defined in.
+pub(crate) async fn call_callable(
+    func: &Value,
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<Value> {
+    let source = match func {
+        Value::Function(f) => f.source.as_ref(),
+        Value::Lambda(l) => l.source.as_ref(),
+        _ => None,
+    };
+    Evaluator::for_source(source)
+        .call_function(func.clone(), args, kwargs, Scope::new_global())
+        .await
+}
+
 pub async fn call_func(func: &Value, args: Vec<Value>) -> Result<Value> {
     match func {
         Value::Lambda(lambda) => {
@@ -72,7 +205,7 @@ pub async fn call_func(func: &Value, args: Vec<Value>) -> Result<Value> {
                 }
             }
 
-            let evaluator = Evaluator::new();
+            let evaluator = Evaluator::for_source(lambda.source.as_ref());
             evaluator.eval_expr(body, call_scope).await
         }
         Value::Function(func) => {
@@ -98,7 +231,7 @@ pub async fn call_func(func: &Value, args: Vec<Value>) -> Result<Value> {
                 }
             }
 
-            let evaluator = Evaluator::new();
+            let evaluator = Evaluator::for_source(func.source.as_ref());
             match evaluator.eval_stmt(body, call_scope).await {
                 Ok(_) => Ok(Value::None),
                 Err(BlueprintError::Return { value }) => Ok((*value).clone()),
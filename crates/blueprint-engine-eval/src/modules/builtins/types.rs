@@ -4,7 +4,11 @@ use std::sync::Arc;
 use indexmap::{IndexMap, IndexSet};
 use tokio::sync::{mpsc, RwLock};
 
-use blueprint_engine_core::{BlueprintError, Generator, GeneratorMessage, Result, Value};
+use blueprint_engine_core::{
+    intern, validation::require_args, BlueprintError, Generator, GeneratorMessage, Result, Value,
+};
+
+use super::{collect_iterable, display_string};
 
 pub async fn to_str(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.len() != 1 {
@@ -13,46 +17,166 @@ pub async fn to_str(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result
         });
     }
 
-    Ok(Value::String(Arc::new(args[0].to_display_string())))
+    Ok(Value::String(Arc::new(display_string(&args[0]).await?)))
 }
 
-pub async fn to_int(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+pub async fn to_int(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.is_empty() || args.len() > 2 {
         return Err(BlueprintError::ArgumentError {
             message: format!("int() takes 1 or 2 arguments ({} given)", args.len()),
         });
     }
 
-    let base = if args.len() == 2 {
-        args[1].as_int()? as u32
-    } else {
-        10
+    let base = match args.get(1).or_else(|| kwargs.get("base")) {
+        Some(base) => Some(base_arg("int", base)?),
+        None => None,
     };
 
-    match &args[0] {
-        Value::Int(i) => Ok(Value::Int(*i)),
-        Value::Float(f) => Ok(Value::Int(*f as i64)),
-        Value::String(s) => {
-            let s = s.trim();
-            let result = if base == 10 {
-                s.parse::<i64>()
-            } else {
-                i64::from_str_radix(s.trim_start_matches("0x").trim_start_matches("0X"), base)
-            };
-            result
-                .map(Value::Int)
-                .map_err(|_| BlueprintError::ValueError {
-                    message: format!("invalid literal for int() with base {}: '{}'", base, s),
-                })
-        }
-        Value::Bool(b) => Ok(Value::Int(if *b { 1 } else { 0 })),
-        other => Err(BlueprintError::TypeError {
+    match (&args[0], base) {
+        (Value::String(s), base) => parse_int(s, base.unwrap_or(10)).map(Value::Int),
+        (other, Some(_)) => Err(BlueprintError::TypeError {
+            expected: "string when an explicit base is given to int()".into(),
+            actual: other.type_name().into(),
+        }),
+        (Value::Int(i), None) => Ok(Value::Int(*i)),
+        (Value::Float(f), None) => float_to_int(*f).map(Value::Int),
+        (Value::Bool(b), None) => Ok(Value::Int(if *b { 1 } else { 0 })),
+        (other, None) => Err(BlueprintError::TypeError {
             expected: "int, float, string, or bool".into(),
             actual: other.type_name().into(),
         }),
     }
 }
 
+/// `try_int(value, default=None, base=10)`: like `int()`, but returns
+/// `default` when the value cannot be converted.
+pub async fn try_int(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("try_int() takes 1 or 2 arguments ({} given)", args.len()),
+        });
+    }
+
+    let default = args
+        .get(1)
+        .or_else(|| kwargs.get("default"))
+        .cloned()
+        .unwrap_or(Value::None);
+    let base = match kwargs.get("base") {
+        Some(base) => base_arg("try_int", base)?,
+        None => 10,
+    };
+
+    let parsed = match &args[0] {
+        Value::String(s) => parse_int(s, base).ok(),
+        Value::Int(i) => Some(*i),
+        Value::Float(f) => float_to_int(*f).ok(),
+        Value::Bool(b) => Some(if *b { 1 } else { 0 }),
+        _ => None,
+    };
+    Ok(parsed.map_or(default, Value::Int))
+}
+
+fn base_arg(name: &str, value: &Value) -> Result<u32> {
+    let base = value.as_int()?;
+    if base == 0 || (2..=36).contains(&base) {
+        Ok(base as u32)
+    } else {
+        Err(BlueprintError::ValueError {
+            message: format!(
+                "{}() base must be >= 2 and <= 36, or 0 (got {})",
+                name, base
+            ),
+        })
+    }
+}
+
+fn float_to_int(f: f64) -> Result<i64> {
+    if f.is_nan() || f.is_infinite() {
+        return Err(BlueprintError::ValueError {
+            message: format!("cannot convert float {} to integer", f),
+        });
+    }
+    let truncated = f.trunc();
+    if truncated < i64::MIN as f64 || truncated >= i64::MAX as f64 {
+        return Err(BlueprintError::ValueError {
+            message: format!("float {} is out of range for int()", f),
+        });
+    }
+    Ok(truncated as i64)
+}
+
+/// Parses an integer literal the way Python's `int(s, base)` does: surrounding
+/// whitespace and a sign are allowed, digits may be grouped with single
+/// underscores, and a `0x`/`0o`/`0b` prefix is accepted when it matches
+/// `base`. Base 0 infers the base from the prefix, defaulting to decimal.
+fn parse_int(text: &str, base: u32) -> Result<i64> {
+    let invalid = || BlueprintError::ValueError {
+        message: format!("invalid literal for int() with base {}: {:?}", base, text),
+    };
+
+    let trimmed = text.trim();
+    let (negative, unsigned) = match trimmed.as_bytes().first() {
+        Some(b'-') => (true, &trimmed[1..]),
+        Some(b'+') => (false, &trimmed[1..]),
+        _ => (false, trimmed),
+    };
+
+    let prefix = unsigned
+        .get(..2)
+        .map(|p| p.to_ascii_lowercase())
+        .and_then(|p| match p.as_str() {
+            "0x" => Some(16),
+            "0o" => Some(8),
+            "0b" => Some(2),
+            _ => None,
+        });
+    let (radix, digits) = match (base, prefix) {
+        (0, Some(radix)) => (
+            radix,
+            unsigned[2..].strip_prefix('_').unwrap_or(&unsigned[2..]),
+        ),
+        (0, None) => {
+            if unsigned.len() > 1
+                && unsigned.starts_with('0')
+                && unsigned.chars().any(|c| c != '0' && c != '_')
+            {
+                return Err(invalid());
+            }
+            (10, unsigned)
+        }
+        (base, Some(radix)) if base == radix => (
+            radix,
+            unsigned[2..].strip_prefix('_').unwrap_or(&unsigned[2..]),
+        ),
+        (base, _) => (base, unsigned),
+    };
+
+    if digits.is_empty()
+        || digits.starts_with('_')
+        || digits.ends_with('_')
+        || digits.contains("__")
+        || digits.starts_with(['+', '-'])
+    {
+        return Err(invalid());
+    }
+    let digits = digits.replace('_', "");
+    let signed = if negative {
+        format!("-{}", digits)
+    } else {
+        digits
+    };
+
+    i64::from_str_radix(&signed, radix).map_err(|e| match e.kind() {
+        std::num::IntErrorKind::PosOverflow | std::num::IntErrorKind::NegOverflow => {
+            BlueprintError::ValueError {
+                message: format!("int() literal out of 64-bit range: {:?}", text),
+            }
+        }
+        _ => invalid(),
+    })
+}
+
 pub async fn to_float(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.len() != 1 {
         return Err(BlueprintError::ArgumentError {
@@ -63,21 +187,35 @@ pub async fn to_float(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resu
     match &args[0] {
         Value::Int(i) => Ok(Value::Float(*i as f64)),
         Value::Float(f) => Ok(Value::Float(*f)),
-        Value::String(s) => {
-            s.trim()
-                .parse::<f64>()
-                .map(Value::Float)
-                .map_err(|_| BlueprintError::ValueError {
-                    message: format!("could not convert string to float: '{}'", s),
-                })
-        }
+        Value::Bool(b) => Ok(Value::Float(if *b { 1.0 } else { 0.0 })),
+        Value::String(s) => parse_float(s).map(Value::Float),
         other => Err(BlueprintError::TypeError {
-            expected: "int, float, or string".into(),
+            expected: "int, float, string, or bool".into(),
             actual: other.type_name().into(),
         }),
     }
 }
 
+/// Accepts what Python's `float()` does, including `inf`/`nan` and
+/// underscore-grouped digits; the error quotes the original string.
+fn parse_float(text: &str) -> Result<f64> {
+    let trimmed = text.trim();
+    let grouped_ok = trimmed
+        .char_indices()
+        .filter(|(_, c)| *c == '_')
+        .all(|(i, _)| {
+            let before = trimmed[..i].chars().next_back();
+            let after = trimmed[i + 1..].chars().next();
+            before.is_some_and(|c| c.is_ascii_digit()) && after.is_some_and(|c| c.is_ascii_digit())
+        });
+    grouped_ok
+        .then(|| trimmed.replace('_', "").parse::<f64>().ok())
+        .flatten()
+        .ok_or_else(|| BlueprintError::ValueError {
+            message: format!("could not convert string to float: {:?}", text),
+        })
+}
+
 pub async fn to_bool(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.len() != 1 {
         return Err(BlueprintError::ArgumentError {
@@ -99,42 +237,7 @@ pub async fn to_list(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resul
         });
     }
 
-    let items = match &args[0] {
-        Value::List(l) => l.read().await.clone(),
-        Value::Tuple(t) => t.as_ref().clone(),
-        Value::String(s) => s
-            .chars()
-            .map(|c| Value::String(Arc::new(c.to_string())))
-            .collect(),
-        Value::Dict(d) => d
-            .read()
-            .await
-            .keys()
-            .map(|k| Value::String(Arc::new(k.clone())))
-            .collect(),
-        Value::Set(s) => s.read().await.iter().cloned().collect(),
-        Value::Generator(gen) => {
-            let mut items = Vec::new();
-            while let Some(item) = gen.next().await {
-                items.push(item);
-            }
-            items
-        }
-        Value::Iterator(iter) => {
-            let mut items = Vec::new();
-            while let Some(item) = iter.next().await {
-                items.push(item);
-            }
-            items
-        }
-        other => {
-            return Err(BlueprintError::TypeError {
-                expected: "iterable".into(),
-                actual: other.type_name().into(),
-            })
-        }
-    };
-
+    let items = collect_iterable(&args[0]).await?;
     Ok(Value::List(Arc::new(RwLock::new(items))))
 }
 
@@ -153,43 +256,7 @@ pub async fn to_dict(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resul
         Value::Dict(d) => Ok(Value::Dict(Arc::new(RwLock::new(d.read().await.clone())))),
         Value::List(l) => {
             let items = l.read().await;
-            let mut map = IndexMap::new();
-            for item in items.iter() {
-                match item {
-                    Value::List(pair_list) => {
-                        let pair = pair_list.read().await;
-                        if pair.len() != 2 {
-                            return Err(BlueprintError::ValueError {
-                                message: "dict() argument must be iterable of key-value pairs"
-                                    .into(),
-                            });
-                        }
-                        let key = match &pair[0] {
-                            Value::String(s) => s.as_ref().clone(),
-                            other => other.to_display_string(),
-                        };
-                        map.insert(key, pair[1].clone());
-                    }
-                    Value::Tuple(pair) => {
-                        if pair.len() != 2 {
-                            return Err(BlueprintError::ValueError {
-                                message: "dict() argument must be iterable of key-value pairs"
-                                    .into(),
-                            });
-                        }
-                        let key = match &pair[0] {
-                            Value::String(s) => s.as_ref().clone(),
-                            other => other.to_display_string(),
-                        };
-                        map.insert(key, pair[1].clone());
-                    }
-                    _ => {
-                        return Err(BlueprintError::ValueError {
-                            message: "dict() argument must be iterable of key-value pairs".into(),
-                        })
-                    }
-                }
-            }
+            let map = pairs_to_map("dict", &items).await?;
             Ok(Value::Dict(Arc::new(RwLock::new(map))))
         }
         other => Err(BlueprintError::TypeError {
@@ -199,6 +266,41 @@ pub async fn to_dict(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resul
     }
 }
 
+/// `dict_from_pairs(pairs)` builds a dict from any iterable of two-element
+/// lists or tuples, sized up front. Later pairs win on duplicate keys.
+pub async fn dict_from_pairs(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("dict_from_pairs", &args, 1)?;
+    let pairs = collect_iterable(&args[0]).await?;
+    let map = pairs_to_map("dict_from_pairs", &pairs).await?;
+    Ok(Value::Dict(Arc::new(RwLock::new(map))))
+}
+
+async fn pairs_to_map(func: &str, pairs: &[Value]) -> Result<IndexMap<String, Value>> {
+    let not_a_pair = || BlueprintError::ValueError {
+        message: format!("{}() argument must be iterable of key-value pairs", func),
+    };
+    let mut map = IndexMap::with_capacity(pairs.len());
+    for item in pairs {
+        let (key, value) = match item {
+            Value::List(pair_list) => {
+                let pair = pair_list.read().await;
+                if pair.len() != 2 {
+                    return Err(not_a_pair());
+                }
+                (pair[0].clone(), pair[1].clone())
+            }
+            Value::Tuple(pair) if pair.len() == 2 => (pair[0].clone(), pair[1].clone()),
+            _ => return Err(not_a_pair()),
+        };
+        let key = match key {
+            Value::String(s) => s.as_ref().clone(),
+            other => other.to_display_string(),
+        };
+        map.insert(key, value);
+    }
+    Ok(map)
+}
+
 pub async fn to_tuple(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.is_empty() {
         return Ok(Value::Tuple(Arc::new(vec![])));
@@ -210,20 +312,7 @@ pub async fn to_tuple(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resu
         });
     }
 
-    let items = match &args[0] {
-        Value::Tuple(t) => t.as_ref().clone(),
-        Value::List(l) => l.read().await.clone(),
-        Value::String(s) => s
-            .chars()
-            .map(|c| Value::String(Arc::new(c.to_string())))
-            .collect(),
-        other => {
-            return Err(BlueprintError::TypeError {
-                expected: "iterable".into(),
-                actual: other.type_name().into(),
-            })
-        }
-    };
+    let items = collect_iterable(&args[0]).await?;
 
     Ok(Value::Tuple(Arc::new(items)))
 }
@@ -251,18 +340,19 @@ pub async fn to_set(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result
             .read()
             .await
             .keys()
-            .map(|k| Value::String(Arc::new(k.clone())))
+            .map(|k| Value::String(intern(k)))
             .collect(),
         Value::Generator(gen) => {
             let mut items = IndexSet::new();
-            while let Some(item) = gen.next().await {
+            while let Some(item) = gen.try_next().await? {
                 items.insert(item);
             }
             items
         }
         Value::Iterator(iter) => {
+            let iter = iter.iter().await;
             let mut items = IndexSet::new();
-            while let Some(item) = iter.next().await {
+            while let Some(item) = iter.try_next().await? {
                 items.insert(item);
             }
             items
@@ -288,8 +378,9 @@ pub async fn to_iter(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resul
     let iterable = args[0].clone();
 
     match &iterable {
-        Value::Generator(_) | Value::Iterator(_) => Ok(iterable),
-        Value::List(_) | Value::Tuple(_) | Value::String(_) | Value::Dict(_) => {
+        Value::Generator(_) => Ok(iterable),
+        Value::Iterator(it) => Ok(Value::Iterator(it.iter().await)),
+        Value::List(_) | Value::Tuple(_) | Value::String(_) | Value::Dict(_) | Value::Set(_) => {
             let (tx, rx) = mpsc::channel::<GeneratorMessage>(1);
 
             tokio::spawn(async move {
@@ -396,9 +487,23 @@ async fn iter_generator_task(iterable: Value, tx: mpsc::Sender<GeneratorMessage>
             }
         }
         Value::Dict(d) => {
-            let keys: Vec<String> = d.read().await.keys().cloned().collect();
+            let keys: Vec<Arc<String>> = d.read().await.keys().map(|k| intern(k)).collect();
             for key in keys {
-                let item = Value::String(Arc::new(key));
+                let item = Value::String(key);
+                let (resume_tx, resume_rx) = tokio::sync::oneshot::channel();
+                if tx
+                    .send(GeneratorMessage::Yielded(item, resume_tx))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+                let _ = resume_rx.await;
+            }
+        }
+        Value::Set(s) => {
+            let items: Vec<Value> = s.read().await.iter().cloned().collect();
+            for item in items {
                 let (resume_tx, resume_rx) = tokio::sync::oneshot::channel();
                 if tx
                     .send(GeneratorMessage::Yielded(item, resume_tx))
@@ -415,3 +520,46 @@ async fn iter_generator_task(iterable: Value, tx: mpsc::Sender<GeneratorMessage>
     let _ = tx.send(GeneratorMessage::Complete).await;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_int_bases_and_prefixes() {
+        assert_eq!(parse_int(" -42 ", 10).unwrap(), -42);
+        assert_eq!(parse_int("1_000", 10).unwrap(), 1000);
+        assert_eq!(parse_int("ff", 16).unwrap(), 255);
+        assert_eq!(parse_int("0xFF", 16).unwrap(), 255);
+        assert_eq!(parse_int("0b1010", 0).unwrap(), 10);
+        assert_eq!(parse_int("-0o17", 0).unwrap(), -15);
+        assert_eq!(parse_int("0x_ff", 0).unwrap(), 255);
+        assert_eq!(parse_int("0", 0).unwrap(), 0);
+        assert_eq!(parse_int("z", 36).unwrap(), 35);
+
+        for (text, base) in [
+            ("010", 0),
+            ("0x1f", 10),
+            ("1__0", 10),
+            ("_1", 10),
+            ("", 10),
+            ("--1", 10),
+        ] {
+            let err = parse_int(text, base).unwrap_err().to_string();
+            assert!(err.contains(&format!("{:?}", text)), "{}", err);
+        }
+        assert!(parse_int("99999999999999999999", 10)
+            .unwrap_err()
+            .to_string()
+            .contains("out of 64-bit range"));
+    }
+
+    #[test]
+    fn test_parse_float_quotes_input() {
+        assert_eq!(parse_float(" 1_000.5 ").unwrap(), 1000.5);
+        assert!(parse_float("inf").unwrap().is_infinite());
+        let err = parse_float("1.2.3").unwrap_err().to_string();
+        assert!(err.contains("\"1.2.3\""), "{}", err);
+        assert!(parse_float("1__0").is_err());
+    }
+}
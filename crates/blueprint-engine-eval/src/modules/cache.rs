@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use blueprint_engine_core::{
+    find_workspace_root,
+    validation::{get_string_arg, require_args, require_args_range},
+    BlueprintError, NativeFunction, Result, Value,
+};
+use sha2::{Digest, Sha256};
+
+use super::json::{json_to_value, value_to_json};
+
+/// A stored cache value: JSON text plus an optional unix expiry time.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub value: String,
+    pub expires_at: Option<f64>,
+}
+
+/// Storage behind the `cache` module. Implementations may block; they are
+/// always called from a blocking task.
+pub trait CacheBackend: Send + Sync {
+    fn get(&self, key: &str) -> io::Result<Option<CacheEntry>>;
+    fn set(&self, key: &str, entry: CacheEntry) -> io::Result<()>;
+    fn delete(&self, key: &str) -> io::Result<bool>;
+}
+
+/// Keeps entries for the lifetime of the process.
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get(&self, key: &str) -> io::Result<Option<CacheEntry>> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, entry: CacheEntry) -> io::Result<()> {
+        self.entries.lock().unwrap().insert(key.to_string(), entry);
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> io::Result<bool> {
+        Ok(self.entries.lock().unwrap().remove(key).is_some())
+    }
+}
+
+/// One JSON file per key. Writes go to a temporary file that is renamed into
+/// place, so readers never observe a partially written entry.
+pub struct FileCache {
+    dir: PathBuf,
+}
+
+impl FileCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `.blueprint/cache` in the workspace root, or under `$HOME` outside a workspace.
+    pub fn default_dir() -> PathBuf {
+        let base = find_workspace_root().unwrap_or_else(|| {
+            PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".to_string()))
+        });
+        base.join(".blueprint").join("cache")
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        let digest = hex::encode(Sha256::digest(key.as_bytes()));
+        self.dir.join(format!("{}.json", digest))
+    }
+}
+
+impl CacheBackend for FileCache {
+    fn get(&self, key: &str) -> io::Result<Option<CacheEntry>> {
+        let content = match std::fs::read_to_string(self.path_for(key)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let stored: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // A different key with the same digest is treated as a miss.
+        if stored.get("key").and_then(|k| k.as_str()) != Some(key) {
+            return Ok(None);
+        }
+        let value = stored
+            .get("value")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "null".to_string());
+        let expires_at = stored.get("expires_at").and_then(|v| v.as_f64());
+        Ok(Some(CacheEntry { value, expires_at }))
+    }
+
+    fn set(&self, key: &str, entry: CacheEntry) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let value: serde_json::Value = serde_json::from_str(&entry.value)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let stored = serde_json::json!({
+            "key": key,
+            "expires_at": entry.expires_at,
+            "value": value,
+        });
+
+        let path = self.path_for(key);
+        let tmp = path.with_extension(format!(
+            "tmp.{}.{}",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        std::fs::write(&tmp, stored.to_string())?;
+        if let Err(e) = std::fs::rename(&tmp, &path) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> io::Result<bool> {
+        match std::fs::remove_file(self.path_for(key)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref BACKEND: RwLock<Option<Arc<dyn CacheBackend>>> = RwLock::new(None);
+}
+
+/// Replaces the store used by the `cache` module for the whole process.
+pub fn set_cache_backend(backend: Arc<dyn CacheBackend>) {
+    *BACKEND.write().unwrap() = Some(backend);
+}
+
+fn backend() -> Arc<dyn CacheBackend> {
+    if let Some(backend) = BACKEND.read().unwrap().as_ref() {
+        return backend.clone();
+    }
+    BACKEND
+        .write()
+        .unwrap()
+        .get_or_insert_with(|| Arc::new(FileCache::new(FileCache::default_dir())))
+        .clone()
+}
+
+async fn with_backend<T, F>(op: &str, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&dyn CacheBackend) -> io::Result<T> + Send + 'static,
+{
+    let backend = backend();
+    tokio::task::spawn_blocking(move || f(backend.as_ref()))
+        .await
+        .map_err(|e| BlueprintError::InternalError {
+            message: format!("cache.{} task failed: {}", op, e),
+        })?
+        .map_err(|e| BlueprintError::IoError {
+            path: format!("cache.{}", op),
+            message: e.to_string(),
+        })
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("get", cache_get),
+        NativeFunction::new("set", cache_set),
+        NativeFunction::new("delete", cache_delete),
+    ]
+}
+
+async fn cache_get(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("cache.get", &args, 1, 2)?;
+    let key = get_string_arg("cache.get", &args, 0)?;
+    let default = args
+        .get(1)
+        .or_else(|| kwargs.get("default"))
+        .cloned()
+        .unwrap_or(Value::None);
+
+    let lookup_key = key.clone();
+    let entry = with_backend("get", move |b| b.get(&lookup_key)).await?;
+
+    match entry {
+        Some(entry) if entry.expires_at.is_some_and(|at| at <= now()) => {
+            with_backend("delete", move |b| b.delete(&key)).await?;
+            Ok(default)
+        }
+        Some(entry) => {
+            let json: serde_json::Value =
+                serde_json::from_str(&entry.value).map_err(|e| BlueprintError::JsonError {
+                    message: e.to_string(),
+                })?;
+            json_to_value(json)
+        }
+        None => Ok(default),
+    }
+}
+
+async fn cache_set(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("cache.set", &args, 2, 3)?;
+    let key = get_string_arg("cache.set", &args, 0)?;
+
+    let ttl = match args.get(2).or_else(|| kwargs.get("ttl")) {
+        None | Some(Value::None) => None,
+        Some(v) => {
+            let secs = v.as_float()?;
+            if !(secs > 0.0 && secs.is_finite()) {
+                return Err(BlueprintError::ValueError {
+                    message: format!("cache.set() ttl must be a positive number, got {}", secs),
+                });
+            }
+            Some(secs)
+        }
+    };
+
+    let value = value_to_json(&args[1], None).await?;
+    let entry = CacheEntry {
+        value: value.to_string(),
+        expires_at: ttl.map(|secs| now() + secs),
+    };
+
+    with_backend("set", move |b| b.set(&key, entry)).await?;
+    Ok(Value::None)
+}
+
+async fn cache_delete(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("cache.delete", &args, 1)?;
+    let key = get_string_arg("cache.delete", &args, 0)?;
+
+    let removed = with_backend("delete", move |b| b.delete(&key)).await?;
+    Ok(Value::Bool(removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(value: &str, expires_at: Option<f64>) -> CacheEntry {
+        CacheEntry {
+            value: value.to_string(),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_file_cache_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("bp_cache_{}", std::process::id()));
+        let cache = FileCache::new(&dir);
+
+        assert!(cache.get("missing").unwrap().is_none());
+
+        cache.set("k", entry(r#"{"a":[1,2]}"#, Some(12.5))).unwrap();
+        let stored = cache.get("k").unwrap().unwrap();
+        assert_eq!(stored.value, r#"{"a":[1,2]}"#);
+        assert_eq!(stored.expires_at, Some(12.5));
+
+        cache.set("k", entry("3", None)).unwrap();
+        assert_eq!(cache.get("k").unwrap().unwrap().value, "3");
+
+        let leftovers: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().contains(".tmp."))
+            .collect();
+        assert!(leftovers.is_empty());
+
+        assert!(cache.delete("k").unwrap());
+        assert!(!cache.delete("k").unwrap());
+        assert!(cache.get("k").unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_return_default() {
+        set_cache_backend(Arc::new(MemoryCache::new()));
+        let key = Value::String(Arc::new("ttl-key".into()));
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("ttl".to_string(), Value::Float(0.05));
+        cache_set(vec![key.clone(), Value::Int(7)], kwargs)
+            .await
+            .unwrap();
+        assert_eq!(
+            cache_get(vec![key.clone()], HashMap::new()).await.unwrap(),
+            Value::Int(7)
+        );
+
+        tokio::time::sleep(std::time::Duration::from_millis(80)).await;
+        let fallback = Value::String(Arc::new("gone".into()));
+        assert_eq!(
+            cache_get(vec![key, fallback.clone()], HashMap::new())
+                .await
+                .unwrap(),
+            fallback
+        );
+    }
+}
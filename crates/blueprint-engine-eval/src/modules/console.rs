@@ -1,67 +1,294 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use blueprint_engine_core::{BlueprintError, NativeFunction, Result, Value};
+use blueprint_engine_core::{
+    validation::{get_string_arg, require_args},
+    BlueprintError, NativeFunction, Result, Value,
+};
+use chrono::{DateTime, SecondsFormat};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
+use super::builtins::display_string;
 use crate::eval::Evaluator;
+use crate::modules::json::value_to_json;
+use crate::modules::pretty::pformat;
+
+const LEVELS: [&str; 4] = ["debug", "info", "warn", "error"];
+
+/// Set by `set_format("json")`: `debug`/`info`/`warn`/`error` then write the
+/// same JSON lines as `json_log`.
+static JSON_FORMAT: AtomicBool = AtomicBool::new(false);
 
 pub fn register(evaluator: &mut Evaluator) {
     evaluator.register_native(NativeFunction::new("print", print));
     evaluator.register_native(NativeFunction::new("eprint", eprint));
     evaluator.register_native(NativeFunction::new("input", input));
+    evaluator.register_native(NativeFunction::new("pprint", pprint));
 }
 
-async fn print(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
-    let sep = kwargs
-        .get("sep")
-        .map(|v| v.to_display_string())
-        .unwrap_or_else(|| " ".to_string());
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("debug", |args, kwargs| log("debug", args, kwargs)),
+        NativeFunction::new("info", |args, kwargs| log("info", args, kwargs)),
+        NativeFunction::new("warn", |args, kwargs| log("warn", args, kwargs)),
+        NativeFunction::new("error", |args, kwargs| log("error", args, kwargs)),
+        NativeFunction::new("json_log", json_log),
+        NativeFunction::new("set_format", set_format),
+    ]
+}
+
+async fn log(level: &str, args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    let name = format!("console.{}", level);
+    require_args(&name, &args, 1)?;
+    let message = args[0].to_display_string();
+    let json = JSON_FORMAT.load(Ordering::Relaxed);
+    write_log(&name, level, &message, kwargs, json).await
+}
 
-    let end = kwargs
-        .get("end")
-        .map(|v| v.to_display_string())
-        .unwrap_or_else(|| "\n".to_string());
+/// Always writes JSON, whatever `set_format` chose.
+async fn json_log(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("console.json_log", &args, 2)?;
+    let level = get_string_arg("console.json_log", &args, 0)?;
+    let Some(level) = LEVELS.iter().find(|l| **l == level) else {
+        return Err(BlueprintError::ValueError {
+            message: format!(
+                "console.json_log() level must be one of {}, got {:?}",
+                LEVELS.join(", "),
+                level
+            ),
+        });
+    };
+    let message = args[1].to_display_string();
+    write_log("console.json_log", level, &message, kwargs, true).await
+}
 
-    let output: String = args
+async fn set_format(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("console.set_format", &args, 1)?;
+    let json = match get_string_arg("console.set_format", &args, 0)?.as_str() {
+        "json" => true,
+        "text" => false,
+        other => {
+            return Err(BlueprintError::ValueError {
+                message: format!(
+                    "console.set_format() format must be \"text\" or \"json\", got {:?}",
+                    other
+                ),
+            });
+        }
+    };
+    JSON_FORMAT.store(json, Ordering::Relaxed);
+    Ok(Value::None)
+}
+
+/// Writes one record to stderr. Fields are sorted by name so lines diff
+/// cleanly; values with no JSON form are logged as their display string.
+async fn write_log(
+    name: &str,
+    level: &str,
+    message: &str,
+    fields: HashMap<String, Value>,
+    json: bool,
+) -> Result<Value> {
+    let mut fields: Vec<(String, Value)> = fields.into_iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(&b.0));
+    if let Some((key, _)) = fields
         .iter()
-        .map(|v| v.to_display_string())
-        .collect::<Vec<_>>()
-        .join(&sep);
+        .find(|(k, _)| matches!(k.as_str(), "timestamp" | "level" | "message"))
+    {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("{}() field '{}' is reserved", name, key),
+        });
+    }
 
-    let mut stdout = tokio::io::stdout();
-    stdout.write_all(output.as_bytes()).await.ok();
-    stdout.write_all(end.as_bytes()).await.ok();
-    stdout.flush().await.ok();
+    let mut json_fields = Vec::with_capacity(fields.len());
+    for (key, value) in &fields {
+        let json_value = match value_to_json(value, None).await {
+            Ok(v) => v,
+            Err(_) => serde_json::Value::String(value.to_display_string()),
+        };
+        json_fields.push((key.clone(), json_value));
+    }
 
+    let timestamp = format_timestamp(super::time::current_time());
+    let mut line = if json {
+        format_json_record(&timestamp, level, message, json_fields)
+    } else {
+        format_text_record(&timestamp, level, message, &json_fields)
+    };
+    line.push('\n');
+
+    let mut stderr = tokio::io::stderr();
+    stderr.write_all(line.as_bytes()).await.ok();
+    stderr.flush().await.ok();
     Ok(Value::None)
 }
 
+/// RFC 3339 in UTC with millisecond precision, e.g. `2023-11-14T22:13:20.000Z`.
+fn format_timestamp(epoch: f64) -> String {
+    let secs = epoch.floor();
+    let nanos = ((epoch - secs) * 1e9) as u32;
+    DateTime::from_timestamp(secs as i64, nanos)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn format_json_record(
+    timestamp: &str,
+    level: &str,
+    message: &str,
+    fields: Vec<(String, serde_json::Value)>,
+) -> String {
+    let mut record = serde_json::Map::new();
+    record.insert("timestamp".into(), timestamp.into());
+    record.insert("level".into(), level.into());
+    record.insert("message".into(), message.into());
+    record.extend(fields);
+    serde_json::Value::Object(record).to_string()
+}
+
+/// `<timestamp> <LEVEL> <message> key=value ...` with values in JSON form.
+fn format_text_record(
+    timestamp: &str,
+    level: &str,
+    message: &str,
+    fields: &[(String, serde_json::Value)],
+) -> String {
+    let mut line = format!("{} {:<5} {}", timestamp, level.to_uppercase(), message);
+    for (key, value) in fields {
+        line.push_str(&format!(" {}={}", key, value));
+    }
+    line
+}
+
+async fn print(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    write_output("print", args, kwargs, Stream::Stdout).await
+}
+
 async fn eprint(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
-    let sep = kwargs
-        .get("sep")
-        .map(|v| v.to_display_string())
-        .unwrap_or_else(|| " ".to_string());
+    write_output("eprint", args, kwargs, Stream::Stderr).await
+}
 
-    let end = kwargs
-        .get("end")
-        .map(|v| v.to_display_string())
-        .unwrap_or_else(|| "\n".to_string());
+enum Stream {
+    Stdout,
+    Stderr,
+}
 
-    let output: String = args
-        .iter()
-        .map(|v| v.to_display_string())
-        .collect::<Vec<_>>()
-        .join(&sep);
+/// Shared by `print` and `eprint`: Python-style `sep` and `end`, plus `file`
+/// to pick `"stdout"` or `"stderr"`.
+async fn write_output(
+    name: &str,
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+    default_stream: Stream,
+) -> Result<Value> {
+    if let Some(key) = kwargs
+        .keys()
+        .find(|k| !matches!(k.as_str(), "sep" | "end" | "file"))
+    {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("{}() got an unexpected keyword argument '{}'", name, key),
+        });
+    }
 
-    let mut stderr = tokio::io::stderr();
-    stderr.write_all(output.as_bytes()).await.ok();
-    stderr.write_all(end.as_bytes()).await.ok();
-    stderr.flush().await.ok();
+    let sep = string_kwarg(name, &kwargs, "sep")?.unwrap_or_else(|| " ".to_string());
+    let end = string_kwarg(name, &kwargs, "end")?.unwrap_or_else(|| "\n".to_string());
+    let stream = match string_kwarg(name, &kwargs, "file")?.as_deref() {
+        None => default_stream,
+        Some("stdout") => Stream::Stdout,
+        Some("stderr") => Stream::Stderr,
+        Some(other) => {
+            return Err(BlueprintError::ValueError {
+                message: format!(
+                    "{}() file must be \"stdout\" or \"stderr\", got {:?}",
+                    name, other
+                ),
+            });
+        }
+    };
+
+    let mut parts = Vec::with_capacity(args.len());
+    for arg in &args {
+        parts.push(display_string(arg).await?);
+    }
+    let mut output = parts.join(&sep);
+    output.push_str(&end);
+
+    match stream {
+        Stream::Stdout => {
+            let mut stdout = tokio::io::stdout();
+            stdout.write_all(output.as_bytes()).await.ok();
+            stdout.flush().await.ok();
+        }
+        Stream::Stderr => {
+            let mut stderr = tokio::io::stderr();
+            stderr.write_all(output.as_bytes()).await.ok();
+            stderr.flush().await.ok();
+        }
+    }
+
+    Ok(Value::None)
+}
+
+fn string_kwarg(name: &str, kwargs: &HashMap<String, Value>, key: &str) -> Result<Option<String>> {
+    match kwargs.get(key) {
+        None | Some(Value::None) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.as_ref().clone())),
+        Some(other) => Err(BlueprintError::TypeError {
+            expected: format!("{}() {} to be a string or None", name, key),
+            actual: other.type_name().into(),
+        }),
+    }
+}
+
+async fn pprint(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("pprint() takes 1 to 3 arguments ({} given)", args.len()),
+        });
+    }
+    if let Some(key) = kwargs
+        .keys()
+        .find(|k| !matches!(k.as_str(), "indent" | "max_width"))
+    {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("pprint() got an unexpected keyword argument '{}'", key),
+        });
+    }
+
+    let indent = usize_arg(&args, &kwargs, 1, "indent", 2)?;
+    let max_width = usize_arg(&args, &kwargs, 2, "max_width", 80)?;
+
+    let mut output = pformat(&args[0], indent, max_width);
+    output.push('\n');
+    let mut stdout = tokio::io::stdout();
+    stdout.write_all(output.as_bytes()).await.ok();
+    stdout.flush().await.ok();
 
     Ok(Value::None)
 }
 
+fn usize_arg(
+    args: &[Value],
+    kwargs: &HashMap<String, Value>,
+    position: usize,
+    key: &str,
+    default: usize,
+) -> Result<usize> {
+    let value = match (args.get(position), kwargs.get(key)) {
+        (Some(_), Some(_)) => {
+            return Err(BlueprintError::ArgumentError {
+                message: format!("pprint() got multiple values for argument '{}'", key),
+            });
+        }
+        (Some(v), None) | (None, Some(v)) => v.as_int()?,
+        (None, None) => return Ok(default),
+    };
+    usize::try_from(value).map_err(|_| BlueprintError::ValueError {
+        message: format!("pprint() {} must be non-negative, got {}", key, value),
+    })
+}
+
 async fn input(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.len() > 1 {
         return Err(BlueprintError::ArgumentError {
@@ -97,3 +324,27 @@ async fn input(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Valu
 
     Ok(Value::String(Arc::new(line)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_records() {
+        let timestamp = format_timestamp(1700000000.25);
+        assert_eq!(timestamp, "2023-11-14T22:13:20.250Z");
+
+        let fields = vec![
+            ("attempt".to_string(), serde_json::json!(2)),
+            ("host".to_string(), serde_json::json!("db-1")),
+        ];
+        assert_eq!(
+            format_json_record(&timestamp, "warn", "retrying", fields.clone()),
+            r#"{"timestamp":"2023-11-14T22:13:20.250Z","level":"warn","message":"retrying","attempt":2,"host":"db-1"}"#
+        );
+        assert_eq!(
+            format_text_record(&timestamp, "warn", "retrying", &fields),
+            r#"2023-11-14T22:13:20.250Z WARN  retrying attempt=2 host="db-1""#
+        );
+    }
+}
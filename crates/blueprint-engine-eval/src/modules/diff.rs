@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use blueprint_engine_core::{
+    validation::{require_args, require_args_range},
+    BlueprintError, NativeFunction, Result, Value,
+};
+
+const DEFAULT_CONTEXT: i64 = 3;
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("unified", unified_fn),
+        NativeFunction::new("ratio", ratio_fn),
+    ]
+}
+
+async fn unified_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("diff.unified", &args, 2, 3)?;
+    let a = lines_arg(&args[0]).await?;
+    let b = lines_arg(&args[1]).await?;
+    let context = match args.get(2).or_else(|| kwargs.get("context")) {
+        Some(v) => v.as_int()?,
+        None => DEFAULT_CONTEXT,
+    };
+    let context = usize::try_from(context).map_err(|_| BlueprintError::ValueError {
+        message: format!(
+            "diff.unified() context must be non-negative, got {}",
+            context
+        ),
+    })?;
+    let fromfile = match kwargs.get("fromfile") {
+        Some(v) => v.as_string()?,
+        None => "a".to_string(),
+    };
+    let tofile = match kwargs.get("tofile") {
+        Some(v) => v.as_string()?,
+        None => "b".to_string(),
+    };
+
+    Ok(Value::String(Arc::new(unified(
+        &a, &b, context, &fromfile, &tofile,
+    ))))
+}
+
+async fn ratio_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("diff.ratio", &args, 2)?;
+    let ratio = match (&args[0], &args[1]) {
+        (Value::String(a), Value::String(b)) => {
+            let a: Vec<char> = a.chars().collect();
+            let b: Vec<char> = b.chars().collect();
+            ratio(&a, &b)
+        }
+        _ => ratio(&lines_arg(&args[0]).await?, &lines_arg(&args[1]).await?),
+    };
+    Ok(Value::Float(ratio))
+}
+
+/// A string is split into lines; a list must already hold one string per line.
+async fn lines_arg(value: &Value) -> Result<Vec<String>> {
+    match value {
+        Value::String(s) => Ok(s.lines().map(str::to_string).collect()),
+        Value::List(l) => l.read().await.iter().map(|v| v.as_string()).collect(),
+        other => Err(BlueprintError::TypeError {
+            expected: "string or list of strings".into(),
+            actual: other.type_name().into(),
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Shortest edit script between `a` and `b`, in order, with deletions ahead
+/// of the insertions that replace them. This is Myers' O(ND) algorithm in its
+/// linear-space form: each step finds the middle snake of the remaining
+/// range and recurses on either side of it, so memory stays O(N + M).
+fn diff_ops<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    let max_d = max_d(a.len(), b.len());
+    let mut vf = V::new(max_d);
+    let mut vb = V::new(max_d);
+    let mut ops = Vec::with_capacity(a.len().max(b.len()));
+    conquer(a, 0, a.len(), b, 0, b.len(), &mut vf, &mut vb, &mut ops);
+
+    // Recursion can leave an insertion ahead of a deletion within one change.
+    let mut start = 0;
+    while start < ops.len() {
+        let end = ops[start..]
+            .iter()
+            .position(|op| matches!(op, Op::Equal(..)))
+            .map_or(ops.len(), |i| start + i);
+        ops[start..end].sort_by_key(|op| matches!(op, Op::Insert(_)));
+        start = end + 1;
+    }
+    ops
+}
+
+/// Furthest-reaching x per diagonal `k`, for `k` in `-max_d..=max_d`.
+struct V {
+    offset: isize,
+    v: Vec<usize>,
+}
+
+impl V {
+    fn new(max_d: usize) -> Self {
+        V {
+            offset: max_d as isize + 1,
+            v: vec![0; 2 * max_d + 3],
+        }
+    }
+}
+
+impl std::ops::Index<isize> for V {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.v[(k + self.offset) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for V {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.v[(k + self.offset) as usize]
+    }
+}
+
+/// The largest `d` the middle snake search can need for inputs of these sizes.
+fn max_d(n: usize, m: usize) -> usize {
+    (n + m).div_ceil(2) + 1
+}
+
+#[allow(clippy::too_many_arguments)]
+fn conquer<T: PartialEq>(
+    a: &[T],
+    mut a_lo: usize,
+    mut a_hi: usize,
+    b: &[T],
+    mut b_lo: usize,
+    mut b_hi: usize,
+    vf: &mut V,
+    vb: &mut V,
+    ops: &mut Vec<Op>,
+) {
+    while a_lo < a_hi && b_lo < b_hi && a[a_lo] == b[b_lo] {
+        ops.push(Op::Equal(a_lo, b_lo));
+        a_lo += 1;
+        b_lo += 1;
+    }
+    let mut suffix = 0;
+    while a_lo < a_hi && b_lo < b_hi && a[a_hi - 1] == b[b_hi - 1] {
+        a_hi -= 1;
+        b_hi -= 1;
+        suffix += 1;
+    }
+
+    if a_lo == a_hi {
+        ops.extend((b_lo..b_hi).map(Op::Insert));
+    } else if b_lo == b_hi {
+        ops.extend((a_lo..a_hi).map(Op::Delete));
+    } else {
+        let (x, y) = middle_snake(a, a_lo, a_hi, b, b_lo, b_hi, vf, vb);
+        conquer(a, a_lo, x, b, b_lo, y, vf, vb, ops);
+        conquer(a, x, a_hi, b, y, b_hi, vf, vb, ops);
+    }
+
+    ops.extend((0..suffix).map(|i| Op::Equal(a_hi + i, b_hi + i)));
+}
+
+/// Runs the forward and backward searches towards each other and returns
+/// where they first overlap: a point on some shortest path through the range
+/// that splits it into two smaller problems.
+#[allow(clippy::too_many_arguments)]
+fn middle_snake<T: PartialEq>(
+    a: &[T],
+    a_lo: usize,
+    a_hi: usize,
+    b: &[T],
+    b_lo: usize,
+    b_hi: usize,
+    vf: &mut V,
+    vb: &mut V,
+) -> (usize, usize) {
+    let (n, m) = (a_hi - a_lo, b_hi - b_lo);
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+    vf[1] = 0;
+    vb[1] = 0;
+
+    for d in 0..max_d(n, m) as isize {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
+                vf[k + 1]
+            } else {
+                vf[k - 1] + 1
+            };
+            let (x0, y0) = (x, (x as isize - k) as usize);
+            let mut y = y0;
+            while x < n && y < m && a[a_lo + x] == b[b_lo + y] {
+                x += 1;
+                y += 1;
+            }
+            vf[k] = x;
+            if odd && (k - delta).abs() < d && vf[k] + vb[delta - k] >= n {
+                return (a_lo + x0, b_lo + y0);
+            }
+        }
+
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && vb[k - 1] < vb[k + 1]) {
+                vb[k + 1]
+            } else {
+                vb[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            while x < n && y < m && a[a_hi - x - 1] == b[b_hi - y - 1] {
+                x += 1;
+                y += 1;
+            }
+            vb[k] = x;
+            if !odd && (k - delta).abs() <= d && vb[k] + vf[delta - k] >= n {
+                return (a_hi - x, b_hi - y);
+            }
+        }
+    }
+    unreachable!("the forward and backward searches always meet")
+}
+
+/// Length of the shortest edit script, from the forward search alone.
+fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let (n, m) = (a.len(), b.len());
+    let mut v = V::new(n + m);
+    for d in 0..=(n + m) as isize {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[k - 1] < v[k + 1]) {
+                v[k + 1]
+            } else {
+                v[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            while x < n && y < m && a[x] == b[y] {
+                x += 1;
+                y += 1;
+            }
+            v[k] = x;
+            if x >= n && y >= m {
+                return d as usize;
+            }
+        }
+    }
+    n + m
+}
+
+/// `2 * matches / total`, as in difflib: 1.0 for identical inputs, 0.0 when
+/// nothing is shared. Every edit is one unmatched element, so the matches
+/// follow from the edit distance without building the script.
+fn ratio<T: PartialEq>(a: &[T], b: &[T]) -> f64 {
+    let total = a.len() + b.len();
+    if total == 0 {
+        return 1.0;
+    }
+    let matches = (total - edit_distance(a, b)) / 2;
+    2.0 * matches as f64 / total as f64
+}
+
+/// Renders the differences as a unified diff with `context` unchanged lines
+/// around each change. Identical inputs produce an empty string.
+pub(crate) fn unified(
+    a: &[String],
+    b: &[String],
+    context: usize,
+    fromfile: &str,
+    tofile: &str,
+) -> String {
+    let ops = diff_ops(a, b);
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Line positions in `a` and `b` reached before each op.
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut ai, mut bi) = (0, 0);
+    for op in &ops {
+        positions.push((ai, bi));
+        match op {
+            Op::Equal(..) => {
+                ai += 1;
+                bi += 1;
+            }
+            Op::Delete(_) => ai += 1,
+            Op::Insert(_) => bi += 1,
+        }
+    }
+    positions.push((ai, bi));
+
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changes {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", fromfile, tofile);
+    for (start, end) in hunks {
+        let (a_start, b_start) = positions[start];
+        let (a_end, b_end) = positions[end];
+        let _ = writeln!(
+            out,
+            "@@ -{} +{} @@",
+            hunk_range(a_start, a_end - a_start),
+            hunk_range(b_start, b_end - b_start)
+        );
+        for op in &ops[start..end] {
+            let _ = match *op {
+                Op::Equal(x, _) => writeln!(out, " {}", a[x]),
+                Op::Delete(x) => writeln!(out, "-{}", a[x]),
+                Op::Insert(y) => writeln!(out, "+{}", b[y]),
+            };
+        }
+    }
+    out
+}
+
+/// `start,len` with 1-based `start`; a single line omits the length and an
+/// empty range points at the line before it.
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_unified_hunks() {
+        let a = lines("one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten");
+        let b = lines("one\ntwo\nTHREE\nfour\nfive\nsix\nseven\neight\nnine\nten\neleven");
+        assert_eq!(
+            unified(&a, &b, 1, "old", "new"),
+            "--- old\n+++ new\n\
+             @@ -2,3 +2,3 @@\n two\n-three\n+THREE\n four\n\
+             @@ -10 +10,2 @@\n ten\n+eleven\n"
+        );
+        assert_eq!(
+            unified(&a, &b, 3, "a", "b"),
+            "--- a\n+++ b\n\
+             @@ -1,6 +1,6 @@\n one\n two\n-three\n+THREE\n four\n five\n six\n\
+             @@ -8,3 +8,4 @@\n eight\n nine\n ten\n+eleven\n"
+        );
+        assert_eq!(unified(&a, &a, 3, "a", "b"), "");
+        assert_eq!(
+            unified(&[], &lines("x"), 3, "a", "b"),
+            "--- a\n+++ b\n@@ -0,0 +1 @@\n+x\n"
+        );
+    }
+
+    #[test]
+    fn test_ratio() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(ratio(&chars("abcd"), &chars("abcd")), 1.0);
+        assert_eq!(ratio(&chars("abcd"), &chars("bcde")), 0.75);
+        assert_eq!(ratio(&chars("abc"), &chars("xyz")), 0.0);
+        assert_eq!(ratio::<char>(&[], &[]), 1.0);
+    }
+
+    #[test]
+    fn test_diff_ops_are_shortest_and_replay() {
+        let cases = [
+            ("abcabba", "cbabac"),
+            ("kitten", "sitting"),
+            ("xaxbxcx", "yaybycy"),
+            ("", "abc"),
+            ("abc", ""),
+        ];
+        for (a, b) in cases {
+            let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+            let ops = diff_ops(&a, &b);
+            let mut replayed = Vec::new();
+            for (i, op) in ops.iter().enumerate() {
+                match *op {
+                    Op::Equal(x, y) => {
+                        assert_eq!(a[x], b[y]);
+                        replayed.push(b[y]);
+                    }
+                    Op::Insert(y) => replayed.push(b[y]),
+                    Op::Delete(_) => {
+                        assert!(!matches!(ops.get(i.wrapping_sub(1)), Some(Op::Insert(_))));
+                    }
+                }
+            }
+            assert_eq!(replayed, b);
+            let edits = ops.iter().filter(|op| !matches!(op, Op::Equal(..))).count();
+            assert_eq!(edits, edit_distance(&a, &b));
+        }
+        assert_eq!(
+            edit_distance(
+                &['a', 'b', 'c', 'a', 'b', 'b', 'a'],
+                &['c', 'b', 'a', 'b', 'a', 'c']
+            ),
+            5
+        );
+    }
+}
@@ -1,14 +1,61 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use blueprint_engine_core::{
     check_fs_delete, check_fs_read, check_fs_write,
-    validation::{get_string_arg, require_args},
-    BlueprintError, NativeFunction, Result, Value,
+    validation::{get_int_arg, get_string_arg, require_args, require_args_range},
+    BlueprintError, NativeFunction, Result, StructField, StructInstance, StructType,
+    TypeAnnotation, Value,
 };
+use indexmap::IndexMap;
+use sha2::Digest;
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
-use tokio::sync::RwLock;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::sync::{Mutex, RwLock};
+
+use super::json::{json_to_string, json_to_value, value_to_json};
+use super::triggers::spawn_file_watch;
+
+/// Seconds of quiet before a burst of changes is reported to the handler.
+const DEFAULT_WATCH_DEBOUNCE: f64 = 0.1;
+
+lazy_static::lazy_static! {
+    static ref FILE_TYPE: Arc<StructType> = Arc::new(StructType {
+        name: "File".into(),
+        fields: ["path", "mode"]
+            .into_iter()
+            .map(|name| StructField {
+                name: name.into(),
+                typ: TypeAnnotation::Simple("str".into()),
+                default: None,
+            })
+            .collect(),
+        methods: IndexMap::new(),
+    });
+    static ref STAT_TYPE: Arc<StructType> = Arc::new(StructType {
+        name: "FileStat".into(),
+        fields: [
+            ("size", "int"),
+            ("modified", "float"),
+            ("is_dir", "bool"),
+            ("is_file", "bool"),
+            ("permissions", "str"),
+        ]
+        .into_iter()
+        .map(|(name, typ)| StructField {
+            name: name.into(),
+            typ: TypeAnnotation::Simple(typ.into()),
+            default: None,
+        })
+        .collect(),
+        methods: IndexMap::new(),
+    });
+}
 
 pub fn get_functions() -> Vec<NativeFunction> {
     vec![
@@ -27,6 +74,12 @@ pub fn get_functions() -> Vec<NativeFunction> {
         NativeFunction::new("basename", basename),
         NativeFunction::new("dirname", dirname),
         NativeFunction::new("abspath", abspath),
+        NativeFunction::new("open", open),
+        NativeFunction::new("watch", watch),
+        NativeFunction::new("read_json", read_json),
+        NativeFunction::new("write_json", write_json),
+        NativeFunction::new("stat", stat),
+        NativeFunction::new("checksum", checksum),
     ]
 }
 
@@ -45,18 +98,34 @@ async fn read_file(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<
     Ok(Value::String(Arc::new(content)))
 }
 
-async fn write_file(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+/// `atomic=True` replaces the file via a temp file and rename; `fsync=True`
+/// waits until the data has reached the disk before returning.
+async fn write_file(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
     require_args("file.write_file", &args, 2)?;
     let path = get_string_arg("file.write_file", &args, 0)?;
     check_fs_write(&path).await?;
     let content = get_string_arg("file.write_file", &args, 1)?;
+    let atomic = kwargs.get("atomic").is_some_and(Value::is_truthy);
+    let fsync = kwargs.get("fsync").is_some_and(Value::is_truthy);
 
-    fs::write(&path, &content)
-        .await
-        .map_err(|e| BlueprintError::IoError {
-            path: path.clone(),
-            message: e.to_string(),
-        })?;
+    if atomic {
+        write_atomic(&path, content.as_bytes(), fsync).await?;
+    } else if fsync {
+        let mut file = fs::File::create(&path)
+            .await
+            .map_err(|e| io_error(&path, e))?;
+        file.write_all(content.as_bytes())
+            .await
+            .map_err(|e| io_error(&path, e))?;
+        file.sync_all().await.map_err(|e| io_error(&path, e))?;
+    } else {
+        fs::write(&path, &content)
+            .await
+            .map_err(|e| BlueprintError::IoError {
+                path: path.clone(),
+                message: e.to_string(),
+            })?;
+    }
 
     Ok(Value::None)
 }
@@ -87,6 +156,106 @@ async fn append_file(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resul
     Ok(Value::None)
 }
 
+async fn read_json(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("file.read_json", &args, 1)?;
+    let path = get_string_arg("file.read_json", &args, 0)?;
+    check_fs_read(&path).await?;
+
+    let content = fs::read_to_string(&path)
+        .await
+        .map_err(|e| io_error(&path, e))?;
+    let json = serde_json::from_str(&content).map_err(|e| BlueprintError::JsonError {
+        message: format!("{}: {}", path, e),
+    })?;
+
+    json_to_value(json)
+}
+
+async fn write_json(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("file.write_json", &args, 2, 3)?;
+    let path = get_string_arg("file.write_json", &args, 0)?;
+    check_fs_write(&path).await?;
+    let indent = match args.get(2).or_else(|| kwargs.get("indent")) {
+        None | Some(Value::None) => None,
+        Some(v) => Some(v.as_int()?.max(0) as usize),
+    };
+
+    let json = value_to_json(&args[1], None).await.map_err(|e| match e {
+        BlueprintError::JsonError { message } => BlueprintError::JsonError {
+            message: format!("{}: {}", path, message),
+        },
+        other => other,
+    })?;
+    let mut content = json_to_string(&json, indent)?;
+    content.push('\n');
+    let fsync = kwargs.get("fsync").is_some_and(Value::is_truthy);
+    write_atomic(&path, content.as_bytes(), fsync).await?;
+
+    Ok(Value::None)
+}
+
+/// Writes to a temporary sibling of `path` and renames it into place, so
+/// readers see the old or the new contents but never a partial file. With
+/// `fsync`, the data and the rename are both flushed to disk before returning.
+async fn write_atomic(path: &str, content: &[u8], fsync: bool) -> Result<()> {
+    static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+    let target = Path::new(path);
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| BlueprintError::IoError {
+            path: path.to_string(),
+            message: "not a file path".into(),
+        })?
+        .to_string_lossy();
+    let temp = target.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        file_name,
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = async {
+        let mut file = fs::File::create(&temp).await?;
+        file.write_all(content).await?;
+        if fsync {
+            file.sync_all().await?;
+        }
+        drop(file);
+        if let Ok(existing) = fs::metadata(target).await {
+            fs::set_permissions(&temp, existing.permissions()).await?;
+        }
+        fs::rename(&temp, target).await?;
+        if fsync {
+            sync_parent_dir(target).await?;
+        }
+        Ok::<(), std::io::Error>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp).await;
+        return Err(io_error(path, e));
+    }
+    Ok(())
+}
+
+/// Makes a rename durable: the new directory entry only survives a crash once
+/// the directory itself has been synced.
+#[cfg(unix)]
+async fn sync_parent_dir(target: &Path) -> std::io::Result<()> {
+    let dir = match target.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    fs::File::open(dir).await?.sync_all().await
+}
+
+#[cfg(not(unix))]
+async fn sync_parent_dir(_target: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
 async fn exists(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     require_args("file.exists", &args, 1)?;
     let path = get_string_arg("file.exists", &args, 0)?;
@@ -139,6 +308,94 @@ async fn glob_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
     Ok(Value::List(Arc::new(RwLock::new(paths))))
 }
 
+async fn stat(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("file.stat", &args, 1)?;
+    let path = get_string_arg("file.stat", &args, 0)?;
+    check_fs_read(&path).await?;
+
+    let metadata = fs::metadata(&path).await.map_err(|e| io_error(&path, e))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0);
+
+    let mut fields = IndexMap::new();
+    fields.insert("size".to_string(), Value::Int(metadata.len() as i64));
+    fields.insert("modified".to_string(), Value::Float(modified));
+    fields.insert("is_dir".to_string(), Value::Bool(metadata.is_dir()));
+    fields.insert("is_file".to_string(), Value::Bool(metadata.is_file()));
+    fields.insert(
+        "permissions".to_string(),
+        Value::String(Arc::new(permission_bits(&metadata))),
+    );
+
+    Ok(Value::StructInstance(Arc::new(StructInstance {
+        struct_type: STAT_TYPE.clone(),
+        fields,
+    })))
+}
+
+/// Octal permission bits such as `"0644"`.
+#[cfg(unix)]
+fn permission_bits(metadata: &std::fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    format!("{:04o}", metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn permission_bits(metadata: &std::fs::Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "0444".into()
+    } else {
+        "0644".into()
+    }
+}
+
+async fn checksum(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("file.checksum", &args, 1, 2)?;
+    let path = get_string_arg("file.checksum", &args, 0)?;
+    let algo = match args.get(1).or_else(|| kwargs.get("algo")) {
+        Some(v) => v.as_string()?,
+        None => "sha256".to_string(),
+    };
+    check_fs_read(&path).await?;
+
+    let digest = match algo.as_str() {
+        "sha256" => digest_file::<sha2::Sha256>(&path).await?,
+        "sha512" => digest_file::<sha2::Sha512>(&path).await?,
+        "sha1" => digest_file::<sha1::Sha1>(&path).await?,
+        "md5" => digest_file::<md5::Md5>(&path).await?,
+        other => {
+            return Err(BlueprintError::ValueError {
+                message: format!(
+                    "file.checksum() algo must be sha256, sha512, sha1 or md5 (got {:?})",
+                    other
+                ),
+            })
+        }
+    };
+
+    Ok(Value::String(Arc::new(digest)))
+}
+
+/// Hashes the file in fixed-size chunks so large files are never held in
+/// memory at once.
+async fn digest_file<D: Digest>(path: &str) -> Result<String> {
+    let mut file = fs::File::open(path).await.map_err(|e| io_error(path, e))?;
+    let mut hasher = D::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await.map_err(|e| io_error(path, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
 async fn mkdir(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     require_args("file.mkdir", &args, 1)?;
     let path = get_string_arg("file.mkdir", &args, 0)?;
@@ -274,3 +531,555 @@ async fn abspath(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
 
     Ok(Value::String(Arc::new(abs)))
 }
+
+async fn watch(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("file.watch", &args, 2)?;
+    let path = get_string_arg("file.watch", &args, 0)?;
+    check_fs_read(&path).await?;
+
+    let recursive = match kwargs.get("recursive") {
+        Some(v) => v.is_truthy(),
+        None => true,
+    };
+    let debounce = match kwargs.get("debounce") {
+        Some(v) => v.as_float()?,
+        None => DEFAULT_WATCH_DEBOUNCE,
+    };
+    if !(0.0..=3600.0).contains(&debounce) {
+        return Err(BlueprintError::ValueError {
+            message: format!(
+                "file.watch() debounce must be 0-3600 seconds, got {}",
+                debounce
+            ),
+        });
+    }
+
+    spawn_file_watch(
+        path,
+        recursive,
+        Duration::from_secs_f64(debounce),
+        args[1].clone(),
+    )
+    .await
+}
+
+/// State behind a handle from `file.open`. The file is dropped on `close()`
+/// or when the last reference to the handle goes away.
+struct OpenFile {
+    path: String,
+    readable: bool,
+    writable: bool,
+    file: Mutex<Option<Stream>>,
+}
+
+struct Stream {
+    reader: BufReader<fs::File>,
+    /// The start of a character cut off by the last `read(n)`, decoded
+    /// together with the bytes that complete it on the next read.
+    partial: Vec<u8>,
+}
+
+fn io_error(path: &str, e: std::io::Error) -> BlueprintError {
+    BlueprintError::IoError {
+        path: path.to_string(),
+        message: e.to_string(),
+    }
+}
+
+async fn open(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("file.open", &args, 1, 2)?;
+    let path = get_string_arg("file.open", &args, 0)?;
+    let mode = match args.get(1).or_else(|| kwargs.get("mode")) {
+        Some(v) => v.as_string()?,
+        None => "r".to_string(),
+    };
+
+    let (readable, writable, append, truncate, create) = match mode.as_str() {
+        "r" => (true, false, false, false, false),
+        "r+" => (true, true, false, false, false),
+        "w" => (false, true, false, true, true),
+        "w+" => (true, true, false, true, true),
+        "a" => (false, true, true, false, true),
+        "a+" => (true, true, true, false, true),
+        other => {
+            return Err(BlueprintError::ValueError {
+                message: format!(
+                    "file.open() mode must be one of r, r+, w, w+, a, a+ (got {:?})",
+                    other
+                ),
+            })
+        }
+    };
+    if readable {
+        check_fs_read(&path).await?;
+    }
+    if writable {
+        check_fs_write(&path).await?;
+    }
+
+    let file = fs::OpenOptions::new()
+        .read(readable)
+        .write(writable && !append)
+        .append(append)
+        .truncate(truncate)
+        .create(create)
+        .open(&path)
+        .await
+        .map_err(|e| io_error(&path, e))?;
+
+    let state = Arc::new(OpenFile {
+        path: path.clone(),
+        readable,
+        writable,
+        file: Mutex::new(Some(Stream {
+            reader: BufReader::new(file),
+            partial: Vec::new(),
+        })),
+    });
+
+    let mut fields = IndexMap::new();
+    fields.insert("path".to_string(), Value::String(Arc::new(path)));
+    fields.insert("mode".to_string(), Value::String(Arc::new(mode)));
+    fields.insert("read".to_string(), method("read", &state, handle_read));
+    fields.insert(
+        "readline".to_string(),
+        method("readline", &state, handle_readline),
+    );
+    fields.insert("write".to_string(), method("write", &state, handle_write));
+    fields.insert("seek".to_string(), method("seek", &state, handle_seek));
+    fields.insert("close".to_string(), method("close", &state, handle_close));
+
+    Ok(Value::StructInstance(Arc::new(StructInstance {
+        struct_type: FILE_TYPE.clone(),
+        fields,
+    })))
+}
+
+/// Binds a handle method to its file. Methods are stored as fields that
+/// `File` does not declare, so they are callable but left out of `repr`.
+fn method<F, Fut>(name: &str, state: &Arc<OpenFile>, f: F) -> Value
+where
+    F: Fn(Arc<OpenFile>, Vec<Value>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Value>> + Send + 'static,
+{
+    let state = state.clone();
+    Value::NativeFunction(Arc::new(NativeFunction::new(name, move |args, _kwargs| {
+        f(state.clone(), args)
+    })))
+}
+
+fn closed_error() -> BlueprintError {
+    BlueprintError::ValueError {
+        message: "I/O operation on closed file".into(),
+    }
+}
+
+async fn handle_read(state: Arc<OpenFile>, args: Vec<Value>) -> Result<Value> {
+    require_args_range("File.read", &args, 0, 1)?;
+    if !state.readable {
+        return Err(BlueprintError::ValueError {
+            message: format!("{} is not open for reading", state.path),
+        });
+    }
+    let limit = if args.is_empty() {
+        -1
+    } else {
+        get_int_arg("File.read", &args, 0)?
+    };
+
+    let mut guard = state.file.lock().await;
+    let stream = guard.as_mut().ok_or_else(closed_error)?;
+    let mut buf = std::mem::take(&mut stream.partial);
+    let read = if limit < 0 {
+        stream.reader.read_to_end(&mut buf).await
+    } else {
+        (&mut stream.reader)
+            .take(limit as u64)
+            .read_to_end(&mut buf)
+            .await
+    }
+    .map_err(|e| io_error(&state.path, e))?;
+
+    // A short read means end of file, where a cut-off character stays invalid.
+    if limit >= 0 && read == limit as usize {
+        let keep = incomplete_char_len(&buf);
+        stream.partial = buf.split_off(buf.len() - keep);
+    }
+    Ok(Value::String(Arc::new(
+        String::from_utf8_lossy(&buf).into_owned(),
+    )))
+}
+
+/// How many bytes at the end of `bytes` start a UTF-8 character that the
+/// following bytes have yet to complete.
+fn incomplete_char_len(bytes: &[u8]) -> usize {
+    for len in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - len];
+        if byte & 0xC0 == 0x80 {
+            continue;
+        }
+        let width = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if len < width { len } else { 0 };
+    }
+    0
+}
+
+async fn handle_readline(state: Arc<OpenFile>, args: Vec<Value>) -> Result<Value> {
+    require_args("File.readline", &args, 0)?;
+    if !state.readable {
+        return Err(BlueprintError::ValueError {
+            message: format!("{} is not open for reading", state.path),
+        });
+    }
+
+    let mut guard = state.file.lock().await;
+    let stream = guard.as_mut().ok_or_else(closed_error)?;
+    let mut line = std::mem::take(&mut stream.partial);
+    stream
+        .reader
+        .read_until(b'\n', &mut line)
+        .await
+        .map_err(|e| io_error(&state.path, e))?;
+
+    Ok(Value::String(Arc::new(
+        String::from_utf8_lossy(&line).into_owned(),
+    )))
+}
+
+async fn handle_write(state: Arc<OpenFile>, args: Vec<Value>) -> Result<Value> {
+    require_args("File.write", &args, 1)?;
+    if !state.writable {
+        return Err(BlueprintError::ValueError {
+            message: format!("{} is not open for writing", state.path),
+        });
+    }
+    let data = get_string_arg("File.write", &args, 0)?;
+
+    let mut guard = state.file.lock().await;
+    let stream = guard.as_mut().ok_or_else(closed_error)?;
+    stream.partial.clear();
+    // Drop read-ahead so the write lands at the position the script sees.
+    stream
+        .reader
+        .seek(SeekFrom::Current(0))
+        .await
+        .map_err(|e| io_error(&state.path, e))?;
+    let inner = stream.reader.get_mut();
+    inner
+        .write_all(data.as_bytes())
+        .await
+        .map_err(|e| io_error(&state.path, e))?;
+    inner.flush().await.map_err(|e| io_error(&state.path, e))?;
+
+    Ok(Value::Int(data.len() as i64))
+}
+
+async fn handle_seek(state: Arc<OpenFile>, args: Vec<Value>) -> Result<Value> {
+    require_args_range("File.seek", &args, 1, 2)?;
+    let offset = get_int_arg("File.seek", &args, 0)?;
+    let whence = if args.len() > 1 {
+        get_int_arg("File.seek", &args, 1)?
+    } else {
+        0
+    };
+    let target = match whence {
+        0 if offset >= 0 => SeekFrom::Start(offset as u64),
+        0 => {
+            return Err(BlueprintError::ValueError {
+                message: format!("negative seek position {}", offset),
+            })
+        }
+        1 => SeekFrom::Current(offset),
+        2 => SeekFrom::End(offset),
+        other => {
+            return Err(BlueprintError::ValueError {
+                message: format!("invalid whence ({}, should be 0, 1 or 2)", other),
+            })
+        }
+    };
+
+    let mut guard = state.file.lock().await;
+    let stream = guard.as_mut().ok_or_else(closed_error)?;
+    stream.partial.clear();
+    let position = stream
+        .reader
+        .seek(target)
+        .await
+        .map_err(|e| io_error(&state.path, e))?;
+
+    Ok(Value::Int(position as i64))
+}
+
+async fn handle_close(state: Arc<OpenFile>, args: Vec<Value>) -> Result<Value> {
+    require_args("File.close", &args, 0)?;
+    if let Some(mut stream) = state.file.lock().await.take() {
+        stream
+            .reader
+            .get_mut()
+            .flush()
+            .await
+            .map_err(|e| io_error(&state.path, e))?;
+    }
+    Ok(Value::None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(v: &str) -> Value {
+        Value::String(Arc::new(v.to_string()))
+    }
+
+    async fn call(handle: &Value, name: &str, args: Vec<Value>) -> Result<Value> {
+        match handle.get_attr(name) {
+            Some(Value::NativeFunction(f)) => f.call(args, HashMap::new()).await,
+            _ => panic!("handle has no method {}", name),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_handle_reads_writes_and_closes() {
+        let path = std::env::temp_dir()
+            .join(format!("bp_file_open_{}.txt", std::process::id()))
+            .display()
+            .to_string();
+
+        let out = open(vec![s(&path), s("w+")], HashMap::new()).await.unwrap();
+        assert!(out.repr().starts_with("File(path="));
+        call(&out, "write", vec![s("first\nsecond\n")])
+            .await
+            .unwrap();
+        call(&out, "seek", vec![Value::Int(0)]).await.unwrap();
+        assert_eq!(
+            call(&out, "readline", vec![]).await.unwrap().repr(),
+            r#""first\n""#
+        );
+        assert_eq!(
+            call(&out, "read", vec![Value::Int(3)])
+                .await
+                .unwrap()
+                .repr(),
+            r#""sec""#
+        );
+        call(&out, "close", vec![]).await.unwrap();
+        assert!(call(&out, "read", vec![]).await.is_err());
+
+        let appended = open(vec![s(&path), s("a")], HashMap::new()).await.unwrap();
+        call(&appended, "write", vec![s("third\n")]).await.unwrap();
+        assert!(call(&appended, "read", vec![]).await.is_err());
+        call(&appended, "close", vec![]).await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, "first\nsecond\nthird\n");
+        assert!(open(vec![s(&path), s("rw")], HashMap::new()).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_partial_reads_keep_split_characters() {
+        let path = std::env::temp_dir()
+            .join(format!("bp_file_utf8_{}.txt", std::process::id()))
+            .display()
+            .to_string();
+        std::fs::write(&path, "aé€\nz").unwrap();
+
+        let handle = open(vec![s(&path)], HashMap::new()).await.unwrap();
+        let read = |n: i64| call(&handle, "read", vec![Value::Int(n)]);
+        assert_eq!(read(2).await.unwrap().as_string().unwrap(), "a");
+        assert_eq!(read(2).await.unwrap().as_string().unwrap(), "é");
+        assert_eq!(read(1).await.unwrap().as_string().unwrap(), "");
+        assert_eq!(
+            call(&handle, "readline", vec![])
+                .await
+                .unwrap()
+                .as_string()
+                .unwrap(),
+            "€\n"
+        );
+        assert_eq!(read(-1).await.unwrap().as_string().unwrap(), "z");
+
+        // A character cut off by the end of the file is still replaced.
+        std::fs::write(&path, [b'a', 0xE2, 0x82]).unwrap();
+        let truncated = open(vec![s(&path)], HashMap::new()).await.unwrap();
+        let text = call(&truncated, "read", vec![Value::Int(10)])
+            .await
+            .unwrap();
+        assert_eq!(text.as_string().unwrap(), "a\u{fffd}");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// A handler that forwards each watch event's `path` and `kind`.
+    fn watch_handler() -> (
+        Value,
+        tokio::sync::mpsc::UnboundedReceiver<(String, String)>,
+    ) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let handler = NativeFunction::new_with_state("on_change", move |args, _kwargs| {
+            let tx = tx.clone();
+            Box::pin(async move {
+                let Value::Dict(event) = &args[0] else {
+                    panic!("watch event should be a dict");
+                };
+                let event = event.read().await;
+                let field = |key: &str| event.get(key).unwrap().as_string().unwrap();
+                let _ = tx.send((field("path"), field("kind")));
+                Ok(Value::None)
+            })
+        });
+        (Value::NativeFunction(Arc::new(handler)), rx)
+    }
+
+    async fn stop_trigger(handle: Value) {
+        let stop = crate::modules::triggers::get_functions()
+            .into_iter()
+            .find(|f| f.name == "stop")
+            .unwrap();
+        stop.call(vec![handle], HashMap::new()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watch_reports_each_changed_path_once_per_burst() {
+        let dir = std::env::temp_dir().join(format!("bp_file_watch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (handler, mut events) = watch_handler();
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("debounce".to_string(), Value::Float(0.2));
+        let handle = watch(vec![s(&dir.display().to_string()), handler], kwargs)
+            .await
+            .unwrap();
+        let Value::Dict(info) = &handle else {
+            panic!("watch should return a trigger handle");
+        };
+        assert_eq!(info.read().await.get("type").unwrap().repr(), r#""watch""#);
+
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "one").unwrap();
+        std::fs::write(&path, "two").unwrap();
+
+        let (changed, kind) = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("no watch event within 5 seconds")
+            .unwrap();
+        assert!(
+            changed.ends_with("notes.txt"),
+            "unexpected path {}",
+            changed
+        );
+        assert!(
+            ["created", "modified", "other"].contains(&kind.as_str()),
+            "unexpected kind {}",
+            kind
+        );
+
+        // Both writes fall in one burst, so the file is not reported again.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        while let Ok((changed, _)) = events.try_recv() {
+            assert!(
+                !changed.ends_with("notes.txt"),
+                "reported twice: {}",
+                changed
+            );
+        }
+
+        stop_trigger(handle).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watch_rejects_bad_arguments() {
+        let (handler, _events) = watch_handler();
+        let dir = std::env::temp_dir().display().to_string();
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("debounce".to_string(), Value::Float(-1.0));
+        let err = watch(vec![s(&dir), handler.clone()], kwargs)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("debounce"));
+
+        let missing = std::env::temp_dir()
+            .join(format!("bp_file_watch_missing_{}", std::process::id()))
+            .display()
+            .to_string();
+        let err = watch(vec![s(&missing), handler], HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BlueprintError::IoError { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_json_round_trip_and_error_names_file() {
+        let dir = std::env::temp_dir().join(format!("bp_file_json_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json").display().to_string();
+
+        let value = Value::from(vec![Value::from("a"), Value::Int(1)]);
+        let mut kwargs = HashMap::new();
+        kwargs.insert("indent".to_string(), Value::Int(2));
+        write_json(vec![s(&path), value], kwargs).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[\n  \"a\",\n  1\n]\n"
+        );
+        let read = read_json(vec![s(&path)], HashMap::new()).await.unwrap();
+        assert_eq!(read.repr(), r#"["a", 1]"#);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::write(&path, "{\"broken\": ").unwrap();
+        let err = read_json(vec![s(&path)], HashMap::new()).await.unwrap_err();
+        assert!(err.to_string().contains("config.json"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_atomic_write_replaces_file() {
+        let dir = std::env::temp_dir().join(format!("bp_file_atomic_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.conf").display().to_string();
+        std::fs::write(&path, "old contents that are longer").unwrap();
+
+        let mut kwargs = HashMap::new();
+        kwargs.insert("atomic".to_string(), Value::Bool(true));
+        kwargs.insert("fsync".to_string(), Value::Bool(true));
+        write_file(vec![s(&path), s("new")], kwargs).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stat_and_checksum() {
+        let path = std::env::temp_dir()
+            .join(format!("bp_file_stat_{}.txt", std::process::id()))
+            .display()
+            .to_string();
+        std::fs::write(&path, "abc").unwrap();
+
+        let info = stat(vec![s(&path)], HashMap::new()).await.unwrap();
+        assert!(matches!(info.get_attr("size"), Some(Value::Int(3))));
+        assert!(matches!(info.get_attr("is_file"), Some(Value::Bool(true))));
+
+        let sha = checksum(vec![s(&path)], HashMap::new()).await.unwrap();
+        assert_eq!(
+            sha.as_string().unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let md5 = checksum(vec![s(&path), s("md5")], HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(md5.as_string().unwrap(), "900150983cd24fb0d6963f7d28e17f72");
+
+        std::fs::remove_file(&path).unwrap();
+        let err = stat(vec![s(&path)], HashMap::new()).await.unwrap_err();
+        assert!(matches!(err, BlueprintError::IoError { .. }));
+    }
+}
@@ -0,0 +1,455 @@
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use blueprint_engine_core::{
+    validation::{get_arg, require_args},
+    BlueprintError, NativeFunction, Result, Value,
+};
+use tokio::sync::RwLock;
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![NativeFunction::new("parse", parse)]
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FlagType {
+    String,
+    Int,
+    Float,
+    Bool,
+    List,
+}
+
+impl FlagType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "string" | "str" => Some(FlagType::String),
+            "int" => Some(FlagType::Int),
+            "float" => Some(FlagType::Float),
+            "bool" => Some(FlagType::Bool),
+            "list" => Some(FlagType::List),
+            _ => None,
+        }
+    }
+
+    fn metavar(&self) -> &'static str {
+        match self {
+            FlagType::String | FlagType::List => "VALUE",
+            FlagType::Int => "INT",
+            FlagType::Float => "FLOAT",
+            FlagType::Bool => "",
+        }
+    }
+}
+
+struct FlagSpec {
+    name: String,
+    ty: FlagType,
+    default: Option<Value>,
+    help: String,
+    short: Option<char>,
+    positional: bool,
+    required: bool,
+}
+
+impl FlagSpec {
+    fn long(&self) -> String {
+        self.name.replace('_', "-")
+    }
+}
+
+enum FlagsError {
+    Help,
+    Invalid(String),
+}
+
+async fn parse(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("flags.parse", &args, 2)?;
+
+    let specs = parse_spec(get_arg("flags.parse", &args, 0)?).await?;
+
+    let argv: Vec<String> = match get_arg("flags.parse", &args, 1)? {
+        Value::List(l) => l
+            .read()
+            .await
+            .iter()
+            .map(|v| v.to_display_string())
+            .collect(),
+        other => {
+            return Err(BlueprintError::TypeError {
+                expected: "list".into(),
+                actual: other.type_name().into(),
+            })
+        }
+    };
+
+    let prog = match kwargs.get("prog") {
+        Some(v) => v.to_display_string(),
+        None => argv
+            .first()
+            .map(|p| {
+                std::path::Path::new(p)
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_else(|| p.clone())
+            })
+            .unwrap_or_else(|| "script".to_string()),
+    };
+    let description = kwargs.get("description").map(|v| v.to_display_string());
+
+    let tokens = argv.get(1..).unwrap_or_default();
+    match parse_tokens(&specs, tokens) {
+        Ok(values) => Ok(Value::Dict(Arc::new(RwLock::new(values)))),
+        Err(FlagsError::Help) => {
+            println!("{}", usage(&prog, description.as_deref(), &specs));
+            Err(BlueprintError::Exit { code: 0 })
+        }
+        Err(FlagsError::Invalid(message)) => {
+            eprintln!("{}", usage(&prog, description.as_deref(), &specs));
+            eprintln!("{}: error: {}", prog, message);
+            Err(BlueprintError::Exit { code: 2 })
+        }
+    }
+}
+
+async fn parse_spec(spec: &Value) -> Result<Vec<FlagSpec>> {
+    let entries = match spec {
+        Value::Dict(d) => d.read().await.clone(),
+        other => {
+            return Err(BlueprintError::TypeError {
+                expected: "dict".into(),
+                actual: other.type_name().into(),
+            })
+        }
+    };
+
+    let mut specs = Vec::with_capacity(entries.len());
+    for (name, options) in entries {
+        let invalid = |message: String| BlueprintError::ArgumentError {
+            message: format!("flags.parse(): option '{}': {}", name, message),
+        };
+
+        let options = match options {
+            Value::String(ty) => {
+                let mut map = IndexMap::new();
+                map.insert("type".to_string(), Value::String(ty));
+                map
+            }
+            Value::Dict(d) => d.read().await.clone(),
+            other => return Err(invalid(format!("expected dict, got {}", other.type_name()))),
+        };
+
+        let default = options.get("default").cloned();
+        let ty = match options.get("type") {
+            Some(v) => {
+                let ty = v.to_display_string();
+                FlagType::from_name(&ty).ok_or_else(|| invalid(format!("unknown type '{}'", ty)))?
+            }
+            None => match &default {
+                Some(Value::Bool(_)) => FlagType::Bool,
+                Some(Value::Int(_)) => FlagType::Int,
+                Some(Value::Float(_)) => FlagType::Float,
+                Some(Value::List(_)) => FlagType::List,
+                _ => FlagType::String,
+            },
+        };
+
+        let short = match options.get("short") {
+            Some(v) => {
+                let s = v.to_display_string();
+                let mut chars = s.trim_start_matches('-').chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => {
+                        return Err(invalid(format!(
+                            "short name must be one character: '{}'",
+                            s
+                        )))
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let positional = options
+            .get("positional")
+            .map(|v| v.is_truthy())
+            .unwrap_or(false);
+        let required = options
+            .get("required")
+            .map(|v| v.is_truthy())
+            .unwrap_or(positional && default.is_none() && ty != FlagType::List);
+
+        specs.push(FlagSpec {
+            name,
+            ty,
+            default,
+            help: options
+                .get("help")
+                .map(|v| v.to_display_string())
+                .unwrap_or_default(),
+            short,
+            positional,
+            required,
+        });
+    }
+
+    Ok(specs)
+}
+
+fn parse_tokens(
+    specs: &[FlagSpec],
+    tokens: &[String],
+) -> std::result::Result<IndexMap<String, Value>, FlagsError> {
+    let mut values: IndexMap<String, Value> = IndexMap::new();
+    let mut lists: HashMap<String, Vec<Value>> = HashMap::new();
+    let mut positionals: Vec<&String> = Vec::new();
+    let mut only_positional = false;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let token = &tokens[i];
+        i += 1;
+
+        if only_positional || token == "-" || !token.starts_with('-') || is_number(token) {
+            positionals.push(token);
+            continue;
+        }
+        if token == "--" {
+            only_positional = true;
+            continue;
+        }
+        if token == "-h" || token == "--help" {
+            return Err(FlagsError::Help);
+        }
+
+        let (spec, inline, negated) = if let Some(long) = token.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let key = name.replace('-', "_");
+            match find_option(specs, |s| s.name == key) {
+                Some(spec) => (spec, inline, false),
+                None => {
+                    let negated = key
+                        .strip_prefix("no_")
+                        .and_then(|k| find_option(specs, |s| s.name == k))
+                        .filter(|s| s.ty == FlagType::Bool);
+                    match negated {
+                        Some(spec) if inline.is_none() => (spec, None, true),
+                        _ => {
+                            return Err(FlagsError::Invalid(format!("unknown option '{}'", token)))
+                        }
+                    }
+                }
+            }
+        } else {
+            let mut chars = token[1..].chars();
+            let c = chars.next().unwrap_or_default();
+            let rest: String = chars.collect();
+            let spec = find_option(specs, |s| s.short == Some(c))
+                .ok_or_else(|| FlagsError::Invalid(format!("unknown option '-{}'", c)))?;
+            let inline = if rest.is_empty() {
+                None
+            } else {
+                Some(rest.trim_start_matches('=').to_string())
+            };
+            (spec, inline, false)
+        };
+
+        if spec.ty == FlagType::Bool {
+            let value = match inline {
+                Some(raw) => parse_bool(&raw).ok_or_else(|| {
+                    FlagsError::Invalid(format!("option --{}: invalid bool '{}'", spec.long(), raw))
+                })?,
+                None => !negated,
+            };
+            values.insert(spec.name.clone(), Value::Bool(value));
+            continue;
+        }
+
+        let raw = match inline {
+            Some(raw) => raw,
+            None => match tokens.get(i) {
+                Some(next) => {
+                    i += 1;
+                    next.clone()
+                }
+                None => {
+                    return Err(FlagsError::Invalid(format!(
+                        "option --{} requires a value",
+                        spec.long()
+                    )))
+                }
+            },
+        };
+
+        let value = convert(spec, &raw)?;
+        if spec.ty == FlagType::List {
+            lists.entry(spec.name.clone()).or_default().push(value);
+        } else {
+            values.insert(spec.name.clone(), value);
+        }
+    }
+
+    let mut remaining = positionals.into_iter();
+    for spec in specs.iter().filter(|s| s.positional) {
+        if spec.ty == FlagType::List {
+            let items = remaining
+                .by_ref()
+                .map(|raw| convert(spec, raw))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            if !items.is_empty() {
+                lists.insert(spec.name.clone(), items);
+            }
+        } else if let Some(raw) = remaining.next() {
+            values.insert(spec.name.clone(), convert(spec, raw)?);
+        }
+    }
+    if let Some(extra) = remaining.next() {
+        return Err(FlagsError::Invalid(format!(
+            "unexpected argument '{}'",
+            extra
+        )));
+    }
+
+    let mut result = IndexMap::with_capacity(specs.len());
+    for spec in specs {
+        let value = if let Some(items) = lists.remove(&spec.name) {
+            Value::List(Arc::new(RwLock::new(items)))
+        } else if let Some(value) = values.shift_remove(&spec.name) {
+            value
+        } else if spec.required {
+            return Err(FlagsError::Invalid(if spec.positional {
+                format!("missing required argument '{}'", spec.name)
+            } else {
+                format!("missing required option --{}", spec.long())
+            }));
+        } else if let Some(default) = &spec.default {
+            default.clone()
+        } else {
+            match spec.ty {
+                FlagType::Bool => Value::Bool(false),
+                FlagType::List => Value::List(Arc::new(RwLock::new(Vec::new()))),
+                _ => Value::None,
+            }
+        };
+        result.insert(spec.name.clone(), value);
+    }
+
+    Ok(result)
+}
+
+fn find_option(specs: &[FlagSpec], pred: impl Fn(&FlagSpec) -> bool) -> Option<&FlagSpec> {
+    specs.iter().find(|s| !s.positional && pred(s))
+}
+
+fn is_number(token: &str) -> bool {
+    token.len() > 1 && token[1..].parse::<f64>().is_ok()
+}
+
+fn parse_bool(raw: &str) -> Option<bool> {
+    match raw.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn convert(spec: &FlagSpec, raw: &str) -> std::result::Result<Value, FlagsError> {
+    let invalid = |kind: &str| {
+        let label = if spec.positional {
+            format!("argument '{}'", spec.name)
+        } else {
+            format!("option --{}", spec.long())
+        };
+        FlagsError::Invalid(format!("{}: invalid {} value '{}'", label, kind, raw))
+    };
+
+    match spec.ty {
+        FlagType::String | FlagType::List => Ok(Value::String(Arc::new(raw.to_string()))),
+        FlagType::Int => raw.parse().map(Value::Int).map_err(|_| invalid("int")),
+        FlagType::Float => raw.parse().map(Value::Float).map_err(|_| invalid("float")),
+        FlagType::Bool => parse_bool(raw)
+            .map(Value::Bool)
+            .ok_or_else(|| invalid("bool")),
+    }
+}
+
+fn usage(prog: &str, description: Option<&str>, specs: &[FlagSpec]) -> String {
+    let mut synopsis = format!("usage: {} [options]", prog);
+    for spec in specs.iter().filter(|s| s.positional) {
+        let name = if spec.ty == FlagType::List {
+            format!("{}...", spec.name)
+        } else {
+            spec.name.clone()
+        };
+        if spec.required {
+            synopsis.push_str(&format!(" <{}>", name));
+        } else {
+            synopsis.push_str(&format!(" [{}]", name));
+        }
+    }
+
+    let mut rows: Vec<(String, String)> = Vec::new();
+    let mut positional_rows: Vec<(String, String)> = Vec::new();
+    for spec in specs {
+        let mut help = spec.help.clone();
+        if let Some(default) = &spec.default {
+            if !help.is_empty() {
+                help.push(' ');
+            }
+            help.push_str(&format!("(default: {})", default.repr()));
+        }
+
+        if spec.positional {
+            positional_rows.push((spec.name.clone(), help));
+            continue;
+        }
+
+        let mut label = match spec.short {
+            Some(c) => format!("-{}, --{}", c, spec.long()),
+            None => format!("    --{}", spec.long()),
+        };
+        if spec.ty != FlagType::Bool {
+            label.push(' ');
+            label.push_str(spec.ty.metavar());
+        }
+        if spec.required {
+            help = format!("(required) {}", help).trim_end().to_string();
+        }
+        rows.push((label, help));
+    }
+    rows.push((
+        "-h, --help".to_string(),
+        "show this help message and exit".to_string(),
+    ));
+
+    let width = rows
+        .iter()
+        .chain(positional_rows.iter())
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut out = synopsis;
+    if let Some(description) = description {
+        out.push_str("\n\n");
+        out.push_str(description);
+    }
+    if !positional_rows.is_empty() {
+        out.push_str("\n\narguments:");
+        for (label, help) in &positional_rows {
+            let row = format!("\n  {:width$}  {}", label, help, width = width);
+            out.push_str(row.trim_end());
+        }
+    }
+    out.push_str("\n\noptions:");
+    for (label, help) in &rows {
+        let row = format!("\n  {:width$}  {}", label, help, width = width);
+        out.push_str(row.trim_end());
+    }
+    out
+}
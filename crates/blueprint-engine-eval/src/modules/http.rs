@@ -1,19 +1,25 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use axum::{body::Body, http::Request, Router};
 use blueprint_engine_core::{
-    check_fs_write, check_http,
+    check_fs_write, check_http, check_net_listen, session,
     validation::{get_string_arg, require_args, require_args_range},
-    BlueprintError, HttpResponse, NativeFunction, Result, StreamIterator, Value,
+    with_session, BlueprintError, HttpResponse, NativeFunction, Result, StreamIterator, Value,
 };
 use futures_util::StreamExt;
+use indexmap::IndexMap;
 use reqwest::Client;
-use tokio::sync::mpsc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, RwLock};
+
+use super::triggers::{execute_http_handler, spawn_http_server};
 
 pub fn get_functions() -> Vec<NativeFunction> {
     vec![
         NativeFunction::new("http_request", http_request),
         NativeFunction::new("download", download),
+        NativeFunction::new("serve", serve),
     ]
 }
 
@@ -165,43 +171,121 @@ async fn stream_request(
     Ok(())
 }
 
-async fn download(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+async fn download(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
     require_args("http.download", &args, 2)?;
     let url = get_string_arg("http.download", &args, 0)?;
     let path = get_string_arg("http.download", &args, 1)?;
     check_http(&url).await?;
     check_fs_write(&path).await?;
 
-    let response = reqwest::get(&url)
+    let headers = match kwargs.get("headers") {
+        Some(h) => extract_headers(h).await?,
+        None => HashMap::new(),
+    };
+    let timeout = kwargs.get("timeout").and_then(|v| v.as_float().ok());
+    let resume = kwargs.get("resume").map(|v| v.is_truthy()).unwrap_or(false);
+
+    let existing_len = if resume {
+        tokio::fs::metadata(&path)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0)
+    } else {
+        0
+    };
+
+    let mut builder = Client::builder();
+    if let Some(secs) = timeout {
+        builder = builder.timeout(std::time::Duration::from_secs_f64(secs));
+    }
+    let client = builder.build().map_err(|e| BlueprintError::HttpError {
+        url: url.clone(),
+        message: e.to_string(),
+    })?;
+
+    let mut request = client.get(&url);
+    for (key, value) in headers {
+        request = request.header(&key, &value);
+    }
+    if existing_len > 0 {
+        request = request.header("Range", format!("bytes={}-", existing_len));
+    }
+
+    let response = request
+        .send()
         .await
         .map_err(|e| BlueprintError::HttpError {
             url: url.clone(),
             message: e.to_string(),
         })?;
 
+    let status = response.status().as_u16();
+
+    // 416 on a resumed request means the local file already holds the whole body
+    if existing_len > 0 && status == 416 {
+        return Ok(download_result(status, 0, existing_len));
+    }
+
     if !response.status().is_success() {
         return Err(BlueprintError::HttpError {
             url: url.clone(),
-            message: format!("HTTP {}", response.status().as_u16()),
+            message: format!("HTTP {}", status),
         });
     }
 
-    let bytes = response
-        .bytes()
+    let append = existing_len > 0 && status == 206;
+    let mut options = tokio::fs::OpenOptions::new();
+    options.create(true);
+    if append {
+        options.append(true);
+    } else {
+        options.write(true).truncate(true);
+    }
+    let mut file = options
+        .open(&path)
         .await
-        .map_err(|e| BlueprintError::HttpError {
-            url: url.clone(),
+        .map_err(|e| BlueprintError::IoError {
+            path: path.clone(),
             message: e.to_string(),
         })?;
 
-    tokio::fs::write(&path, &bytes)
-        .await
-        .map_err(|e| BlueprintError::IoError {
-            path: path.clone(),
+    let mut stream = response.bytes_stream();
+    let mut written: u64 = 0;
+
+    while let Some(chunk_result) = stream.next().await {
+        let chunk = chunk_result.map_err(|e| BlueprintError::HttpError {
+            url: url.clone(),
             message: e.to_string(),
         })?;
 
-    Ok(Value::None)
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| BlueprintError::IoError {
+                path: path.clone(),
+                message: e.to_string(),
+            })?;
+        written += chunk.len() as u64;
+    }
+
+    file.flush().await.map_err(|e| BlueprintError::IoError {
+        path: path.clone(),
+        message: e.to_string(),
+    })?;
+
+    let total = if append {
+        existing_len + written
+    } else {
+        written
+    };
+    Ok(download_result(status, written, total))
+}
+
+fn download_result(status: u16, written: u64, total: u64) -> Value {
+    let mut result = IndexMap::new();
+    result.insert("status".to_string(), Value::Int(status as i64));
+    result.insert("bytes".to_string(), Value::Int(written as i64));
+    result.insert("size".to_string(), Value::Int(total as i64));
+    Value::Dict(Arc::new(RwLock::new(result)))
 }
 
 async fn extract_headers(value: &Value) -> Result<HashMap<String, String>> {
@@ -269,23 +353,63 @@ async fn make_request(
 
     let status = response.status().as_u16() as i64;
 
-    let resp_headers: HashMap<String, String> = response
+    let resp_headers: Vec<(String, String)> = response
         .headers()
         .iter()
         .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
         .collect();
 
-    let body_text = response
-        .text()
+    let body = response
+        .bytes()
         .await
         .map_err(|e| BlueprintError::HttpError {
             url: url.into(),
             message: e.to_string(),
         })?;
 
-    Ok(Value::Response(Arc::new(HttpResponse {
+    Ok(Value::Response(Arc::new(HttpResponse::new(
         status,
-        body: body_text,
-        headers: resp_headers,
-    })))
+        body.to_vec(),
+        resp_headers,
+    ))))
+}
+
+/// Serves every request with `handler(request)`. The server runs as an `http`
+/// trigger, so the script stays alive until it is stopped.
+async fn serve(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("http.serve", &args, 2)?;
+    let port = args[0].as_int()?;
+    let port = u16::try_from(port).map_err(|_| BlueprintError::ValueError {
+        message: format!(
+            "http.serve() port must be between 0 and 65535, got {}",
+            port
+        ),
+    })?;
+
+    let handler = args[1].clone();
+    if !matches!(
+        handler,
+        Value::Function(_) | Value::Lambda(_) | Value::NativeFunction(_)
+    ) {
+        return Err(BlueprintError::TypeError {
+            expected: "callable handler".into(),
+            actual: handler.type_name().into(),
+        });
+    }
+
+    let host = match kwargs.get("host") {
+        Some(v) => v.as_string()?,
+        None => "0.0.0.0".to_string(),
+    };
+    check_net_listen(&format!("{}:{}", host, port)).await?;
+
+    let handler_session = session();
+    let router = Router::new().fallback(move |req: Request<Body>| {
+        let handler = handler.clone();
+        with_session(handler_session.clone(), async move {
+            execute_http_handler(handler, req).await
+        })
+    });
+
+    spawn_http_server(host, port, router, vec!["*".to_string()]).await
 }
@@ -1,13 +1,14 @@
-use indexmap::IndexMap;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+pub(crate) use blueprint_engine_core::json_to_value;
 use blueprint_engine_core::{
     validation::{get_arg, require_args},
     BlueprintError, NativeFunction, Result, Value,
 };
 use serde_json;
-use tokio::sync::RwLock;
+
+use super::builtins::call_func;
 
 pub fn get_functions() -> Vec<NativeFunction> {
     vec![
@@ -28,28 +29,39 @@ async fn json_encode(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result
         .and_then(|v| v.as_int().ok())
         .map(|i| i as usize);
 
-    let json_value = value_to_json(get_arg("json.encode", &args, 0)?).await?;
+    let default = kwargs.get("default").filter(|v| !v.is_none());
+
+    let json_value = value_to_json(get_arg("json.encode", &args, 0)?, default).await?;
 
-    let json_str = if let Some(spaces) = indent {
+    Ok(Value::String(Arc::new(json_to_string(
+        &json_value,
+        indent,
+    )?)))
+}
+
+/// Serializes compactly, or pretty-printed with `indent` spaces per level.
+pub(crate) fn json_to_string(
+    json_value: &serde_json::Value,
+    indent: Option<usize>,
+) -> Result<String> {
+    if let Some(spaces) = indent {
         let buf = Vec::new();
         let indent_bytes = vec![b' '; spaces];
         let formatter = serde_json::ser::PrettyFormatter::with_indent(&indent_bytes);
         let mut ser = serde_json::Serializer::with_formatter(buf, formatter);
-        serde::Serialize::serialize(&json_value, &mut ser).map_err(|e| {
+        serde::Serialize::serialize(json_value, &mut ser).map_err(|e| {
             BlueprintError::JsonError {
                 message: e.to_string(),
             }
         })?;
         String::from_utf8(ser.into_inner()).map_err(|e| BlueprintError::JsonError {
             message: e.to_string(),
-        })?
+        })
     } else {
-        serde_json::to_string(&json_value).map_err(|e| BlueprintError::JsonError {
+        serde_json::to_string(json_value).map_err(|e| BlueprintError::JsonError {
             message: e.to_string(),
-        })?
-    };
-
-    Ok(Value::String(Arc::new(json_str)))
+        })
+    }
 }
 
 async fn json_decode(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
@@ -65,7 +77,12 @@ async fn json_decode(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resul
     json_to_value(json_value)
 }
 
-async fn value_to_json(value: &Value) -> Result<serde_json::Value> {
+/// Converts `value` to JSON. Values with no JSON form are passed to `default`,
+/// whose result must itself be serializable without it.
+pub(crate) async fn value_to_json(
+    value: &Value,
+    default: Option<&Value>,
+) -> Result<serde_json::Value> {
     match value {
         Value::None => Ok(serde_json::Value::Null),
         Value::Bool(b) => Ok(serde_json::Value::Bool(*b)),
@@ -84,14 +101,14 @@ async fn value_to_json(value: &Value) -> Result<serde_json::Value> {
             let items = l.read().await;
             let mut arr = Vec::with_capacity(items.len());
             for item in items.iter() {
-                arr.push(Box::pin(value_to_json(item)).await?);
+                arr.push(Box::pin(value_to_json(item, default)).await?);
             }
             Ok(serde_json::Value::Array(arr))
         }
         Value::Tuple(t) => {
             let mut arr = Vec::with_capacity(t.len());
             for item in t.iter() {
-                arr.push(Box::pin(value_to_json(item)).await?);
+                arr.push(Box::pin(value_to_json(item, default)).await?);
             }
             Ok(serde_json::Value::Array(arr))
         }
@@ -99,45 +116,50 @@ async fn value_to_json(value: &Value) -> Result<serde_json::Value> {
             let map = d.read().await;
             let mut obj = serde_json::Map::with_capacity(map.len());
             for (k, v) in map.iter() {
-                obj.insert(k.clone(), Box::pin(value_to_json(v)).await?);
+                obj.insert(k.clone(), Box::pin(value_to_json(v, default)).await?);
             }
             Ok(serde_json::Value::Object(obj))
         }
-        _ => Err(BlueprintError::JsonError {
-            message: format!("Cannot serialize {} to JSON", value.type_name()),
-        }),
-    }
-}
-
-fn json_to_value(json: serde_json::Value) -> Result<Value> {
-    match json {
-        serde_json::Value::Null => Ok(Value::None),
-        serde_json::Value::Bool(b) => Ok(Value::Bool(b)),
-        serde_json::Value::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                Ok(Value::Int(i))
-            } else if let Some(f) = n.as_f64() {
-                Ok(Value::Float(f))
-            } else {
-                Err(BlueprintError::JsonError {
-                    message: "Invalid JSON number".into(),
-                })
+        Value::Iterator(it) if it.is_view() => {
+            let items = it.iter().await.collect().await?;
+            let mut arr = Vec::with_capacity(items.len());
+            for item in items.iter() {
+                arr.push(Box::pin(value_to_json(item, default)).await?);
             }
+            Ok(serde_json::Value::Array(arr))
         }
-        serde_json::Value::String(s) => Ok(Value::String(Arc::new(s))),
-        serde_json::Value::Array(arr) => {
-            let mut items = Vec::with_capacity(arr.len());
-            for item in arr {
-                items.push(json_to_value(item)?);
+        Value::Set(s) => {
+            let items = s.read().await;
+            let mut arr = Vec::with_capacity(items.len());
+            for item in items.iter() {
+                arr.push(Box::pin(value_to_json(item, default)).await?);
             }
-            Ok(Value::List(Arc::new(RwLock::new(items))))
+            Ok(serde_json::Value::Array(arr))
         }
-        serde_json::Value::Object(obj) => {
-            let mut map = IndexMap::with_capacity(obj.len());
-            for (k, v) in obj {
-                map.insert(k, json_to_value(v)?);
+        Value::StructInstance(s) => {
+            let fields = s.to_dict();
+            let mut obj = serde_json::Map::with_capacity(fields.len());
+            for (k, v) in fields.iter() {
+                obj.insert(k.clone(), Box::pin(value_to_json(v, default)).await?);
             }
-            Ok(Value::Dict(Arc::new(RwLock::new(map))))
+            Ok(serde_json::Value::Object(obj))
         }
+        _ => match default {
+            Some(func) => {
+                let replacement = call_func(func, vec![value.clone()]).await?;
+                Box::pin(value_to_json(&replacement, None))
+                    .await
+                    .map_err(|e| BlueprintError::JsonError {
+                        message: format!(
+                            "default() returned a value that cannot be serialized for {}: {}",
+                            value.type_name(),
+                            e
+                        ),
+                    })
+            }
+            None => Err(BlueprintError::JsonError {
+                message: format!("Cannot serialize {} to JSON", value.type_name()),
+            }),
+        },
     }
 }
@@ -0,0 +1,496 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::{routing::get, Router};
+use blueprint_engine_core::{
+    check_net_listen,
+    validation::{get_float_arg, get_string_arg, require_args, require_args_range},
+    BlueprintError, NativeFunction, Result, StructField, StructInstance, StructType,
+    TypeAnnotation, Value,
+};
+use indexmap::IndexMap;
+use tokio::sync::RwLock;
+
+use super::time::current_time;
+use super::triggers::spawn_http_server;
+
+const DEFAULT_STATSD_ADDRESS: &str = "127.0.0.1:8125";
+const DEFAULT_PROMETHEUS_PORT: i64 = 9102;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Counter,
+    Gauge,
+    Timer,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Timer => "timer",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Metric {
+    kind: Option<Kind>,
+    value: f64,
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+/// Where updates go as they happen. Prometheus is scraped instead, from
+/// whatever `snapshot` would return at that moment.
+enum Sink {
+    None,
+    Stdout,
+    Statsd(UdpSocket),
+}
+
+struct Registry {
+    metrics: IndexMap<String, Metric>,
+    /// Start times of running timers by name, innermost last.
+    started: HashMap<String, Vec<f64>>,
+    sink: Sink,
+}
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+        metrics: IndexMap::new(),
+        started: HashMap::new(),
+        sink: Sink::None,
+    });
+    static ref COUNTER_TYPE: Arc<StructType> =
+        handle_type("Counter", vec![method("inc", counter_inc), method("value", value)]);
+    static ref GAUGE_TYPE: Arc<StructType> = handle_type(
+        "Gauge",
+        vec![
+            method("set", gauge_set),
+            method("inc", gauge_inc),
+            method("dec", gauge_dec),
+            method("value", value),
+        ],
+    );
+    static ref TIMER_TYPE: Arc<StructType> = handle_type(
+        "Timer",
+        vec![
+            method("__enter__", timer_enter),
+            method("__exit__", timer_exit),
+            method("observe", timer_observe),
+        ],
+    );
+}
+
+/// Metric handles are structs with a single `name` field, so `using()` and
+/// attribute access find their methods like those of any other struct.
+fn handle_type(name: &str, methods: Vec<(String, Arc<NativeFunction>)>) -> Arc<StructType> {
+    Arc::new(StructType {
+        name: name.into(),
+        fields: vec![StructField {
+            name: "name".into(),
+            typ: TypeAnnotation::Simple("str".into()),
+            default: None,
+        }],
+        methods: methods.into_iter().collect(),
+    })
+}
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("counter", counter),
+        NativeFunction::new("gauge", gauge),
+        NativeFunction::new("timer", timer),
+        NativeFunction::new("snapshot", snapshot),
+        NativeFunction::new("reset", reset),
+        NativeFunction::new("set_sink", set_sink),
+    ]
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Applies one update to the metric called `name`, creating it on first use,
+/// and forwards it to the active sink.
+fn record(name: &str, kind: Kind, update: Update) -> Result<f64> {
+    let mut registry = registry();
+    let metric = registry.metrics.entry(name.to_string()).or_default();
+    match metric.kind {
+        Some(existing) if existing != kind => {
+            return Err(BlueprintError::ValueError {
+                message: format!("metric '{}' is already a {}", name, existing.name()),
+            });
+        }
+        _ => metric.kind = Some(kind),
+    }
+
+    let line = match update {
+        Update::Register => return Ok(metric.value),
+        Update::Add(n) => {
+            metric.value += n;
+            match kind {
+                Kind::Counter => format!("{}:{}|c", name, n),
+                _ => format!("{}:{}|g", name, metric.value),
+            }
+        }
+        Update::Set(v) => {
+            metric.value = v;
+            format!("{}:{}|g", name, v)
+        }
+        Update::Observe(seconds) => {
+            if metric.count == 0 || seconds < metric.min {
+                metric.min = seconds;
+            }
+            if metric.count == 0 || seconds > metric.max {
+                metric.max = seconds;
+            }
+            metric.count += 1;
+            metric.value += seconds;
+            format!("{}:{}|ms", name, seconds * 1000.0)
+        }
+    };
+    let value = metric.value;
+
+    let print = match &registry.sink {
+        Sink::None => false,
+        Sink::Stdout => true,
+        Sink::Statsd(socket) => {
+            let _ = socket.send(line.as_bytes());
+            false
+        }
+    };
+    // Printing can block on a slow terminal; other threads must not wait on it.
+    drop(registry);
+    if print {
+        println!("{}", line);
+    }
+    Ok(value)
+}
+
+enum Update {
+    Register,
+    Add(f64),
+    Set(f64),
+    Observe(f64),
+}
+
+/// A native method on a metric handle, called with the handle's metric name
+/// and the remaining arguments; metric updates never need to await.
+fn method(name: &str, f: fn(&str, &[Value]) -> Result<Value>) -> (String, Arc<NativeFunction>) {
+    let function = NativeFunction::new_with_state(name, move |args, _kwargs| {
+        let result = match args.split_first() {
+            Some((Value::StructInstance(handle), rest)) => match handle.get_field("name") {
+                Some(Value::String(metric)) => f(metric.as_str(), rest),
+                _ => Err(handle_error(&args[0])),
+            },
+            Some((other, _)) => Err(handle_error(other)),
+            None => Err(BlueprintError::ArgumentError {
+                message: "metric method called without a handle".into(),
+            }),
+        };
+        Box::pin(async move { result })
+    });
+    (name.to_string(), Arc::new(function))
+}
+
+fn handle_error(value: &Value) -> BlueprintError {
+    BlueprintError::TypeError {
+        expected: "metric handle".into(),
+        actual: value.type_name().into(),
+    }
+}
+
+fn handle(struct_type: &Arc<StructType>, name: &str) -> Value {
+    let mut fields: IndexMap<String, Value> = IndexMap::new();
+    fields.insert("name".into(), Value::String(Arc::new(name.to_string())));
+    Value::StructInstance(Arc::new(StructInstance {
+        struct_type: struct_type.clone(),
+        fields,
+    }))
+}
+
+/// Reads an optional amount argument, defaulting to 1.
+fn amount(method: &str, args: &[Value]) -> Result<f64> {
+    require_args_range(method, args, 0, 1)?;
+    match args.first() {
+        Some(_) => get_float_arg(method, args, 0),
+        None => Ok(1.0),
+    }
+}
+
+fn name_arg(function: &str, args: &[Value]) -> Result<String> {
+    require_args(function, args, 1)?;
+    let name = get_string_arg(function, args, 0)?;
+    if name.is_empty() {
+        return Err(BlueprintError::ValueError {
+            message: format!("{}() name must not be empty", function),
+        });
+    }
+    Ok(name)
+}
+
+fn value(metric: &str, args: &[Value]) -> Result<Value> {
+    require_args("value", args, 0)?;
+    let registry = registry();
+    Ok(Value::Float(
+        registry.metrics.get(metric).map_or(0.0, |m| m.value),
+    ))
+}
+
+async fn counter(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    let name = name_arg("metrics.counter", &args)?;
+    record(&name, Kind::Counter, Update::Register)?;
+    Ok(handle(&COUNTER_TYPE, &name))
+}
+
+fn counter_inc(metric: &str, args: &[Value]) -> Result<Value> {
+    let n = amount("inc", args)?;
+    if n < 0.0 {
+        return Err(BlueprintError::ValueError {
+            message: format!("counter '{}' can only increase, got inc({})", metric, n),
+        });
+    }
+    record(metric, Kind::Counter, Update::Add(n)).map(Value::Float)
+}
+
+async fn gauge(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    let name = name_arg("metrics.gauge", &args)?;
+    record(&name, Kind::Gauge, Update::Register)?;
+    Ok(handle(&GAUGE_TYPE, &name))
+}
+
+fn gauge_set(metric: &str, args: &[Value]) -> Result<Value> {
+    require_args("set", args, 1)?;
+    let v = get_float_arg("set", args, 0)?;
+    record(metric, Kind::Gauge, Update::Set(v)).map(Value::Float)
+}
+
+fn gauge_inc(metric: &str, args: &[Value]) -> Result<Value> {
+    let n = amount("inc", args)?;
+    record(metric, Kind::Gauge, Update::Add(n)).map(Value::Float)
+}
+
+fn gauge_dec(metric: &str, args: &[Value]) -> Result<Value> {
+    let n = amount("dec", args)?;
+    record(metric, Kind::Gauge, Update::Add(-n)).map(Value::Float)
+}
+
+/// A timer is a resource for `with`: `__enter__` starts the clock and
+/// `__exit__` records the elapsed seconds. `observe(seconds)` records a
+/// duration measured some other way.
+async fn timer(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    let name = name_arg("metrics.timer", &args)?;
+    record(&name, Kind::Timer, Update::Register)?;
+    Ok(handle(&TIMER_TYPE, &name))
+}
+
+fn timer_enter(metric: &str, _args: &[Value]) -> Result<Value> {
+    registry()
+        .started
+        .entry(metric.to_string())
+        .or_default()
+        .push(current_time());
+    Ok(Value::None)
+}
+
+fn timer_exit(metric: &str, _args: &[Value]) -> Result<Value> {
+    let start = registry()
+        .started
+        .get_mut(metric)
+        .and_then(|starts| starts.pop());
+    let Some(start) = start else {
+        return Err(BlueprintError::ValueError {
+            message: format!("timer '{}' was not started", metric),
+        });
+    };
+    let elapsed = (current_time() - start).max(0.0);
+    record(metric, Kind::Timer, Update::Observe(elapsed))?;
+    Ok(Value::Float(elapsed))
+}
+
+fn timer_observe(metric: &str, args: &[Value]) -> Result<Value> {
+    require_args("observe", args, 1)?;
+    let seconds = get_float_arg("observe", args, 0)?;
+    record(metric, Kind::Timer, Update::Observe(seconds))?;
+    Ok(Value::None)
+}
+
+/// Current values by name: a number for counters and gauges, and a dict of
+/// `count`, `sum`, `min` and `max` seconds for timers.
+async fn snapshot(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("metrics.snapshot", &args, 0)?;
+    let registry = registry();
+    let mut out: IndexMap<String, Value> = IndexMap::new();
+    for (name, metric) in &registry.metrics {
+        let value = match metric.kind {
+            Some(Kind::Timer) => {
+                let mut stats = IndexMap::new();
+                stats.insert("count".to_string(), Value::Int(metric.count as i64));
+                stats.insert("sum".to_string(), Value::Float(metric.value));
+                stats.insert("min".to_string(), Value::Float(metric.min));
+                stats.insert("max".to_string(), Value::Float(metric.max));
+                Value::Dict(Arc::new(RwLock::new(stats)))
+            }
+            _ => Value::Float(metric.value),
+        };
+        out.insert(name.clone(), value);
+    }
+    Ok(Value::Dict(Arc::new(RwLock::new(out))))
+}
+
+async fn reset(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("metrics.reset", &args, 0)?;
+    let mut registry = registry();
+    registry.metrics.clear();
+    registry.started.clear();
+    Ok(Value::None)
+}
+
+/// `set_sink("stdout")` prints each update as a statsd line,
+/// `set_sink("statsd", address=...)` sends it over UDP, and
+/// `set_sink("prometheus", port=..., host=...)` serves `GET /metrics` and
+/// returns the server's trigger handle. `"none"` keeps metrics in memory.
+async fn set_sink(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("metrics.set_sink", &args, 1)?;
+    let kind = get_string_arg("metrics.set_sink", &args, 0)?;
+    let sink = match kind.as_str() {
+        "none" | "prometheus" => Sink::None,
+        "stdout" => Sink::Stdout,
+        "statsd" => {
+            let address = match kwargs.get("address") {
+                Some(v) => v.as_string()?,
+                None => DEFAULT_STATSD_ADDRESS.to_string(),
+            };
+            let socket = UdpSocket::bind("0.0.0.0:0")
+                .and_then(|socket| socket.connect(&address).map(|_| socket))
+                .map_err(|e| BlueprintError::IoError {
+                    path: address.clone(),
+                    message: format!("Failed to open statsd socket: {}", e),
+                })?;
+            Sink::Statsd(socket)
+        }
+        other => {
+            return Err(BlueprintError::ValueError {
+                message: format!(
+                    "metrics.set_sink() sink must be \"none\", \"stdout\", \"statsd\" or \"prometheus\", got {:?}",
+                    other
+                ),
+            });
+        }
+    };
+
+    if kind != "prometheus" {
+        registry().sink = sink;
+        return Ok(Value::None);
+    }
+
+    let port = match kwargs.get("port") {
+        Some(v) => v.as_int()?,
+        None => DEFAULT_PROMETHEUS_PORT,
+    };
+    let port = u16::try_from(port).map_err(|_| BlueprintError::ValueError {
+        message: format!("metrics.set_sink() port out of range: {}", port),
+    })?;
+    let host = match kwargs.get("host") {
+        Some(v) => v.as_string()?,
+        None => "127.0.0.1".to_string(),
+    };
+    check_net_listen(&format!("{}:{}", host, port)).await?;
+
+    registry().sink = sink;
+    let router = Router::new().route(
+        "/metrics",
+        get(|| async {
+            let text = prometheus_text(&registry().metrics);
+            ([("content-type", "text/plain; version=0.0.4")], text)
+        }),
+    );
+    spawn_http_server(host, port, router, vec!["GET /metrics".to_string()]).await
+}
+
+/// Prometheus text exposition format. Timers are reported as summaries
+/// without quantiles.
+fn prometheus_text(metrics: &IndexMap<String, Metric>) -> String {
+    let mut out = String::new();
+    for (name, metric) in metrics {
+        let name = prometheus_name(name);
+        let _ = match metric.kind {
+            Some(Kind::Timer) => writeln!(
+                out,
+                "# TYPE {0} summary\n{0}_sum {1}\n{0}_count {2}",
+                name, metric.value, metric.count
+            ),
+            Some(kind) => writeln!(
+                out,
+                "# TYPE {0} {1}\n{0} {2}",
+                name,
+                kind.name(),
+                metric.value
+            ),
+            None => Ok(()),
+        };
+    }
+    out
+}
+
+/// Replaces characters Prometheus does not allow in metric names with `_`.
+fn prometheus_name(name: &str) -> String {
+    name.char_indices()
+        .map(|(i, c)| {
+            if c.is_ascii_alphabetic() || c == '_' || c == ':' || (i > 0 && c.is_ascii_digit()) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_text() {
+        let mut metrics = IndexMap::new();
+        metrics.insert(
+            "http.requests".to_string(),
+            Metric {
+                kind: Some(Kind::Counter),
+                value: 3.0,
+                ..Metric::default()
+            },
+        );
+        metrics.insert(
+            "queue_depth".to_string(),
+            Metric {
+                kind: Some(Kind::Gauge),
+                value: 7.5,
+                ..Metric::default()
+            },
+        );
+        metrics.insert(
+            "db".to_string(),
+            Metric {
+                kind: Some(Kind::Timer),
+                value: 0.75,
+                count: 2,
+                min: 0.25,
+                max: 0.5,
+            },
+        );
+        assert_eq!(
+            prometheus_text(&metrics),
+            "# TYPE http_requests counter\nhttp_requests 3\n\
+             # TYPE queue_depth gauge\nqueue_depth 7.5\n\
+             # TYPE db summary\ndb_sum 0.75\ndb_count 2\n"
+        );
+        assert_eq!(prometheus_name("9lives-total"), "_lives_total");
+    }
+}
@@ -1,24 +1,43 @@
 mod approval;
+mod bench;
 mod builtins;
+mod cache;
 mod console;
 mod crypto;
+mod diff;
 mod file;
+mod flags;
 mod http;
 mod json;
 mod jwt;
+mod metrics;
 mod parallel;
+mod pretty;
 mod process;
 mod random;
 mod redact;
 mod regex;
 pub mod registry;
+mod semver;
 mod socket;
+mod store;
 mod task;
+mod template;
+mod testing;
+mod textwrap;
 mod time;
 pub mod triggers;
+mod url;
 mod websocket;
 
+pub use bench::{run_benchmark, take_bench_results, BenchResult};
+pub(crate) use builtins::scope_bindings;
+pub use builtins::{run_breakpoint, set_breakpoint_handler, BreakpointHandler};
+pub use cache::{set_cache_backend, CacheBackend, CacheEntry, FileCache, MemoryCache};
+pub use random::set_seed;
 pub use registry::ModuleRegistry;
+pub use testing::{collect_test_results, set_update_snapshots, TestResult};
+pub use time::freeze_clock;
 
 use crate::eval::Evaluator;
 
@@ -30,20 +49,33 @@ pub fn register_builtins(evaluator: &mut Evaluator) {
 pub fn build_registry() -> ModuleRegistry {
     let mut registry = ModuleRegistry::new();
     registry.register_module("approval", approval::get_functions());
+    registry.register_module("bench", bench::get_functions());
+    registry.register_module("cache", cache::get_functions());
+    registry.register_module("console", console::get_functions());
     registry.register_module("crypto", crypto::get_functions());
+    registry.register_module("diff", diff::get_functions());
     registry.register_module("file", file::get_functions());
+    registry.register_module("flags", flags::get_functions());
     registry.register_module("http", http::get_functions());
     registry.register_module("json", json::get_functions());
     registry.register_module("jwt", jwt::get_functions());
+    registry.register_module("metrics", metrics::get_functions());
     registry.register_module("parallel", parallel::get_functions());
     registry.register_module("process", process::get_functions());
     registry.register_module("random", random::get_functions());
     registry.register_module("redact", redact::get_functions());
     registry.register_module("regex", regex::get_functions());
+    registry.register_module("semver", semver::get_functions());
     registry.register_module("socket", socket::get_functions());
+    registry.register_module("store", store::get_functions());
     registry.register_module("task", task::get_functions());
+    registry.register_module("template", template::get_functions());
+    registry.register_module("test", testing::get_functions());
+    registry.register_module("textwrap", textwrap::get_functions());
     registry.register_module("time", time::get_functions());
     registry.register_module("triggers", triggers::get_functions());
+    registry.register_module("url", url::get_functions());
     registry.register_module("websocket", websocket::get_functions());
+    registry.register_alias("io", "file");
     registry
 }
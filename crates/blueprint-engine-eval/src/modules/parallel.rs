@@ -2,13 +2,14 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use blueprint_engine_core::{
-    validation::require_args,
+    inherit_permissions, validation::require_args,
     BlueprintError, NativeFunction, Result, Value,
 };
 use tokio::sync::RwLock;
 use tokio::task::JoinSet;
 
 use crate::eval::Evaluator;
+use crate::profiler;
 
 pub fn get_functions() -> Vec<NativeFunction> {
     vec![NativeFunction::new("parallel", parallel)]
@@ -39,16 +40,16 @@ async fn parallel(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<V
         match func_value {
             Value::NativeFunction(native) => {
                 let native = native.clone();
-                join_set.spawn(async move {
+                join_set.spawn(profiler::inherit(inherit_permissions(async move {
                     match native.call(vec![], HashMap::new()).await {
                         Ok(v) => Ok((idx, v)),
                         Err(e) => Err((idx, e)),
                     }
-                });
+                })));
             }
             Value::Lambda(lambda) => {
                 let lambda = lambda.clone();
-                join_set.spawn(async move {
+                join_set.spawn(profiler::inherit(inherit_permissions(async move {
                     let body = lambda
                         .body
                         .downcast_ref::<blueprint_engine_parser::AstExpr>()
@@ -78,16 +79,16 @@ async fn parallel(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<V
                         }
                     }
 
-                    let evaluator = Evaluator::new();
+                    let evaluator = Evaluator::for_source(lambda.source.as_ref());
                     match evaluator.eval_expr(body, call_scope).await {
                         Ok(v) => Ok((idx, v)),
                         Err(e) => Err((idx, e)),
                     }
-                });
+                })));
             }
             Value::Function(func) => {
                 let func = func.clone();
-                join_set.spawn(async move {
+                join_set.spawn(profiler::inherit(inherit_permissions(async move {
                     let body = func
                         .body
                         .downcast_ref::<blueprint_engine_parser::AstStmt>()
@@ -117,13 +118,13 @@ async fn parallel(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<V
                         }
                     }
 
-                    let evaluator = Evaluator::new();
+                    let evaluator = Evaluator::for_source(func.source.as_ref());
                     match evaluator.eval_stmt(body, call_scope).await {
                         Ok(_) => Ok((idx, Value::None)),
                         Err(BlueprintError::Return { value }) => Ok((idx, (*value).clone())),
                         Err(e) => Err((idx, e)),
                     }
-                });
+                })));
             }
             other => {
                 return Err(BlueprintError::TypeError {
@@ -0,0 +1,263 @@
+use blueprint_engine_core::Value;
+
+/// Formats `value` like `repr`, but breaks containers that do not fit in
+/// `max_width` columns onto one line per element, indented by `indent`.
+/// Containers already being printed further up render as `[...]`/`{...}`.
+pub(crate) fn pformat(value: &Value, indent: usize, max_width: usize) -> String {
+    let mut visiting = Vec::new();
+    let node = layout(value, &mut visiting);
+    let mut out = String::with_capacity(node.width);
+    Printer { indent, max_width }.render(&node, 0, 0, &mut out);
+    out
+}
+
+/// A value with the width of its one-line form, measured once, bottom up.
+struct Node {
+    width: usize,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Text(String),
+    Container {
+        open: String,
+        close: &'static str,
+        /// Each element with the key (`"k": ` or `name=`) printed before it.
+        children: Vec<(String, Node)>,
+        trailing_comma: bool,
+    },
+}
+
+fn layout(value: &Value, visiting: &mut Vec<usize>) -> Node {
+    let Some(container) = container(value) else {
+        return text(value.repr());
+    };
+    if visiting.contains(&container.id) {
+        return text(container.placeholder.to_string());
+    }
+
+    visiting.push(container.id);
+    let children: Vec<(String, Node)> = match container.entries {
+        Entries::Items(items) => items
+            .iter()
+            .map(|v| (String::new(), layout(v, visiting)))
+            .collect(),
+        Entries::Pairs(pairs) => pairs
+            .into_iter()
+            .map(|(key, v)| {
+                let node = layout(&v, visiting);
+                (key, node)
+            })
+            .collect(),
+    };
+    visiting.pop();
+
+    let trailing_comma = container.single_trailing_comma && children.len() == 1;
+    let width = container.open.len()
+        + children
+            .iter()
+            .map(|(key, node)| key.len() + node.width)
+            .sum::<usize>()
+        + 2 * children.len().saturating_sub(1)
+        + usize::from(trailing_comma)
+        + container.close.len();
+    Node {
+        width,
+        kind: NodeKind::Container {
+            open: container.open,
+            close: container.close,
+            children,
+            trailing_comma,
+        },
+    }
+}
+
+fn text(s: String) -> Node {
+    Node {
+        width: s.len(),
+        kind: NodeKind::Text(s),
+    }
+}
+
+struct Printer {
+    indent: usize,
+    max_width: usize,
+}
+
+enum Entries {
+    Items(Vec<Value>),
+    Pairs(Vec<(String, Value)>),
+}
+
+struct Container {
+    id: usize,
+    open: String,
+    close: &'static str,
+    placeholder: &'static str,
+    entries: Entries,
+    single_trailing_comma: bool,
+}
+
+impl Printer {
+    fn render(&self, node: &Node, level: usize, prefix: usize, out: &mut String) {
+        let NodeKind::Container {
+            open,
+            close,
+            children,
+            ..
+        } = &node.kind
+        else {
+            return flat(node, out);
+        };
+        if level * self.indent + prefix + node.width <= self.max_width {
+            return flat(node, out);
+        }
+
+        let pad = " ".repeat((level + 1) * self.indent);
+        out.push_str(open);
+        out.push('\n');
+        for (key, child) in children {
+            out.push_str(&pad);
+            out.push_str(key);
+            self.render(child, level + 1, key.len(), out);
+            out.push_str(",\n");
+        }
+        out.push_str(&" ".repeat(level * self.indent));
+        out.push_str(close);
+    }
+}
+
+/// Writes the one-line form of `node`, the same text `repr` would produce.
+fn flat(node: &Node, out: &mut String) {
+    match &node.kind {
+        NodeKind::Text(s) => out.push_str(s),
+        NodeKind::Container {
+            open,
+            close,
+            children,
+            trailing_comma,
+        } => {
+            out.push_str(open);
+            for (i, (key, child)) in children.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                out.push_str(key);
+                flat(child, out);
+            }
+            if *trailing_comma {
+                out.push(',');
+            }
+            out.push_str(close);
+        }
+    }
+}
+
+fn container(value: &Value) -> Option<Container> {
+    let (id, open, close, placeholder, entries, single_trailing_comma) = match value {
+        Value::List(l) => {
+            let items = l.try_read().ok()?.clone();
+            (
+                ptr(l),
+                "[".to_string(),
+                "]",
+                "[...]",
+                Entries::Items(items),
+                false,
+            )
+        }
+        Value::Tuple(t) => {
+            let items = t.as_ref().clone();
+            (
+                ptr(t),
+                "(".to_string(),
+                ")",
+                "(...)",
+                Entries::Items(items),
+                true,
+            )
+        }
+        Value::Set(s) => {
+            let items = s.try_read().ok()?.iter().cloned().collect();
+            (
+                ptr(s),
+                "{".to_string(),
+                "}",
+                "{...}",
+                Entries::Items(items),
+                false,
+            )
+        }
+        Value::Dict(d) => {
+            let pairs = d
+                .try_read()
+                .ok()?
+                .iter()
+                .map(|(k, v)| (format!("{:?}: ", k), v.clone()))
+                .collect();
+            (
+                ptr(d),
+                "{".to_string(),
+                "}",
+                "{...}",
+                Entries::Pairs(pairs),
+                false,
+            )
+        }
+        Value::StructInstance(s) => {
+            let pairs = s
+                .struct_type
+                .fields
+                .iter()
+                .filter_map(|f| Some((format!("{}=", f.name), s.fields.get(&f.name)?.clone())))
+                .collect();
+            let open = format!("{}(", s.struct_type.name);
+            (ptr(s), open, ")", "(...)", Entries::Pairs(pairs), false)
+        }
+        _ => return None,
+    };
+    Some(Container {
+        id,
+        open,
+        close,
+        placeholder,
+        entries,
+        single_trailing_comma,
+    })
+}
+
+fn ptr<T: ?Sized>(arc: &std::sync::Arc<T>) -> usize {
+    std::sync::Arc::as_ptr(arc) as *const () as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::IndexMap;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    #[test]
+    fn test_pformat_wraps_and_stops_at_cycles() {
+        let inner = Value::from(vec![1i64, 2, 3]);
+        let mut map = IndexMap::new();
+        map.insert("name".to_string(), Value::from("blueprint"));
+        map.insert("values".to_string(), inner);
+        let dict = Value::Dict(Arc::new(RwLock::new(map)));
+
+        assert_eq!(
+            pformat(&dict, 2, 80),
+            r#"{"name": "blueprint", "values": [1, 2, 3]}"#
+        );
+        assert_eq!(
+            pformat(&dict, 2, 24),
+            "{\n  \"name\": \"blueprint\",\n  \"values\": [1, 2, 3],\n}"
+        );
+
+        let list = Arc::new(RwLock::new(vec![Value::Int(1)]));
+        let cyclic = Value::List(list.clone());
+        list.try_write().unwrap().push(cyclic.clone());
+        assert_eq!(pformat(&cyclic, 2, 80), "[1, [...]]");
+        assert_eq!(pformat(&cyclic, 2, 4), "[\n  1,\n  [...],\n]");
+    }
+}
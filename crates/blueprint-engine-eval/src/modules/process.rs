@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::sync::Arc;
 
 use blueprint_engine_core::{
@@ -6,12 +8,15 @@ use blueprint_engine_core::{
     validation::{get_string_arg, require_args, require_args_range},
     BlueprintError, NativeFunction, ProcessResult, Result, Value,
 };
+use futures_util::future::join_all;
 use tokio::process::Command;
 
 pub fn get_functions() -> Vec<NativeFunction> {
     vec![
         NativeFunction::new("run", run),
         NativeFunction::new("shell", shell),
+        NativeFunction::new("pipeline", pipeline),
+        NativeFunction::new("which", which),
         NativeFunction::new("env", env_var),
         NativeFunction::new("set_env", set_env),
         NativeFunction::new("getenv", env_var),
@@ -76,10 +81,13 @@ async fn run(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value>
             message: e.to_string(),
         })?;
 
+    let code = output.status.code().unwrap_or(-1) as i64;
     Ok(Value::ProcessResult(Arc::new(ProcessResult {
-        code: output.status.code().unwrap_or(-1) as i64,
+        command: cmd_args.join(" "),
+        code,
         stdout: String::from_utf8_lossy(&output.stdout).to_string(),
         stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        stage_codes: vec![code],
     })))
 }
 
@@ -116,13 +124,197 @@ async fn shell_impl(cmd: &str, kwargs: &HashMap<String, Value>) -> Result<Value>
             message: e.to_string(),
         })?;
 
+    let code = output.status.code().unwrap_or(-1) as i64;
     Ok(Value::ProcessResult(Arc::new(ProcessResult {
-        code: output.status.code().unwrap_or(-1) as i64,
+        command: cmd.to_string(),
+        code,
         stdout: String::from_utf8_lossy(&output.stdout).to_string(),
         stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        stage_codes: vec![code],
     })))
 }
 
+async fn pipeline(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("process.pipeline", &args, 1)?;
+
+    let stage_values = match &args[0] {
+        Value::List(l) => l.read().await.clone(),
+        other => {
+            return Err(BlueprintError::TypeError {
+                expected: "list of commands".into(),
+                actual: other.type_name().into(),
+            })
+        }
+    };
+
+    if stage_values.is_empty() {
+        return Err(BlueprintError::ArgumentError {
+            message: "pipeline() requires at least one stage".into(),
+        });
+    }
+
+    let mut stages: Vec<Vec<String>> = Vec::with_capacity(stage_values.len());
+    for (i, stage) in stage_values.iter().enumerate() {
+        let cmd_args = match stage {
+            Value::List(l) => {
+                let items = l.read().await;
+                items
+                    .iter()
+                    .map(|v| v.to_display_string())
+                    .collect::<Vec<_>>()
+            }
+            other => {
+                return Err(BlueprintError::TypeError {
+                    expected: format!("list for pipeline stage {}", i),
+                    actual: other.type_name().into(),
+                })
+            }
+        };
+        if cmd_args.is_empty() {
+            return Err(BlueprintError::ArgumentError {
+                message: format!("pipeline() stage {} is an empty command", i),
+            });
+        }
+        stages.push(cmd_args);
+    }
+
+    for stage in &stages {
+        check_process_run(&stage[0]).await?;
+    }
+
+    let cwd = kwargs.get("cwd").map(|v| v.to_display_string());
+    let env_vars = extract_env(&kwargs).await?;
+    let last = stages.len() - 1;
+
+    let mut children = Vec::with_capacity(stages.len());
+    for (i, stage) in stages.iter().enumerate() {
+        let mut command = Command::new(&stage[0]);
+        command.args(&stage[1..]);
+        if let Some(dir) = &cwd {
+            command.current_dir(dir);
+        }
+        for (key, value) in &env_vars {
+            command.env(key, value);
+        }
+        if i > 0 {
+            command.stdin(Stdio::piped());
+        }
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+        command.kill_on_drop(true);
+
+        let child = command.spawn().map_err(|e| BlueprintError::ProcessError {
+            command: stage.join(" "),
+            message: format!("pipeline stage {} failed to start: {}", i, e),
+        })?;
+        children.push(child);
+    }
+
+    // Pump each stage's stdout into the next stage's stdin. Dropping the
+    // writer when the source is exhausted closes the downstream stdin.
+    for i in 0..last {
+        let stdout = children[i].stdout.take();
+        let stdin = children[i + 1].stdin.take();
+        if let (Some(mut stdout), Some(mut stdin)) = (stdout, stdin) {
+            tokio::spawn(async move {
+                let _ = tokio::io::copy(&mut stdout, &mut stdin).await;
+            });
+        }
+    }
+
+    let outputs = join_all(children.into_iter().map(|c| c.wait_with_output())).await;
+
+    let mut stage_codes = Vec::with_capacity(outputs.len());
+    let mut stderr = String::new();
+    let mut stdout = String::new();
+    for (i, output) in outputs.into_iter().enumerate() {
+        let output = output.map_err(|e| BlueprintError::ProcessError {
+            command: stages[i].join(" "),
+            message: format!("pipeline stage {} failed: {}", i, e),
+        })?;
+        stage_codes.push(output.status.code().unwrap_or(-1) as i64);
+        stderr.push_str(&String::from_utf8_lossy(&output.stderr));
+        if i == last {
+            stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        }
+    }
+
+    let code = stage_codes.iter().copied().find(|c| *c != 0).unwrap_or(0);
+
+    Ok(Value::ProcessResult(Arc::new(ProcessResult {
+        command: stages
+            .iter()
+            .map(|stage| stage.join(" "))
+            .collect::<Vec<_>>()
+            .join(" | "),
+        code,
+        stdout,
+        stderr,
+        stage_codes,
+    })))
+}
+
+async fn which(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("process.which", &args, 1)?;
+    let name = get_string_arg("process.which", &args, 0)?;
+
+    Ok(match find_executable(&name) {
+        Some(path) => Value::String(Arc::new(path.to_string_lossy().to_string())),
+        None => Value::None,
+    })
+}
+
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let extensions = executable_extensions();
+    let candidates = |dir: &Path| -> Option<PathBuf> {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", name, ext)))
+            .find(|p| is_executable(p))
+    };
+
+    if name.contains('/') || name.contains(std::path::MAIN_SEPARATOR) {
+        let found = candidates(Path::new(""))?;
+        return std::fs::canonicalize(&found).ok().or(Some(found));
+    }
+
+    let path_var = std::env::var_os("PATH")?;
+    std::env::split_paths(&path_var)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find_map(|dir| candidates(&dir))
+        .map(|found| match std::env::current_dir() {
+            Ok(cwd) => cwd.join(found),
+            Err(_) => found,
+        })
+}
+
+fn executable_extensions() -> Vec<String> {
+    let mut extensions = vec![String::new()];
+    if cfg!(windows) {
+        let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+        extensions.extend(
+            pathext
+                .split(';')
+                .filter(|ext| !ext.is_empty())
+                .map(|ext| ext.to_string()),
+        );
+    }
+    extensions
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
 async fn env_var(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     require_args_range("process.env", &args, 1, 2)?;
     let name = get_string_arg("process.env", &args, 0)?;
@@ -1,43 +1,128 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use blueprint_engine_core::NativeFunction;
 
+pub const DEFAULT_MODULE_VERSION: u32 = 1;
+
 pub struct ModuleRegistry {
-    modules: HashMap<String, HashMap<String, Arc<NativeFunction>>>,
+    modules: HashMap<String, BTreeMap<u32, HashMap<String, Arc<NativeFunction>>>>,
+    aliases: HashMap<String, String>,
 }
 
 impl ModuleRegistry {
     pub fn new() -> Self {
         Self {
             modules: HashMap::new(),
+            aliases: HashMap::new(),
         }
     }
 
     pub fn register_module(&mut self, name: &str, functions: Vec<NativeFunction>) {
+        self.register_module_version(name, DEFAULT_MODULE_VERSION, functions);
+    }
+
+    pub fn register_module_version(
+        &mut self,
+        name: &str,
+        version: u32,
+        functions: Vec<NativeFunction>,
+    ) {
         let mut module_funcs = HashMap::new();
         for func in functions {
             module_funcs.insert(func.name.clone(), Arc::new(func));
         }
-        self.modules.insert(name.to_string(), module_funcs);
+        self.modules
+            .entry(name.to_string())
+            .or_default()
+            .insert(version, module_funcs);
+    }
+
+    pub fn register_alias(&mut self, alias: &str, target: &str) {
+        self.aliases.insert(alias.to_string(), target.to_string());
+    }
+
+    fn canonical_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map(|s| s.as_str()).unwrap_or(name)
     }
 
     pub fn get_module(&self, name: &str) -> Option<&HashMap<String, Arc<NativeFunction>>> {
-        self.modules.get(name)
+        self.modules
+            .get(self.canonical_name(name))
+            .and_then(|versions| versions.values().next_back())
+    }
+
+    /// Resolves a module spec such as `http` or `http@1`.
+    pub fn resolve(&self, spec: &str) -> Result<&HashMap<String, Arc<NativeFunction>>, String> {
+        let (name, version) = split_module_spec(spec)?;
+
+        let canonical = self.canonical_name(name);
+        let versions = self
+            .modules
+            .get(canonical)
+            .ok_or_else(|| format!("Module '@bp/{}' not found in stdlib", name))?;
+
+        match version {
+            None => versions
+                .values()
+                .next_back()
+                .ok_or_else(|| format!("Module '@bp/{}' not found in stdlib", name)),
+            Some(version) => versions.get(&version).ok_or_else(|| {
+                let available: Vec<String> = versions.keys().map(|v| v.to_string()).collect();
+                format!(
+                    "Module '@bp/{}' has no version {} (available: {})",
+                    name,
+                    version,
+                    available.join(", ")
+                )
+            }),
+        }
     }
 
     #[allow(dead_code)]
     pub fn get_function(&self, module: &str, func: &str) -> Option<Arc<NativeFunction>> {
-        self.modules.get(module).and_then(|m| m.get(func).cloned())
+        self.get_module(module).and_then(|m| m.get(func).cloned())
     }
 
+    #[allow(dead_code)]
     pub fn has_module(&self, name: &str) -> bool {
-        self.modules.contains_key(name)
+        self.modules.contains_key(self.canonical_name(name))
     }
 
     #[allow(dead_code)]
     pub fn module_names(&self) -> Vec<&str> {
-        self.modules.keys().map(|s| s.as_str()).collect()
+        self.modules
+            .keys()
+            .chain(self.aliases.keys())
+            .map(|s| s.as_str())
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn module_versions(&self, name: &str) -> Vec<u32> {
+        self.modules
+            .get(self.canonical_name(name))
+            .map(|versions| versions.keys().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Splits `name@version` into its parts. The version is a major version number.
+pub fn split_module_spec(spec: &str) -> Result<(&str, Option<u32>), String> {
+    match spec.split_once('@') {
+        None => Ok((spec, None)),
+        Some((name, version)) => {
+            let version = version.strip_prefix('v').unwrap_or(version);
+            version
+                .parse::<u32>()
+                .map(|v| (name, Some(v)))
+                .map_err(|_| {
+                    format!(
+                        "Invalid version '{}' for module '@bp/{}': expected a major version number",
+                        version, name
+                    )
+                })
+        }
     }
 }
 
@@ -0,0 +1,213 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use blueprint_engine_core::{
+    validation::require_args, BlueprintError, NativeFunction, Result, StructField, StructInstance,
+    StructType, TypeAnnotation, Value,
+};
+use indexmap::IndexMap;
+use semver::{Version, VersionReq};
+
+lazy_static::lazy_static! {
+    static ref VERSION_TYPE: Arc<StructType> = Arc::new(StructType {
+        name: "Version".into(),
+        fields: [
+            ("major", "int"),
+            ("minor", "int"),
+            ("patch", "int"),
+            ("pre", "str"),
+            ("build", "str"),
+        ]
+        .into_iter()
+        .map(|(name, typ)| StructField {
+            name: name.into(),
+            typ: TypeAnnotation::Simple(typ.into()),
+            default: None,
+        })
+        .collect(),
+        methods: IndexMap::new(),
+    });
+}
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("parse", parse),
+        NativeFunction::new("compare", compare),
+        NativeFunction::new("satisfies", satisfies),
+        NativeFunction::new("max_satisfying", max_satisfying),
+    ]
+}
+
+async fn parse(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("semver.parse", &args, 1)?;
+    let version = version_arg("semver.parse", &args[0])?;
+
+    let mut fields = IndexMap::new();
+    fields.insert("major".into(), Value::Int(version.major as i64));
+    fields.insert("minor".into(), Value::Int(version.minor as i64));
+    fields.insert("patch".into(), Value::Int(version.patch as i64));
+    fields.insert(
+        "pre".into(),
+        Value::String(Arc::new(version.pre.as_str().to_string())),
+    );
+    fields.insert(
+        "build".into(),
+        Value::String(Arc::new(version.build.as_str().to_string())),
+    );
+    Ok(Value::StructInstance(Arc::new(StructInstance {
+        struct_type: VERSION_TYPE.clone(),
+        fields,
+    })))
+}
+
+/// Returns -1, 0 or 1. Build metadata does not affect precedence, so
+/// `1.0.0+a` and `1.0.0+b` compare equal.
+async fn compare(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("semver.compare", &args, 2)?;
+    let a = version_arg("semver.compare", &args[0])?;
+    let b = version_arg("semver.compare", &args[1])?;
+    Ok(Value::Int(match precedence(&a, &b) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }))
+}
+
+async fn satisfies(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("semver.satisfies", &args, 2)?;
+    let version = version_arg("semver.satisfies", &args[0])?;
+    let req = req_arg("semver.satisfies", &args[1])?;
+    Ok(Value::Bool(req.matches(&version)))
+}
+
+/// The highest of `versions` matching `range`, returned as it was passed in,
+/// or `None`. Entries that are not valid semver are skipped.
+async fn max_satisfying(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("semver.max_satisfying", &args, 2)?;
+    let candidates = match &args[0] {
+        Value::List(l) => l.read().await.clone(),
+        Value::Tuple(t) => t.as_ref().clone(),
+        other => {
+            return Err(BlueprintError::TypeError {
+                expected: "list of versions".into(),
+                actual: other.type_name().into(),
+            })
+        }
+    };
+    let req = req_arg("semver.max_satisfying", &args[1])?;
+
+    let best = candidates
+        .into_iter()
+        .filter_map(|value| {
+            let version = version_arg("semver.max_satisfying", &value).ok()?;
+            req.matches(&version).then_some((version, value))
+        })
+        .max_by(|(a, _), (b, _)| precedence(a, b));
+    Ok(best.map_or(Value::None, |(_, value)| value))
+}
+
+/// Accepts a version string or a `Version` struct returned by `parse`.
+fn version_arg(name: &str, value: &Value) -> Result<Version> {
+    let text = match value {
+        Value::String(s) => s.as_ref().clone(),
+        Value::StructInstance(s) if Arc::ptr_eq(&s.struct_type, &VERSION_TYPE) => {
+            let field = |key: &str| s.fields.get(key).map(Value::to_display_string);
+            let mut text = format!(
+                "{}.{}.{}",
+                field("major").unwrap_or_default(),
+                field("minor").unwrap_or_default(),
+                field("patch").unwrap_or_default()
+            );
+            if let Some(pre) = field("pre").filter(|p| !p.is_empty()) {
+                text.push('-');
+                text.push_str(&pre);
+            }
+            if let Some(build) = field("build").filter(|b| !b.is_empty()) {
+                text.push('+');
+                text.push_str(&build);
+            }
+            text
+        }
+        other => {
+            return Err(BlueprintError::TypeError {
+                expected: format!("version string for {}()", name),
+                actual: other.type_name().into(),
+            })
+        }
+    };
+    Version::parse(&text).map_err(|e| BlueprintError::ValueError {
+        message: format!("{}(): invalid version {:?}: {}", name, text, e),
+    })
+}
+
+fn req_arg(name: &str, value: &Value) -> Result<VersionReq> {
+    let text = value.as_string()?;
+    VersionReq::parse(&text).map_err(|e| BlueprintError::ValueError {
+        message: format!("{}(): invalid version range {:?}: {}", name, text, e),
+    })
+}
+
+fn precedence(a: &Version, b: &Version) -> Ordering {
+    (a.major, a.minor, a.patch, &a.pre).cmp(&(b.major, b.minor, b.patch, &b.pre))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(v: &str) -> Value {
+        Value::String(Arc::new(v.to_string()))
+    }
+
+    fn list(items: &[&str]) -> Value {
+        Value::List(Arc::new(tokio::sync::RwLock::new(
+            items.iter().map(|v| s(v)).collect(),
+        )))
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_ranges() {
+        let cmp = |a: &str, b: &str| compare(vec![s(a), s(b)], HashMap::new());
+        assert!(matches!(
+            cmp("1.2.3", "1.10.0").await.unwrap(),
+            Value::Int(-1)
+        ));
+        assert!(matches!(
+            cmp("1.0.0", "1.0.0-rc.1").await.unwrap(),
+            Value::Int(1)
+        ));
+        assert!(matches!(
+            cmp("1.0.0+a", "1.0.0+b").await.unwrap(),
+            Value::Int(0)
+        ));
+        assert!(cmp("1.0", "1.0.0").await.is_err());
+
+        let parsed = parse(vec![s("2.1.0-beta.2+build.5")], HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            parsed.repr(),
+            r#"Version(major=2, minor=1, patch=0, pre="beta.2", build="build.5")"#
+        );
+        let same = compare(vec![parsed, s("2.1.0-beta.2")], HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(same, Value::Int(0)));
+
+        let versions = list(&["1.2.0", "1.9.3", "2.0.0", "not-a-version", "1.10.1"]);
+        let best = max_satisfying(vec![versions.clone(), s("^1.2")], HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(best.repr(), r#""1.10.1""#);
+        let none = max_satisfying(vec![versions, s(">=3")], HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(none, Value::None));
+
+        let ok = satisfies(vec![s("1.4.0"), s(">=1.2, <2")], HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(ok, Value::Bool(true)));
+    }
+}
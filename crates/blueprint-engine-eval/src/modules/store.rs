@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use blueprint_engine_core::{
+    check_fs_read, check_fs_write,
+    validation::{get_arg, get_string_arg, require_args, require_args_range},
+    BlueprintError, NativeFunction, Result, StructField, StructInstance, StructType,
+    TypeAnnotation, Value,
+};
+use indexmap::IndexMap;
+use rusqlite::{params, Connection, OptionalExtension};
+use tokio::sync::RwLock;
+
+use super::builtins::call_callable;
+use super::json::{json_to_value, value_to_json};
+
+/// One connection per database file. `txn` serializes writers across tasks
+/// so a transaction never interleaves with another task's statements.
+struct Store {
+    path: PathBuf,
+    conn: Mutex<Connection>,
+    txn: tokio::sync::Mutex<()>,
+}
+
+lazy_static::lazy_static! {
+    static ref STORES: Mutex<HashMap<PathBuf, Arc<Store>>> = Mutex::new(HashMap::new());
+    static ref STORE_TYPE: Arc<StructType> = Arc::new(StructType {
+        name: "Store".into(),
+        fields: vec![StructField {
+            name: "path".into(),
+            typ: TypeAnnotation::Simple("str".into()),
+            default: None,
+        }],
+        methods: IndexMap::new(),
+    });
+}
+
+tokio::task_local! {
+    static ACTIVE_TXN: PathBuf;
+}
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("open", store_open),
+        NativeFunction::new("get", store_get),
+        NativeFunction::new("set", store_set),
+        NativeFunction::new("delete", store_delete),
+        NativeFunction::new("keys", store_keys),
+        NativeFunction::new("transaction", store_transaction),
+    ]
+}
+
+fn store_error(path: &Path, e: impl std::fmt::Display) -> BlueprintError {
+    BlueprintError::IoError {
+        path: path.display().to_string(),
+        message: e.to_string(),
+    }
+}
+
+fn handle_for(store: &Store) -> Value {
+    let mut fields = IndexMap::new();
+    fields.insert(
+        "path".to_string(),
+        Value::String(Arc::new(store.path.display().to_string())),
+    );
+    Value::StructInstance(Arc::new(StructInstance {
+        struct_type: STORE_TYPE.clone(),
+        fields,
+    }))
+}
+
+fn store_arg(name: &str, args: &[Value]) -> Result<Arc<Store>> {
+    let handle = get_arg(name, args, 0)?;
+    let path = match handle {
+        Value::StructInstance(s) if Arc::ptr_eq(&s.struct_type, &STORE_TYPE) => {
+            s.get_field("path").unwrap_or(Value::None).as_string()?
+        }
+        other => {
+            return Err(BlueprintError::TypeError {
+                expected: format!("Store from store.open() as first argument to {}()", name),
+                actual: other.type_name().into(),
+            })
+        }
+    };
+    STORES
+        .lock()
+        .unwrap()
+        .get(Path::new(&path))
+        .cloned()
+        .ok_or_else(|| BlueprintError::ValueError {
+            message: format!("store '{}' is not open", path),
+        })
+}
+
+/// Runs `f` against the connection on a blocking thread. Outside a
+/// transaction on this store, waits for any running transaction to finish.
+async fn with_conn<T, F>(store: Arc<Store>, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+{
+    let in_txn = ACTIVE_TXN
+        .try_with(|path| *path == store.path)
+        .unwrap_or(false);
+    let _guard = if in_txn {
+        None
+    } else {
+        Some(store.txn.lock().await)
+    };
+
+    let path = store.path.clone();
+    let conn_store = store.clone();
+    tokio::task::spawn_blocking(move || f(&conn_store.conn.lock().unwrap()))
+        .await
+        .map_err(|e| store_error(&path, e))?
+        .map_err(|e| store_error(&path, e))
+}
+
+async fn store_open(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("store.open", &args, 1)?;
+    let path = get_string_arg("store.open", &args, 0)?;
+    check_fs_read(&path).await?;
+    check_fs_write(&path).await?;
+
+    let absolute = match Path::new(&path) {
+        p if p.is_absolute() => p.to_path_buf(),
+        p => std::env::current_dir()
+            .map_err(|e| store_error(p, e))?
+            .join(p),
+    };
+    if let Some(store) = STORES.lock().unwrap().get(&absolute) {
+        return Ok(handle_for(store));
+    }
+
+    let open_path = absolute.clone();
+    let conn = tokio::task::spawn_blocking(move || -> rusqlite::Result<Connection> {
+        let conn = Connection::open(&open_path)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS kv (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+        )?;
+        Ok(conn)
+    })
+    .await
+    .map_err(|e| store_error(&absolute, e))?
+    .map_err(|e| store_error(&absolute, e))?;
+
+    let mut stores = STORES.lock().unwrap();
+    let store = stores.entry(absolute.clone()).or_insert_with(|| {
+        Arc::new(Store {
+            path: absolute,
+            conn: Mutex::new(conn),
+            txn: tokio::sync::Mutex::new(()),
+        })
+    });
+    Ok(handle_for(store))
+}
+
+async fn store_get(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("store.get", &args, 2, 3)?;
+    let store = store_arg("store.get", &args)?;
+    let key = get_string_arg("store.get", &args, 1)?;
+    let default = args
+        .get(2)
+        .or_else(|| kwargs.get("default"))
+        .cloned()
+        .unwrap_or(Value::None);
+
+    let stored = with_conn(store, move |conn| {
+        conn.query_row("SELECT value FROM kv WHERE key = ?1", params![key], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()
+    })
+    .await?;
+
+    match stored {
+        Some(text) => {
+            let json = serde_json::from_str(&text).map_err(|e| BlueprintError::JsonError {
+                message: e.to_string(),
+            })?;
+            json_to_value(json)
+        }
+        None => Ok(default),
+    }
+}
+
+async fn store_set(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("store.set", &args, 3)?;
+    let store = store_arg("store.set", &args)?;
+    let key = get_string_arg("store.set", &args, 1)?;
+    let value = value_to_json(&args[2], None).await?.to_string();
+
+    with_conn(store, move |conn| {
+        conn.execute(
+            "INSERT INTO kv (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )
+    })
+    .await?;
+    Ok(Value::None)
+}
+
+async fn store_delete(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("store.delete", &args, 2)?;
+    let store = store_arg("store.delete", &args)?;
+    let key = get_string_arg("store.delete", &args, 1)?;
+
+    let removed = with_conn(store, move |conn| {
+        conn.execute("DELETE FROM kv WHERE key = ?1", params![key])
+    })
+    .await?;
+    Ok(Value::Bool(removed > 0))
+}
+
+async fn store_keys(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("store.keys", &args, 1, 2)?;
+    let store = store_arg("store.keys", &args)?;
+    let prefix = match args.get(1).or_else(|| kwargs.get("prefix")) {
+        Some(v) => v.as_string()?,
+        None => String::new(),
+    };
+
+    let keys = with_conn(store, move |conn| {
+        let mut stmt =
+            conn.prepare("SELECT key FROM kv WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key")?;
+        let mut keys = Vec::new();
+        for key in stmt.query_map(params![prefix], |row| row.get::<_, String>(0))? {
+            keys.push(key?);
+        }
+        Ok(keys)
+    })
+    .await?;
+
+    let keys = keys
+        .into_iter()
+        .map(|k| Value::String(Arc::new(k)))
+        .collect();
+    Ok(Value::List(Arc::new(RwLock::new(keys))))
+}
+
+/// Calls `func(store)` inside a transaction. Any error rolls back every
+/// write made by `func` and is re-raised.
+async fn store_transaction(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("store.transaction", &args, 2)?;
+    let store = store_arg("store.transaction", &args)?;
+    let func = args[1].clone();
+
+    if ACTIVE_TXN
+        .try_with(|path| *path == store.path)
+        .unwrap_or(false)
+    {
+        return Err(BlueprintError::ValueError {
+            message: "store.transaction() cannot be nested".into(),
+        });
+    }
+
+    let _guard = store.txn.lock().await;
+    let exec = |sql: &'static str| {
+        let store = store.clone();
+        async move {
+            let path = store.path.clone();
+            tokio::task::spawn_blocking(move || store.conn.lock().unwrap().execute_batch(sql))
+                .await
+                .map_err(|e| store_error(&path, e))?
+                .map_err(|e| store_error(&path, e))
+        }
+    };
+
+    exec("BEGIN IMMEDIATE").await?;
+    let result = ACTIVE_TXN
+        .scope(
+            store.path.clone(),
+            call_callable(&func, vec![args[0].clone()], HashMap::new()),
+        )
+        .await;
+
+    match result {
+        Ok(value) => {
+            exec("COMMIT").await?;
+            Ok(value)
+        }
+        Err(e) => {
+            exec("ROLLBACK").await?;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(v: &str) -> Value {
+        Value::String(Arc::new(v.into()))
+    }
+
+    #[tokio::test]
+    async fn test_failed_transaction_rolls_back() {
+        let path = std::env::temp_dir().join(format!("bp_store_{}.db", std::process::id()));
+        let db = store_open(vec![s(&path.display().to_string())], HashMap::new())
+            .await
+            .unwrap();
+
+        store_set(vec![db.clone(), s("a:1"), Value::Int(1)], HashMap::new())
+            .await
+            .unwrap();
+
+        let failing = Value::NativeFunction(Arc::new(NativeFunction::new_with_state(
+            "failing",
+            |args, _kwargs| {
+                Box::pin(async move {
+                    store_set(
+                        vec![args[0].clone(), s("a:2"), Value::Int(2)],
+                        HashMap::new(),
+                    )
+                    .await?;
+                    Err(BlueprintError::ValueError {
+                        message: "boom".into(),
+                    })
+                })
+            },
+        )));
+        assert!(store_transaction(vec![db.clone(), failing], HashMap::new())
+            .await
+            .is_err());
+
+        let keys = store_keys(vec![db.clone(), s("a:")], HashMap::new())
+            .await
+            .unwrap();
+        match keys {
+            Value::List(items) => assert_eq!(*items.read().await, vec![s("a:1")]),
+            other => panic!("expected list, got {:?}", other),
+        }
+        assert_eq!(
+            store_get(vec![db, s("a:2"), Value::Int(0)], HashMap::new())
+                .await
+                .unwrap(),
+            Value::Int(0)
+        );
+
+        for suffix in ["", "-wal", "-shm"] {
+            std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+        }
+    }
+}
@@ -4,16 +4,23 @@ use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use blueprint_engine_core::{
-    validation::require_args,
+    validation::{require_args, require_args_range},
     BlueprintError, NativeFunction, Result, Value,
 };
 use tokio::sync::RwLock;
 use tokio::time::timeout;
 
+use super::builtins::{call_callable, callable_name, require_callable};
 use crate::eval::Evaluator;
 
 pub fn get_functions() -> Vec<NativeFunction> {
-    vec![NativeFunction::new("task", task_fn)]
+    vec![
+        NativeFunction::new("task", task_fn),
+        NativeFunction::new("with_retry", with_retry),
+        NativeFunction::new("with_timeout", with_timeout),
+        NativeFunction::new("requires", requires),
+        NativeFunction::new("ensures", ensures),
+    ]
 }
 
 async fn task_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
@@ -36,12 +43,7 @@ async fn task_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Val
         }
         Some(Duration::from_secs_f64(secs))
     } else if let Some(timestamp) = wait_until {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs_f64();
-
-        let remaining = timestamp - now;
+        let remaining = timestamp - super::time::current_time();
         if remaining <= 0.0 {
             return Ok(build_result(Value::None, false, Some("deadline_passed")));
         }
@@ -109,7 +111,7 @@ async fn execute_callable(func_value: Value) -> Result<Value> {
                 }
             }
 
-            let evaluator = Evaluator::new();
+            let evaluator = Evaluator::for_source(lambda.source.as_ref());
             evaluator.eval_expr(body, call_scope).await
         }
         Value::Function(func) => {
@@ -135,7 +137,7 @@ async fn execute_callable(func_value: Value) -> Result<Value> {
                 }
             }
 
-            let evaluator = Evaluator::new();
+            let evaluator = Evaluator::for_source(func.source.as_ref());
             match evaluator.eval_stmt(body, call_scope).await {
                 Ok(_) => Ok(Value::None),
                 Err(BlueprintError::Return { value }) => Ok((*value).clone()),
@@ -149,6 +151,171 @@ async fn execute_callable(func_value: Value) -> Result<Value> {
     }
 }
 
+fn positional_or_kwarg<'a>(
+    args: &'a [Value],
+    kwargs: &'a HashMap<String, Value>,
+    index: usize,
+    name: &str,
+) -> Option<&'a Value> {
+    args.get(index).or_else(|| kwargs.get(name))
+}
+
+/// Wraps `func` so each call is retried up to `max_attempts` times, sleeping
+/// `backoff` seconds (doubling each time) between attempts.
+async fn with_retry(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("task.with_retry", &args, 1, 3)?;
+    let func = args[0].clone();
+    require_callable("task.with_retry", &func)?;
+
+    let max_attempts = match positional_or_kwarg(&args, &kwargs, 1, "max_attempts") {
+        Some(v) => v.as_int()?,
+        None => 3,
+    };
+    if max_attempts < 1 {
+        return Err(BlueprintError::ValueError {
+            message: format!("max_attempts must be at least 1, got {}", max_attempts),
+        });
+    }
+
+    let backoff = match positional_or_kwarg(&args, &kwargs, 2, "backoff") {
+        Some(v) => v.as_float()?,
+        None => 0.0,
+    };
+    let backoff = Duration::try_from_secs_f64(backoff).map_err(|_| BlueprintError::ValueError {
+        message: format!("backoff must be a non-negative number, got {}", backoff),
+    })?;
+
+    Ok(Value::NativeFunction(Arc::new(
+        NativeFunction::new_with_state("with_retry", move |args, kwargs| {
+            let func = func.clone();
+            Box::pin(async move {
+                let mut delay = backoff;
+                let mut attempt = 1;
+                loop {
+                    match call_callable(&func, args.clone(), kwargs.clone()).await {
+                        Ok(value) => return Ok(value),
+                        Err(e)
+                            if e.is_control_flow()
+                                || e.is_resource_exhausted()
+                                || attempt >= max_attempts =>
+                        {
+                            return Err(e)
+                        }
+                        Err(_) => {
+                            if !delay.is_zero() {
+                                tokio::time::sleep(delay).await;
+                                delay *= 2;
+                            }
+                            attempt += 1;
+                        }
+                    }
+                }
+            })
+        }),
+    )))
+}
+
+/// Wraps `func` so each call fails with a timeout error after `seconds`.
+async fn with_timeout(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("task.with_timeout", &args, 1, 2)?;
+    let func = args[0].clone();
+    require_callable("task.with_timeout", &func)?;
+
+    let seconds = positional_or_kwarg(&args, &kwargs, 1, "seconds")
+        .ok_or_else(|| BlueprintError::ArgumentError {
+            message: "task.with_timeout() missing required argument 'seconds'".into(),
+        })?
+        .as_float()?;
+    let limit = Duration::try_from_secs_f64(seconds).map_err(|_| BlueprintError::ValueError {
+        message: format!("seconds must be a non-negative number, got {}", seconds),
+    })?;
+
+    Ok(Value::NativeFunction(Arc::new(
+        NativeFunction::new_with_state("with_timeout", move |args, kwargs| {
+            let func = func.clone();
+            Box::pin(async move {
+                match timeout(limit, call_callable(&func, args, kwargs)).await {
+                    Ok(result) => result,
+                    Err(_) => Err(BlueprintError::Timeout { seconds }),
+                }
+            })
+        }),
+    )))
+}
+
+/// Wraps `func` so each call first passes its arguments to `predicate` and
+/// fails with an `AssertionError` naming the contract if that is falsy.
+async fn requires(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    let (func, predicate, contract) = contract_args("task.requires", &args, &kwargs)?;
+
+    Ok(Value::NativeFunction(Arc::new(
+        NativeFunction::new_with_state("requires", move |args, kwargs| {
+            let func = func.clone();
+            let predicate = predicate.clone();
+            let contract = contract.clone();
+            Box::pin(async move {
+                let ok = call_callable(&predicate, args.clone(), kwargs.clone()).await?;
+                if !ok.is_truthy_async().await {
+                    return Err(contract_violation("precondition", &func, &contract));
+                }
+                call_callable(&func, args, kwargs).await
+            })
+        }),
+    )))
+}
+
+/// Wraps `func` so each result is passed to `predicate` before it is
+/// returned, failing with an `AssertionError` if that is falsy.
+async fn ensures(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    let (func, predicate, contract) = contract_args("task.ensures", &args, &kwargs)?;
+
+    Ok(Value::NativeFunction(Arc::new(
+        NativeFunction::new_with_state("ensures", move |args, kwargs| {
+            let func = func.clone();
+            let predicate = predicate.clone();
+            let contract = contract.clone();
+            Box::pin(async move {
+                let result = call_callable(&func, args, kwargs).await?;
+                let ok = call_callable(&predicate, vec![result.clone()], HashMap::new()).await?;
+                if !ok.is_truthy_async().await {
+                    return Err(contract_violation("postcondition", &func, &contract));
+                }
+                Ok(result)
+            })
+        }),
+    )))
+}
+
+/// Reads `(func, predicate, message=None)`. Without a message the contract
+/// is described by the predicate's name.
+fn contract_args(
+    name: &str,
+    args: &[Value],
+    kwargs: &HashMap<String, Value>,
+) -> Result<(Value, Value, String)> {
+    require_args_range(name, args, 2, 3)?;
+    let func = args[0].clone();
+    require_callable(name, &func)?;
+    let predicate = args[1].clone();
+    require_callable(name, &predicate)?;
+    let contract = match positional_or_kwarg(args, kwargs, 2, "message") {
+        None | Some(Value::None) => callable_name(&predicate),
+        Some(v) => v.as_string()?,
+    };
+    Ok((func, predicate, contract))
+}
+
+fn contract_violation(kind: &str, func: &Value, contract: &str) -> BlueprintError {
+    BlueprintError::AssertionError {
+        message: format!(
+            "{} of {}() violated: {}",
+            kind,
+            callable_name(func),
+            contract
+        ),
+    }
+}
+
 fn build_result(value: Value, success: bool, reason: Option<&str>) -> Value {
     let mut result = IndexMap::new();
     result.insert("value".to_string(), value);
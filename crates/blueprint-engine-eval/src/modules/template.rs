@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use blueprint_engine_core::{
+    validation::{get_string_arg, require_args_range},
+    BlueprintError, NativeFunction, Result, Value,
+};
+use indexmap::IndexMap;
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("substitute", substitute),
+        NativeFunction::new("safe_substitute", safe_substitute),
+    ]
+}
+
+async fn substitute(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    let (template, mapping) = template_args("template.substitute", args, kwargs).await?;
+    Ok(Value::String(Arc::new(render(&template, &mapping, false)?)))
+}
+
+async fn safe_substitute(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    let (template, mapping) = template_args("template.safe_substitute", args, kwargs).await?;
+    Ok(Value::String(Arc::new(render(&template, &mapping, true)?)))
+}
+
+/// Placeholders come from the optional mapping argument; keyword arguments
+/// override it, as with Python's `string.Template`.
+async fn template_args(
+    name: &str,
+    args: Vec<Value>,
+    kwargs: HashMap<String, Value>,
+) -> Result<(String, IndexMap<String, Value>)> {
+    require_args_range(name, &args, 1, 2)?;
+    let template = get_string_arg(name, &args, 0)?;
+    let mut mapping = match args.get(1) {
+        None | Some(Value::None) => IndexMap::new(),
+        Some(Value::Dict(d)) => d.read().await.clone(),
+        Some(Value::StructInstance(s)) => s.to_dict(),
+        Some(other) => {
+            return Err(BlueprintError::TypeError {
+                expected: format!("dict as mapping for {}()", name),
+                actual: other.type_name().into(),
+            })
+        }
+    };
+    mapping.extend(kwargs);
+    Ok((template, mapping))
+}
+
+/// Expands `$name` and `${name}`; `$$` is a literal `$`. With `safe`, unknown
+/// names and malformed placeholders are left as written instead of raising.
+fn render(template: &str, mapping: &IndexMap<String, Value>, safe: bool) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        if let Some(tail) = after.strip_prefix('$') {
+            out.push('$');
+            rest = tail;
+            continue;
+        }
+
+        let (name, consumed) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) if is_identifier(&braced[..end]) => (&braced[..end], end + 2),
+                _ => ("", 0),
+            },
+            None => {
+                let len = identifier_len(after);
+                (&after[..len], len)
+            }
+        };
+
+        if name.is_empty() {
+            if !safe {
+                return Err(BlueprintError::ValueError {
+                    message: format!(
+                        "invalid placeholder in template at offset {}",
+                        template.len() - rest.len() + pos
+                    ),
+                });
+            }
+            out.push('$');
+            rest = after;
+            continue;
+        }
+
+        match mapping.get(name) {
+            Some(value) => out.push_str(&value.to_display_string()),
+            None if safe => out.push_str(&rest[pos..pos + 1 + consumed]),
+            None => {
+                return Err(BlueprintError::KeyError {
+                    key: name.to_string(),
+                })
+            }
+        }
+        rest = &after[consumed..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn identifier_len(s: &str) -> usize {
+    let mut len = 0;
+    for (i, c) in s.char_indices() {
+        let valid = c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit());
+        if !valid {
+            break;
+        }
+        len = i + c.len_utf8();
+    }
+    len
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && identifier_len(s) == s.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping() -> IndexMap<String, Value> {
+        let mut map = IndexMap::new();
+        map.insert("name".to_string(), Value::from("Ana"));
+        map.insert("count".to_string(), Value::Int(3));
+        map
+    }
+
+    #[test]
+    fn test_substitute_and_safe_substitute() {
+        let map = mapping();
+        assert_eq!(
+            render(
+                "Hi $name, ${count} new for ${name}s. Cost: $$5",
+                &map,
+                false
+            )
+            .unwrap(),
+            "Hi Ana, 3 new for Anas. Cost: $5"
+        );
+
+        let err = render("Hi $who", &map, false).unwrap_err();
+        assert!(matches!(err, BlueprintError::KeyError { key } if key == "who"));
+        assert!(render("Total: $5", &map, false).is_err());
+
+        assert_eq!(
+            render("Hi $who, ${missing} and $name at 5$ or ${bad", &map, true).unwrap(),
+            "Hi $who, ${missing} and Ana at 5$ or ${bad"
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use blueprint_engine_core::{
+    check_fs_read, check_fs_write, find_workspace_root,
+    validation::{get_float_arg, get_string_arg, require_args, require_args_range, require_float},
+    BlueprintError, NativeFunction, Result, Value,
+};
+use indexmap::IndexMap;
+use tokio::sync::RwLock;
+
+use super::builtins::{call_callable, require_callable};
+use super::diff::unified;
+use super::json::{json_to_string, value_to_json};
+
+const DEFAULT_SUITE: &str = "default";
+const DEFAULT_TOLERANCE: f64 = 1e-9;
+const SNAPSHOT_DIR: &str = "__snapshots__";
+
+/// Set by `bp test --update-snapshots`: `snapshot()` rewrites stored
+/// snapshots instead of comparing against them.
+static UPDATE_SNAPSHOTS: AtomicBool = AtomicBool::new(false);
+
+#[derive(Default)]
+struct Hooks {
+    before_each: Vec<Value>,
+    after_each: Vec<Value>,
+}
+
+struct Case {
+    suite: String,
+    name: String,
+    func: Value,
+}
+
+/// Cases wait here until `run()` or `bp test` runs them. Results of every
+/// run are kept in `completed` so `bp test` can report cases a script ran
+/// itself.
+struct Registry {
+    current: String,
+    suites: IndexMap<String, Hooks>,
+    cases: Vec<Case>,
+    completed: Vec<TestResult>,
+}
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+        current: DEFAULT_SUITE.into(),
+        suites: IndexMap::new(),
+        cases: Vec::new(),
+        completed: Vec::new(),
+    });
+}
+
+/// The outcome of one `test.case`.
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub suite: String,
+    pub name: String,
+    pub error: Option<BlueprintError>,
+    pub duration: Duration,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("suite", suite),
+        NativeFunction::new("case", case),
+        NativeFunction::new("before_each", before_each),
+        NativeFunction::new("after_each", after_each),
+        NativeFunction::new("run", run),
+        NativeFunction::new("raises", raises),
+        NativeFunction::new("approx", approx),
+        NativeFunction::new("snapshot", snapshot),
+    ]
+}
+
+pub fn set_update_snapshots(update: bool) {
+    UPDATE_SNAPSHOTS.store(update, Ordering::SeqCst);
+}
+
+fn lock() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Runs the cases still waiting to run and returns the results of every case
+/// run since the last call, in the order they ran. Used by `bp test` after
+/// each script.
+pub async fn collect_test_results() -> Vec<TestResult> {
+    run_pending().await;
+    let mut registry = lock();
+    registry.current = DEFAULT_SUITE.into();
+    registry.suites.clear();
+    std::mem::take(&mut registry.completed)
+}
+
+/// Cases registered after `suite(name)` belong to that suite and share its
+/// hooks, until the next `suite()` call.
+async fn suite(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("test.suite", &args, 1)?;
+    let name = get_string_arg("test.suite", &args, 0)?;
+    let mut registry = lock();
+    registry.suites.entry(name.clone()).or_default();
+    registry.current = name;
+    Ok(Value::None)
+}
+
+async fn case(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("test.case", &args, 2)?;
+    let name = get_string_arg("test.case", &args, 0)?;
+    require_callable("test.case", &args[1])?;
+
+    let mut registry = lock();
+    let suite = registry.current.clone();
+    if registry
+        .cases
+        .iter()
+        .any(|c| c.suite == suite && c.name == name)
+    {
+        return Err(BlueprintError::ValueError {
+            message: format!(
+                "test.case(): {:?} is already defined in suite {:?}",
+                name, suite
+            ),
+        });
+    }
+    registry.cases.push(Case {
+        suite,
+        name,
+        func: args[1].clone(),
+    });
+    Ok(Value::None)
+}
+
+/// A hook whose return value is not `None` becomes the fixture: it is passed
+/// to each case and to the `after_each` hooks.
+async fn before_each(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    add_hook("test.before_each", args, |hooks| &mut hooks.before_each)
+}
+
+async fn after_each(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    add_hook("test.after_each", args, |hooks| &mut hooks.after_each)
+}
+
+fn add_hook(
+    name: &str,
+    args: Vec<Value>,
+    list: impl FnOnce(&mut Hooks) -> &mut Vec<Value>,
+) -> Result<Value> {
+    require_args(name, &args, 1)?;
+    require_callable(name, &args[0])?;
+    let mut registry = lock();
+    let current = registry.current.clone();
+    list(registry.suites.entry(current).or_default()).push(args[0].clone());
+    Ok(Value::None)
+}
+
+/// Runs the registered cases and returns `{"passed", "failed", "results"}`.
+/// A failing case does not stop the others.
+async fn run(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("test.run", &args, 0)?;
+    let results = run_pending().await;
+
+    let passed = results.iter().filter(|r| r.passed()).count();
+    let rows = results
+        .iter()
+        .map(|result| {
+            let mut row = IndexMap::new();
+            row.insert("suite".to_string(), Value::from(result.suite.as_str()));
+            row.insert("name".to_string(), Value::from(result.name.as_str()));
+            row.insert("passed".to_string(), Value::Bool(result.passed()));
+            row.insert(
+                "error".to_string(),
+                result
+                    .error
+                    .as_ref()
+                    .map_or(Value::None, |e| Value::from(e.to_string())),
+            );
+            row.insert(
+                "seconds".to_string(),
+                Value::Float(result.duration.as_secs_f64()),
+            );
+            Value::Dict(Arc::new(RwLock::new(row)))
+        })
+        .collect();
+
+    let mut report = IndexMap::new();
+    report.insert("passed".to_string(), Value::Int(passed as i64));
+    report.insert(
+        "failed".to_string(),
+        Value::Int((results.len() - passed) as i64),
+    );
+    report.insert(
+        "results".to_string(),
+        Value::List(Arc::new(RwLock::new(rows))),
+    );
+    Ok(Value::Dict(Arc::new(RwLock::new(report))))
+}
+
+async fn run_pending() -> Vec<TestResult> {
+    let cases = std::mem::take(&mut lock().cases);
+    let mut results = Vec::with_capacity(cases.len());
+    for case in cases {
+        let (before, after) = {
+            let registry = lock();
+            registry
+                .suites
+                .get(&case.suite)
+                .map(|h| (h.before_each.clone(), h.after_each.clone()))
+                .unwrap_or_default()
+        };
+        let started = Instant::now();
+        let error = run_case(&case.func, &before, &after).await.err();
+        results.push(TestResult {
+            suite: case.suite,
+            name: case.name,
+            error,
+            duration: started.elapsed(),
+        });
+    }
+    lock().completed.extend(results.iter().cloned());
+    results
+}
+
+/// `after_each` hooks run even when the case fails; the first error wins.
+async fn run_case(func: &Value, before: &[Value], after: &[Value]) -> Result<()> {
+    let mut fixture = Value::None;
+    for hook in before {
+        let value = call_callable(hook, vec![], HashMap::new()).await?;
+        if !matches!(value, Value::None) {
+            fixture = value;
+        }
+    }
+    let args = match fixture {
+        Value::None => vec![],
+        fixture => vec![fixture],
+    };
+
+    let mut result = call_callable(func, args.clone(), HashMap::new())
+        .await
+        .map(|_| ());
+    for hook in after {
+        let teardown = call_callable(hook, args.clone(), HashMap::new()).await;
+        if let (Ok(()), Err(e)) = (&result, teardown) {
+            result = Err(e);
+        }
+    }
+    result
+}
+
+/// Calls `func` and returns the message of the error it raises, failing if
+/// it returns normally or if the message does not contain `match`.
+async fn raises(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("test.raises", &args, 1, 2)?;
+    require_callable("test.raises", &args[0])?;
+    let expected = match args.get(1).or_else(|| kwargs.get("match")) {
+        None | Some(Value::None) => None,
+        Some(v) => Some(v.as_string()?),
+    };
+
+    let error = match call_callable(&args[0], vec![], HashMap::new()).await {
+        Ok(_) => {
+            return Err(BlueprintError::AssertionError {
+                message: "expected an error, but none was raised".into(),
+            })
+        }
+        Err(e)
+            if matches!(e.inner_error(), BlueprintError::Exit { .. })
+                || e.is_resource_exhausted() =>
+        {
+            return Err(e)
+        }
+        Err(e) => e.inner_error().to_string(),
+    };
+    if let Some(expected) = expected {
+        if !error.contains(&expected) {
+            return Err(BlueprintError::AssertionError {
+                message: format!(
+                    "expected an error containing {:?}, got {:?}",
+                    expected, error
+                ),
+            });
+        }
+    }
+    Ok(Value::from(error))
+}
+
+async fn approx(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("test.approx", &args, 2, 3)?;
+    let actual = get_float_arg("test.approx", &args, 0)?;
+    let expected = get_float_arg("test.approx", &args, 1)?;
+    let tolerance = match args.get(2).or_else(|| kwargs.get("tolerance")) {
+        None => DEFAULT_TOLERANCE,
+        Some(v) => require_float(v)?,
+    };
+
+    if (actual - expected).abs() > tolerance {
+        return Err(BlueprintError::AssertionError {
+            message: format!("{} != {} within {}", actual, expected, tolerance),
+        });
+    }
+    Ok(Value::None)
+}
+
+/// Compares `value`, as JSON with sorted keys, against
+/// `__snapshots__/<name>.json` in the workspace root (or the current
+/// directory). A missing snapshot is written; a different one fails with a
+/// diff unless snapshots are being updated.
+async fn snapshot(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("test.snapshot", &args, 2)?;
+    let name = get_string_arg("test.snapshot", &args, 0)?;
+    let path = snapshot_path(&name)?;
+    let path_str = path.to_string_lossy().to_string();
+
+    let json = canonical(value_to_json(&args[1], None).await?);
+    let mut actual = json_to_string(&json, Some(2))?;
+    actual.push('\n');
+
+    check_fs_read(&path_str).await?;
+    let stored = match tokio::fs::read_to_string(&path).await {
+        Ok(stored) => Some(stored),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(BlueprintError::IoError {
+                path: path_str,
+                message: e.to_string(),
+            })
+        }
+    };
+
+    match stored {
+        Some(stored) if stored == actual => Ok(Value::None),
+        Some(stored) if !UPDATE_SNAPSHOTS.load(Ordering::SeqCst) => {
+            let lines = |text: &str| text.lines().map(str::to_string).collect::<Vec<_>>();
+            let diff = unified(&lines(&stored), &lines(&actual), 3, &path_str, "actual");
+            Err(BlueprintError::AssertionError {
+                message: format!(
+                    "snapshot {:?} does not match (run with --update-snapshots to accept)\n{}",
+                    name, diff
+                ),
+            })
+        }
+        _ => {
+            check_fs_write(&path_str).await?;
+            let write = async {
+                if let Some(dir) = path.parent() {
+                    tokio::fs::create_dir_all(dir).await?;
+                }
+                tokio::fs::write(&path, actual).await
+            };
+            write.await.map_err(|e| BlueprintError::IoError {
+                path: path_str,
+                message: e.to_string(),
+            })?;
+            Ok(Value::None)
+        }
+    }
+}
+
+/// Names may use `/` to group snapshots in subdirectories but must stay
+/// inside the snapshot directory.
+fn snapshot_path(name: &str) -> Result<PathBuf> {
+    let relative = Path::new(name);
+    let valid = !name.is_empty()
+        && relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+    if !valid {
+        return Err(BlueprintError::ValueError {
+            message: format!("test.snapshot(): invalid snapshot name {:?}", name),
+        });
+    }
+    let root = find_workspace_root()
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+    Ok(root.join(SNAPSHOT_DIR).join(format!("{}.json", name)))
+}
+
+/// Sorts object keys at every level so the stored form does not depend on
+/// insertion order.
+fn canonical(json: serde_json::Value) -> serde_json::Value {
+    match json {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, canonical(v)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(canonical).collect())
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_form_is_canonical() {
+        let json = serde_json::json!({"b": 1, "a": {"z": [{"y": 2, "x": 1}], "c": null}});
+        assert_eq!(
+            json_to_string(&canonical(json), None).unwrap(),
+            r#"{"a":{"c":null,"z":[{"x":1,"y":2}]},"b":1}"#
+        );
+
+        assert!(snapshot_path("users/list")
+            .unwrap()
+            .ends_with("__snapshots__/users/list.json"));
+        for name in ["", "../escape", "/etc/passwd", "./a"] {
+            assert!(snapshot_path(name).is_err(), "{:?}", name);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use blueprint_engine_core::{
+    validation::{get_string_arg, require_args, require_args_range},
+    BlueprintError, NativeFunction, Result, Value,
+};
+use tokio::sync::RwLock;
+
+const DEFAULT_WIDTH: i64 = 70;
+
+pub fn get_functions() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("wrap", wrap_fn),
+        NativeFunction::new("fill", fill_fn),
+        NativeFunction::new("dedent", dedent_fn),
+        NativeFunction::new("indent", indent_fn),
+    ]
+}
+
+async fn wrap_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    let lines = wrap_lines("textwrap.wrap", &args, &kwargs)?;
+    let lines = lines
+        .into_iter()
+        .map(|l| Value::String(Arc::new(l)))
+        .collect();
+    Ok(Value::List(Arc::new(RwLock::new(lines))))
+}
+
+async fn fill_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    let lines = wrap_lines("textwrap.fill", &args, &kwargs)?;
+    Ok(Value::String(Arc::new(lines.join("\n"))))
+}
+
+async fn dedent_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("textwrap.dedent", &args, 1)?;
+    let text = get_string_arg("textwrap.dedent", &args, 0)?;
+    Ok(Value::String(Arc::new(dedent(&text))))
+}
+
+async fn indent_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("textwrap.indent", &args, 2)?;
+    let text = get_string_arg("textwrap.indent", &args, 0)?;
+    let prefix = get_string_arg("textwrap.indent", &args, 1)?;
+    Ok(Value::String(Arc::new(indent(&text, &prefix))))
+}
+
+struct WrapOptions {
+    width: usize,
+    break_long_words: bool,
+    initial_indent: String,
+    subsequent_indent: String,
+}
+
+fn wrap_lines(name: &str, args: &[Value], kwargs: &HashMap<String, Value>) -> Result<Vec<String>> {
+    require_args_range(name, args, 1, 2)?;
+    let text = get_string_arg(name, args, 0)?;
+
+    let width = match args.get(1).or_else(|| kwargs.get("width")) {
+        Some(v) => v.as_int()?,
+        None => DEFAULT_WIDTH,
+    };
+    if width <= 0 {
+        return Err(BlueprintError::ValueError {
+            message: format!("{}() width must be positive, got {}", name, width),
+        });
+    }
+    let string_kwarg = |key: &str| match kwargs.get(key) {
+        Some(v) => v.as_string(),
+        None => Ok(String::new()),
+    };
+    let options = WrapOptions {
+        width: width as usize,
+        break_long_words: kwargs.get("break_long_words").is_none_or(Value::is_truthy),
+        initial_indent: string_kwarg("initial_indent")?,
+        subsequent_indent: string_kwarg("subsequent_indent")?,
+    };
+    wrap(&text, &options)
+}
+
+/// Python's `textwrap.wrap`: newlines and tabs count as spaces, lines break
+/// only between words, and a word longer than the line is split only when
+/// `break_long_words` is set (otherwise it overflows on a line of its own).
+fn wrap(text: &str, options: &WrapOptions) -> Result<Vec<String>> {
+    let mut chunks: Vec<Vec<char>> = split_chunks(text);
+    chunks.reverse();
+
+    let mut lines = Vec::new();
+    while !chunks.is_empty() {
+        let indent = if lines.is_empty() {
+            &options.initial_indent
+        } else {
+            &options.subsequent_indent
+        };
+        let width = options
+            .width
+            .checked_sub(indent.chars().count())
+            .filter(|w| *w > 0)
+            .ok_or_else(|| BlueprintError::ValueError {
+                message: "textwrap: indent is wider than the line width".into(),
+            })?;
+
+        if !lines.is_empty() && chunks.last().is_some_and(|c| is_space(c)) {
+            chunks.pop();
+        }
+
+        let mut line: Vec<Vec<char>> = Vec::new();
+        let mut len = 0;
+        while let Some(chunk) = chunks.last() {
+            if len + chunk.len() > width {
+                break;
+            }
+            len += chunk.len();
+            line.extend(chunks.pop());
+        }
+
+        if let Some(chunk) = chunks.last_mut() {
+            if chunk.len() > width {
+                if options.break_long_words {
+                    // A full line is emitted as is; the word starts the next one.
+                    if len < width {
+                        line.push(chunk.drain(..width - len).collect());
+                    }
+                } else if line.is_empty() {
+                    line.extend(chunks.pop());
+                }
+            }
+        }
+
+        if line.last().is_some_and(|c| is_space(c)) {
+            line.pop();
+        }
+        if !line.is_empty() {
+            let mut out = indent.clone();
+            out.extend(line.into_iter().flatten());
+            lines.push(out);
+        }
+    }
+    Ok(lines)
+}
+
+/// Alternating runs of word and whitespace characters, whitespace replaced by
+/// single spaces so each keeps its width.
+fn split_chunks(text: &str) -> Vec<Vec<char>> {
+    let mut chunks: Vec<Vec<char>> = Vec::new();
+    for c in text.chars() {
+        let space = c.is_whitespace();
+        let c = if space { ' ' } else { c };
+        match chunks.last_mut() {
+            Some(last) if is_space(last) == space => last.push(c),
+            _ => chunks.push(vec![c]),
+        }
+    }
+    chunks
+}
+
+fn is_space(chunk: &[char]) -> bool {
+    chunk.first() == Some(&' ')
+}
+
+/// Removes the longest leading whitespace shared by every non-blank line.
+/// Blank lines do not count towards the margin and come out empty.
+fn dedent(text: &str) -> String {
+    let mut margin: Option<&str> = None;
+    for line in text.lines() {
+        let content = line.trim_start();
+        if content.is_empty() {
+            continue;
+        }
+        let leading = &line[..line.len() - content.len()];
+        margin = Some(match margin {
+            None => leading,
+            Some(m) => {
+                let common = m
+                    .char_indices()
+                    .zip(leading.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map_or(m.len().min(leading.len()), |((i, _), _)| i);
+                &m[..common]
+            }
+        });
+    }
+    let margin = margin.unwrap_or("");
+
+    text.split_inclusive('\n')
+        .map(|line| {
+            let body = line.trim_end_matches(['\n', '\r']);
+            if body.trim().is_empty() {
+                &line[body.len()..]
+            } else {
+                line.strip_prefix(margin).unwrap_or(line)
+            }
+        })
+        .collect()
+}
+
+/// Prefixes every line that is not whitespace-only, keeping line endings.
+fn indent(text: &str, prefix: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        if !line.trim().is_empty() {
+            out.push_str(prefix);
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(width: usize, break_long_words: bool) -> WrapOptions {
+        WrapOptions {
+            width,
+            break_long_words,
+            initial_indent: String::new(),
+            subsequent_indent: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_wrap_breaks_between_words() {
+        let text = "The quick brown\nfox jumps over the lazy dog";
+        assert_eq!(
+            wrap(text, &options(15, true)).unwrap(),
+            vec!["The quick brown", "fox jumps over", "the lazy dog"]
+        );
+
+        assert_eq!(
+            wrap("see supercalifragilistic now", &options(10, true)).unwrap(),
+            vec!["see superc", "alifragili", "stic now"]
+        );
+        assert_eq!(
+            wrap("abcdefghi supercalifragilistic", &options(10, true)).unwrap(),
+            vec!["abcdefghi", "supercalif", "ragilistic"]
+        );
+        assert_eq!(
+            wrap("see supercalifragilistic now", &options(10, false)).unwrap(),
+            vec!["see", "supercalifragilistic", "now"]
+        );
+
+        let indented = WrapOptions {
+            initial_indent: "* ".into(),
+            subsequent_indent: "  ".into(),
+            ..options(12, true)
+        };
+        assert_eq!(
+            wrap("one two three four", &indented).unwrap(),
+            vec!["* one two", "  three four"]
+        );
+        assert!(wrap("", &options(10, true)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dedent_and_indent() {
+        assert_eq!(
+            dedent("    def f():\n        return 1\n  \n    f()\n"),
+            "def f():\n    return 1\n\nf()\n"
+        );
+        assert_eq!(dedent("\tone\n  two\n"), "\tone\n  two\n");
+        assert_eq!(indent("a\n\nb\n", "> "), "> a\n\n> b\n");
+    }
+}
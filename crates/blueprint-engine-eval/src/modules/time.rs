@@ -1,26 +1,142 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use blueprint_engine_core::{
-    validation::{get_float_arg, require_args},
+    session,
+    validation::{get_float_arg, require_args, require_args_range},
     BlueprintError, NativeFunction, Result, Value,
 };
+use futures_util::future::BoxFuture;
 use tokio::time::{sleep, Duration};
 
+pub(crate) type TimerCallback = Arc<dyn Fn() -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// A trigger created while the clock was frozen. It fires only when
+/// `advance`, `sleep` or `sleep_until` move the frozen clock past `due`.
+struct VirtualTimer {
+    id: String,
+    due: f64,
+    next: Box<dyn FnMut(f64) -> Option<f64> + Send>,
+    run: TimerCallback,
+}
+
+/// A script run's clock. Each run starts unfrozen with no timers, so one
+/// script freezing time never affects another.
+#[derive(Default)]
+struct Clock {
+    frozen_at: Mutex<Option<f64>>,
+    timers: Mutex<Vec<VirtualTimer>>,
+}
+
+fn clock() -> Arc<Clock> {
+    session().get::<Clock>()
+}
+
 pub fn get_functions() -> Vec<NativeFunction> {
     vec![
         NativeFunction::new("now", now),
         NativeFunction::new("sleep", sleep_fn),
+        NativeFunction::new("sleep_until", sleep_until_fn),
         NativeFunction::new("time", now),
+        NativeFunction::new("freeze", freeze_fn),
+        NativeFunction::new("unfreeze", unfreeze_fn),
+        NativeFunction::new("advance", advance_fn),
     ]
 }
 
-async fn now(_args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
-    let duration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Pins `time.now()` to `epoch` seconds for the current script run. Used by
+/// `bp run --freeze-time`.
+pub fn freeze_clock(epoch: f64) {
+    *lock(&clock().frozen_at) = Some(epoch);
+}
+
+fn frozen_at() -> Option<f64> {
+    *lock(&clock().frozen_at)
+}
+
+pub(crate) fn is_frozen() -> bool {
+    frozen_at().is_some()
+}
 
-    Ok(Value::Float(duration.as_secs_f64()))
+/// Seconds since the Unix epoch, or the frozen instant if the clock is frozen.
+pub(crate) fn current_time() -> f64 {
+    frozen_at().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+    })
+}
+
+/// Registers a timer on the frozen clock. `next` maps the time of one firing
+/// (or the current time, for the first) to the next due time; `None` ends it.
+pub(crate) fn schedule_virtual(
+    id: &str,
+    mut next: impl FnMut(f64) -> Option<f64> + Send + 'static,
+    run: TimerCallback,
+) {
+    if let Some(due) = next(current_time()) {
+        lock(&clock().timers).push(VirtualTimer {
+            id: id.to_string(),
+            due,
+            next: Box::new(next),
+            run,
+        });
+    }
+}
+
+pub(crate) fn cancel_virtual(id: &str) {
+    lock(&clock().timers).retain(|timer| timer.id != id);
+}
+
+/// Moves the frozen clock forward to `target`, stopping at each timer that
+/// falls due on the way and running it before going on, earliest first.
+async fn advance_to(target: f64) {
+    let clock = clock();
+    loop {
+        let fire = {
+            let Some(now) = *lock(&clock.frozen_at) else {
+                return;
+            };
+            let mut timers = lock(&clock.timers);
+            let earliest = timers
+                .iter()
+                .enumerate()
+                .filter(|(_, timer)| timer.due <= target)
+                .min_by(|(_, a), (_, b)| a.due.total_cmp(&b.due))
+                .map(|(i, _)| i);
+            earliest.map(|i| {
+                let timer = &mut timers[i];
+                let due = timer.due;
+                let run = timer.run.clone();
+                match (timer.next)(due) {
+                    Some(next) => timer.due = next,
+                    None => {
+                        timers.remove(i);
+                    }
+                }
+                (due.max(now), run)
+            })
+        };
+        let Some((due, run)) = fire else {
+            break;
+        };
+        *lock(&clock.frozen_at) = Some(due);
+        run().await;
+    }
+    let mut frozen_at = lock(&clock.frozen_at);
+    if let Some(now) = *frozen_at {
+        *frozen_at = Some(target.max(now));
+    }
+}
+
+async fn now(_args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    Ok(Value::Float(current_time()))
 }
 
 async fn sleep_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
@@ -33,7 +149,124 @@ async fn sleep_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<V
         });
     }
 
-    sleep(Duration::from_secs_f64(seconds)).await;
+    if is_frozen() {
+        advance_to(current_time() + seconds).await;
+    } else {
+        sleep(Duration::from_secs_f64(seconds)).await;
+    }
+
+    Ok(Value::None)
+}
+
+/// Sleeps until the Unix timestamp `epoch`; returns at once if it has passed.
+async fn sleep_until_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("time.sleep_until", &args, 1)?;
+    let epoch = get_float_arg("time.sleep_until", &args, 0)?;
+
+    if is_frozen() {
+        advance_to(epoch).await;
+    } else {
+        let remaining = epoch - current_time();
+        if remaining > 0.0 {
+            sleep(Duration::from_secs_f64(remaining)).await;
+        }
+    }
+
+    Ok(Value::None)
+}
+
+/// Stops the clock at `epoch` (default: the current time) and returns it.
+async fn freeze_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args_range("time.freeze", &args, 0, 1)?;
+    let epoch = match args.first() {
+        None | Some(Value::None) => current_time(),
+        Some(_) => get_float_arg("time.freeze", &args, 0)?,
+    };
+    freeze_clock(epoch);
+    Ok(Value::Float(epoch))
+}
 
+async fn unfreeze_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("time.unfreeze", &args, 0)?;
+    *lock(&clock().frozen_at) = None;
     Ok(Value::None)
 }
+
+/// Moves the frozen clock forward, firing any virtual timers that fall due,
+/// and returns the new time.
+async fn advance_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
+    require_args("time.advance", &args, 1)?;
+    let seconds = get_float_arg("time.advance", &args, 0)?;
+
+    if seconds < 0.0 {
+        return Err(BlueprintError::ValueError {
+            message: "advance() argument must not be negative".into(),
+        });
+    }
+    let Some(now) = frozen_at() else {
+        return Err(BlueprintError::ValueError {
+            message: "advance() requires a frozen clock; call freeze() first".into(),
+        });
+    };
+
+    advance_to(now + seconds).await;
+    Ok(Value::Float(current_time()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blueprint_engine_core::{with_session, Session};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_frozen_clock_fires_virtual_timers() {
+        with_session(Arc::new(Session::default()), frozen_clock_run()).await;
+
+        // A later run starts with a running clock.
+        let other = with_session(Arc::new(Session::default()), async { is_frozen() });
+        assert!(!other.await);
+    }
+
+    async fn frozen_clock_run() {
+        freeze_clock(1000.0);
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let (log, count) = (fired.clone(), calls.clone());
+        schedule_virtual(
+            "test-every-10",
+            |t| Some(t + 10.0),
+            Arc::new(move || -> BoxFuture<'static, ()> {
+                lock(&log).push(current_time());
+                count.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async {})
+            }),
+        );
+
+        let now = advance_fn(vec![Value::Int(25)], HashMap::new())
+            .await
+            .unwrap();
+        assert!(matches!(now, Value::Float(t) if t == 1025.0));
+        assert_eq!(*lock(&fired), vec![1010.0, 1020.0]);
+
+        sleep_fn(vec![Value::Float(5.0)], HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(current_time(), 1030.0);
+
+        cancel_virtual("test-every-10");
+        sleep_until_fn(vec![Value::Int(2000)], HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(current_time(), 2000.0);
+
+        unfreeze_fn(vec![], HashMap::new()).await.unwrap();
+        assert!(advance_fn(vec![Value::Int(1)], HashMap::new())
+            .await
+            .is_err());
+        assert!(current_time() > 1_600_000_000.0);
+    }
+}
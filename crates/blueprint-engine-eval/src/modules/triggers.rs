@@ -7,27 +7,39 @@ use std::time::Duration;
 
 use axum::{
     body::Body,
-    http::{Request, StatusCode},
+    http::{HeaderName, HeaderValue, Request, StatusCode},
     response::IntoResponse,
     routing::{delete, get, head, patch, post, put},
     Router,
 };
-use blueprint_engine_core::{BlueprintError, NativeFunction, Result, Value};
+use blueprint_engine_core::{
+    inherit_permissions, inherit_session, session, with_session, BlueprintError, NativeFunction,
+    Result, Value,
+};
+use futures_util::future::BoxFuture;
 use tokio::sync::{oneshot, RwLock};
-use tokio_cron_scheduler::{Job, JobScheduler};
 
+use super::builtins::call_callable;
+use super::random::random_id;
+use super::time;
 use crate::eval::Evaluator;
 
-fn random_id() -> String {
-    use rand::Rng;
-    let bytes: [u8; 4] = rand::thread_rng().gen();
-    hex::encode(bytes)
-}
+const DEFAULT_SHUTDOWN_TIMEOUT: f64 = 5.0;
 
 lazy_static::lazy_static! {
     pub static ref TRIGGER_REGISTRY: Arc<RwLock<TriggerRegistry>> = Arc::new(RwLock::new(TriggerRegistry::new()));
 }
 
+/// Handlers registered with `on_shutdown` in the current session.
+#[derive(Default)]
+struct ShutdownHooks(std::sync::Mutex<Vec<(Value, Duration)>>);
+
+impl ShutdownHooks {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(Value, Duration)>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
 pub struct TriggerRegistry {
     triggers: HashMap<String, TriggerHandle>,
     shutdown_senders: HashMap<String, oneshot::Sender<()>>,
@@ -57,6 +69,10 @@ pub enum TriggerType {
         command: String,
         pid: u32,
     },
+    Watch {
+        path: String,
+        recursive: bool,
+    },
 }
 
 impl TriggerRegistry {
@@ -84,6 +100,7 @@ impl TriggerRegistry {
         }
         if let Some(tx) = self.shutdown_senders.remove(id) {
             let _ = tx.send(());
+            time::cancel_virtual(id);
             self.triggers.remove(id);
             true
         } else {
@@ -126,6 +143,7 @@ pub fn get_functions() -> Vec<NativeFunction> {
         NativeFunction::new("running", running_fn),
         NativeFunction::new("triggers", triggers_fn),
         NativeFunction::new("wait_for_port", wait_for_port_fn),
+        NativeFunction::new("on_shutdown", on_shutdown_fn),
     ]
 }
 
@@ -140,6 +158,32 @@ pub async fn wait_for_shutdown() {
             break;
         }
     }
+    run_shutdown_hooks().await;
+}
+
+pub async fn shutdown_all() {
+    TRIGGER_REGISTRY.write().await.stop_all();
+    run_shutdown_hooks().await;
+}
+
+/// Runs the handlers the current session registered with `on_shutdown`,
+/// most recent first. Each gets its own timeout; failures are reported and do
+/// not stop later handlers.
+pub async fn run_shutdown_hooks() {
+    let hooks = session().get::<ShutdownHooks>();
+    loop {
+        let Some((handler, timeout)) = hooks.lock().pop() else {
+            break;
+        };
+        match tokio::time::timeout(timeout, execute_trigger_handler(handler)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => eprintln!("on_shutdown handler failed: {}", e),
+            Err(_) => eprintln!(
+                "on_shutdown handler timed out after {:.1}s",
+                timeout.as_secs_f64()
+            ),
+        }
+    }
 }
 
 fn handle_to_value(handle: &TriggerHandle) -> Value {
@@ -191,6 +235,14 @@ fn handle_to_value(handle: &TriggerHandle) -> Value {
             );
             map.insert("pid".to_string(), Value::Int(*pid as i64));
         }
+        TriggerType::Watch { path, recursive } => {
+            map.insert(
+                "type".to_string(),
+                Value::String(Arc::new("watch".to_string())),
+            );
+            map.insert("path".to_string(), Value::String(Arc::new(path.clone())));
+            map.insert("recursive".to_string(), Value::Bool(*recursive));
+        }
     }
 
     Value::Dict(Arc::new(RwLock::new(map)))
@@ -222,9 +274,6 @@ async fn http_server_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Res
         }
     };
 
-    let id = format!("http-{}", random_id());
-    let running = Arc::new(RwLock::new(true));
-
     let mut router = Router::new();
     let mut route_list = Vec::new();
 
@@ -245,9 +294,12 @@ async fn http_server_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Res
 
         route_list.push(route_key.clone());
 
+        let route_session = session();
         let handler_fn = move |req: Request<Body>| {
             let handler = handler_clone.clone();
-            async move { execute_http_handler(handler, req).await }
+            with_session(route_session.clone(), async move {
+                execute_http_handler(handler, req).await
+            })
         };
 
         router = match method.as_str() {
@@ -265,6 +317,17 @@ async fn http_server_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Res
         };
     }
 
+    spawn_http_server(host, port, router, route_list).await
+}
+
+/// Binds `host:port`, serves `router` in the background and registers it as
+/// an `http` trigger so `wait_for_shutdown` and `stop()` see it.
+pub(crate) async fn spawn_http_server(
+    host: String,
+    port: u16,
+    router: Router,
+    routes: Vec<String>,
+) -> Result<Value> {
     let addr: SocketAddr =
         format!("{}:{}", host, port)
             .parse()
@@ -272,16 +335,21 @@ async fn http_server_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Res
                 message: format!("Invalid address: {}", e),
             })?;
 
+    let listener =
+        tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| BlueprintError::IoError {
+                path: format!("{}:{}", host, port),
+                message: e.to_string(),
+            })?;
+
+    let id = format!("http-{}", random_id());
     let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
 
     let handle = TriggerHandle {
         id: id.clone(),
-        trigger_type: TriggerType::Http {
-            port,
-            host: host.clone(),
-            routes: route_list,
-        },
-        running: running.clone(),
+        trigger_type: TriggerType::Http { port, host, routes },
+        running: Arc::new(RwLock::new(true)),
     };
 
     TRIGGER_REGISTRY
@@ -289,15 +357,6 @@ async fn http_server_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Res
         .await
         .register(handle.clone(), Some(shutdown_tx));
 
-    let listener =
-        tokio::net::TcpListener::bind(addr)
-            .await
-            .map_err(|e| BlueprintError::IoError {
-                path: format!("{}:{}", host, port),
-                message: e.to_string(),
-            })?;
-
-    let id_clone = id.clone();
     tokio::spawn(async move {
         axum::serve(listener, router)
             .with_graceful_shutdown(async {
@@ -305,13 +364,116 @@ async fn http_server_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Res
             })
             .await
             .ok();
-        TRIGGER_REGISTRY.write().await.triggers.remove(&id_clone);
+        TRIGGER_REGISTRY.write().await.triggers.remove(&id);
     });
 
     Ok(handle_to_value(&handle))
 }
 
-async fn execute_http_handler(handler: Value, req: Request<Body>) -> impl IntoResponse {
+/// Watches `path` and calls `handler` with a `{path, kind}` dict per changed
+/// path. Events are collected until `debounce` passes without a new one, and
+/// repeats for the same path within a burst are reported once.
+pub(crate) async fn spawn_file_watch(
+    path: String,
+    recursive: bool,
+    debounce: Duration,
+    handler: Value,
+) -> Result<Value> {
+    use notify::{RecursiveMode, Watcher};
+
+    let watch_error = |e: notify::Error| BlueprintError::IoError {
+        path: path.clone(),
+        message: e.to_string(),
+    };
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let _ = event_tx.send(event);
+        }
+    })
+    .map_err(watch_error)?;
+    let mode = if recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(std::path::Path::new(&path), mode)
+        .map_err(watch_error)?;
+
+    let id = format!("watch-{}", random_id());
+    let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
+
+    let handle = TriggerHandle {
+        id: id.clone(),
+        trigger_type: TriggerType::Watch { path, recursive },
+        running: Arc::new(RwLock::new(true)),
+    };
+
+    TRIGGER_REGISTRY
+        .write()
+        .await
+        .register(handle.clone(), Some(shutdown_tx));
+
+    tokio::spawn(inherit_permissions(async move {
+        let _watcher = watcher;
+        loop {
+            let first = tokio::select! {
+                event = event_rx.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                _ = &mut shutdown_rx => break,
+            };
+
+            let mut changes: IndexMap<String, &'static str> = IndexMap::new();
+            let mut record = |event: notify::Event| {
+                let Some(kind) = watch_event_kind(&event.kind) else {
+                    return;
+                };
+                for changed in event.paths {
+                    changes.insert(changed.display().to_string(), kind);
+                }
+            };
+            record(first);
+            while let Ok(Some(event)) = tokio::time::timeout(debounce, event_rx.recv()).await {
+                record(event);
+            }
+
+            for (changed, kind) in changes {
+                let mut event = IndexMap::new();
+                event.insert("path".to_string(), Value::String(Arc::new(changed)));
+                event.insert(
+                    "kind".to_string(),
+                    Value::String(Arc::new(kind.to_string())),
+                );
+                let event = Value::Dict(Arc::new(RwLock::new(event)));
+                if let Err(e) = call_callable(&handler, vec![event], HashMap::new()).await {
+                    eprintln!("file.watch handler failed: {}", e);
+                }
+            }
+        }
+        TRIGGER_REGISTRY.write().await.triggers.remove(&id);
+    }));
+
+    Ok(handle_to_value(&handle))
+}
+
+fn watch_event_kind(kind: &notify::EventKind) -> Option<&'static str> {
+    use notify::event::ModifyKind;
+    use notify::EventKind;
+
+    match kind {
+        EventKind::Create(_) => Some("created"),
+        EventKind::Modify(ModifyKind::Name(_)) => Some("renamed"),
+        EventKind::Modify(_) => Some("modified"),
+        EventKind::Remove(_) => Some("removed"),
+        EventKind::Access(_) => None,
+        EventKind::Any | EventKind::Other => Some("other"),
+    }
+}
+
+pub(crate) async fn execute_http_handler(handler: Value, req: Request<Body>) -> impl IntoResponse {
     let method = req.method().to_string();
     let path = req.uri().path().to_string();
     let query = req.uri().query().unwrap_or("").to_string();
@@ -396,7 +558,18 @@ async fn build_http_response(value: Value) -> axum::response::Response {
                     None => String::new(),
                 };
 
-                (status_code, body).into_response()
+                let mut response = (status_code, body).into_response();
+                if let Some(Value::Dict(headers)) = dict.get("headers") {
+                    for (name, value) in headers.read().await.iter() {
+                        if let (Ok(name), Ok(value)) = (
+                            HeaderName::from_bytes(name.as_bytes()),
+                            HeaderValue::from_str(&value.to_display_string()),
+                        ) {
+                            response.headers_mut().insert(name, value);
+                        }
+                    }
+                }
+                response
             } else {
                 drop(dict);
                 let json = serde_json::to_string(&value_to_json(&Value::Dict(d)).await)
@@ -463,11 +636,18 @@ async fn cron_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
     let handler = args[1].clone();
 
     let schedule = normalize_cron_schedule(&schedule_input);
+    let cron = croner::Cron::new(&schedule)
+        .with_seconds_required()
+        .with_dom_and_dow()
+        .parse()
+        .map_err(|e| BlueprintError::ArgumentError {
+            message: format!("Invalid cron schedule '{}': {}", schedule_input, e),
+        })?;
 
     let id = format!("cron-{}", random_id());
     let running = Arc::new(RwLock::new(true));
 
-    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
 
     let handle = TriggerHandle {
         id: id.clone(),
@@ -482,44 +662,35 @@ async fn cron_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Va
         .await
         .register(handle.clone(), Some(shutdown_tx));
 
-    let mut sched = JobScheduler::new()
-        .await
-        .map_err(|e| BlueprintError::InternalError {
-            message: format!("Failed to create scheduler: {}", e),
-        })?;
-
-    let handler_clone = handler.clone();
-    let id_clone = id.clone();
-
-    let job = Job::new_async(schedule.as_str(), move |_uuid, _lock| {
-        let handler = handler_clone.clone();
-        Box::pin(async move {
-            let _ = execute_trigger_handler(handler).await;
-        })
-    })
-    .map_err(|e| BlueprintError::ArgumentError {
-        message: format!("Invalid cron schedule '{}': {}", schedule_input, e),
-    })?;
-
-    sched
-        .add(job)
-        .await
-        .map_err(|e| BlueprintError::InternalError {
-            message: format!("Failed to add cron job: {}", e),
-        })?;
-
-    sched
-        .start()
-        .await
-        .map_err(|e| BlueprintError::InternalError {
-            message: format!("Failed to start scheduler: {}", e),
-        })?;
+    if time::is_frozen() {
+        let next = move |t: f64| {
+            let from = chrono::DateTime::from_timestamp(t.floor() as i64, 0)?;
+            let next = cron.find_next_occurrence(&from, false).ok()?;
+            Some(next.timestamp() as f64)
+        };
+        time::schedule_virtual(&id, next, virtual_handler(handler));
+        spawn_virtual_shutdown(id, shutdown_rx);
+        return Ok(handle_to_value(&handle));
+    }
 
-    tokio::spawn(async move {
-        let _ = shutdown_rx.await;
-        sched.shutdown().await.ok();
-        TRIGGER_REGISTRY.write().await.triggers.remove(&id_clone);
-    });
+    tokio::spawn(inherit_session(async move {
+        loop {
+            let now = chrono::Utc::now();
+            let Ok(next) = cron.find_next_occurrence(&now, false) else {
+                break;
+            };
+            let delay = (next - now).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {
+                    let _ = execute_trigger_handler(handler.clone()).await;
+                }
+                _ = &mut shutdown_rx => {
+                    break;
+                }
+            }
+        }
+        TRIGGER_REGISTRY.write().await.triggers.remove(&id);
+    }));
 
     Ok(handle_to_value(&handle))
 }
@@ -531,7 +702,13 @@ async fn interval_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resul
         });
     }
 
-    let seconds = args[0].as_int()? as u64;
+    let seconds = args[0].as_int()?;
+    if seconds <= 0 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!("interval() seconds must be positive, got {}", seconds),
+        });
+    }
+    let seconds = seconds as u64;
     let handler = args[1].clone();
 
     let id = format!("interval-{}", random_id());
@@ -552,7 +729,14 @@ async fn interval_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resul
 
     let id_clone = id.clone();
 
-    tokio::spawn(async move {
+    if time::is_frozen() {
+        let step = seconds as f64;
+        time::schedule_virtual(&id, move |t| Some(t + step), virtual_handler(handler));
+        spawn_virtual_shutdown(id_clone, shutdown_rx);
+        return Ok(handle_to_value(&handle));
+    }
+
+    tokio::spawn(inherit_session(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(seconds));
         interval.tick().await;
 
@@ -567,11 +751,32 @@ async fn interval_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Resul
             }
         }
         TRIGGER_REGISTRY.write().await.triggers.remove(&id_clone);
-    });
+    }));
 
     Ok(handle_to_value(&handle))
 }
 
+/// Wraps a trigger handler for a timer on the frozen clock. Errors are
+/// dropped, as for handlers run by the real schedulers.
+fn virtual_handler(handler: Value) -> time::TimerCallback {
+    Arc::new(move || -> BoxFuture<'static, ()> {
+        let handler = handler.clone();
+        Box::pin(async move {
+            let _ = execute_trigger_handler(handler).await;
+        })
+    })
+}
+
+/// Drops the timer once the trigger is stopped. `stop()` also cancels it
+/// directly so no firing can slip in before this task runs.
+fn spawn_virtual_shutdown(id: String, shutdown_rx: oneshot::Receiver<()>) {
+    tokio::spawn(inherit_session(async move {
+        let _ = shutdown_rx.await;
+        time::cancel_virtual(&id);
+        TRIGGER_REGISTRY.write().await.triggers.remove(&id);
+    }));
+}
+
 async fn spawn_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.is_empty() {
         return Err(BlueprintError::ArgumentError {
@@ -742,6 +947,45 @@ async fn execute_trigger_handler(handler: Value) -> Result<Value> {
     }
 }
 
+async fn on_shutdown_fn(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
+    if args.len() != 1 {
+        return Err(BlueprintError::ArgumentError {
+            message: format!(
+                "on_shutdown() takes exactly 1 argument ({} given)",
+                args.len()
+            ),
+        });
+    }
+
+    let handler = args[0].clone();
+    if !matches!(
+        handler,
+        Value::Function(_) | Value::Lambda(_) | Value::NativeFunction(_)
+    ) {
+        return Err(BlueprintError::TypeError {
+            expected: "callable".into(),
+            actual: handler.type_name().into(),
+        });
+    }
+
+    let seconds = match kwargs.get("timeout") {
+        Some(v) => v.as_float()?,
+        None => DEFAULT_SHUTDOWN_TIMEOUT,
+    };
+    let timeout = Duration::try_from_secs_f64(seconds).map_err(|_| BlueprintError::ValueError {
+        message: format!(
+            "on_shutdown() timeout must be a non-negative number, got {}",
+            seconds
+        ),
+    })?;
+
+    session()
+        .get::<ShutdownHooks>()
+        .lock()
+        .push((handler, timeout));
+    Ok(Value::None)
+}
+
 async fn stop_fn(args: Vec<Value>, _kwargs: HashMap<String, Value>) -> Result<Value> {
     if args.is_empty() {
         return Err(BlueprintError::ArgumentError {
@@ -11,9 +11,9 @@ use axum::{
     Router,
 };
 use blueprint_engine_core::{
-    check_ws,
+    check_ws, session,
     validation::{get_int_arg, get_string_arg, require_args, require_args_min},
-    BlueprintError, NativeFunction, Result, StreamIterator, Value,
+    with_session, BlueprintError, NativeFunction, Result, Session, StreamIterator, Value,
 };
 use futures_util::{SinkExt, StreamExt};
 use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
@@ -21,11 +21,7 @@ use tokio_tungstenite::{connect_async, tungstenite::Message};
 
 use crate::eval::Evaluator;
 
-fn random_id() -> String {
-    use rand::Rng;
-    let bytes: [u8; 4] = rand::thread_rng().gen();
-    hex::encode(bytes)
-}
+use crate::modules::random::random_id;
 use crate::modules::triggers::{TriggerHandle, TriggerType, TRIGGER_REGISTRY};
 
 pub fn get_functions() -> Vec<NativeFunction> {
@@ -210,6 +206,7 @@ async fn ws_connect(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<
 #[derive(Clone)]
 struct WsServerState {
     handler: Value,
+    session: Arc<Session>,
 }
 
 async fn ws_server(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<Value> {
@@ -232,7 +229,10 @@ async fn ws_server(args: Vec<Value>, kwargs: HashMap<String, Value>) -> Result<V
     let id = format!("ws-{}", random_id());
     let running = Arc::new(RwLock::new(true));
 
-    let state = WsServerState { handler };
+    let state = WsServerState {
+        handler,
+        session: session(),
+    };
 
     let path_clone = path.clone();
     let router = Router::new()
@@ -293,7 +293,9 @@ async fn ws_upgrade_handler(
     ws: WebSocketUpgrade,
     State(state): State<WsServerState>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_ws_connection(socket, state.handler))
+    ws.on_upgrade(move |socket| {
+        with_session(state.session, handle_ws_connection(socket, state.handler))
+    })
 }
 
 async fn handle_ws_connection(socket: WebSocket, handler: Value) {
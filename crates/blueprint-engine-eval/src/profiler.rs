@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use blueprint_engine_core::StackFrame;
+
+type LiveStack = Arc<Mutex<Vec<StackFrame>>>;
+/// Weak handles to every registered call stack, with the root it is labelled by.
+type StackRegistry = Vec<(String, Weak<Mutex<Vec<StackFrame>>>)>;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref STACKS: Mutex<StackRegistry> = Mutex::new(Vec::new());
+    static ref SAMPLER: Mutex<Option<Sampler>> = Mutex::new(None);
+}
+
+tokio::task_local! {
+    static CURRENT_STACK: (String, LiveStack);
+}
+
+struct Sampler {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<HashMap<String, u64>>,
+}
+
+/// Starts a background thread that samples every live call stack once per
+/// `interval`. Until this is called the evaluator does no stack bookkeeping.
+pub fn start_profiler(interval: Duration) {
+    let mut sampler = SAMPLER.lock().unwrap();
+    if sampler.is_some() {
+        return;
+    }
+
+    ENABLED.store(true, Ordering::Relaxed);
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_flag = stop.clone();
+    let handle = std::thread::spawn(move || {
+        let mut folded: HashMap<String, u64> = HashMap::new();
+        while !stop_flag.load(Ordering::Relaxed) {
+            std::thread::sleep(interval);
+            sample(&mut folded);
+        }
+        folded
+    });
+
+    *sampler = Some(Sampler { stop, handle });
+}
+
+/// Stops sampling and returns the collected stacks in folded format
+/// (`root;outer;inner count` per line), sorted for stable output.
+pub fn stop_profiler() -> String {
+    ENABLED.store(false, Ordering::Relaxed);
+
+    let Some(sampler) = SAMPLER.lock().unwrap().take() else {
+        return String::new();
+    };
+    sampler.stop.store(true, Ordering::Relaxed);
+    let folded = sampler.handle.join().unwrap_or_default();
+
+    let mut lines: Vec<String> = folded
+        .into_iter()
+        .map(|(stack, count)| format!("{} {}", stack, count))
+        .collect();
+    lines.sort();
+    lines.join("\n")
+}
+
+fn sample(folded: &mut HashMap<String, u64>) {
+    let mut stacks = STACKS.lock().unwrap();
+    stacks.retain(|(_, stack)| stack.strong_count() > 0);
+
+    for (root, stack) in stacks.iter() {
+        let Some(stack) = stack.upgrade() else {
+            continue;
+        };
+        let frames = stack.lock().unwrap();
+        let mut key = root.clone();
+        for frame in frames.iter() {
+            key.push(';');
+            key.push_str(&frame_label(frame));
+        }
+        *folded.entry(key).or_default() += 1;
+    }
+}
+
+fn frame_label(frame: &StackFrame) -> String {
+    match &frame.file {
+        Some(file) => {
+            let name = std::path::Path::new(file)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| file.clone());
+            format!("{}@{}", frame.function_name, name)
+        }
+        None => frame.function_name.clone(),
+    }
+}
+
+fn register(root: String, frames: Vec<StackFrame>) -> LiveStack {
+    let stack = Arc::new(Mutex::new(frames));
+    STACKS.lock().unwrap().push((root, Arc::downgrade(&stack)));
+    stack
+}
+
+/// Runs `fut` with its own sampled call stack labelled `root`.
+pub async fn with_profile_stack<F: Future>(root: &str, fut: F) -> F::Output {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return fut.await;
+    }
+    let stack = register(root.to_string(), Vec::new());
+    CURRENT_STACK.scope((root.to_string(), stack), fut).await
+}
+
+/// Wraps a future about to be spawned so its samples continue the caller's
+/// stack instead of being lost.
+pub(crate) fn inherit<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    let parent = if ENABLED.load(Ordering::Relaxed) {
+        CURRENT_STACK
+            .try_with(|(root, stack)| (root.clone(), stack.lock().unwrap().clone()))
+            .ok()
+    } else {
+        None
+    };
+
+    async move {
+        match parent {
+            Some((root, frames)) => {
+                let stack = register(root.clone(), frames);
+                CURRENT_STACK.scope((root, stack), fut).await
+            }
+            None => fut.await,
+        }
+    }
+}
+
+/// Pops the frame pushed by `enter` when the call returns or is cancelled.
+pub(crate) struct FrameGuard {
+    stack: LiveStack,
+}
+
+impl Drop for FrameGuard {
+    fn drop(&mut self) {
+        self.stack.lock().unwrap().pop();
+    }
+}
+
+pub(crate) fn enter(frame: impl FnOnce() -> StackFrame) -> Option<FrameGuard> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let stack = CURRENT_STACK.try_with(|(_, stack)| stack.clone()).ok()?;
+    stack.lock().unwrap().push(frame());
+    Some(FrameGuard { stack })
+}
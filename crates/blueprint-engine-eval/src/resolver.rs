@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use blueprint_engine_core::{BlueprintError, Result};
+
+pub struct ResolvedModule {
+    /// Identifies the module in error messages, the module cache and cycle
+    /// detection; it is also passed back as `importer` for nested loads.
+    pub name: String,
+    pub source: String,
+}
+
+/// Host hook consulted by `load()` before the filesystem. `@bp/` modules are
+/// always served by the stdlib and never reach the resolver.
+pub trait ModuleResolver: Send + Sync {
+    /// Returns the module to load, `Ok(None)` to fall back to normal path
+    /// resolution, or an error to reject the load.
+    fn resolve(&self, module_path: &str, importer: Option<&Path>)
+        -> Result<Option<ResolvedModule>>;
+}
+
+/// Serves modules from an in-memory map and rejects everything else, so
+/// scripts cannot load files from disk.
+#[derive(Default)]
+pub struct MemoryResolver {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_module(mut self, path: impl Into<String>, source: impl Into<String>) -> Self {
+        self.modules.insert(path.into(), source.into());
+        self
+    }
+}
+
+impl ModuleResolver for MemoryResolver {
+    fn resolve(
+        &self,
+        module_path: &str,
+        _importer: Option<&Path>,
+    ) -> Result<Option<ResolvedModule>> {
+        match self.modules.get(module_path) {
+            Some(source) => Ok(Some(ResolvedModule {
+                name: module_path.to_string(),
+                source: source.clone(),
+            })),
+            None => Err(BlueprintError::ImportError {
+                message: format!("Module '{}' is not available", module_path),
+            }),
+        }
+    }
+}
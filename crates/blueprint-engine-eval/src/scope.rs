@@ -17,6 +17,7 @@ pub struct Scope {
     parent: Option<Arc<Scope>>,
     kind: ScopeKind,
     yield_tx: Option<mpsc::Sender<GeneratorMessage>>,
+    function_id: Option<usize>,
 }
 
 impl std::fmt::Debug for Scope {
@@ -35,6 +36,7 @@ impl Scope {
             parent: None,
             kind: ScopeKind::Global,
             yield_tx: None,
+            function_id: None,
         })
     }
 
@@ -44,6 +46,7 @@ impl Scope {
             parent: Some(parent),
             kind,
             yield_tx: None,
+            function_id: None,
         })
     }
 
@@ -56,9 +59,32 @@ impl Scope {
             parent: Some(parent),
             kind: ScopeKind::Generator,
             yield_tx: Some(yield_tx),
+            function_id: None,
         })
     }
 
+    /// Scope for one call of a user function, tagged with the function's
+    /// identity so `return f(...)` can recognise a self tail call.
+    pub(crate) fn new_function_call(parent: Arc<Scope>, function_id: usize) -> Arc<Self> {
+        Arc::new(Self {
+            variables: RwLock::new(HashMap::new()),
+            parent: Some(parent),
+            kind: ScopeKind::Function,
+            yield_tx: None,
+            function_id: Some(function_id),
+        })
+    }
+
+    /// Identity of the function whose body is running in this scope, if it
+    /// was entered through a regular call rather than as a generator.
+    pub(crate) fn function_id(&self) -> Option<usize> {
+        match self.kind {
+            ScopeKind::Function => self.function_id,
+            ScopeKind::Global | ScopeKind::Generator => None,
+            ScopeKind::Loop | ScopeKind::Block => self.parent.as_ref()?.function_id(),
+        }
+    }
+
     pub fn get_yield_tx(&self) -> Option<mpsc::Sender<GeneratorMessage>> {
         if let Some(ref tx) = self.yield_tx {
             return Some(tx.clone());
@@ -109,6 +135,36 @@ impl Scope {
         }
     }
 
+    /// Appends `piece` to the string bound to `name` if that binding is still
+    /// `current` and is the one `set` would replace. `current` is released
+    /// first, so the string is only copied when something else still holds it
+    /// and repeated `s += piece` is amortized linear. Otherwise hands
+    /// `current` back, changing nothing.
+    #[async_recursion::async_recursion]
+    pub(crate) async fn append_str(
+        &self,
+        name: &str,
+        current: Arc<String>,
+        piece: &str,
+    ) -> std::result::Result<(), Arc<String>> {
+        if let Some(value) = self.variables.write().await.get_mut(name) {
+            return match value {
+                Value::String(s) if Arc::ptr_eq(s, &current) => {
+                    drop(current);
+                    Arc::make_mut(s).push_str(piece);
+                    Ok(())
+                }
+                _ => Err(current),
+            };
+        }
+        match (self.kind, &self.parent) {
+            (ScopeKind::Loop | ScopeKind::Block, Some(parent)) => {
+                parent.append_str(name, current, piece).await
+            }
+            _ => Err(current),
+        }
+    }
+
     pub async fn define(&self, name: &str, value: Value) {
         self.variables.write().await.insert(name.to_string(), value);
     }
@@ -143,6 +199,28 @@ impl Scope {
         self.parent.as_ref()
     }
 
+    /// The module-level scope this scope was created under.
+    pub fn root(self: &Arc<Self>) -> Arc<Scope> {
+        match &self.parent {
+            Some(parent) => parent.root(),
+            None => self.clone(),
+        }
+    }
+
+    /// Bindings of the innermost function or generator body (or of the module,
+    /// at top level), with loop and block bindings shadowing outer ones.
+    #[async_recursion::async_recursion]
+    pub async fn local_variables(&self) -> HashMap<String, Value> {
+        let mut vars = match (self.kind, &self.parent) {
+            (ScopeKind::Loop | ScopeKind::Block, Some(parent)) => parent.local_variables().await,
+            _ => HashMap::new(),
+        };
+        for (k, v) in self.variables.read().await.iter() {
+            vars.insert(k.clone(), v.clone());
+        }
+        vars
+    }
+
     pub async fn all_variables(&self) -> HashMap<String, Value> {
         let mut vars = HashMap::new();
         self.collect_variables(&mut vars).await;
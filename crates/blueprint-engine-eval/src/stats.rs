@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use blueprint_engine_core::NativeFunction;
+
+const TOP_STATEMENTS: usize = 10;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref STATS: Mutex<StatsData> = Mutex::new(StatsData::default());
+}
+
+#[derive(Default)]
+struct StatsData {
+    function_calls: HashMap<String, u64>,
+    native_calls: HashMap<String, u64>,
+    statements: Vec<(String, Duration)>,
+}
+
+/// Turns on counters for every evaluator in the process. Disabled runs only
+/// pay for a relaxed atomic load at each instrumentation point.
+pub fn enable_stats() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn stats_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub(crate) fn record_function_call(name: &str) {
+    let mut stats = STATS.lock().unwrap();
+    *stats.function_calls.entry(name.to_string()).or_default() += 1;
+}
+
+pub(crate) fn record_native_call(module: &str) {
+    let mut stats = STATS.lock().unwrap();
+    *stats.native_calls.entry(module.to_string()).or_default() += 1;
+}
+
+pub(crate) fn record_statement(location: String, elapsed: Duration) {
+    STATS.lock().unwrap().statements.push((location, elapsed));
+}
+
+/// Wraps each function so calls are counted against `module`.
+pub(crate) fn instrument_module(
+    module: &str,
+    funcs: &HashMap<String, Arc<NativeFunction>>,
+) -> HashMap<String, Arc<NativeFunction>> {
+    funcs
+        .iter()
+        .map(|(name, func)| {
+            let module = module.to_string();
+            let inner = func.clone();
+            let wrapped = NativeFunction::new_with_state(name.clone(), move |args, kwargs| {
+                record_native_call(&module);
+                let inner = inner.clone();
+                Box::pin(async move { inner.call(args, kwargs).await })
+            });
+            (name.clone(), Arc::new(wrapped))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EvalStats {
+    /// User function calls by name, most frequent first.
+    pub function_calls: Vec<(String, u64)>,
+    /// Native calls by stdlib module (`builtins` for globals), most frequent first.
+    pub native_calls: Vec<(String, u64)>,
+    /// Wall time of each top-level statement as `file:line`, slowest first.
+    pub statements: Vec<(String, Duration)>,
+}
+
+pub fn stats_snapshot() -> EvalStats {
+    let stats = STATS.lock().unwrap();
+
+    let mut function_calls: Vec<(String, u64)> = stats
+        .function_calls
+        .iter()
+        .map(|(k, v)| (k.clone(), *v))
+        .collect();
+    function_calls.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut native_calls: Vec<(String, u64)> = stats
+        .native_calls
+        .iter()
+        .map(|(k, v)| (k.clone(), *v))
+        .collect();
+    native_calls.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut statements = stats.statements.clone();
+    statements.sort_by_key(|s| std::cmp::Reverse(s.1));
+
+    EvalStats {
+        function_calls,
+        native_calls,
+        statements,
+    }
+}
+
+impl fmt::Display for EvalStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total_calls: u64 = self.function_calls.iter().map(|(_, n)| n).sum();
+        let total_native: u64 = self.native_calls.iter().map(|(_, n)| n).sum();
+        let total_time: Duration = self.statements.iter().map(|(_, d)| *d).sum();
+
+        writeln!(f, "=== Evaluation stats ===")?;
+
+        writeln!(f, "\nFunction calls ({} total)", total_calls)?;
+        for (name, count) in &self.function_calls {
+            writeln!(f, "  {:<32} {:>10}", name, count)?;
+        }
+
+        writeln!(f, "\nNative calls by module ({} total)", total_native)?;
+        for (module, count) in &self.native_calls {
+            writeln!(f, "  {:<32} {:>10}", module, count)?;
+        }
+
+        writeln!(
+            f,
+            "\nTop-level statements ({} total, {:.3?})",
+            self.statements.len(),
+            total_time
+        )?;
+        for (location, elapsed) in self.statements.iter().take(TOP_STATEMENTS) {
+            writeln!(f, "  {:<32} {:>10.3?}", location, elapsed)?;
+        }
+        if self.statements.len() > TOP_STATEMENTS {
+            writeln!(f, "  ... {} more", self.statements.len() - TOP_STATEMENTS)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+
+use blueprint_engine_core::SourceLocation;
+use blueprint_engine_parser::{
+    get_location, AssignTargetP, AstExpr, AstParameter, AstStmt, Clause, ExprP, ForClause,
+    ParameterP, ParsedModule, StmtP,
+};
+use blueprint_starlark_syntax::codemap::{CodeMap, Span};
+use blueprint_starlark_syntax::syntax::ast::{ArgumentP, AstAssignTarget};
+
+use crate::eval::Evaluator;
+
+#[derive(Debug, Clone)]
+pub enum Definition {
+    Local(SourceLocation),
+    Import {
+        location: SourceLocation,
+        module: String,
+        name: String,
+    },
+    Builtin,
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub location: SourceLocation,
+    pub definition: Definition,
+}
+
+/// Returns the symbol at byte `offset` in `module` along with where it is defined.
+/// Nothing is executed and `load`ed files are not read.
+pub fn symbol_at(module: &ParsedModule, offset: usize) -> Option<SymbolInfo> {
+    let evaluator = Evaluator::new();
+    let finder = SymbolFinder {
+        codemap: &module.codemap,
+        offset,
+        builtins: &evaluator.builtins,
+    };
+
+    let mut module_frame = Frame::new();
+    collect_bindings(module.statements(), &mut module_frame);
+    let mut frames = vec![module_frame];
+
+    finder.find_stmt(module.statements(), &mut frames)
+}
+
+struct Binding {
+    span: Span,
+    import: Option<(String, String)>,
+}
+
+type Frame = HashMap<String, Binding>;
+
+fn bind(frame: &mut Frame, name: &str, span: Span, import: Option<(String, String)>) {
+    frame
+        .entry(name.to_string())
+        .or_insert(Binding { span, import });
+}
+
+fn collect_bindings(stmt: &AstStmt, frame: &mut Frame) {
+    match &stmt.node {
+        StmtP::Statements(stmts) => {
+            for s in stmts {
+                collect_bindings(s, frame);
+            }
+        }
+        StmtP::Assign(assign) => collect_target(&assign.lhs, frame),
+        StmtP::AssignModify(lhs, _, _) => collect_target(lhs, frame),
+        StmtP::If(_, then_block) => collect_bindings(then_block, frame),
+        StmtP::IfElse(_, branches) => {
+            collect_bindings(&branches.0, frame);
+            collect_bindings(&branches.1, frame);
+        }
+        StmtP::For(for_stmt) => {
+            collect_target(&for_stmt.var, frame);
+            collect_bindings(&for_stmt.body, frame);
+        }
+        StmtP::With(with_stmt) => {
+            if let Some(target) = &with_stmt.target {
+                collect_target(target, frame);
+            }
+            collect_bindings(&with_stmt.body, frame);
+        }
+        StmtP::Def(def) => bind(frame, &def.name.node.ident, def.name.span, None),
+        StmtP::Struct(struct_def) => bind(
+            frame,
+            &struct_def.name.node.ident,
+            struct_def.name.span,
+            None,
+        ),
+        StmtP::Load(load) => {
+            for arg in &load.args {
+                bind(
+                    frame,
+                    &arg.local.node.ident,
+                    arg.local.span,
+                    Some((load.module.node.clone(), arg.their.node.clone())),
+                );
+            }
+        }
+        StmtP::Match(match_stmt) => {
+            for case in &match_stmt.cases {
+                collect_bindings(&case.node.body, frame);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_target(target: &AstAssignTarget, frame: &mut Frame) {
+    match &target.node {
+        AssignTargetP::Identifier(ident) => bind(frame, &ident.node.ident, ident.span, None),
+        AssignTargetP::Tuple(items) => {
+            for item in items {
+                collect_target(item, frame);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_params(params: &[AstParameter], frame: &mut Frame) {
+    for param in params {
+        match &param.node {
+            ParameterP::Normal(ident, _, _)
+            | ParameterP::Args(ident, _)
+            | ParameterP::KwArgs(ident, _) => bind(frame, &ident.node.ident, ident.span, None),
+            ParameterP::NoArgs | ParameterP::Slash => {}
+        }
+    }
+}
+
+struct SymbolFinder<'a> {
+    codemap: &'a CodeMap,
+    offset: usize,
+    builtins: &'a HashMap<String, std::sync::Arc<blueprint_engine_core::NativeFunction>>,
+}
+
+impl SymbolFinder<'_> {
+    fn contains(&self, span: Span) -> bool {
+        let begin = span.begin().get() as usize;
+        let end = span.end().get() as usize;
+        begin <= self.offset && self.offset <= end
+    }
+
+    fn resolve(&self, name: &str, span: Span, frames: &[Frame]) -> Option<SymbolInfo> {
+        let definition = match frames.iter().rev().find_map(|frame| frame.get(name)) {
+            Some(binding) => {
+                let location = get_location(self.codemap, binding.span);
+                match &binding.import {
+                    Some((module, their)) => Definition::Import {
+                        location,
+                        module: module.clone(),
+                        name: their.clone(),
+                    },
+                    None => Definition::Local(location),
+                }
+            }
+            None if self.builtins.contains_key(name) => Definition::Builtin,
+            None => return None,
+        };
+
+        Some(SymbolInfo {
+            name: name.to_string(),
+            location: get_location(self.codemap, span),
+            definition,
+        })
+    }
+
+    fn find_stmt(&self, stmt: &AstStmt, frames: &mut Vec<Frame>) -> Option<SymbolInfo> {
+        if !self.contains(stmt.span) {
+            return None;
+        }
+
+        match &stmt.node {
+            StmtP::Statements(stmts) => stmts.iter().find_map(|s| self.find_stmt(s, frames)),
+            StmtP::Expression(expr) => self.find_expr(expr, frames),
+            StmtP::Return(Some(expr)) | StmtP::Yield(Some(expr)) => self.find_expr(expr, frames),
+            StmtP::Assign(assign) => self
+                .find_target(&assign.lhs, frames)
+                .or_else(|| self.find_expr(&assign.rhs, frames)),
+            StmtP::AssignModify(lhs, _, rhs) => self
+                .find_target(lhs, frames)
+                .or_else(|| self.find_expr(rhs, frames)),
+            StmtP::If(cond, then_block) => self
+                .find_expr(cond, frames)
+                .or_else(|| self.find_stmt(then_block, frames)),
+            StmtP::IfElse(cond, branches) => self
+                .find_expr(cond, frames)
+                .or_else(|| self.find_stmt(&branches.0, frames))
+                .or_else(|| self.find_stmt(&branches.1, frames)),
+            StmtP::For(for_stmt) => self
+                .find_expr(&for_stmt.over, frames)
+                .or_else(|| self.find_target(&for_stmt.var, frames))
+                .or_else(|| self.find_stmt(&for_stmt.body, frames)),
+            StmtP::With(with_stmt) => self
+                .find_expr(&with_stmt.resource, frames)
+                .or_else(|| {
+                    with_stmt
+                        .target
+                        .as_ref()
+                        .and_then(|target| self.find_target(target, frames))
+                })
+                .or_else(|| self.find_stmt(&with_stmt.body, frames)),
+            StmtP::Def(def) => {
+                if self.contains(def.name.span) {
+                    return self.resolve(&def.name.node.ident, def.name.span, frames);
+                }
+                if let Some(found) = self.find_params(&def.params, frames) {
+                    return Some(found);
+                }
+
+                let mut frame = Frame::new();
+                collect_params(&def.params, &mut frame);
+                collect_bindings(&def.body, &mut frame);
+                frames.push(frame);
+                let found = self.find_stmt(&def.body, frames);
+                frames.pop();
+                found
+            }
+            StmtP::Struct(struct_def) => {
+                if self.contains(struct_def.name.span) {
+                    return self.resolve(&struct_def.name.node.ident, struct_def.name.span, frames);
+                }
+                struct_def.fields.iter().find_map(|field| {
+                    field
+                        .node
+                        .default
+                        .as_ref()
+                        .and_then(|default| self.find_expr(default, frames))
+                })
+            }
+            StmtP::Load(load) => load
+                .args
+                .iter()
+                .find(|arg| self.contains(arg.local.span))
+                .and_then(|arg| self.resolve(&arg.local.node.ident, arg.local.span, frames)),
+            StmtP::Match(match_stmt) => self.find_expr(&match_stmt.subject, frames).or_else(|| {
+                match_stmt.cases.iter().find_map(|case| {
+                    case.node
+                        .guard
+                        .as_ref()
+                        .and_then(|guard| self.find_expr(guard, frames))
+                        .or_else(|| self.find_stmt(&case.node.body, frames))
+                })
+            }),
+            _ => None,
+        }
+    }
+
+    fn find_params(&self, params: &[AstParameter], frames: &mut Vec<Frame>) -> Option<SymbolInfo> {
+        for param in params {
+            match &param.node {
+                ParameterP::Normal(ident, _, default) => {
+                    if self.contains(ident.span) {
+                        let mut frame = Frame::new();
+                        collect_params(params, &mut frame);
+                        frames.push(frame);
+                        let found = self.resolve(&ident.node.ident, ident.span, frames);
+                        frames.pop();
+                        return found;
+                    }
+                    if let Some(found) = default.as_ref().and_then(|d| self.find_expr(d, frames)) {
+                        return Some(found);
+                    }
+                }
+                ParameterP::Args(ident, _) | ParameterP::KwArgs(ident, _) => {
+                    if self.contains(ident.span) {
+                        let mut frame = Frame::new();
+                        collect_params(params, &mut frame);
+                        frames.push(frame);
+                        let found = self.resolve(&ident.node.ident, ident.span, frames);
+                        frames.pop();
+                        return found;
+                    }
+                }
+                ParameterP::NoArgs | ParameterP::Slash => {}
+            }
+        }
+        None
+    }
+
+    fn find_target(&self, target: &AstAssignTarget, frames: &mut Vec<Frame>) -> Option<SymbolInfo> {
+        if !self.contains(target.span) {
+            return None;
+        }
+
+        match &target.node {
+            AssignTargetP::Identifier(ident) => self.resolve(&ident.node.ident, ident.span, frames),
+            AssignTargetP::Tuple(items) => items.iter().find_map(|t| self.find_target(t, frames)),
+            AssignTargetP::Index(pair) => self
+                .find_expr(&pair.0, frames)
+                .or_else(|| self.find_expr(&pair.1, frames)),
+            AssignTargetP::Dot(expr, _) => self.find_expr(expr, frames),
+            AssignTargetP::Starred(inner) => self.find_target(inner, frames),
+        }
+    }
+
+    fn find_comprehension(
+        &self,
+        first: &ForClause,
+        clauses: &[Clause],
+        bodies: &[&AstExpr],
+        frames: &mut Vec<Frame>,
+    ) -> Option<SymbolInfo> {
+        if let Some(found) = self.find_expr(&first.over, frames) {
+            return Some(found);
+        }
+
+        let mut frame = Frame::new();
+        collect_target(&first.var, &mut frame);
+        for clause in clauses {
+            if let Clause::For(for_clause) = clause {
+                collect_target(&for_clause.var, &mut frame);
+            }
+        }
+        frames.push(frame);
+
+        let found = self
+            .find_target(&first.var, frames)
+            .or_else(|| {
+                clauses.iter().find_map(|clause| match clause {
+                    Clause::For(for_clause) => self
+                        .find_expr(&for_clause.over, frames)
+                        .or_else(|| self.find_target(&for_clause.var, frames)),
+                    Clause::If(cond) => self.find_expr(cond, frames),
+                })
+            })
+            .or_else(|| bodies.iter().find_map(|body| self.find_expr(body, frames)));
+
+        frames.pop();
+        found
+    }
+
+    fn find_expr(&self, expr: &AstExpr, frames: &mut Vec<Frame>) -> Option<SymbolInfo> {
+        if !self.contains(expr.span) {
+            return None;
+        }
+
+        let children: Vec<&AstExpr> = match &expr.node {
+            ExprP::Identifier(ident) => return self.resolve(&ident.node.ident, expr.span, frames),
+            ExprP::Lambda(lambda) => {
+                if let Some(found) = self.find_params(&lambda.params, frames) {
+                    return Some(found);
+                }
+                let mut frame = Frame::new();
+                collect_params(&lambda.params, &mut frame);
+                frames.push(frame);
+                let found = self.find_expr(&lambda.body, frames);
+                frames.pop();
+                return found;
+            }
+            ExprP::ListComprehension(body, first, clauses)
+            | ExprP::SetComprehension(body, first, clauses) => {
+                return self.find_comprehension(first, clauses, &[&**body], frames);
+            }
+            ExprP::DictComprehension(pair, first, clauses) => {
+                return self.find_comprehension(first, clauses, &[&pair.0, &pair.1], frames);
+            }
+            ExprP::Tuple(items) | ExprP::List(items) | ExprP::Set(items) => items.iter().collect(),
+            ExprP::Dict(pairs) => pairs.iter().flat_map(|(k, v)| [k, v]).collect(),
+            ExprP::Call(callee, args) => std::iter::once(&**callee)
+                .chain(args.args.iter().map(|arg| match &arg.node {
+                    ArgumentP::Positional(e)
+                    | ArgumentP::Named(_, e)
+                    | ArgumentP::Args(e)
+                    | ArgumentP::KwArgs(e) => e,
+                }))
+                .collect(),
+            ExprP::Index(pair) => vec![&pair.0, &pair.1],
+            ExprP::Index2(triple) => vec![&triple.0, &triple.1, &triple.2],
+            ExprP::Slice(target, start, stop, step) => std::iter::once(&**target)
+                .chain(start.iter().map(|e| &**e))
+                .chain(stop.iter().map(|e| &**e))
+                .chain(step.iter().map(|e| &**e))
+                .collect(),
+            ExprP::Dot(target, _) => vec![&**target],
+            ExprP::Not(inner) | ExprP::Minus(inner) | ExprP::Plus(inner) => vec![&**inner],
+            ExprP::Op(lhs, _, rhs) => vec![&**lhs, &**rhs],
+            ExprP::If(triple) => vec![&triple.0, &triple.1, &triple.2],
+            ExprP::FString(fstring) => fstring.expressions.iter().collect(),
+            _ => vec![],
+        };
+
+        children
+            .into_iter()
+            .find_map(|child| self.find_expr(child, frames))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(source: &str, needle: &str, nth: usize) -> Option<SymbolInfo> {
+        let module = blueprint_engine_parser::parse("test.bp", source).unwrap();
+        let offset = source.match_indices(needle).nth(nth).unwrap().0;
+        symbol_at(&module, offset)
+    }
+
+    #[test]
+    fn test_local_definition() {
+        let source = "def greet(name):\n    return name\n\ngreet(\"x\")\n";
+        let info = symbol(source, "greet", 1).unwrap();
+        match info.definition {
+            Definition::Local(location) => assert_eq!((location.line, location.column), (1, 5)),
+            other => panic!("unexpected definition: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parameter_definition() {
+        let source = "def greet(name):\n    return name\n";
+        let info = symbol(source, "name", 1).unwrap();
+        match info.definition {
+            Definition::Local(location) => assert_eq!((location.line, location.column), (1, 11)),
+            other => panic!("unexpected definition: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_loaded_definition() {
+        let source = "load(\"./lib.bp\", say=\"greet\")\nsay(\"x\")\n";
+        let info = symbol(source, "say", 1).unwrap();
+        match info.definition {
+            Definition::Import { module, name, .. } => {
+                assert_eq!(module, "./lib.bp");
+                assert_eq!(name, "greet");
+            }
+            other => panic!("unexpected definition: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_builtin_definition() {
+        let info = symbol("print(1)\n", "print", 0).unwrap();
+        assert!(matches!(info.definition, Definition::Builtin));
+    }
+
+    #[test]
+    fn test_forward_reference_at_module_level() {
+        let source = "def a():\n    return b()\n\ndef b():\n    return 1\n";
+        let info = symbol(source, "b", 0).unwrap();
+        match info.definition {
+            Definition::Local(location) => assert_eq!(location.line, 4),
+            other => panic!("unexpected definition: {:?}", other),
+        }
+    }
+}
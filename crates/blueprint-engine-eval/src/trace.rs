@@ -0,0 +1,93 @@
+//! `tracing` spans around script function calls, native calls and module
+//! loads, compiled in with the `tracing` feature. Without it the helpers
+//! below do nothing and cost nothing.
+
+use std::future::Future;
+
+use blueprint_starlark_syntax::codemap::Span as CodeSpan;
+
+use crate::eval::Evaluator;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone)]
+pub(crate) struct Span;
+
+/// A call to a script function or lambda, located at its definition.
+#[cfg(feature = "tracing")]
+pub(crate) fn call_span(function: &str, file: Option<&str>, line: usize, column: usize) -> Span {
+    tracing::debug_span!("call", function, file, line, column)
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn call_span(
+    _function: &str,
+    _file: Option<&str>,
+    _line: usize,
+    _column: usize,
+) -> Span {
+    Span
+}
+
+/// A call to a native function, located at the call site. The location is
+/// only resolved when a subscriber is interested in the span.
+#[cfg(feature = "tracing")]
+pub(crate) fn native_span(evaluator: &Evaluator, function: &str, at: &CodeSpan) -> Span {
+    use tracing::field::Empty;
+
+    let span = tracing::trace_span!(
+        "native",
+        function,
+        file = Empty,
+        line = Empty,
+        column = Empty
+    );
+    if !span.is_disabled() {
+        let (line, column) = evaluator.display_location(at);
+        if let Some(file) = &evaluator.current_file {
+            span.record("file", file.display().to_string().as_str());
+        }
+        span.record("line", line);
+        span.record("column", column);
+    }
+    span
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn native_span(_evaluator: &Evaluator, _function: &str, _at: &CodeSpan) -> Span {
+    Span
+}
+
+/// No span, for calls to script functions, which open their own.
+#[cfg(feature = "tracing")]
+pub(crate) fn no_span() -> Span {
+    Span::none()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn no_span() -> Span {
+    Span
+}
+
+/// Evaluation of a loaded module's top level.
+#[cfg(feature = "tracing")]
+pub(crate) fn load_span(module: &str, file: &str) -> Span {
+    tracing::debug_span!("load", module, file)
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn load_span(_module: &str, _file: &str) -> Span {
+    Span
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn instrument<F: Future>(span: Span, fut: F) -> impl Future<Output = F::Output> {
+    tracing::Instrument::instrument(fut, span)
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn instrument<F: Future>(_span: Span, fut: F) -> impl Future<Output = F::Output> {
+    fut
+}
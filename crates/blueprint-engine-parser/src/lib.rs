@@ -21,36 +21,141 @@ impl ParsedModule {
     }
 }
 
-pub fn parse(filename: &str, content: &str) -> Result<ParsedModule> {
-    let dialect = Dialect {
+const MAX_RECOVERED_ERRORS: usize = 50;
+
+fn dialect() -> Dialect {
+    Dialect {
         enable_f_strings: true,
         enable_lambda: true,
         enable_keyword_only_arguments: true,
         enable_top_level_stmt: true,
         enable_types: DialectTypes::Enable,
         ..Dialect::Standard
-    };
+    }
+}
 
-    match AstModule::parse(filename, content.to_owned(), &dialect) {
+pub fn parse(filename: &str, content: &str) -> Result<ParsedModule> {
+    match AstModule::parse(filename, content.to_owned(), &dialect()) {
         Ok(module) => {
             let (codemap, statement, _dialect, _) = module.into_parts();
             Ok(ParsedModule { codemap, statement })
         }
-        Err(e) => {
-            let location = SourceLocation {
+        Err(e) => Err(parse_error(filename, &e)),
+    }
+}
+
+/// Parses `content`, recovering from syntax errors to report as many as possible.
+///
+/// The underlying parser stops at the first error, so recovery is done by replacing
+/// the offending statement with `pass` and parsing again. The returned module (if any)
+/// is the recovered one and should only be used for analysis when errors are present.
+pub fn parse_all(filename: &str, content: &str) -> (Option<ParsedModule>, Vec<BlueprintError>) {
+    let dialect = dialect();
+    let mut source = content.to_owned();
+    let mut errors = Vec::new();
+    let mut last_line: Option<usize> = None;
+
+    loop {
+        let e = match AstModule::parse(filename, source.clone(), &dialect) {
+            Ok(module) => {
+                let (codemap, statement, _dialect, _) = module.into_parts();
+                return (Some(ParsedModule { codemap, statement }), errors);
+            }
+            Err(e) => e,
+        };
+
+        errors.push(parse_error(filename, &e));
+
+        let line = match e.span() {
+            Some(file_span) => file_span.resolve_span().begin.line,
+            None => return (None, errors),
+        };
+        if last_line.is_some_and(|last| line <= last) || errors.len() >= MAX_RECOVERED_ERRORS {
+            return (None, errors);
+        }
+        last_line = Some(line);
+        source = replace_statement_with_pass(&source, line);
+    }
+}
+
+fn replace_statement_with_pass(source: &str, line: usize) -> String {
+    let lines: Vec<&str> = source.split('\n').collect();
+    let indent_of = |l: &str| l.len() - l.trim_start().len();
+    let base_indent = lines.get(line).map(|l| indent_of(l)).unwrap_or(0);
+
+    let mut end = line + 1;
+    while end < lines.len() && (lines[end].trim().is_empty() || indent_of(lines[end]) > base_indent)
+    {
+        end += 1;
+    }
+
+    let mut result: Vec<String> = Vec::with_capacity(lines.len());
+    for (i, l) in lines.iter().enumerate() {
+        if i == line {
+            result.push(format!("{}pass", &l[..base_indent]));
+        } else if i > line && i < end {
+            result.push(String::new());
+        } else {
+            result.push(l.to_string());
+        }
+    }
+    result.join("\n")
+}
+
+fn parse_error(filename: &str, error: &blueprint_starlark_syntax::Error) -> BlueprintError {
+    let message = error.without_diagnostic().to_string();
+
+    match error.span() {
+        Some(file_span) => {
+            let location = get_location(&file_span.file, file_span.span);
+            let snippet = render_snippet(&file_span.file, file_span.span);
+            BlueprintError::ParseError {
+                location,
+                message: format!("{}\n{}", message, snippet),
+            }
+        }
+        None => BlueprintError::ParseError {
+            location: SourceLocation {
                 file: Some(filename.to_string()),
                 line: 1,
                 column: 1,
                 span: None,
-            };
-            Err(BlueprintError::ParseError {
-                location,
-                message: e.to_string(),
-            })
-        }
+            },
+            message,
+        },
     }
 }
 
+/// Renders the source line containing `span` with a `^` underline beneath it.
+pub fn render_snippet(codemap: &CodeMap, span: blueprint_starlark_syntax::codemap::Span) -> String {
+    let resolved = codemap.resolve_span(span);
+    let line_index = resolved.begin.line;
+    let line = match codemap.line_span_opt(line_index) {
+        Some(_) => codemap.source_line(line_index),
+        None => "",
+    };
+
+    let indent: String = line
+        .chars()
+        .take(resolved.begin.column)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let width = if resolved.end.line == resolved.begin.line {
+        resolved.end.column.saturating_sub(resolved.begin.column)
+    } else {
+        line.chars().count().saturating_sub(resolved.begin.column)
+    }
+    .max(1);
+
+    let line_number = (line_index + 1).to_string();
+    let gutter = " ".repeat(line_number.len());
+
+    format!(
+        "{gutter} |\n{line_number} | {line}\n{gutter} | {indent}{carets}",
+        carets = "^".repeat(width)
+    )
+}
+
 pub fn get_location(
     codemap: &CodeMap,
     span: blueprint_starlark_syntax::codemap::Span,
@@ -99,4 +204,84 @@ def hello(name):
         let result = parse("test.star", "x = ");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_parse_error_location() {
+        let err = parse("test.star", "x = 1\ny = (2 +\n").err().unwrap();
+        let location = err.error_location().unwrap();
+        assert_eq!(location.file.as_deref(), Some("test.star"));
+        assert!(
+            location.line >= 2,
+            "expected line >= 2, got {}",
+            location.line
+        );
+    }
+
+    #[test]
+    fn test_parse_error_snippet() {
+        let err = parse("test.star", "x = 1\ny = )\n").err().unwrap();
+        let message = err.to_string();
+        assert!(message.contains("2 | y = )"), "{}", message);
+        assert!(message.contains("  |     ^"), "{}", message);
+    }
+
+    #[test]
+    fn test_parse_all_reports_multiple_errors() {
+        let code = "x = )\ny = 1\nz = ]\n";
+        let (module, errors) = parse_all("test.star", code);
+        assert!(module.is_some());
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].error_location().unwrap().line, 1);
+        assert_eq!(errors[1].error_location().unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_parse_starred_targets() {
+        let code = "first, *rest = xs\nfor *init, last in pairs:\n    pass\n";
+        let module = parse("test.star", code).unwrap();
+
+        let StmtP::Statements(stmts) = &module.statement.node else {
+            panic!("expected statements");
+        };
+        let StmtP::Assign(assign) = &stmts[0].node else {
+            panic!("expected an assignment");
+        };
+        let AssignTargetP::Tuple(targets) = &assign.lhs.node else {
+            panic!("expected a tuple target");
+        };
+        assert!(!matches!(targets[0].node, AssignTargetP::Starred(_)));
+        assert!(matches!(targets[1].node, AssignTargetP::Starred(_)));
+
+        let StmtP::For(for_) = &stmts[1].node else {
+            panic!("expected a for loop");
+        };
+        let AssignTargetP::Tuple(targets) = &for_.var.node else {
+            panic!("expected a tuple target");
+        };
+        assert!(matches!(targets[0].node, AssignTargetP::Starred(_)));
+
+        for code in [
+            "f(a, *b)\n",
+            "def f(*args, **kwargs):\n    pass\n",
+            "x = a * b\n",
+        ] {
+            assert!(parse("test.star", code).is_ok(), "{}", code);
+        }
+        for code in [
+            "x = [*a, 1]\n",
+            "a, **b = xs\n",
+            "print(*)\n",
+            "*a = xs\n",
+            "a, *b, *c = xs\n",
+        ] {
+            assert!(parse("test.star", code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_parse_all_valid() {
+        let (module, errors) = parse_all("test.star", "x = 1\n");
+        assert!(module.is_some());
+        assert!(errors.is_empty());
+    }
 }
@@ -1,8 +1,9 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use axum::{
-    extract::{Multipart, Path, Query, State},
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    routing::{delete, get, post},
     Json, Router,
 };
 use chrono::Utc;
@@ -12,8 +13,9 @@ use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::error::{ApiError, ApiResult};
-use crate::manifest::BlueprintManifest;
-use crate::models::{Package, Version};
+use crate::manifest::{BlueprintManifest, DependencySpec};
+use crate::models::{OwnerError, Package, Version};
+use crate::webhooks::{self, Webhook, WebhookDelivery};
 use crate::AppState;
 
 pub fn routes() -> Router<Arc<AppState>> {
@@ -24,9 +26,18 @@ pub fn routes() -> Router<Arc<AppState>> {
         // Packages
         .route("/packages", get(list_packages))
         .route("/packages/{namespace}/{name}", get(get_package))
-        .route("/packages/{namespace}/{name}", post(publish_package))
+        .route(
+            "/packages/{namespace}/{name}",
+            post(publish_package).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/packages/{namespace}/{name}/owners", get(list_owners).post(add_owner))
+        .route("/packages/{namespace}/{name}/owners/{user_id}", delete(remove_owner))
+        .route("/packages/{namespace}/{name}/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/packages/{namespace}/{name}/webhooks/{id}", delete(delete_webhook))
+        .route("/packages/{namespace}/{name}/webhooks/{id}/deliveries", get(list_deliveries))
         .route("/packages/{namespace}/{name}/{version}", get(get_version))
         .route("/packages/{namespace}/{name}/{version}/download", get(download_package))
+        .route("/packages/{namespace}/{name}/{version}/deps", get(get_version_deps))
         .route("/packages/{namespace}/{name}/{version}/yank", post(yank_version))
         // Search
         .route("/search", get(search_packages))
@@ -188,6 +199,7 @@ pub struct PackageDetail {
     keywords: Vec<String>,
     categories: Vec<String>,
     owner_id: Uuid,
+    owners: Vec<Uuid>,
     versions: Vec<VersionInfo>,
     created_at: chrono::DateTime<Utc>,
 }
@@ -222,6 +234,7 @@ async fn get_package(
         keywords: pkg.keywords,
         categories: pkg.categories,
         owner_id: pkg.owner_id,
+        owners: pkg.owners(),
         versions: versions
             .into_iter()
             .map(|v| VersionInfo {
@@ -257,6 +270,32 @@ async fn get_version(
     }))
 }
 
+#[derive(Serialize)]
+pub struct VersionDeps {
+    namespace: String,
+    name: String,
+    version: String,
+    dependencies: BTreeMap<String, DependencySpec>,
+}
+
+async fn get_version_deps(
+    State(state): State<Arc<AppState>>,
+    Path((namespace, name, ver)): Path<(String, String, String)>,
+) -> ApiResult<Json<VersionDeps>> {
+    let pkg = state.packages.find_package(&namespace, &name)
+        .ok_or_else(|| ApiError::NotFound("Package not found".into()))?;
+
+    let version = state.packages.find_version(pkg.id, &ver)
+        .ok_or_else(|| ApiError::NotFound("Version not found".into()))?;
+
+    Ok(Json(VersionDeps {
+        namespace: pkg.namespace,
+        name: pkg.name,
+        version: version.version,
+        dependencies: version.dependencies.into_iter().collect(),
+    }))
+}
+
 async fn download_package(
     State(state): State<Arc<AppState>>,
     Path((namespace, name, ver)): Path<(String, String, String)>,
@@ -277,8 +316,7 @@ async fn download_package(
     version.downloads += 1;
     state.packages.update_version(version.clone());
 
-    let key = format!("{}/{}/{}", namespace, name, ver);
-    let data = state.packages.get_package_data(&key)
+    let data = state.packages.get_package_data(&version.checksum)
         .ok_or_else(|| ApiError::NotFound("Package file not found".into()))?;
 
     let filename = format!("{}-{}.tar.gz", name, ver);
@@ -286,6 +324,7 @@ async fn download_package(
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "application/gzip")
         .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .header("X-Checksum-Sha256", &version.checksum)
         .body(Body::from(data))
         .map_err(|e| ApiError::Internal(e.to_string()))
 }
@@ -304,26 +343,65 @@ async fn publish_package(
         ));
     }
 
-    let user_namespace = user.email.split('@').next().unwrap_or(&user.email);
-    if namespace != user_namespace {
-        return Err(ApiError::Forbidden(
-            "You can only publish to your own namespace".into(),
-        ));
+    match state.packages.find_package(&namespace, &name) {
+        Some(p) if !p.is_owner(user.id) => {
+            return Err(ApiError::Forbidden("You don't own this package".into()));
+        }
+        Some(_) => {}
+        None => {
+            let user_namespace = user.email.split('@').next().unwrap_or(&user.email);
+            if namespace != user_namespace {
+                return Err(ApiError::Forbidden(
+                    "You can only publish to your own namespace".into(),
+                ));
+            }
+        }
     }
 
+    let reservation = if state.limits.per_hour > 0 {
+        let window = chrono::Duration::hours(1);
+        match state.packages.reserve_publish(user.id, state.limits.per_hour, window) {
+            Ok(reservation) => Some(reservation),
+            Err(retry_after) => {
+                return Err(ApiError::TooManyRequests(format!(
+                    "Publish limit of {} per hour reached; retry in {} seconds",
+                    state.limits.per_hour, retry_after
+                )));
+            }
+        }
+    } else {
+        None
+    };
+
     let mut manifest_content: Option<String> = None;
     let mut package_data: Option<Vec<u8>> = None;
+    let mut readme: Option<String> = None;
+    let mut received = 0usize;
 
-    while let Some(field) = multipart.next_field().await.map_err(|e| ApiError::BadRequest(e.to_string()))? {
+    while let Some(mut field) = multipart.next_field().await.map_err(|e| ApiError::BadRequest(e.to_string()))? {
         let field_name = field.name().unwrap_or("").to_string();
+        let mut data = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(|e| ApiError::BadRequest(e.to_string()))? {
+            received += chunk.len();
+            if received > state.limits.max_size {
+                return Err(ApiError::PayloadTooLarge(format!(
+                    "Upload exceeds the {} byte limit",
+                    state.limits.max_size
+                )));
+            }
+            data.extend_from_slice(&chunk);
+        }
         match field_name.as_str() {
             "manifest" => {
-                let data = field.bytes().await.map_err(|e| ApiError::BadRequest(e.to_string()))?;
-                manifest_content = Some(String::from_utf8(data.to_vec())
+                manifest_content = Some(String::from_utf8(data)
                     .map_err(|_| ApiError::BadRequest("Invalid UTF-8 in manifest".into()))?);
             }
             "package" => {
-                package_data = Some(field.bytes().await.map_err(|e| ApiError::BadRequest(e.to_string()))?.to_vec());
+                package_data = Some(data);
+            }
+            "readme" => {
+                readme = Some(String::from_utf8(data)
+                    .map_err(|_| ApiError::BadRequest("Invalid UTF-8 in readme".into()))?);
             }
             _ => {}
         }
@@ -347,7 +425,7 @@ async fn publish_package(
 
     let pkg = match state.packages.find_package(&namespace, &name) {
         Some(p) => {
-            if p.owner_id != user.id {
+            if !p.is_owner(user.id) {
                 return Err(ApiError::Forbidden("You don't own this package".into()));
             }
             p
@@ -366,6 +444,7 @@ async fn publish_package(
                 keywords: manifest.package.keywords.clone(),
                 categories: manifest.package.categories.clone(),
                 owner_id: user.id,
+                co_owners: Vec::new(),
                 created_at: now,
                 updated_at: now,
             })
@@ -380,8 +459,11 @@ async fn publish_package(
     hasher.update(&data);
     let checksum = hex::encode(hasher.finalize());
 
-    let key = format!("{}/{}/{}", namespace, name, manifest.package.version);
-    state.packages.store_package_data(&key, data.clone());
+    state.packages.store_package_data(&checksum, data.clone());
+
+    if let Some(reservation) = reservation {
+        reservation.keep();
+    }
 
     let now = Utc::now();
     let version = state.packages.create_version(Version {
@@ -393,7 +475,22 @@ async fn publish_package(
         downloads: 0,
         yanked: false,
         published_at: now,
+        readme,
+        dependencies: manifest.dependencies.clone(),
+    });
+
+    let payload = serde_json::json!({
+        "event": "publish",
+        "package": format!("@{}/{}", namespace, name),
+        "namespace": namespace,
+        "name": name,
+        "version": version.version,
+        "checksum": version.checksum,
+        "published_at": version.published_at,
     });
+    for webhook in state.packages.list_webhooks(pkg.id) {
+        state.webhooks.enqueue(webhook, "publish", &payload);
+    }
 
     Ok(Json(VersionInfo {
         version: version.version,
@@ -413,7 +510,7 @@ async fn yank_version(
     let pkg = state.packages.find_package(&namespace, &name)
         .ok_or_else(|| ApiError::NotFound("Package not found".into()))?;
 
-    if pkg.owner_id != user.id {
+    if !pkg.is_owner(user.id) {
         return Err(ApiError::Forbidden("You don't own this package".into()));
     }
 
@@ -426,6 +523,166 @@ async fn yank_version(
     Ok(Json(serde_json::json!({"yanked": true})))
 }
 
+#[derive(Serialize)]
+pub struct OwnersResponse {
+    owners: Vec<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct AddOwnerRequest {
+    user_id: Uuid,
+}
+
+async fn list_owners(
+    State(state): State<Arc<AppState>>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> ApiResult<Json<OwnersResponse>> {
+    let pkg = state.packages.find_package(&namespace, &name)
+        .ok_or_else(|| ApiError::NotFound("Package not found".into()))?;
+
+    Ok(Json(OwnersResponse { owners: pkg.owners() }))
+}
+
+async fn add_owner(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((namespace, name)): Path<(String, String)>,
+    Json(req): Json<AddOwnerRequest>,
+) -> ApiResult<Json<OwnersResponse>> {
+    let pkg = state.packages.find_package(&namespace, &name)
+        .ok_or_else(|| ApiError::NotFound("Package not found".into()))?;
+
+    state.auth.get_user(req.user_id).await.map_err(|e| match e {
+        tsa_auth::TsaError::UserNotFound => ApiError::NotFound("User not found".into()),
+        _ => ApiError::Internal(e.to_string()),
+    })?;
+
+    let pkg = state.packages.add_co_owner(pkg.id, user.id, req.user_id)
+        .map_err(owner_error)?;
+
+    Ok(Json(OwnersResponse { owners: pkg.owners() }))
+}
+
+async fn remove_owner(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((namespace, name, owner_id)): Path<(String, String, Uuid)>,
+) -> ApiResult<Json<OwnersResponse>> {
+    let pkg = state.packages.find_package(&namespace, &name)
+        .ok_or_else(|| ApiError::NotFound("Package not found".into()))?;
+
+    let pkg = state.packages.remove_co_owner(pkg.id, user.id, owner_id)
+        .map_err(owner_error)?;
+
+    Ok(Json(OwnersResponse { owners: pkg.owners() }))
+}
+
+fn owner_error(e: OwnerError) -> ApiError {
+    match e {
+        OwnerError::PackageNotFound => ApiError::NotFound("Package not found".into()),
+        OwnerError::NotOwner => ApiError::Forbidden("You don't own this package".into()),
+        OwnerError::AlreadyOwner => ApiError::Conflict("User is already an owner".into()),
+        OwnerError::NotCoOwner => ApiError::NotFound("User is not an owner".into()),
+        OwnerError::OriginalOwner => ApiError::BadRequest("The original owner cannot be removed".into()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateWebhookRequest {
+    url: String,
+    secret: String,
+}
+
+/// Anyone signed in may subscribe to a package; a webhook can be managed by
+/// whoever created it or by an owner of the package.
+fn can_manage_webhook(webhook: &Webhook, pkg: &Package, user: &AuthUser) -> bool {
+    webhook.created_by == user.id || pkg.is_owner(user.id)
+}
+
+async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((namespace, name)): Path<(String, String)>,
+) -> ApiResult<Json<Vec<Webhook>>> {
+    let pkg = state.packages.find_package(&namespace, &name)
+        .ok_or_else(|| ApiError::NotFound("Package not found".into()))?;
+
+    let webhooks = state.packages.list_webhooks(pkg.id)
+        .into_iter()
+        .filter(|w| can_manage_webhook(w, &pkg, &user))
+        .collect();
+
+    Ok(Json(webhooks))
+}
+
+async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((namespace, name)): Path<(String, String)>,
+    Json(req): Json<CreateWebhookRequest>,
+) -> ApiResult<Json<Webhook>> {
+    let pkg = state.packages.find_package(&namespace, &name)
+        .ok_or_else(|| ApiError::NotFound("Package not found".into()))?;
+
+    webhooks::resolve_public(&req.url).await.map_err(ApiError::BadRequest)?;
+    if req.secret.len() < 16 {
+        return Err(ApiError::BadRequest("Webhook secret must be at least 16 characters".into()));
+    }
+
+    let webhook = state.packages.create_webhook(Webhook {
+        id: Uuid::new_v4(),
+        package_id: pkg.id,
+        url: req.url,
+        secret: req.secret,
+        created_by: user.id,
+        created_at: Utc::now(),
+    });
+
+    Ok(Json(webhook))
+}
+
+fn find_managed_webhook(
+    state: &AppState,
+    user: &AuthUser,
+    namespace: &str,
+    name: &str,
+    id: Uuid,
+) -> ApiResult<Webhook> {
+    let pkg = state.packages.find_package(namespace, name)
+        .ok_or_else(|| ApiError::NotFound("Package not found".into()))?;
+
+    let webhook = state.packages.find_webhook(id)
+        .filter(|w| w.package_id == pkg.id)
+        .ok_or_else(|| ApiError::NotFound("Webhook not found".into()))?;
+
+    if !can_manage_webhook(&webhook, &pkg, user) {
+        return Err(ApiError::Forbidden("You can't manage this webhook".into()));
+    }
+
+    Ok(webhook)
+}
+
+async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((namespace, name, id)): Path<(String, String, Uuid)>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let webhook = find_managed_webhook(&state, &user, &namespace, &name, id)?;
+    state.packages.delete_webhook(webhook.id);
+
+    Ok(Json(serde_json::json!({"deleted": true})))
+}
+
+async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((namespace, name, id)): Path<(String, String, Uuid)>,
+) -> ApiResult<Json<Vec<WebhookDelivery>>> {
+    let webhook = find_managed_webhook(&state, &user, &namespace, &name, id)?;
+
+    Ok(Json(state.webhooks.deliveries(webhook.id)))
+}
+
 #[derive(Deserialize)]
 pub struct SearchParams {
     q: String,
@@ -1,6 +1,7 @@
 use maud::{html, Markup, PreEscaped, DOCTYPE};
 use uuid::Uuid;
 
+use crate::markdown::render_readme;
 use crate::models::{ApiToken, Package, Version};
 
 pub struct SessionUser {
@@ -106,6 +107,16 @@ fn extra_css() -> &'static str {
     .btn {
         @apply inline-flex items-center justify-center rounded-md text-sm font-medium ring-offset-background transition-colors focus-visible:outline-none focus-visible:ring-2 focus-visible:ring-ring focus-visible:ring-offset-2 disabled:pointer-events-none disabled:opacity-50;
     }
+    .readme h1 { font-size: 1.5rem; font-weight: 700; margin: 1.5rem 0 0.75rem; }
+    .readme h2 { font-size: 1.25rem; font-weight: 600; margin: 1.25rem 0 0.5rem; }
+    .readme h3 { font-weight: 600; margin: 1rem 0 0.5rem; }
+    .readme p, .readme ul, .readme ol, .readme table { margin: 0.75rem 0; }
+    .readme ul { list-style: disc; padding-left: 1.5rem; }
+    .readme ol { list-style: decimal; padding-left: 1.5rem; }
+    .readme a { text-decoration: underline; }
+    .readme pre { border-radius: 0.375rem; overflow-x: auto; }
+    .readme :not(pre) > code { font-family: monospace; padding: 0.1rem 0.3rem; border-radius: 0.25rem; background: rgba(127, 127, 127, 0.2); }
+    .readme th, .readme td { border: 1px solid rgba(127, 127, 127, 0.3); padding: 0.25rem 0.5rem; }
     "#
 }
 
@@ -226,6 +237,26 @@ pub fn package_detail(user: Option<&SessionUser>, pkg: &Package, versions: &[Ver
                 }
             }
 
+            @if let Some(readme) = latest.and_then(|v| v.readme.as_deref()) {
+                div class="rounded-lg border border-border bg-card p-6" {
+                    h2 class="text-lg font-semibold mb-4" { "README" }
+                    div class="readme" { (PreEscaped(render_readme(readme))) }
+                }
+                // Pinned with SRI so a compromised CDN cannot inject script
+                // into package pages.
+                link rel="stylesheet"
+                    href="https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.9.0/styles/github-dark.min.css"
+                    integrity="sha512-rO+olRTkcf304DQBxSWxln8JXCzTHlKnIdnMUwYvQa9/Jd4cQaNkItIUj6Z4nvW1dqK0SKXLbn9h4KwZTNtAyw=="
+                    crossorigin="anonymous"
+                    referrerpolicy="no-referrer";
+                script
+                    src="https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.9.0/highlight.min.js"
+                    integrity="sha512-D9gUyxqja7hBtkWpPWGt9wfbfaMGVt9gnyCvYa+jojwwPHLCzUm5i8rpk7vD7wNee9bA35eYIjobYPaQuKS1MQ=="
+                    crossorigin="anonymous"
+                    referrerpolicy="no-referrer" {}
+                script { "hljs.highlightAll();" }
+            }
+
             div {
                 h2 class="text-lg font-semibold mb-4" { "Versions" }
                 div class="rounded-lg border border-border divide-y divide-border" {
@@ -448,6 +479,11 @@ pub fn dashboard(
             div class="rounded-lg border border-border bg-card" {
                 div class="border-b border-border p-6" {
                     h2 class="text-lg font-semibold" { "Your Packages" }
+                    p class="text-sm text-muted-foreground mt-1" {
+                        "Packages you own or co-own. Owners can add others with "
+                        code class="bg-secondary px-1 rounded" { "POST /api/v1/packages/{namespace}/{name}/owners" }
+                        "."
+                    }
                 }
                 div class="p-6" {
                     @if packages.is_empty() {
@@ -462,12 +498,24 @@ pub fn dashboard(
                             @for pkg in packages {
                                 a href=(format!("/packages/{}/{}", pkg.namespace, pkg.name))
                                     class="block rounded-lg border border-border p-4 transition-colors hover:bg-accent" {
-                                    h3 class="font-semibold" {
-                                        (format!("@{}/{}", pkg.namespace, pkg.name))
+                                    div class="flex items-center gap-2" {
+                                        h3 class="font-semibold" {
+                                            (format!("@{}/{}", pkg.namespace, pkg.name))
+                                        }
+                                        @if pkg.owner_id != user.id {
+                                            span class="inline-flex items-center rounded-md bg-secondary px-2 py-0.5 text-xs font-medium text-muted-foreground" {
+                                                "co-owner"
+                                            }
+                                        }
                                     }
                                     @if let Some(desc) = &pkg.description {
                                         p class="mt-1 text-sm text-muted-foreground" { (desc) }
                                     }
+                                    @if !pkg.co_owners.is_empty() {
+                                        p class="mt-1 text-xs text-muted-foreground" {
+                                            (pkg.co_owners.len() + 1) " owners"
+                                        }
+                                    }
                                 }
                             }
                         }
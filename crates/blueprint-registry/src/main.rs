@@ -3,7 +3,9 @@ mod auth;
 mod error;
 mod html;
 mod manifest;
+mod markdown;
 mod models;
+mod webhooks;
 
 use std::sync::Arc;
 
@@ -25,6 +27,7 @@ use uuid::Uuid;
 use auth::RegistryAuth;
 use html::SessionUser;
 use models::PackageStore;
+use webhooks::WebhookDispatcher;
 
 #[derive(Parser)]
 #[command(name = "bp-server")]
@@ -38,11 +41,32 @@ struct Cli {
 
     #[arg(short, long, help = "Enable verbose logging")]
     verbose: bool,
+
+    #[arg(
+        long,
+        default_value = "30",
+        help = "Publishes allowed per user per hour (0 = unlimited)"
+    )]
+    max_publishes_per_hour: usize,
+
+    #[arg(
+        long,
+        default_value = "10485760",
+        help = "Maximum size in bytes of a publish upload"
+    )]
+    max_package_size: usize,
+}
+
+pub struct PublishLimits {
+    pub per_hour: usize,
+    pub max_size: usize,
 }
 
 pub struct AppState {
     pub auth: RegistryAuth,
     pub packages: PackageStore,
+    pub limits: PublishLimits,
+    pub webhooks: WebhookDispatcher,
 }
 
 fn render(markup: Markup) -> Html<String> {
@@ -304,6 +328,11 @@ async fn main() -> anyhow::Result<()> {
     let state = Arc::new(AppState {
         auth: auth::create_auth(),
         packages: PackageStore::new(),
+        limits: PublishLimits {
+            per_hour: cli.max_publishes_per_hour,
+            max_size: cli.max_package_size,
+        },
+        webhooks: WebhookDispatcher::start(),
     });
 
     let app = Router::new()
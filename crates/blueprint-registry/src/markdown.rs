@@ -0,0 +1,47 @@
+use std::borrow::Cow;
+
+use pulldown_cmark::{html, Options, Parser};
+
+/// Renders a package README to HTML that is safe to embed in a page.
+/// Raw HTML in the source is sanitized; fenced code blocks keep their
+/// `language-*` class so the page can highlight them.
+pub fn render_readme(source: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(source, options));
+
+    ammonia::Builder::default()
+        .add_tag_attributes("code", &["class"])
+        .attribute_filter(|element, attribute, value| {
+            if element == "code" && attribute == "class" && !value.starts_with("language-") {
+                return None;
+            }
+            Some(Cow::Borrowed(value))
+        })
+        .clean(&unsafe_html)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_readme_strips_scripts() {
+        let html =
+            render_readme("# Title\n\n<script>alert(1)</script>\n\n[x](javascript:alert(1))");
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn test_render_readme_keeps_code_language() {
+        let html = render_readme("```python\nprint(1)\n```\n");
+        assert!(html.contains(r#"<code class="language-python">"#));
+    }
+}
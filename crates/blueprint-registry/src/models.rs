@@ -5,6 +5,9 @@ use std::collections::HashMap;
 use std::sync::RwLock;
 use uuid::Uuid;
 
+use crate::manifest::DependencySpec;
+use crate::webhooks::Webhook;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Package {
     pub id: Uuid,
@@ -18,10 +21,25 @@ pub struct Package {
     pub keywords: Vec<String>,
     pub categories: Vec<String>,
     pub owner_id: Uuid,
+    #[serde(default)]
+    pub co_owners: Vec<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl Package {
+    pub fn is_owner(&self, user_id: Uuid) -> bool {
+        self.owner_id == user_id || self.co_owners.contains(&user_id)
+    }
+
+    /// The original owner followed by co-owners in the order they were added.
+    pub fn owners(&self) -> Vec<Uuid> {
+        std::iter::once(self.owner_id)
+            .chain(self.co_owners.iter().copied())
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Version {
     pub id: Uuid,
@@ -32,6 +50,10 @@ pub struct Version {
     pub downloads: i64,
     pub yanked: bool,
     pub published_at: DateTime<Utc>,
+    #[serde(default)]
+    pub readme: Option<String>,
+    #[serde(default)]
+    pub dependencies: HashMap<String, DependencySpec>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -50,6 +72,18 @@ pub struct PackageStore {
     versions: RwLock<HashMap<Uuid, Version>>,
     package_data: RwLock<HashMap<String, Vec<u8>>>,
     api_tokens: RwLock<HashMap<Uuid, ApiToken>>,
+    publish_log: RwLock<HashMap<Uuid, Vec<DateTime<Utc>>>>,
+    webhooks: RwLock<HashMap<Uuid, Webhook>>,
+}
+
+/// Why an ownership change was refused.
+#[derive(Debug, PartialEq, Eq)]
+pub enum OwnerError {
+    PackageNotFound,
+    NotOwner,
+    AlreadyOwner,
+    NotCoOwner,
+    OriginalOwner,
 }
 
 impl PackageStore {
@@ -59,6 +93,8 @@ impl PackageStore {
             versions: RwLock::new(HashMap::new()),
             package_data: RwLock::new(HashMap::new()),
             api_tokens: RwLock::new(HashMap::new()),
+            publish_log: RwLock::new(HashMap::new()),
+            webhooks: RwLock::new(HashMap::new()),
         }
     }
 
@@ -90,11 +126,67 @@ impl PackageStore {
         let packages = self.packages.read().unwrap();
         packages
             .values()
-            .filter(|p| p.owner_id == owner_id)
+            .filter(|p| p.is_owner(owner_id))
             .cloned()
             .collect()
     }
 
+    pub fn update_package(&self, package: Package) {
+        let mut packages = self.packages.write().unwrap();
+        packages.insert(package.id, package);
+    }
+
+    /// Adds `user_id` as a co-owner on behalf of `acting_user`. The checks and
+    /// the change happen under one lock, so concurrent requests cannot add the
+    /// same owner twice or act on a stale owner list. Callers check that the
+    /// user exists in the auth store first.
+    pub fn add_co_owner(
+        &self,
+        package_id: Uuid,
+        acting_user: Uuid,
+        user_id: Uuid,
+    ) -> Result<Package, OwnerError> {
+        let mut packages = self.packages.write().unwrap();
+        let pkg = packages
+            .get_mut(&package_id)
+            .ok_or(OwnerError::PackageNotFound)?;
+        if !pkg.is_owner(acting_user) {
+            return Err(OwnerError::NotOwner);
+        }
+        if pkg.is_owner(user_id) {
+            return Err(OwnerError::AlreadyOwner);
+        }
+        pkg.co_owners.push(user_id);
+        pkg.updated_at = Utc::now();
+        Ok(pkg.clone())
+    }
+
+    /// Removes co-owner `user_id` on behalf of `acting_user`, under the same
+    /// lock as its checks.
+    pub fn remove_co_owner(
+        &self,
+        package_id: Uuid,
+        acting_user: Uuid,
+        user_id: Uuid,
+    ) -> Result<Package, OwnerError> {
+        let mut packages = self.packages.write().unwrap();
+        let pkg = packages
+            .get_mut(&package_id)
+            .ok_or(OwnerError::PackageNotFound)?;
+        if !pkg.is_owner(acting_user) {
+            return Err(OwnerError::NotOwner);
+        }
+        if user_id == pkg.owner_id {
+            return Err(OwnerError::OriginalOwner);
+        }
+        if !pkg.co_owners.contains(&user_id) {
+            return Err(OwnerError::NotCoOwner);
+        }
+        pkg.co_owners.retain(|id| *id != user_id);
+        pkg.updated_at = Utc::now();
+        Ok(pkg.clone())
+    }
+
     pub fn create_version(&self, version: Version) -> Version {
         let mut versions = self.versions.write().unwrap();
         versions.insert(version.id, version.clone());
@@ -123,14 +215,70 @@ impl PackageStore {
         versions.insert(version.id, version);
     }
 
-    pub fn store_package_data(&self, key: &str, data: Vec<u8>) {
+    /// Archives are content-addressed: `checksum` is the sha256 of `data`, so
+    /// identical uploads share one entry.
+    pub fn store_package_data(&self, checksum: &str, data: Vec<u8>) {
         let mut package_data = self.package_data.write().unwrap();
-        package_data.insert(key.to_string(), data);
+        package_data.insert(checksum.to_string(), data);
     }
 
-    pub fn get_package_data(&self, key: &str) -> Option<Vec<u8>> {
+    pub fn get_package_data(&self, checksum: &str) -> Option<Vec<u8>> {
         let package_data = self.package_data.read().unwrap();
-        package_data.get(key).cloned()
+        package_data.get(checksum).cloned()
+    }
+
+    /// Counts a publish by `user_id` against `limit` publishes per trailing
+    /// `window`, or returns the seconds until a slot frees up. The check and
+    /// the count happen under one lock, so concurrent publishes cannot both
+    /// take the last slot.
+    pub fn reserve_publish(
+        &self,
+        user_id: Uuid,
+        limit: usize,
+        window: chrono::Duration,
+    ) -> Result<PublishReservation<'_>, i64> {
+        let now = Utc::now();
+        let mut log = self.publish_log.write().unwrap();
+        let times = log.entry(user_id).or_default();
+        times.retain(|t| now - *t < window);
+        if times.len() >= limit {
+            let oldest = times.iter().min().copied().unwrap_or(now);
+            return Err((oldest + window - now).num_seconds().max(1));
+        }
+        times.push(now);
+        Ok(PublishReservation {
+            store: self,
+            user_id,
+            at: now,
+            kept: false,
+        })
+    }
+
+    pub fn create_webhook(&self, webhook: Webhook) -> Webhook {
+        let mut webhooks = self.webhooks.write().unwrap();
+        webhooks.insert(webhook.id, webhook.clone());
+        webhook
+    }
+
+    pub fn find_webhook(&self, id: Uuid) -> Option<Webhook> {
+        let webhooks = self.webhooks.read().unwrap();
+        webhooks.get(&id).cloned()
+    }
+
+    pub fn list_webhooks(&self, package_id: Uuid) -> Vec<Webhook> {
+        let webhooks = self.webhooks.read().unwrap();
+        let mut list: Vec<Webhook> = webhooks
+            .values()
+            .filter(|w| w.package_id == package_id)
+            .cloned()
+            .collect();
+        list.sort_by_key(|w| w.created_at);
+        list
+    }
+
+    pub fn delete_webhook(&self, id: Uuid) -> bool {
+        let mut webhooks = self.webhooks.write().unwrap();
+        webhooks.remove(&id).is_some()
     }
 
     // API Token methods
@@ -191,6 +339,35 @@ impl PackageStore {
     }
 }
 
+/// A publish counted against its user's rate limit. Dropping it without
+/// calling `keep` gives the slot back, so rejected uploads do not count.
+pub struct PublishReservation<'a> {
+    store: &'a PackageStore,
+    user_id: Uuid,
+    at: DateTime<Utc>,
+    kept: bool,
+}
+
+impl PublishReservation<'_> {
+    pub fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for PublishReservation<'_> {
+    fn drop(&mut self) {
+        if self.kept {
+            return;
+        }
+        let mut log = self.store.publish_log.write().unwrap();
+        if let Some(times) = log.get_mut(&self.user_id) {
+            if let Some(i) = times.iter().position(|t| *t == self.at) {
+                times.remove(i);
+            }
+        }
+    }
+}
+
 impl Default for PackageStore {
     fn default() -> Self {
         Self::new()
@@ -206,3 +383,73 @@ pub fn hash_token(token: &str) -> String {
     hasher.update(token.as_bytes());
     hex::encode(hasher.finalize())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_reservations_count_only_when_kept() {
+        let store = PackageStore::new();
+        let user = Uuid::new_v4();
+        let window = chrono::Duration::hours(1);
+
+        store.reserve_publish(user, 1, window).unwrap().keep();
+        assert!(store.reserve_publish(user, 1, window).is_err());
+
+        let other = Uuid::new_v4();
+        let rejected = store.reserve_publish(other, 1, window).unwrap();
+        assert!(store.reserve_publish(other, 1, window).is_err());
+        drop(rejected);
+        assert!(store.reserve_publish(other, 1, window).is_ok());
+    }
+
+    fn test_package(owner_id: Uuid) -> Package {
+        Package {
+            id: Uuid::new_v4(),
+            namespace: "acme".into(),
+            name: "tools".into(),
+            description: None,
+            repository: None,
+            homepage: None,
+            documentation: None,
+            license: None,
+            keywords: vec![],
+            categories: vec![],
+            owner_id,
+            co_owners: vec![],
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_co_owner_changes_check_owners() {
+        let store = PackageStore::new();
+        let owner = Uuid::new_v4();
+        let pkg = store.create_package(test_package(owner));
+
+        let user = Uuid::new_v4();
+        assert_eq!(
+            store.add_co_owner(pkg.id, user, user).unwrap_err(),
+            OwnerError::NotOwner
+        );
+        let updated = store.add_co_owner(pkg.id, owner, user).unwrap();
+        assert_eq!(updated.owners(), vec![owner, user]);
+        assert_eq!(
+            store.add_co_owner(pkg.id, owner, user).unwrap_err(),
+            OwnerError::AlreadyOwner
+        );
+
+        assert_eq!(
+            store.remove_co_owner(pkg.id, user, owner).unwrap_err(),
+            OwnerError::OriginalOwner
+        );
+        let updated = store.remove_co_owner(pkg.id, user, user).unwrap();
+        assert_eq!(updated.owners(), vec![owner]);
+        assert_eq!(
+            store.remove_co_owner(pkg.id, owner, user).unwrap_err(),
+            OwnerError::NotCoOwner
+        );
+    }
+}
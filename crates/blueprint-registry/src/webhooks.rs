@@ -0,0 +1,321 @@
+use std::collections::VecDeque;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::sync::{mpsc, Semaphore};
+use uuid::Uuid;
+
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const LOG_CAPACITY: usize = 1000;
+const QUEUE_CAPACITY: usize = 1000;
+const MAX_IN_FLIGHT: usize = 32;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub package_id: Uuid,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: String,
+    pub attempts: u32,
+    pub success: bool,
+    pub finished_at: DateTime<Utc>,
+}
+
+struct Job {
+    webhook: Webhook,
+    event: String,
+    body: String,
+}
+
+/// Background queue that POSTs events to webhooks. Each delivery is signed
+/// with `X-Blueprint-Signature: sha256=<hex HMAC of the body>`, retried with
+/// exponential backoff, and recorded in a bounded in-memory log. Only
+/// success is recorded: status codes and connection errors stay in the
+/// server log, so the delivery log cannot be used to probe other hosts.
+pub struct WebhookDispatcher {
+    tx: mpsc::Sender<Job>,
+    log: Arc<RwLock<VecDeque<WebhookDelivery>>>,
+}
+
+impl WebhookDispatcher {
+    /// Starts the delivery worker; must be called inside a Tokio runtime.
+    pub fn start() -> Self {
+        let (tx, mut rx) = mpsc::channel::<Job>(QUEUE_CAPACITY);
+        let log = Arc::new(RwLock::new(VecDeque::new()));
+        let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+
+        let worker_log = log.clone();
+        tokio::spawn(async move {
+            while let Some(job) = rx.recv().await {
+                let Ok(permit) = in_flight.clone().acquire_owned().await else {
+                    break;
+                };
+                let log = worker_log.clone();
+                tokio::spawn(async move {
+                    let delivery = deliver(job).await;
+                    drop(permit);
+                    let mut log = log.write().unwrap();
+                    if log.len() == LOG_CAPACITY {
+                        log.pop_front();
+                    }
+                    log.push_back(delivery);
+                });
+            }
+        });
+
+        Self { tx, log }
+    }
+
+    pub fn enqueue(&self, webhook: Webhook, event: &str, payload: &serde_json::Value) {
+        let job = Job {
+            webhook,
+            event: event.to_string(),
+            body: payload.to_string(),
+        };
+        match self.tx.try_send(job) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(job)) => {
+                tracing::warn!(
+                    "webhook queue is full; dropping delivery to {}",
+                    job.webhook.id
+                );
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                tracing::error!("webhook worker has stopped; dropping delivery");
+            }
+        }
+    }
+
+    /// Most recent deliveries for `webhook_id`, newest first.
+    pub fn deliveries(&self, webhook_id: Uuid) -> Vec<WebhookDelivery> {
+        let log = self.log.read().unwrap();
+        log.iter()
+            .rev()
+            .filter(|d| d.webhook_id == webhook_id)
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Checks that `url` is an http(s) URL whose host resolves only to public
+/// addresses, and returns the host with the address to connect to. Loopback,
+/// private, link-local (which covers cloud metadata endpoints) and other
+/// special-purpose addresses are refused, so a webhook cannot reach services
+/// inside the registry's network.
+pub async fn resolve_public(url: &str) -> Result<(String, SocketAddr), String> {
+    let url = reqwest::Url::parse(url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err("Webhook URL must start with http:// or https://".into());
+    }
+    let host = url
+        .host_str()
+        .ok_or("Webhook URL must have a host")?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|_| format!("Could not resolve webhook host {}", host))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("Could not resolve webhook host {}", host));
+    }
+    if addrs.iter().any(|addr| !is_public(addr.ip())) {
+        return Err("Webhook URL must not point to a private or reserved address".into());
+    }
+    Ok((host, addrs[0]))
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space (100.64.0.0/10) and reserved (240.0.0.0/4).
+        || (a == 100 && (b & 0xc0) == 64)
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local (fc00::/7, including fd00:ec2::254) and link-local
+        // (fe80::/10) addresses.
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// One POST to `job`'s URL. The host is resolved and checked on every
+/// attempt, and the client is pinned to the checked address so a second DNS
+/// lookup cannot swap in a private one. Redirects are not followed.
+async fn post(
+    job: &Job,
+    delivery_id: Uuid,
+    signature: &str,
+) -> Result<reqwest::StatusCode, String> {
+    let (host, addr) = resolve_public(&job.webhook.url).await?;
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve(&host, addr)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let response = client
+        .post(&job.webhook.url)
+        .header("Content-Type", "application/json")
+        .header("X-Blueprint-Event", &job.event)
+        .header("X-Blueprint-Delivery", delivery_id.to_string())
+        .header("X-Blueprint-Signature", signature)
+        .body(job.body.clone())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(response.status())
+}
+
+async fn deliver(job: Job) -> WebhookDelivery {
+    let delivery_id = Uuid::new_v4();
+    let signature = sign(&job.webhook.secret, &job.body);
+    let mut backoff = INITIAL_BACKOFF;
+    let mut success = false;
+    let mut error = String::new();
+    let mut attempts = 0;
+
+    while attempts < MAX_ATTEMPTS {
+        if attempts > 0 {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        attempts += 1;
+
+        match post(&job, delivery_id, &signature).await {
+            Ok(code) => {
+                success = code.is_success();
+                error = format!("status {}", code.as_u16());
+                // Client errors other than rate limiting will not change on retry.
+                if code.is_success()
+                    || (code.is_client_error() && code != reqwest::StatusCode::TOO_MANY_REQUESTS)
+                {
+                    break;
+                }
+            }
+            Err(e) => error = e,
+        }
+    }
+
+    if !success {
+        tracing::warn!(
+            "webhook {} failed after {} attempt(s): {}",
+            job.webhook.id,
+            attempts,
+            error
+        );
+    }
+
+    WebhookDelivery {
+        id: delivery_id,
+        webhook_id: job.webhook.id,
+        event: job.event,
+        attempts,
+        success,
+        finished_at: Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_matches_known_hmac() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_is_public_rejects_internal_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00:ec2::254",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} should be refused", ip);
+        }
+        for ip in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+            assert!(is_public(ip.parse().unwrap()), "{} should be allowed", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_public_rejects_private_urls() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost/hook",
+            "ftp://example.com/hook",
+        ] {
+            assert!(
+                resolve_public(url).await.is_err(),
+                "{} should be refused",
+                url
+            );
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -11,6 +11,20 @@ use std::path::PathBuf;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Never contact the registry; only use installed or vendored packages (or set BP_OFFLINE=1)"
+    )]
+    pub offline: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -42,14 +56,112 @@ pub enum Commands {
         #[arg(
             short = 'j',
             long,
-            default_value = "0",
-            help = "Max concurrent scripts (0 = unlimited)"
+            help = "Max concurrent script weight (default: number of CPUs, 0 = unlimited)"
         )]
-        jobs: usize,
+        jobs: Option<usize>,
 
         #[arg(short, long, help = "Verbose output")]
         verbose: bool,
 
+        #[arg(
+            long,
+            value_enum,
+            default_value = "text",
+            help = "Error output format (json emits one object per line on stderr)"
+        )]
+        format: OutputFormat,
+
+        #[arg(
+            long,
+            help = "Print call counts and per-statement timings after the run"
+        )]
+        stats: bool,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Sample call stacks and write them as folded stacks for flamegraph tools"
+        )]
+        profile: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "MS",
+            default_value = "10",
+            requires = "profile",
+            help = "Sampling interval for --profile in milliseconds"
+        )]
+        profile_interval: u64,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Write line coverage as lcov, or JSON if PATH ends in .json"
+        )]
+        coverage: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Load environment variables from a dotenv file"
+        )]
+        env_file: Option<PathBuf>,
+
+        #[arg(
+            long,
+            requires = "env_file",
+            help = "Let --env-file values replace variables already in the environment"
+        )]
+        env_override: bool,
+
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            help = "Abort a script that runs longer than this many seconds"
+        )]
+        timeout: Option<f64>,
+
+        #[arg(
+            long,
+            requires = "timeout",
+            help = "Also apply --timeout while waiting on active triggers"
+        )]
+        timeout_triggers: bool,
+
+        #[arg(
+            long,
+            value_name = "BYTES",
+            help = "Abort evaluation once the process uses more than this much resident memory"
+        )]
+        max_memory: Option<u64>,
+
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Abort a script after it evaluates this many statements and expressions"
+        )]
+        max_steps: Option<u64>,
+
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Seed random values, uuid4() and trigger ids so runs are reproducible"
+        )]
+        seed: Option<u64>,
+
+        #[arg(
+            long,
+            value_name = "EPOCH",
+            help = "Pin time.now() to this Unix timestamp in seconds"
+        )]
+        freeze_time: Option<f64>,
+
+        #[arg(
+            long,
+            help = "Stop remaining scripts as soon as one fails instead of reporting all failures"
+        )]
+        fail_fast: bool,
+
         #[arg(long, help = "Run in sandbox mode with all permissions denied")]
         sandbox: bool,
 
@@ -77,6 +189,122 @@ pub enum Commands {
         script_args: Vec<String>,
     },
 
+    #[command(about = "Run test scripts and the cases they register with @bp/test")]
+    Test {
+        #[arg(help = "Test files, directories or globs (default: current directory)")]
+        paths: Vec<PathBuf>,
+
+        #[arg(short, long, help = "Verbose output")]
+        verbose: bool,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value = "text",
+            help = "Result output format (json emits one object per test on stdout)"
+        )]
+        format: OutputFormat,
+
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            help = "Abort a test script that runs longer than this many seconds"
+        )]
+        timeout: Option<f64>,
+
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Seed random values, uuid4() and trigger ids so runs are reproducible"
+        )]
+        seed: Option<u64>,
+
+        #[arg(long, help = "Stop after the first test script with a failure")]
+        fail_fast: bool,
+
+        #[arg(
+            long,
+            help = "Rewrite test.snapshot() files that differ instead of failing"
+        )]
+        update_snapshots: bool,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Write line coverage as lcov, or JSON if PATH ends in .json"
+        )]
+        coverage: Option<PathBuf>,
+
+        #[arg(long, help = "Run in sandbox mode with all permissions denied")]
+        sandbox: bool,
+
+        #[arg(long, help = "Allow all permissions without prompting (trust mode)")]
+        allow_all: bool,
+
+        #[arg(
+            long = "allow",
+            value_name = "RULE",
+            help = "Allow permission (e.g., 'fs.read:./data/*', 'process.run:git')"
+        )]
+        allow: Vec<String>,
+
+        #[arg(
+            long = "deny",
+            value_name = "RULE",
+            help = "Deny permission (e.g., 'process.shell', 'fs.delete:*')"
+        )]
+        deny: Vec<String>,
+    },
+
+    #[command(about = "Run bench_* functions in benchmark scripts and report timings")]
+    Bench {
+        #[arg(help = "Benchmark files, directories or globs (default: current directory)")]
+        paths: Vec<PathBuf>,
+
+        #[arg(
+            short = 'n',
+            long,
+            default_value = "100",
+            help = "Timed calls per benchmark"
+        )]
+        iterations: u64,
+
+        #[arg(
+            long,
+            default_value = "10",
+            help = "Untimed calls before timing starts"
+        )]
+        warmup: u64,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value = "text",
+            help = "Result output format (json emits one object per benchmark on stdout)"
+        )]
+        format: OutputFormat,
+
+        #[arg(long, help = "Run in sandbox mode with all permissions denied")]
+        sandbox: bool,
+
+        #[arg(long, help = "Allow all permissions without prompting (trust mode)")]
+        allow_all: bool,
+
+        #[arg(
+            long = "allow",
+            value_name = "RULE",
+            help = "Allow permission (e.g., 'fs.read:./data/*', 'process.run:git')"
+        )]
+        allow: Vec<String>,
+
+        #[arg(
+            long = "deny",
+            value_name = "RULE",
+            help = "Deny permission (e.g., 'process.shell', 'fs.delete:*')"
+        )]
+        deny: Vec<String>,
+    },
+
     #[command(about = "Check scripts for syntax errors (dry run)")]
     Check {
         #[arg(required = true, num_args = 1..)]
@@ -84,6 +312,14 @@ pub enum Commands {
 
         #[arg(short, long, help = "Verbose output")]
         verbose: bool,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value = "text",
+            help = "Error output format (json emits one object per line on stderr)"
+        )]
+        format: OutputFormat,
     },
 
     #[command(about = "Evaluate a Starlark expression")]
@@ -122,6 +358,9 @@ pub enum Commands {
     #[command(about = "Install dependencies from BP.toml")]
     Sync,
 
+    #[command(about = "Download BP.toml dependencies into vendor/ for offline use")]
+    Vendor,
+
     #[command(about = "Login to the package registry")]
     Login {
         #[arg(long, help = "Registry URL (default: https://registry.blueprint.dev)")]
@@ -488,6 +488,26 @@ impl CfgBuilder {
                 vec![node]
             }
 
+            StmtP::With(with_stmt) => {
+                let mut label = format!("with {}", self.expr_to_string(&with_stmt.resource));
+                if let Some(target) = &with_stmt.target {
+                    label = format!("{} as {}", label, self.assign_target_to_string(target));
+                }
+                let node = self.graph.add_node(
+                    NodeKind::Statement,
+                    label,
+                    &self.current_file,
+                    self.current_function.as_deref(),
+                );
+
+                for pred in &predecessors {
+                    self.add_predecessor_edge(*pred, node);
+                }
+
+                self.add_call_edges(&with_stmt.resource, node);
+                self.analyze_stmt(&with_stmt.body, vec![node])
+            }
+
             StmtP::Def(_) => predecessors,
 
             StmtP::Struct(struct_def) => {
@@ -792,6 +812,7 @@ impl CfgBuilder {
             AssignTargetP::Dot(target, attr) => {
                 format!("{}.{}", self.expr_to_string(target), attr.node)
             }
+            AssignTargetP::Starred(inner) => format!("*{}", self.assign_target_to_string(inner)),
         }
     }
 }
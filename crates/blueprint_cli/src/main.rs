@@ -4,14 +4,18 @@ mod runner;
 mod workspace;
 
 use blueprint_engine_core::BlueprintError;
+use blueprint_engine_eval::set_update_snapshots;
 use clap::Parser;
 use tokio::runtime::Builder;
 
 use args::{Cli, Commands, GenerateCommands};
-use runner::PermissionFlags;
+use runner::{PermissionFlags, RunOptions};
 
 fn main() {
     let cli = Cli::parse();
+    if cli.offline {
+        std::env::set_var("BP_OFFLINE", "1");
+    }
 
     let runtime = Builder::new_multi_thread()
         .enable_all()
@@ -25,6 +29,20 @@ fn main() {
                 exec,
                 jobs,
                 verbose,
+                format,
+                stats,
+                profile,
+                profile_interval,
+                coverage,
+                env_file,
+                env_override,
+                timeout,
+                timeout_triggers,
+                max_memory,
+                max_steps,
+                seed,
+                freeze_time,
+                fail_fast,
                 sandbox,
                 allow_all,
                 ask,
@@ -39,13 +57,104 @@ fn main() {
                     allow,
                     deny,
                 };
+                if let Some(path) = env_file {
+                    let applied = runner::load_env_file(&path, env_override)?;
+                    if verbose {
+                        eprintln!("Loaded {} variable(s) from {}", applied, path.display());
+                    }
+                }
+                let run_opts = RunOptions {
+                    timeout: RunOptions::parse_timeout(timeout)?,
+                    timeout_triggers,
+                    max_memory,
+                    max_steps,
+                    seed,
+                    freeze_time,
+                    fail_fast,
+                    stats,
+                    profile,
+                    profile_interval: std::time::Duration::from_millis(profile_interval.max(1)),
+                    coverage,
+                };
                 if let Some(code) = exec {
-                    runner::run_inline(&code, verbose, script_args, perm_flags).await
+                    runner::run_inline(&code, verbose, format, script_args, perm_flags, run_opts)
+                        .await
                 } else {
-                    runner::run_scripts(scripts, jobs, verbose, script_args, perm_flags).await
+                    runner::run_scripts(
+                        scripts,
+                        jobs.unwrap_or_else(runner::default_jobs),
+                        verbose,
+                        format,
+                        script_args,
+                        perm_flags,
+                        run_opts,
+                    )
+                    .await
                 }
             }
-            Commands::Check { scripts, verbose } => runner::check_scripts(scripts, verbose).await,
+            Commands::Test {
+                paths,
+                verbose,
+                format,
+                timeout,
+                seed,
+                fail_fast,
+                update_snapshots,
+                coverage,
+                sandbox,
+                allow_all,
+                allow,
+                deny,
+            } => {
+                let perm_flags = PermissionFlags {
+                    sandbox,
+                    allow_all,
+                    ask: false,
+                    allow,
+                    deny,
+                };
+                let run_opts = RunOptions {
+                    timeout: RunOptions::parse_timeout(timeout)?,
+                    seed,
+                    fail_fast,
+                    coverage,
+                    ..RunOptions::default()
+                };
+                set_update_snapshots(update_snapshots);
+                runner::run_tests(paths, verbose, format, perm_flags, run_opts).await
+            }
+            Commands::Bench {
+                paths,
+                iterations,
+                warmup,
+                format,
+                sandbox,
+                allow_all,
+                allow,
+                deny,
+            } => {
+                let perm_flags = PermissionFlags {
+                    sandbox,
+                    allow_all,
+                    ask: false,
+                    allow,
+                    deny,
+                };
+                runner::run_benches(
+                    paths,
+                    iterations,
+                    warmup,
+                    format,
+                    perm_flags,
+                    RunOptions::default(),
+                )
+                .await
+            }
+            Commands::Check {
+                scripts,
+                verbose,
+                format,
+            } => runner::check_scripts(scripts, verbose, format).await,
             Commands::Eval { expression, port } => runner::eval_expression(&expression, port).await,
             Commands::Repl { port } => runner::repl(port).await,
             Commands::Install { package } => runner::install_package(&package).await,
@@ -53,6 +162,7 @@ fn main() {
             Commands::List => runner::list_packages().await,
             Commands::Init => runner::init_workspace().await,
             Commands::Sync => runner::sync_workspace().await,
+            Commands::Vendor => runner::vendor_workspace().await,
             Commands::Login { registry } => runner::login(registry.as_deref()).await,
             Commands::Logout => runner::logout().await,
             Commands::Publish {
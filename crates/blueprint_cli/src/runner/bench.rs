@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use blueprint_engine_core::{BlueprintError, Result, Value};
+use blueprint_engine_eval::{run_benchmark, take_bench_results, BenchResult};
+
+use super::testing::discover;
+use super::{report_error, run_script_then, PermissionFlags, RunOptions};
+use crate::args::OutputFormat;
+
+/// Runs each benchmark script, then every global `bench_*` function it
+/// defines in name order. Benchmarks the script ran itself with
+/// `bench.run()` are reported first.
+pub async fn run_benches(
+    paths: Vec<PathBuf>,
+    iterations: u64,
+    warmup: u64,
+    format: OutputFormat,
+    perm_flags: PermissionFlags,
+    run_opts: RunOptions,
+) -> Result<()> {
+    let scripts = discover(paths, "bench")?;
+    if scripts.is_empty() {
+        eprintln!("No benchmark scripts found");
+        return Ok(());
+    }
+
+    run_opts.start_instrumentation();
+    let mut failed = false;
+
+    for path in &scripts {
+        let outcome = run_script_then(
+            path,
+            vec![],
+            false,
+            &perm_flags,
+            &run_opts,
+            |scope| async move {
+                let mut benches: Vec<(String, Value)> = scope
+                    .all_variables()
+                    .await
+                    .into_iter()
+                    .filter(|(name, value)| {
+                        name.starts_with("bench_")
+                            && matches!(value, Value::Function(_) | Value::Lambda(_))
+                    })
+                    .collect();
+                benches.sort_by(|a, b| a.0.cmp(&b.0));
+
+                for (name, func) in &benches {
+                    run_benchmark(name, func, iterations, warmup).await?;
+                }
+                Ok(())
+            },
+        )
+        .await;
+
+        for result in take_bench_results() {
+            report_result(path, &result, format);
+        }
+        if let Err(error) = &outcome {
+            failed = true;
+            report_error(path, error, format);
+        }
+    }
+
+    run_opts.finish_instrumentation()?;
+
+    if failed {
+        return Err(BlueprintError::Silent);
+    }
+    Ok(())
+}
+
+fn report_result(path: &Path, result: &BenchResult, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => println!(
+            "{} > {:<32} {:>7} iters  mean {:>10}  median {:>10}  p99 {:>10}",
+            path.display(),
+            result.name,
+            result.iterations,
+            format_duration(result.mean),
+            format_duration(result.median),
+            format_duration(result.p99),
+        ),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "file": path.to_string_lossy(),
+                "name": result.name,
+                "iterations": result.iterations,
+                "mean": result.mean.as_secs_f64(),
+                "median": result.median.as_secs_f64(),
+                "p99": result.p99.as_secs_f64(),
+                "min": result.min.as_secs_f64(),
+                "max": result.max.as_secs_f64(),
+            })
+        ),
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    let nanos = d.as_nanos();
+    if nanos < 1_000 {
+        format!("{}ns", nanos)
+    } else if nanos < 1_000_000 {
+        format!("{:.2}µs", nanos as f64 / 1e3)
+    } else if nanos < 1_000_000_000 {
+        format!("{:.2}ms", nanos as f64 / 1e6)
+    } else {
+        format!("{:.2}s", d.as_secs_f64())
+    }
+}
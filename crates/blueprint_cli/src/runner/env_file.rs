@@ -0,0 +1,159 @@
+use std::path::Path;
+
+use blueprint_engine_core::{BlueprintError, Result};
+
+/// Loads `KEY=VALUE` pairs from a dotenv file into the process environment.
+/// Variables that are already set win unless `override_existing` is true.
+pub fn load_env_file(path: &Path, override_existing: bool) -> Result<usize> {
+    let content = std::fs::read_to_string(path).map_err(|e| BlueprintError::IoError {
+        path: path.to_string_lossy().to_string(),
+        message: e.to_string(),
+    })?;
+
+    let vars = parse_env_file(&content).map_err(|(line, message)| BlueprintError::ValueError {
+        message: format!("{}:{}: {}", path.display(), line, message),
+    })?;
+
+    let mut applied = 0;
+    for (key, value) in vars {
+        if override_existing || std::env::var_os(&key).is_none() {
+            std::env::set_var(&key, &value);
+            applied += 1;
+        }
+    }
+    Ok(applied)
+}
+
+fn parse_env_file(content: &str) -> std::result::Result<Vec<(String, String)>, (usize, String)> {
+    let mut vars = Vec::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((index, raw)) = lines.next() {
+        let line_no = index + 1;
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let (key, rest) = line
+            .split_once('=')
+            .ok_or_else(|| (line_no, format!("expected KEY=VALUE, got '{}'", line)))?;
+
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err((line_no, format!("invalid variable name '{}'", key)));
+        }
+
+        let rest = rest.trim_start();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                // Quoted values may span several lines until the closing quote.
+                let mut body = rest[1..].to_string();
+                loop {
+                    if let Some(end) = find_closing_quote(&body, quote) {
+                        body.truncate(end);
+                        break;
+                    }
+                    match lines.next() {
+                        Some((_, next)) => {
+                            body.push('\n');
+                            body.push_str(next);
+                        }
+                        None => return Err((line_no, format!("unterminated {} quote", quote))),
+                    }
+                }
+                if quote == '"' {
+                    unescape(&body)
+                } else {
+                    body
+                }
+            }
+            _ => match rest.find(" #") {
+                Some(comment) => rest[..comment].trim_end().to_string(),
+                None => rest.trim_end().to_string(),
+            },
+        };
+
+        vars.push((key.to_string(), value));
+    }
+
+    Ok(vars)
+}
+
+fn find_closing_quote(s: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote == '"' {
+            escaped = true;
+        } else if c == quote {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Vec<(String, String)> {
+        parse_env_file(content).unwrap()
+    }
+
+    #[test]
+    fn test_parse_basic_and_comments() {
+        let vars = parse("# comment\nFOO=bar\n\nexport BAZ = qux # trailing\nEMPTY=\n");
+        assert_eq!(
+            vars,
+            vec![
+                ("FOO".into(), "bar".into()),
+                ("BAZ".into(), "qux".into()),
+                ("EMPTY".into(), "".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_quoted_values() {
+        let vars = parse("A=\"hello # not a comment\"\nB='raw \\n value'\nC=\"line\\nbreak\"\n");
+        assert_eq!(vars[0].1, "hello # not a comment");
+        assert_eq!(vars[1].1, "raw \\n value");
+        assert_eq!(vars[2].1, "line\nbreak");
+    }
+
+    #[test]
+    fn test_parse_multiline_quoted_value() {
+        let vars = parse("KEY=\"-----BEGIN-----\nabc\n-----END-----\"\nNEXT=1\n");
+        assert_eq!(vars[0].1, "-----BEGIN-----\nabc\n-----END-----");
+        assert_eq!(vars[1], ("NEXT".into(), "1".into()));
+    }
+
+    #[test]
+    fn test_parse_errors_report_line() {
+        assert_eq!(parse_env_file("OK=1\nnot a pair\n").unwrap_err().0, 2);
+        assert_eq!(parse_env_file("BAD KEY=1\n").unwrap_err().0, 1);
+        assert_eq!(parse_env_file("A=\"open\n").unwrap_err().0, 1);
+    }
+}
@@ -1,24 +1,41 @@
+mod bench;
+mod env_file;
 mod package;
 mod publish;
 mod repl;
+mod testing;
+mod weight;
 
+pub use bench::run_benches;
+pub use env_file::load_env_file;
 pub use package::{
     init_workspace, install_package, list_packages, sync_workspace, uninstall_package,
+    vendor_workspace,
 };
 pub use publish::{login, logout, publish, whoami};
-pub use repl::{eval_expression, repl};
+pub use repl::{eval_expression, install_breakpoint_handler, repl};
+pub use testing::run_tests;
 
+use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use blueprint_engine_core::{
-    with_permissions_async, BlueprintError, Permissions, Policy, Result, Value,
+    with_permissions_async, with_session, BlueprintError, Permissions, Policy, Result, Session,
+    Value,
 };
-use blueprint_engine_eval::{triggers, Checker, Evaluator, Scope};
-use blueprint_engine_parser::parse;
+use blueprint_engine_eval::{
+    coverage_snapshot, enable_coverage, enable_stats, freeze_clock, set_seed,
+    start_memory_watchdog, start_profiler, stats_snapshot, stop_memory_watchdog, stop_profiler,
+    triggers, with_profile_stack, Checker, Evaluator, Scope,
+};
+use blueprint_engine_parser::{parse, parse_all};
 use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
+use tokio::time::Instant;
 
+use crate::args::OutputFormat;
 use crate::workspace::Workspace;
 
 #[derive(Clone, Default)]
@@ -60,6 +77,169 @@ impl PermissionFlags {
     }
 }
 
+#[derive(Clone, Default)]
+pub struct RunOptions {
+    pub timeout: Option<Duration>,
+    pub timeout_triggers: bool,
+    pub max_memory: Option<u64>,
+    pub max_steps: Option<u64>,
+    pub seed: Option<u64>,
+    pub freeze_time: Option<f64>,
+    pub fail_fast: bool,
+    pub stats: bool,
+    pub profile: Option<PathBuf>,
+    pub profile_interval: Duration,
+    pub coverage: Option<PathBuf>,
+}
+
+impl RunOptions {
+    fn evaluator(&self) -> Evaluator {
+        let evaluator = Evaluator::new();
+        match self.max_steps {
+            Some(steps) => evaluator.with_step_budget(steps),
+            None => evaluator,
+        }
+    }
+
+    pub fn parse_timeout(timeout: Option<f64>) -> Result<Option<Duration>> {
+        timeout
+            .map(|secs| {
+                Duration::try_from_secs_f64(secs).map_err(|_| BlueprintError::ArgumentError {
+                    message: format!("--timeout must be a non-negative number, got {}", secs),
+                })
+            })
+            .transpose()
+    }
+
+    /// Applies the options that belong to a single script run. Called at
+    /// the start of each run's session.
+    fn start_session(&self) {
+        if let Some(seed) = self.seed {
+            set_seed(seed);
+        }
+        if let Some(epoch) = self.freeze_time {
+            freeze_clock(epoch);
+        }
+    }
+
+    fn start_instrumentation(&self) {
+        if let Some(limit) = self.max_memory {
+            if !start_memory_watchdog(limit) {
+                eprintln!("warning: --max-memory is not supported on this platform");
+            }
+        }
+        if self.stats {
+            enable_stats();
+        }
+        if self.profile.is_some() {
+            start_profiler(self.profile_interval);
+        }
+        if self.coverage.is_some() {
+            enable_coverage();
+        }
+    }
+
+    fn finish_instrumentation(&self) -> Result<()> {
+        if self.max_memory.is_some() {
+            stop_memory_watchdog();
+        }
+        if self.stats {
+            eprintln!("\n{}", stats_snapshot());
+        }
+        if let Some(path) = &self.profile {
+            let folded = stop_profiler();
+            std::fs::write(path, folded).map_err(|e| BlueprintError::IoError {
+                path: path.to_string_lossy().to_string(),
+                message: e.to_string(),
+            })?;
+            eprintln!("Profile written to {}", path.display());
+        }
+        if let Some(path) = &self.coverage {
+            write_coverage(path)?;
+        }
+        Ok(())
+    }
+
+    fn deadline(&self) -> Option<Deadline> {
+        self.timeout.map(|limit| Deadline {
+            at: Instant::now() + limit,
+            limit,
+        })
+    }
+}
+
+/// Writes JSON when `path` ends in `.json` and an lcov tracefile otherwise.
+fn write_coverage(path: &Path) -> Result<()> {
+    let report = coverage_snapshot();
+    let contents = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::to_string_pretty(&report.to_json()).unwrap_or_default()
+    } else {
+        report.to_lcov()
+    };
+    std::fs::write(path, contents).map_err(|e| BlueprintError::IoError {
+        path: path.to_string_lossy().to_string(),
+        message: e.to_string(),
+    })?;
+
+    let total = report.total();
+    let percent = if total == 0 {
+        100.0
+    } else {
+        report.covered() as f64 * 100.0 / total as f64
+    };
+    eprintln!(
+        "Coverage: {}/{} lines ({:.1}%) written to {}",
+        report.covered(),
+        total,
+        percent,
+        path.display()
+    );
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+struct Deadline {
+    at: Instant,
+    limit: Duration,
+}
+
+async fn with_deadline<T>(deadline: Option<Deadline>, fut: impl Future<Output = T>) -> Result<T> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline.at, fut)
+            .await
+            .map_err(|_| BlueprintError::Timeout {
+                seconds: deadline.limit.as_secs_f64(),
+            }),
+        None => Ok(fut.await),
+    }
+}
+
+async fn wait_for_triggers(
+    verbose: bool,
+    run_opts: &RunOptions,
+    deadline: Option<Deadline>,
+) -> Result<()> {
+    if !triggers::has_active_triggers().await {
+        triggers::run_shutdown_hooks().await;
+        return Ok(());
+    }
+
+    if verbose {
+        eprintln!("Active triggers detected, waiting for shutdown...");
+    }
+
+    if !run_opts.timeout_triggers {
+        triggers::wait_for_shutdown().await;
+        return Ok(());
+    }
+
+    let result = with_deadline(deadline, triggers::wait_for_shutdown()).await;
+    if result.is_err() {
+        triggers::shutdown_all().await;
+    }
+    result
+}
+
 fn load_workspace_permissions(script_path: Option<&Path>) -> Option<Permissions> {
     let start_dir = script_path
         .and_then(|p| p.parent())
@@ -69,12 +249,21 @@ fn load_workspace_permissions(script_path: Option<&Path>) -> Option<Permissions>
     Workspace::find(&start_dir).map(|ws| ws.config.permissions)
 }
 
+/// Default `--jobs` limit: one script per available CPU.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
 pub async fn run_scripts(
     scripts: Vec<PathBuf>,
     jobs: usize,
     verbose: bool,
+    format: OutputFormat,
     script_args: Vec<String>,
     perm_flags: PermissionFlags,
+    run_opts: RunOptions,
 ) -> Result<()> {
     let scripts = expand_globs(scripts)?;
 
@@ -87,14 +276,19 @@ pub async fn run_scripts(
         eprintln!("Running {} script(s)", scripts.len());
     }
 
+    install_breakpoint_handler();
+    run_opts.start_instrumentation();
+
     let semaphore = if jobs > 0 {
         Some(Arc::new(Semaphore::new(jobs)))
     } else {
         None
     };
+    let max_weight = u32::try_from(jobs).unwrap_or(u32::MAX);
 
     let script_args = Arc::new(script_args);
     let perm_flags = Arc::new(perm_flags);
+    let run_opts = Arc::new(run_opts);
     let mut join_set: JoinSet<
         std::result::Result<(PathBuf, Option<BlueprintError>), (PathBuf, BlueprintError)>,
     > = JoinSet::new();
@@ -103,16 +297,24 @@ pub async fn run_scripts(
         let semaphore = semaphore.clone();
         let script_args = script_args.clone();
         let perm_flags = perm_flags.clone();
+        let run_opts = run_opts.clone();
 
         join_set.spawn(async move {
             let _permit = if let Some(sem) = &semaphore {
-                Some(sem.acquire().await.unwrap())
+                let weight = weight::script_weight(&script_path).min(max_weight);
+                Some(sem.acquire_many(weight).await.unwrap())
             } else {
                 None
             };
 
-            match run_single_script(&script_path, (*script_args).clone(), verbose, &perm_flags)
-                .await
+            match run_single_script(
+                &script_path,
+                (*script_args).clone(),
+                verbose,
+                &perm_flags,
+                &run_opts,
+            )
+            .await
             {
                 Ok(()) => Ok((script_path, None)),
                 Err(e) => {
@@ -129,6 +331,7 @@ pub async fn run_scripts(
     let mut errors: Vec<(PathBuf, BlueprintError)> = vec![];
     let mut exit_error: Option<BlueprintError> = None;
     let mut success_count = 0;
+    let mut cancelled = 0;
 
     while let Some(result) = join_set.join_next().await {
         match result {
@@ -143,8 +346,15 @@ pub async fn run_scripts(
                 }
             }
             Ok(Err((path, error))) => {
-                eprintln!("[FAIL] {}", path.display());
+                if format == OutputFormat::Text {
+                    eprintln!("[FAIL] {}", path.display());
+                }
                 errors.push((path, error));
+                if run_opts.fail_fast {
+                    cancelled = join_set.len();
+                    join_set.shutdown().await;
+                    break;
+                }
             }
             Err(join_error) => {
                 eprintln!("[PANIC] Task panicked: {}", join_error);
@@ -152,6 +362,8 @@ pub async fn run_scripts(
         }
     }
 
+    run_opts.finish_instrumentation()?;
+
     if let Some(exit_err) = exit_error {
         return Err(exit_err);
     }
@@ -163,11 +375,16 @@ pub async fn run_scripts(
             errors.len()
         );
     }
+    if cancelled > 0 && format == OutputFormat::Text {
+        eprintln!("--fail-fast: cancelled {} remaining script(s)", cancelled);
+    }
 
     if !errors.is_empty() {
-        eprintln!("\nErrors:");
+        if format == OutputFormat::Text {
+            eprintln!("\nErrors:");
+        }
         for (path, error) in &errors {
-            report_error(path, error);
+            report_error(path, error, format);
         }
         return Err(BlueprintError::Silent);
     }
@@ -180,7 +397,33 @@ async fn run_single_script(
     script_args: Vec<String>,
     verbose: bool,
     perm_flags: &PermissionFlags,
+    run_opts: &RunOptions,
 ) -> Result<()> {
+    run_script_then(
+        path,
+        script_args,
+        verbose,
+        perm_flags,
+        run_opts,
+        |_| async { Ok(()) },
+    )
+    .await
+}
+
+/// Runs a script like `run_single_script`, then calls `then` with its global
+/// scope under the same permissions and deadline, before waiting on triggers.
+async fn run_script_then<F, Fut>(
+    path: &Path,
+    script_args: Vec<String>,
+    verbose: bool,
+    perm_flags: &PermissionFlags,
+    run_opts: &RunOptions,
+    then: F,
+) -> Result<()>
+where
+    F: FnOnce(Arc<Scope>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
     let source = tokio::fs::read_to_string(path)
         .await
         .map_err(|e| BlueprintError::IoError {
@@ -207,8 +450,11 @@ async fn run_single_script(
     let workspace_perms = load_workspace_permissions(Some(path));
     let permissions = perm_flags.resolve(workspace_perms);
 
-    let run_script = async {
-        let mut evaluator = Evaluator::new();
+    let deadline = run_opts.deadline();
+    // Boxed, like the session around it, so the wrappers below hold pointers
+    // rather than copies of the script's future in their stack frames.
+    let run_script = Box::pin(async {
+        let mut evaluator = run_opts.evaluator();
         evaluator.set_file(path);
         let scope = Scope::new_global();
 
@@ -235,17 +481,15 @@ async fn run_single_script(
             scope.define("__verbose__", Value::Bool(true)).await;
         }
 
-        evaluator.eval(&module, scope).await?;
-
-        if triggers::has_active_triggers().await {
-            if verbose {
-                eprintln!("Active triggers detected, waiting for shutdown...");
-            }
-            triggers::wait_for_shutdown().await;
-        }
+        with_deadline(deadline, evaluator.eval(&module, scope.clone())).await??;
+        with_deadline(deadline, then(scope)).await??;
 
-        Ok(())
-    };
+        wait_for_triggers(verbose, run_opts, deadline).await
+    });
+    let run_script = Box::pin(in_session(
+        run_opts,
+        with_profile_stack(&filename, run_script),
+    ));
 
     if let Some(perms) = permissions {
         with_permissions_async(perms, || run_script).await
@@ -254,7 +498,52 @@ async fn run_single_script(
     }
 }
 
-pub async fn check_scripts(scripts: Vec<PathBuf>, verbose: bool) -> Result<()> {
+/// Runs one script in a session of its own, so `--seed` and `--freeze-time`
+/// apply to it alone and its shutdown hooks run when it fails or the process
+/// is signalled.
+async fn in_session(
+    run_opts: &RunOptions,
+    run_script: impl Future<Output = Result<()>>,
+) -> Result<()> {
+    with_session(Arc::new(Session::default()), async {
+        run_opts.start_session();
+        let result = tokio::select! {
+            result = run_script => result,
+            _ = shutdown_signal() => {
+                triggers::shutdown_all().await;
+                return Err(BlueprintError::Exit { code: 130 });
+            }
+        };
+        if result.is_err() {
+            triggers::run_shutdown_hooks().await;
+        }
+        result
+    })
+    .await
+}
+
+/// Resolves when the process is asked to stop with SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+pub async fn check_scripts(
+    scripts: Vec<PathBuf>,
+    verbose: bool,
+    format: OutputFormat,
+) -> Result<()> {
     let scripts = expand_globs(scripts)?;
 
     if scripts.is_empty() {
@@ -284,37 +573,73 @@ pub async fn check_scripts(scripts: Vec<PathBuf>, verbose: bool) -> Result<()> {
         };
 
         let filename = path.to_string_lossy().to_string();
-        if let Err(e) = parse(&filename, &source) {
+        let (_, parse_errors) = parse_all(&filename, &source);
+        for e in parse_errors {
             errors.push((path.clone(), e));
         }
     }
 
     if errors.is_empty() {
-        eprintln!("All {} script(s) OK", scripts.len());
+        if format == OutputFormat::Text {
+            eprintln!("All {} script(s) OK", scripts.len());
+        }
         Ok(())
     } else {
         for (path, error) in &errors {
-            report_error(path, error);
+            report_error(path, error, format);
+        }
+        match format {
+            OutputFormat::Text => Err(BlueprintError::InternalError {
+                message: format!("{} error(s) found", errors.len()),
+            }),
+            OutputFormat::Json => Err(BlueprintError::Silent),
         }
-        Err(BlueprintError::InternalError {
-            message: format!("{} script(s) have errors", errors.len()),
-        })
     }
 }
 
 pub async fn run_inline(
     code: &str,
     verbose: bool,
+    format: OutputFormat,
     script_args: Vec<String>,
     perm_flags: PermissionFlags,
+    run_opts: RunOptions,
+) -> Result<()> {
+    install_breakpoint_handler();
+    run_opts.start_instrumentation();
+    let result = run_inline_code(code, verbose, script_args, perm_flags, &run_opts).await;
+    run_opts.finish_instrumentation()?;
+
+    match result {
+        Err(e)
+            if format == OutputFormat::Json
+                && !matches!(
+                    e.inner_error(),
+                    BlueprintError::Exit { .. } | BlueprintError::Silent
+                ) =>
+        {
+            report_error(Path::new("<inline>"), &e, format);
+            Err(BlueprintError::Silent)
+        }
+        other => other,
+    }
+}
+
+async fn run_inline_code(
+    code: &str,
+    verbose: bool,
+    script_args: Vec<String>,
+    perm_flags: PermissionFlags,
+    run_opts: &RunOptions,
 ) -> Result<()> {
     let module = parse("<inline>", code)?;
 
     let workspace_perms = load_workspace_permissions(None);
     let permissions = perm_flags.resolve(workspace_perms);
 
-    let run_script = async {
-        let mut evaluator = Evaluator::new();
+    let deadline = run_opts.deadline();
+    let run_script = Box::pin(async {
+        let mut evaluator = run_opts.evaluator();
         let scope = Scope::new_global();
 
         let argv: Vec<Value> = std::iter::once(Value::String(Arc::new("<inline>".to_string())))
@@ -336,17 +661,14 @@ pub async fn run_inline(
             scope.define("__verbose__", Value::Bool(true)).await;
         }
 
-        evaluator.eval(&module, scope).await?;
-
-        if triggers::has_active_triggers().await {
-            if verbose {
-                eprintln!("Active triggers detected, waiting for shutdown...");
-            }
-            triggers::wait_for_shutdown().await;
-        }
+        with_deadline(deadline, evaluator.eval(&module, scope)).await??;
 
-        Ok(())
-    };
+        wait_for_triggers(verbose, run_opts, deadline).await
+    });
+    let run_script = Box::pin(in_session(
+        run_opts,
+        with_profile_stack("<inline>", run_script),
+    ));
 
     if let Some(perms) = permissions {
         with_permissions_async(perms, || run_script).await
@@ -382,9 +704,48 @@ fn expand_globs(patterns: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
     Ok(result)
 }
 
-fn report_error(path: &Path, error: &BlueprintError) {
-    eprintln!("\n--- {} ---", path.display());
-    eprintln!("{}", error.format_with_stack());
+fn report_error(path: &Path, error: &BlueprintError, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => {
+            eprintln!("\n--- {} ---", path.display());
+            eprintln!("{}", error.format_with_stack());
+        }
+        OutputFormat::Json => eprintln!("{}", error_to_json(path, error)),
+    }
+}
+
+fn error_to_json(path: &Path, error: &BlueprintError) -> serde_json::Value {
+    let location = error.error_location();
+    let file = location
+        .and_then(|loc| loc.file.clone())
+        .unwrap_or_else(|| path.to_string_lossy().to_string());
+
+    let stack: Vec<serde_json::Value> = error
+        .stack_trace()
+        .map(|stack| {
+            stack
+                .frames
+                .iter()
+                .map(|frame| {
+                    serde_json::json!({
+                        "function": frame.function_name,
+                        "file": frame.file,
+                        "line": frame.line,
+                        "column": frame.column,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    serde_json::json!({
+        "file": file,
+        "line": location.map(|loc| loc.line),
+        "column": location.map(|loc| loc.column),
+        "kind": error.kind(),
+        "message": error.inner_error().to_string(),
+        "stack": stack,
+    })
 }
 
 pub async fn generate_dot(pattern: &str, output: Option<&Path>) -> Result<()> {
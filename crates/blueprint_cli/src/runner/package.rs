@@ -162,3 +162,24 @@ pub async fn sync_workspace() -> Result<()> {
     println!("Done!");
     Ok(())
 }
+
+pub async fn vendor_workspace() -> Result<()> {
+    let current_dir = std::env::current_dir().map_err(|e| BlueprintError::IoError {
+        path: ".".into(),
+        message: e.to_string(),
+    })?;
+
+    let workspace =
+        crate::workspace::Workspace::find(&current_dir).ok_or_else(|| BlueprintError::IoError {
+            path: current_dir.to_string_lossy().to_string(),
+            message: "No BP.toml found in current directory or any parent".into(),
+        })?;
+
+    let fetched = workspace.vendor_all()?;
+    println!(
+        "Vendored {} package(s) into {}",
+        fetched,
+        workspace.vendor_dir.display()
+    );
+    Ok(())
+}
@@ -259,7 +259,15 @@ pub async fn publish(
 
     println!("Uploading to {}/api/v1/packages/{}/{}...", registry, namespace, name);
 
-    let form = Form::new()
+    let readme_path = package_dir.join(
+        package
+            .get("readme")
+            .and_then(|v| v.as_str())
+            .unwrap_or("README.md"),
+    );
+    let readme = std::fs::read_to_string(&readme_path).ok();
+
+    let mut form = Form::new()
         .part("manifest", Part::text(manifest_content.clone()).file_name("blueprint.toml"))
         .part(
             "package",
@@ -271,6 +279,9 @@ pub async fn publish(
                     message: e.to_string(),
                 })?,
         );
+    if let Some(readme) = readme {
+        form = form.part("readme", Part::text(readme).file_name("README.md"));
+    }
 
     let client = reqwest::Client::new();
     let response = client
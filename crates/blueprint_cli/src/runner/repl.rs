@@ -1,14 +1,22 @@
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 
 use blueprint_engine_core::{BlueprintError, Result};
-use blueprint_engine_eval::{Evaluator, Scope};
+use blueprint_engine_eval::{set_breakpoint_handler, Evaluator, Scope};
 use blueprint_engine_parser::parse;
 
 use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
 use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
 use rustyline::validate::{ValidationContext, ValidationResult, Validator};
-use rustyline::{Cmd, ConditionalEventHandler, Event, Helper, RepeatCount};
+use rustyline::{
+    Cmd, ConditionalEventHandler, Config, EditMode, Editor, Event, EventHandler, Helper, KeyEvent,
+    RepeatCount,
+};
+use tokio::sync::oneshot;
 
 #[derive(Clone)]
 pub struct ReplHelper;
@@ -158,40 +166,44 @@ pub async fn repl(port: Option<u16>) -> Result<()> {
 }
 
 async fn repl_interactive() -> Result<()> {
-    use rustyline::error::ReadlineError;
-    use rustyline::{Config, EditMode, Editor, EventHandler, KeyEvent};
-
     println!("Blueprint REPL (type 'exit' or Ctrl+D to quit)");
     println!();
 
     let mut evaluator = Evaluator::new();
     let scope = Scope::new_global();
 
-    let config = Config::builder()
-        .auto_add_history(true)
-        .bracketed_paste(true)
-        .tab_stop(4)
-        .edit_mode(EditMode::Emacs)
-        .build();
+    repl_loop(&mut evaluator, &scope).await?;
 
-    let mut rl: Editor<ReplHelper, _> =
-        Editor::with_config(config).map_err(|e| BlueprintError::InternalError {
-            message: format!("Failed to create REPL: {}", e),
-        })?;
+    println!();
+    Ok(())
+}
 
-    rl.set_helper(Some(ReplHelper));
-    rl.bind_sequence(
-        KeyEvent::from('\t'),
-        EventHandler::Simple(Cmd::Insert(1, "    ".to_string())),
-    );
-    rl.bind_sequence(
-        KeyEvent::from('\r'),
-        EventHandler::Conditional(Box::new(EnterHandler)),
-    );
+/// Opens an interactive REPL on a paused script's scope. Returning resumes
+/// the script.
+async fn repl_in_scope(scope: Arc<Scope>) -> Result<()> {
+    eprintln!("Breakpoint: inspect or modify variables; 'exit' or Ctrl+D to continue");
+
+    let mut evaluator = Evaluator::new();
+    repl_loop(&mut evaluator, &scope).await?;
+
+    eprintln!("Resuming...");
+    Ok(())
+}
+
+pub fn install_breakpoint_handler() {
+    set_breakpoint_handler(Arc::new(
+        |scope| -> Pin<Box<dyn Future<Output = Result<()>> + Send>> {
+            Box::pin(repl_in_scope(scope))
+        },
+    ));
+}
+
+async fn repl_loop(evaluator: &mut Evaluator, scope: &Arc<Scope>) -> Result<()> {
+    let editor = LineEditor::spawn()?;
 
     loop {
-        match rl.readline(">>> ") {
-            Ok(line) => {
+        match editor.readline(">>> ").await {
+            ReadLine::Line(line) => {
                 let trimmed = line.trim();
 
                 if trimmed == "exit" || trimmed == "quit" {
@@ -203,30 +215,115 @@ async fn repl_interactive() -> Result<()> {
                 }
 
                 let clean_code = strip_continuation_prefixes(&line);
-                if let Some(exit_err) = execute_repl_code(&mut evaluator, &scope, &clean_code).await
-                {
+                if let Some(exit_err) = execute_repl_code(evaluator, scope, &clean_code).await {
                     println!();
                     return Err(exit_err);
                 }
             }
-            Err(ReadlineError::Interrupted) => {
+            ReadLine::Interrupted => {
                 println!("^C");
                 continue;
             }
-            Err(ReadlineError::Eof) => {
+            ReadLine::Eof => {
                 break;
             }
-            Err(err) => {
-                eprintln!("Error: {:?}", err);
+            ReadLine::Failed(err) => {
+                eprintln!("Error: {}", err);
                 break;
             }
         }
     }
 
-    println!();
     Ok(())
 }
 
+enum ReadLine {
+    Line(String),
+    Interrupted,
+    Eof,
+    Failed(String),
+}
+
+type ReadRequest = (String, oneshot::Sender<ReadLine>);
+
+/// Owns the rustyline editor on a dedicated thread so the REPL loop can be
+/// awaited from any task, including a paused script.
+struct LineEditor {
+    requests: std::sync::mpsc::Sender<ReadRequest>,
+}
+
+impl LineEditor {
+    fn spawn() -> Result<Self> {
+        let (requests, rx) = std::sync::mpsc::channel::<ReadRequest>();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || {
+            let mut rl = match new_editor() {
+                Ok(rl) => {
+                    let _ = ready_tx.send(Ok(()));
+                    rl
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+
+            while let Ok((prompt, reply)) = rx.recv() {
+                let line = match rl.readline(&prompt) {
+                    Ok(line) => ReadLine::Line(line),
+                    Err(ReadlineError::Interrupted) => ReadLine::Interrupted,
+                    Err(ReadlineError::Eof) => ReadLine::Eof,
+                    Err(err) => ReadLine::Failed(format!("{:?}", err)),
+                };
+                let _ = reply.send(line);
+            }
+        });
+
+        ready_rx
+            .recv()
+            .map_err(|_| BlueprintError::InternalError {
+                message: "Failed to create REPL: editor thread exited".into(),
+            })??;
+
+        Ok(Self { requests })
+    }
+
+    async fn readline(&self, prompt: &str) -> ReadLine {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if self.requests.send((prompt.to_string(), reply_tx)).is_err() {
+            return ReadLine::Eof;
+        }
+        reply_rx.await.unwrap_or(ReadLine::Eof)
+    }
+}
+
+fn new_editor() -> Result<Editor<ReplHelper, DefaultHistory>> {
+    let config = Config::builder()
+        .auto_add_history(true)
+        .bracketed_paste(true)
+        .tab_stop(4)
+        .edit_mode(EditMode::Emacs)
+        .build();
+
+    let mut rl: Editor<ReplHelper, DefaultHistory> =
+        Editor::with_config(config).map_err(|e| BlueprintError::InternalError {
+            message: format!("Failed to create REPL: {}", e),
+        })?;
+
+    rl.set_helper(Some(ReplHelper));
+    rl.bind_sequence(
+        KeyEvent::from('\t'),
+        EventHandler::Simple(Cmd::Insert(1, "    ".to_string())),
+    );
+    rl.bind_sequence(
+        KeyEvent::from('\r'),
+        EventHandler::Conditional(Box::new(EnterHandler)),
+    );
+
+    Ok(rl)
+}
+
 async fn execute_repl_code(
     evaluator: &mut Evaluator,
     scope: &Arc<Scope>,
@@ -255,31 +352,14 @@ pub async fn eval_code_in_scope(
     scope: &Arc<Scope>,
     code: &str,
 ) -> Result<Option<String>> {
-    let is_expr = !code.contains('=')
-        && !code.starts_with("def ")
-        && !code.starts_with("if ")
-        && !code.starts_with("for ")
-        && !code.starts_with("print(")
-        && !code.starts_with("load(");
-
-    let wrapped = if is_expr {
-        format!("__repl_result__ = {}", code)
-    } else {
-        code.to_string()
-    };
-
-    let module = parse("<repl>", &wrapped)?;
-    evaluator.eval(&module, scope.clone()).await?;
+    let module = parse("<repl>", code)?;
+    let result = evaluator.eval_value(&module, scope.clone()).await?;
 
-    if is_expr {
-        if let Some(result) = scope.get("__repl_result__").await {
-            if !result.is_none() {
-                return Ok(Some(result.repr()));
-            }
-        }
+    if result.is_none() {
+        Ok(None)
+    } else {
+        Ok(Some(result.repr()))
     }
-
-    Ok(None)
 }
 
 async fn repl_server(port: u16) -> Result<()> {
@@ -368,18 +448,13 @@ pub async fn eval_expression(expression: &str, port: Option<u16>) -> Result<()>
 }
 
 async fn eval_local(expression: &str) -> Result<()> {
-    let wrapped = format!("__result__ = {}", expression);
-    let module = parse("<eval>", &wrapped)?;
+    let module = parse("<eval>", expression)?;
 
     let mut evaluator = Evaluator::new();
-    let scope = Scope::new_global();
+    let result = evaluator.eval_value(&module, Scope::new_global()).await?;
 
-    evaluator.eval(&module, scope.clone()).await?;
-
-    if let Some(result) = scope.get("__result__").await {
-        if !result.is_none() {
-            println!("{}", result.repr());
-        }
+    if !result.is_none() {
+        println!("{}", result.repr());
     }
 
     Ok(())
@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+
+use blueprint_engine_core::{BlueprintError, Result};
+use blueprint_engine_eval::{collect_test_results, TestResult};
+use walkdir::{DirEntry, WalkDir};
+
+use super::{
+    expand_globs, install_breakpoint_handler, report_error, run_single_script, PermissionFlags,
+    RunOptions,
+};
+use crate::args::OutputFormat;
+
+/// Runs each test script in turn, then any `@bp/test` cases it registered.
+/// A script counts as one passing test when it registers no cases.
+pub async fn run_tests(
+    paths: Vec<PathBuf>,
+    verbose: bool,
+    format: OutputFormat,
+    perm_flags: PermissionFlags,
+    run_opts: RunOptions,
+) -> Result<()> {
+    let scripts = discover(paths, "test")?;
+    if scripts.is_empty() {
+        eprintln!("No test scripts found");
+        return Ok(());
+    }
+
+    install_breakpoint_handler();
+    run_opts.start_instrumentation();
+    let mut passed = 0;
+    let mut failed = 0;
+
+    for path in &scripts {
+        let outcome = run_single_script(path, vec![], verbose, &perm_flags, &run_opts).await;
+        let results = collect_test_results().await;
+
+        if let Err(error) = &outcome {
+            failed += 1;
+            if format == OutputFormat::Text {
+                eprintln!("[FAIL] {}", path.display());
+            }
+            report_error(path, error, format);
+        } else if results.is_empty() {
+            passed += 1;
+            report_script(path, format);
+        }
+
+        for result in &results {
+            if result.passed() {
+                passed += 1;
+            } else {
+                failed += 1;
+            }
+            report_case(path, result, verbose, format);
+        }
+
+        if failed > 0 && run_opts.fail_fast {
+            break;
+        }
+    }
+
+    run_opts.finish_instrumentation()?;
+
+    if format == OutputFormat::Text {
+        eprintln!("\n{} passed, {} failed", passed, failed);
+    }
+    if failed > 0 {
+        return Err(BlueprintError::Silent);
+    }
+    Ok(())
+}
+
+/// Explicit files are kept as given; directories are searched for
+/// `<kind>_*.bp` and `*_<kind>.bp`, skipping hidden, `vendor` and `target`
+/// directories. Defaults to the current directory.
+pub(super) fn discover(paths: Vec<PathBuf>, kind: &str) -> Result<Vec<PathBuf>> {
+    let paths = if paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        expand_globs(paths)?
+    };
+
+    let mut scripts = vec![];
+    for path in paths {
+        if !path.is_dir() {
+            scripts.push(path);
+            continue;
+        }
+        let mut found: Vec<PathBuf> = WalkDir::new(&path)
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || !is_skipped_dir(entry))
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && is_script_of(entry.path(), kind))
+            .map(|entry| entry.into_path())
+            .collect();
+        found.sort();
+        scripts.extend(found);
+    }
+    Ok(scripts)
+}
+
+fn is_skipped_dir(entry: &DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    entry.file_type().is_dir() && (name.starts_with('.') || name == "vendor" || name == "target")
+}
+
+fn is_script_of(path: &Path, kind: &str) -> bool {
+    let Some(stem) = path
+        .extension()
+        .filter(|ext| *ext == "bp")
+        .and_then(|_| path.file_stem())
+        .and_then(|stem| stem.to_str())
+    else {
+        return false;
+    };
+    stem.starts_with(&format!("{}_", kind)) || stem.ends_with(&format!("_{}", kind))
+}
+
+fn report_script(path: &Path, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => eprintln!("[PASS] {}", path.display()),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "file": path.to_string_lossy(),
+                "passed": true,
+            })
+        ),
+    }
+}
+
+fn report_case(path: &Path, result: &TestResult, verbose: bool, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => {
+            let label = format!("{} > {} > {}", path.display(), result.suite, result.name);
+            match &result.error {
+                None if verbose => eprintln!(
+                    "[PASS] {} ({:.1}ms)",
+                    label,
+                    result.duration.as_secs_f64() * 1000.0
+                ),
+                None => eprintln!("[PASS] {}", label),
+                Some(error) => eprintln!("[FAIL] {}\n  {}", label, error.inner_error()),
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "file": path.to_string_lossy(),
+                "suite": result.suite,
+                "name": result.name,
+                "passed": result.passed(),
+                "error": result.error.as_ref().map(|e| e.inner_error().to_string()),
+                "seconds": result.duration.as_secs_f64(),
+            })
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_script_of() {
+        assert!(is_script_of(Path::new("tests/test_math.bp"), "test"));
+        assert!(is_script_of(Path::new("math_test.bp"), "test"));
+        assert!(is_script_of(Path::new("bench_parse.bp"), "bench"));
+        assert!(!is_script_of(Path::new("bench_parse.bp"), "test"));
+        assert!(!is_script_of(Path::new("math.bp"), "test"));
+        assert!(!is_script_of(Path::new("test_math.py"), "test"));
+        assert!(!is_script_of(Path::new("test_data"), "test"));
+    }
+}
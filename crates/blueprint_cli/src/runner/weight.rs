@@ -0,0 +1,45 @@
+use std::path::Path;
+
+/// How many `--jobs` slots a script occupies, read from a `# bp: weight=N`
+/// comment in its leading comment block. Scripts without one weigh 1.
+pub fn script_weight(path: &Path) -> u32 {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|source| parse_weight(&source))
+        .unwrap_or(1)
+}
+
+fn parse_weight(source: &str) -> Option<u32> {
+    for line in source.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let comment = line.strip_prefix('#')?;
+        let Some(directive) = comment.trim().strip_prefix("bp:") else {
+            continue;
+        };
+        let (key, value) = directive.split_once('=')?;
+        if key.trim() == "weight" {
+            return value.trim().parse().ok().filter(|w| *w > 0);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_weight_from_header() {
+        assert_eq!(
+            parse_weight("#!/usr/bin/env bp\n# bp: weight=4\nx = 1\n"),
+            Some(4)
+        );
+        assert_eq!(parse_weight("\n# build step\n#bp:weight = 2\n"), Some(2));
+        assert_eq!(parse_weight("x = 1\n# bp: weight=4\n"), None);
+        assert_eq!(parse_weight("# bp: weight=0\n"), None);
+        assert_eq!(parse_weight("print('hi')\n"), None);
+    }
+}
@@ -74,6 +74,7 @@ pub struct Workspace {
     pub root: PathBuf,
     pub config: BpToml,
     pub packages_dir: PathBuf,
+    pub vendor_dir: PathBuf,
 }
 
 impl Workspace {
@@ -107,11 +108,13 @@ impl Workspace {
         })?;
 
         let packages_dir = root.join(".blueprint").join("packages");
+        let vendor_dir = root.join("vendor");
 
         Ok(Self {
             root: root.to_path_buf(),
             config,
             packages_dir,
+            vendor_dir,
         })
     }
 
@@ -126,13 +129,19 @@ impl Workspace {
     }
 
     pub fn package_path(&self, name: &str, version: &str) -> PathBuf {
+        Self::package_path_in(&self.packages_dir, name, version)
+    }
+
+    pub fn vendored_path(&self, name: &str, version: &str) -> PathBuf {
+        Self::package_path_in(&self.vendor_dir, name, version)
+    }
+
+    fn package_path_in(dir: &Path, name: &str, version: &str) -> PathBuf {
         let parts: Vec<&str> = name.splitn(2, '/').collect();
         if parts.len() == 2 {
-            self.packages_dir
-                .join(parts[0])
-                .join(format!("{}#{}", parts[1], version))
+            dir.join(parts[0]).join(format!("{}#{}", parts[1], version))
         } else {
-            self.packages_dir.join(format!("{}#{}", name, version))
+            dir.join(format!("{}#{}", name, version))
         }
     }
 
@@ -145,7 +154,7 @@ impl Workspace {
         let version = dep.version();
         let pkg_path = self.package_path(name, version);
 
-        if pkg_path.exists() {
+        if pkg_path.exists() || self.vendored_path(name, version).exists() {
             return Ok(());
         }
 
@@ -185,6 +194,34 @@ impl Workspace {
         Ok(())
     }
 
+    /// Downloads every registry dependency into `vendor/` so the workspace
+    /// can run with `--offline`. Returns how many packages were fetched.
+    pub fn vendor_all(&self) -> Result<usize> {
+        let mut fetched = 0;
+        for (name, dep) in &self.config.dependencies {
+            if dep.local_path().is_some() {
+                continue;
+            }
+            let Some((namespace, pkg_name, version)) = dep.registry_spec(name) else {
+                continue;
+            };
+            let dest = self.vendored_path(name, &version);
+            if dest.exists() {
+                continue;
+            }
+
+            println!("Vendoring {}#{}...", name, version);
+            let spec = blueprint_engine_core::PackageSpec {
+                user: namespace,
+                repo: pkg_name,
+                version,
+            };
+            blueprint_engine_core::fetch_package(&spec, &dest)?;
+            fetched += 1;
+        }
+        Ok(fetched)
+    }
+
     #[allow(dead_code)]
     pub fn resolve_package(&self, module_path: &str) -> Option<PathBuf> {
         let path = module_path.strip_prefix('@').unwrap_or(module_path);